@@ -21,14 +21,77 @@ impl SproutManifest {
             return;
         }
 
-        if let Some(pkg) = self.modules.iter().find(|p| p.id() == module_id) {
+        if let Some(pkg) = self.find_module(module_id) {
+            let id = pkg.id();
+            if visited.contains(&id) {
+                return;
+            }
             for dep in &pkg.depends_on {
                 self.visit_dependencies(dep, visited, result);
             }
-            visited.insert(module_id.to_string());
-            result.push(module_id.to_string());
+            visited.insert(id.clone());
+            result.push(id);
+        }
+    }
+
+    /// Resolve a module reference (`name` or `name@version`) to a module.
+    ///
+    /// An exact id match wins; a bare name otherwise resolves only when
+    /// exactly one version of that module is declared.
+    pub fn find_module(&self, reference: &str) -> Option<&ModuleBlock> {
+        if let Some(module) = self.modules.iter().find(|m| m.id() == reference) {
+            return Some(module);
+        }
+        if reference.contains('@') {
+            return None;
+        }
+
+        let mut candidates = self.modules.iter().filter(|m| m.name == reference);
+        match (candidates.next(), candidates.next()) {
+            (Some(module), None) => Some(module),
+            _ => None,
+        }
+    }
+
+    /// Get every dependency chain leading from `from` to `target` (both inclusive)
+    pub fn get_dependency_paths(&self, from: &str, target: &str) -> Vec<Vec<String>> {
+        let mut result = Vec::new();
+        let mut path = Vec::new();
+        self.visit_dependency_paths(from, target, &mut path, &mut result);
+        result
+    }
+
+    fn visit_dependency_paths(&self, module_id: &str, target: &str, path: &mut Vec<String>, result: &mut Vec<Vec<String>>) {
+        if let Some(pkg) = self.find_module(module_id) {
+            let id = pkg.id();
+            // Guard against cycles: never revisit a module already on the current chain
+            if path.contains(&id) {
+                return;
+            }
+
+            path.push(id.clone());
+            if id == target {
+                result.push(path.clone());
+            } else {
+                for dep in &pkg.depends_on {
+                    self.visit_dependency_paths(dep, target, path, result);
+                }
+            }
+            path.pop();
         }
     }
+
+    /// Get modules that no other module depends on, sorted by id
+    pub fn get_root_modules(&self) -> Vec<String> {
+        let mut roots: Vec<String> = self.modules.iter()
+            .map(|m| m.id())
+            .filter(|id| !self.modules.iter().any(|m| {
+                m.depends_on.iter().any(|dep| self.find_module(dep).is_some_and(|d| d.id() == *id))
+            }))
+            .collect();
+        roots.sort();
+        roots
+    }
 }
 
 /// How a provided environment variable combines with any pre-existing value.
@@ -80,8 +143,24 @@ pub struct Export {
 #[derive(Debug, Clone, PartialEq)]
 pub struct ModuleBlock {
     pub name: String,
+    /// Optional version, making `name@version` the module id
+    pub version: Option<String>,
+    /// Human-readable summary; metadata only, never part of a hash
+    pub description: Option<String>,
+    /// Labels for selecting groups of modules (`--tag`); metadata only, never part of a hash
+    pub tags: Vec<String>,
     pub depends_on: Vec<String>,
     pub provides: Vec<Export>,
+    /// Files (relative to the module's dist dir) the build must leave behind
+    pub produces: Vec<String>,
+    /// External commands that must be on PATH before the build runs
+    pub requires: Vec<String>,
+    /// Subdirectory of the source tree the build runs in
+    pub build_dir: Option<String>,
+    /// Build in a fresh temporary directory instead of the source tree
+    pub out_of_tree: bool,
+    /// Run the build with a cleared environment instead of inheriting the caller's
+    pub isolated: bool,
     pub fetch: Option<FetchBlock>,
     pub build: Option<ScriptBlock>,
     pub update: Option<ScriptBlock>,
@@ -89,7 +168,10 @@ pub struct ModuleBlock {
 
 impl ModuleBlock {
     pub fn id(&self) -> String {
-        self.name.clone()
+        match &self.version {
+            Some(version) => format!("{}@{}", self.name, version),
+            None => self.name.clone(),
+        }
     }
 }
 
@@ -97,7 +179,14 @@ impl ModuleBlock {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FetchBlock {
     pub spec: FetchSpec,
+    /// Name for the fetched file of a single-file download (raw, `.gz`, `.xz`)
     pub output: Option<String>,
+    /// `extract = false` keeps a downloaded archive as-is instead of unpacking it
+    pub extract: bool,
+    /// `discard_git = true` deletes a git clone's `.git` dir once the commit is recorded
+    pub discard_git: bool,
+    /// Seconds a clone or download may take before it is aborted
+    pub timeout: Option<u64>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -105,6 +194,8 @@ pub enum FetchSpec {
     Git(GitSpec),
     Http(HttpSpec),
     Local(LocalSpec),
+    Rsync(RsyncSpec),
+    Github(GithubSpec),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -123,13 +214,55 @@ pub struct HttpSpec {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct LocalSpec {
     pub path: String,
+    pub sha256: Option<String>,
 }
 
-/// Script block with optional environment and commands
+/// A source mirrored with `rsync -a --delete`, e.g. `rsync://mirror/tools/foo/`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RsyncSpec {
+    pub url: String,
+}
+
+/// A GitHub release asset; resolved through the releases API to a download URL
+/// and then fetched like an http source
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GithubSpec {
+    /// `owner/name`
+    pub repo: String,
+    /// Release tag, e.g. `v8.7.0`
+    pub tag: String,
+    /// Regex that must match exactly one asset name of the release
+    pub asset: String,
+    pub sha256: Option<String>,
+}
+
+/// Script block with optional environment and commands
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ScriptBlock {
     pub env: Vec<(String, String)>,
     pub commands: Vec<String>,
+    /// Seconds the script may run before it is killed
+    pub timeout: Option<u64>,
+}
+
+impl Hash for ScriptBlock {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.env.hash(state);
+        self.commands.hash(state);
+        // Only hashed when set so existing lockfiles stay valid
+        if let Some(timeout) = self.timeout {
+            timeout.hash(state);
+        }
+    }
+}
+
+/// Render seconds in the largest unit that divides them (`2h`, `30m`, `45s`)
+pub fn format_duration(seconds: u64) -> String {
+    match seconds {
+        s if s > 0 && s % 3600 == 0 => format!("{}h", s / 3600),
+        s if s > 0 && s % 60 == 0 => format!("{}m", s / 60),
+        s => format!("{}s", s),
+    }
 }
 
 /// Environments block
@@ -138,6 +271,10 @@ pub struct EnvironmentsBlock {
     pub environments: HashMap<String, Vec<String>>,
 }
 
+/// Environment entry standing for every module the lockfile records as built,
+/// expanded when the environment is listed or generated
+pub const ALL_BUILT_MODULES: &str = "*";
+
 /// Tokens for the lexer
 #[derive(Debug, Clone, PartialEq)]
 #[allow(dead_code)]
@@ -184,6 +321,7 @@ impl Token {
 }
 
 use std::fmt;
+use std::hash::{Hash, Hasher};
 
 pub trait PrettyPrint {
     fn pretty_print(&self) -> String;
@@ -210,7 +348,17 @@ impl PrettyPrint for SproutManifest {
 
 impl PrettyPrint for ModuleBlock {
     fn pretty_print(&self) -> String {
-        let mut output = format!("module {} {{\n", self.name);
+        let mut output = format!("module {} {{\n", self.id());
+
+        if let Some(description) = &self.description {
+            let escaped = description.replace('\\', "\\\\").replace('"', "\\\"");
+            output.push_str(&format!("    description = \"{}\"\n", escaped));
+        }
+
+        if !self.tags.is_empty() {
+            let tags: Vec<String> = self.tags.iter().map(|tag| format!("\"{}\"", tag)).collect();
+            output.push_str(&format!("    tags = [{}]\n", tags.join(", ")));
+        }
         
         output.push_str("    depends_on = [");
         for (i, dep) in self.depends_on.iter().enumerate() {
@@ -229,12 +377,55 @@ impl PrettyPrint for ModuleBlock {
             output.push_str("    }\n");
         }
         
+        if !self.produces.is_empty() {
+            output.push_str("    produces = [");
+            for (i, file) in self.produces.iter().enumerate() {
+                if i > 0 {
+                    output.push_str(", ");
+                }
+                output.push_str(&format!("\"{}\"", file));
+            }
+            output.push_str("]\n");
+        }
+        
+        if !self.requires.is_empty() {
+            output.push_str("    requires = [");
+            for (i, tool) in self.requires.iter().enumerate() {
+                if i > 0 {
+                    output.push_str(", ");
+                }
+                output.push_str(&format!("\"{}\"", tool));
+            }
+            output.push_str("]\n");
+        }
+        
+        if let Some(build_dir) = &self.build_dir {
+            output.push_str(&format!("    build_dir = \"{}\"\n", build_dir));
+        }
+
+        if self.out_of_tree {
+            output.push_str("    out_of_tree = true\n");
+        }
+
+        if self.isolated {
+            output.push_str("    isolated = true\n");
+        }
+        
         if let Some(fetch) = &self.fetch {
             output.push_str("    fetch {\n");
             output.push_str(&fetch.spec.pretty_print());
             if let Some(output_name) = &fetch.output {
                 output.push_str(&format!("        output = {}\n", output_name));
             }
+            if !fetch.extract {
+                output.push_str("        extract = false\n");
+            }
+            if fetch.discard_git {
+                output.push_str("        discard_git = true\n");
+            }
+            if let Some(timeout) = fetch.timeout {
+                output.push_str(&format!("        timeout = {}\n", format_duration(timeout)));
+            }
             output.push_str("    }\n");
         }
         
@@ -303,7 +494,26 @@ impl PrettyPrint for FetchSpec {
                 output
             }
             FetchSpec::Local(local) => {
-                format!("        local = {{\n            path = \"{}\"\n        }}\n", local.path)
+                let mut output = format!("        local = {{\n            path = \"{}\"\n", local.path);
+                if let Some(sha256) = &local.sha256 {
+                    output.push_str(&format!("            sha256 = {}\n", sha256));
+                }
+                output.push_str("        }\n");
+                output
+            }
+            FetchSpec::Rsync(rsync) => {
+                format!("        rsync = {{\n            url = \"{}\"\n        }}\n", rsync.url)
+            }
+            FetchSpec::Github(github) => {
+                let mut output = String::from("        github = {\n");
+                output.push_str(&format!("            repo = \"{}\"\n", github.repo));
+                output.push_str(&format!("            tag = \"{}\"\n", github.tag));
+                output.push_str(&format!("            asset = \"{}\"\n", github.asset.replace('\\', "\\\\").replace('"', "\\\"")));
+                if let Some(sha256) = &github.sha256 {
+                    output.push_str(&format!("            sha256 = {}\n", sha256));
+                }
+                output.push_str("        }\n");
+                output
             }
         }
     }
@@ -312,6 +522,10 @@ impl PrettyPrint for FetchSpec {
 impl PrettyPrint for ScriptBlock {
     fn pretty_print(&self) -> String {
         let mut output = String::new();
+
+        if let Some(timeout) = self.timeout {
+            output.push_str(&format!("        timeout = {}\n", format_duration(timeout)));
+        }
         
         if !self.env.is_empty() {
             output.push_str("        env {\n");
@@ -338,6 +552,8 @@ impl fmt::Display for FetchSpec {
             FetchSpec::Git(git) => write!(f, "{}", git),
             FetchSpec::Http(http) => write!(f, "{}", http),
             FetchSpec::Local(local) => write!(f, "{}", local),
+            FetchSpec::Rsync(rsync) => write!(f, "{}", rsync),
+            FetchSpec::Github(github) => write!(f, "{}", github),
         }
     }
 }
@@ -358,7 +574,21 @@ impl fmt::Display for HttpSpec {
 
 impl fmt::Display for LocalSpec {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Local{{path:{}}}", self.path)
+        let sha256_str = self.sha256.as_deref().unwrap_or("");
+        write!(f, "Local{{path:{},sha256:{}}}", self.path, sha256_str)
+    }
+}
+
+impl fmt::Display for RsyncSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rsync{{url:{}}}", self.url)
+    }
+}
+
+impl fmt::Display for GithubSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sha256_str = self.sha256.as_deref().unwrap_or("");
+        write!(f, "Github{{repo:{},tag:{},asset:{},sha256:{}}}", self.repo, self.tag, self.asset, sha256_str)
     }
 }
 
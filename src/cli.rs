@@ -5,9 +5,10 @@ use anyhow::{Result, Context};
 use tracing::{info, warn};
 
 use crate::core::*;
-use crate::manifest::{load_manifest, save_manifest};
+use crate::manifest::{load_manifest_from, load_manifest_raw_from, resolve_manifest_path, save_manifest_to};
 use crate::lockfile::{SproutLock, PackageState};
 use crate::ast::PrettyPrint;
+use crate::error::{Categorize, ErrorKind};
 
 const DEFAULT_SPROUT_PATH: &str = "/sprout";
 
@@ -51,9 +52,54 @@ pub struct Cli {
     #[arg(long, global = true)]
     pub sprout_path: Option<PathBuf>,
 
+    /// Manifest file to use instead of <sprout-path>/manifest.sprout
+    #[arg(long, global = true)]
+    pub manifest_path: Option<PathBuf>,
+
     /// Path to track files from (overrides HOME env var for symlink operations)
     #[arg(long, global = true)]
     pub tracking_path: Option<PathBuf>,
+
+    /// When to color output: auto colors a terminal unless NO_COLOR is set;
+    /// always keeps colors when piping, e.g. into `less -R`
+    #[arg(long, global = true, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// Same as --color=never
+    #[arg(long, global = true, hide = true)]
+    pub no_color: bool,
+
+    /// Plain progress for CI: no spinners, periodic "still building" lines with
+    /// the latest build output (on by default when the CI env var is set)
+    #[arg(long, global = true)]
+    pub plain: bool,
+
+    /// Log record format on stderr; json emits one object per line with the event's fields
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Preview what any mutating command would do without changing anything
+    /// (combines with a subcommand's own --dry-run)
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color a terminal unless NO_COLOR is set
+    Auto,
+    /// Always emit color escape sequences
+    Always,
+    /// Never color
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per event, for log aggregation
+    Json,
 }
 
 #[derive(Subcommand, Debug)]
@@ -79,6 +125,10 @@ pub enum Commands {
         /// Create empty manifest instead of template
         #[arg(long, conflicts_with = "from")]
         empty: bool,
+
+        /// Bundled manifest to start from: default, minimal, rust-tools or c-toolchain
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["from", "empty"])]
+        template: Option<String>,
     },
 
     /// Manage dependencies (fetch, build, install modules)
@@ -115,9 +165,21 @@ pub enum Commands {
         /// Show all symlinks including up-to-date ones
         #[arg(long)]
         all: bool,
-        /// Expand module dependency tree
-        #[arg(long)]
+        /// Expand the module dependency tree (default: true, same as `modules status`)
+        #[arg(long, default_value_t = true, num_args = 0..=1, default_missing_value = "true", action = clap::ArgAction::Set)]
         expand: bool,
+        /// Collapse the dependency tree; modules with dependencies show a `(+N deps)` marker
+        #[arg(long)]
+        no_expand: bool,
+        /// Show the modules section (all sections are shown if none is selected)
+        #[arg(long)]
+        modules: bool,
+        /// Show the symlinks section
+        #[arg(long)]
+        symlinks: bool,
+        /// Show the git section
+        #[arg(long)]
+        git: bool,
     },
 
     /// Commit changes in sprout directory
@@ -131,6 +193,18 @@ pub enum Commands {
         /// Generate commit message using AI
         #[arg(long)]
         ai: bool,
+        /// Prompt template for --ai with a {diff} placeholder (default: commit_template.txt)
+        #[arg(long, requires = "ai")]
+        message_template: Option<PathBuf>,
+        /// Create an annotated tag with this name after committing
+        #[arg(long)]
+        tag: Option<String>,
+        /// Move the tag if it already exists
+        #[arg(long, requires = "tag")]
+        force_tag: bool,
+        /// Push to the default remote after committing
+        #[arg(long)]
+        push: bool,
     },
 
     /// Pull changes from remote git repository
@@ -155,12 +229,40 @@ pub enum Commands {
 
     /// Edit manifest.sprout with $EDITOR
     ///
-    /// Opens manifest in your editor and validates syntax after saving
+    /// Opens manifest in your editor and validates syntax after saving.
+    /// An edit that breaks the manifest can be reopened or reverted
     #[command(visible_alias = "e")]
     Edit {
         /// Sprout directory path (defaults to /sprout)
         #[arg(default_value = "/sprout")]
         path: PathBuf,
+        /// Save whatever the editor leaves behind without checking it
+        #[arg(long)]
+        no_validate: bool,
+    },
+
+    /// Check manifest.sprout for likely mistakes
+    ///
+    /// Flags modules that fetch without building, empty build blocks,
+    /// absolute exports, unused modules and branch-tracking git refs.
+    /// Fails if any error-level issue is found
+    Lint,
+
+    /// Bundle manifest, lockfile and tracked symlinks into a .tar.gz
+    ///
+    /// Build artifacts (dist, cache, sources) are left out; use `sprout import`
+    /// on the new machine to unpack the bundle and restore symlinks
+    Export {
+        /// Bundle file to write (e.g. sprout.tar.gz)
+        file: PathBuf,
+    },
+
+    /// Unpack a bundle created by `sprout export` and restore its symlinks
+    Import {
+        /// Bundle file to read
+        file: PathBuf,
+        /// Sprout directory to create (defaults to the sprout path)
+        path: Option<PathBuf>,
     },
 
     /// Verify and reformat manifest.sprout
@@ -188,11 +290,26 @@ pub enum ModulesCommand {
         /// Fetch all dependencies in manifest
         #[arg(long)]
         all: bool,
-        /// Specific packages to fetch (e.g., 'ripgrep cmake')
+        /// Specific packages to fetch (e.g., 'ripgrep cmake'); globs like 'llvm*' match module ids
         packages: Vec<String>,
         /// Show what would be fetched without fetching
         #[arg(long)]
         dry_run: bool,
+        /// Discard cached archives and sources and fetch from scratch
+        #[arg(long)]
+        force: bool,
+        /// With --all, abort on the first failed fetch instead of continuing
+        #[arg(long, requires = "all")]
+        stop_on_error: bool,
+        /// Skip sha256 verification of archives (integrity checking is disabled)
+        #[arg(long)]
+        no_verify: bool,
+        /// Fetch a single git module at this ref instead of the manifest's (not persisted)
+        #[arg(long = "ref", conflicts_with = "all")]
+        ref_: Option<String>,
+        /// Download archives and print their sha256 without writing it into the manifest
+        #[arg(long, conflicts_with = "ref_")]
+        print_hash: bool,
     },
 
     /// Build dependencies using their build scripts
@@ -204,14 +321,43 @@ pub enum ModulesCommand {
         /// Build all dependencies in manifest
         #[arg(long)]
         all: bool,
-        /// Specific packages to build (e.g., 'ripgrep cmake')
+        /// Specific packages to build (e.g., 'ripgrep cmake'); globs like 'llvm*' match module ids
         packages: Vec<String>,
+        /// Also build every module carrying this tag
+        #[arg(long, conflicts_with = "all")]
+        tag: Option<String>,
         /// Force rebuild even if up-to-date
         #[arg(long)]
         rebuild: bool,
         /// Show what would be built without building
         #[arg(long)]
         dry_run: bool,
+        /// Parallel jobs exported to build scripts as SPROUT_JOBS (defaults to the CPU count)
+        #[arg(long)]
+        build_jobs: Option<usize>,
+        /// With --all, skip modules ordered before this one (resume a failed build)
+        #[arg(long, requires = "all")]
+        continue_from: Option<String>,
+        /// Also treat a module as stale when files in its source dir changed
+        /// since the last build (compares paths, sizes and mtimes)
+        #[arg(long)]
+        check_sources: bool,
+        /// With source checking, hash full file contents instead (slow on large trees)
+        #[arg(long)]
+        thorough: bool,
+        /// Cap each build's virtual memory (e.g. 8G, 512M), applied with `ulimit -v`
+        #[arg(long, value_name = "SIZE", value_parser = crate::core::deps::parse_memory_limit)]
+        memory_limit: Option<u64>,
+        /// Run builds at this niceness (0-19) so they yield CPU to interactive work
+        #[arg(long, value_name = "NICENESS", value_parser = clap::value_parser!(u8).range(0..=19))]
+        cpu_limit: Option<u8>,
+        /// Keep the incomplete dist/<module> of a failed build for debugging
+        #[arg(long)]
+        keep_broken: bool,
+        /// Run builds with a cleared environment (minimal PATH, sprout variables
+        /// and the module's env block only), as if every module had `isolated = true`
+        #[arg(long)]
+        isolated_env: bool,
     },
 
     /// Install dependencies (fetch + build in one step)
@@ -222,8 +368,11 @@ pub enum ModulesCommand {
         /// Install all dependencies in manifest
         #[arg(long)]
         all: bool,
-        /// Specific packages to install (e.g., 'ripgrep cmake gcc')
+        /// Specific packages to install (e.g., 'ripgrep cmake gcc'); globs like 'llvm*' match module ids
         packages: Vec<String>,
+        /// Also install every module carrying this tag
+        #[arg(long, conflicts_with = "all")]
+        tag: Option<String>,
         /// Also install dependencies of specified packages
         #[arg(long)]
         with_deps: bool,
@@ -233,20 +382,68 @@ pub enum ModulesCommand {
         /// Show what would be done without doing it
         #[arg(long)]
         dry_run: bool,
+        /// Parallel jobs exported to build scripts as SPROUT_JOBS (defaults to the CPU count)
+        #[arg(long)]
+        build_jobs: Option<usize>,
+        /// Check out the commits recorded in the lockfile instead of the manifest refs
+        #[arg(long)]
+        from_lock: bool,
+        /// Skip sha256 verification of archives (integrity checking is disabled)
+        #[arg(long)]
+        no_verify: bool,
+        /// Only fetch sources (e.g. to prefetch before building offline)
+        #[arg(long, conflicts_with = "only_build")]
+        only_fetch: bool,
+        /// Only build from already fetched sources
+        #[arg(long)]
+        only_build: bool,
+        /// Also treat a module as stale when files in its source dir changed
+        /// since the last build (compares paths, sizes and mtimes)
+        #[arg(long)]
+        check_sources: bool,
+        /// With source checking, hash full file contents instead (slow on large trees)
+        #[arg(long)]
+        thorough: bool,
+        /// Cap each build's virtual memory (e.g. 8G, 512M), applied with `ulimit -v`
+        #[arg(long, value_name = "SIZE", value_parser = crate::core::deps::parse_memory_limit)]
+        memory_limit: Option<u64>,
+        /// Run builds at this niceness (0-19) so they yield CPU to interactive work
+        #[arg(long, value_name = "NICENESS", value_parser = clap::value_parser!(u8).range(0..=19))]
+        cpu_limit: Option<u8>,
+        /// Keep the incomplete dist/<module> of a failed build for debugging
+        #[arg(long)]
+        keep_broken: bool,
+        /// Run builds with a cleared environment (minimal PATH, sprout variables
+        /// and the module's env block only), as if every module had `isolated = true`
+        #[arg(long)]
+        isolated_env: bool,
     },
 
     /// Show module status with build information
     ///
     /// Displays modules with their fetch/build status and dependencies.
-    /// Use --expand to show full dependency tree, --all to show up-to-date modules
+    /// The dependency tree is expanded by default; --no-expand collapses it.
+    /// Use --all=false to hide up-to-date modules
     #[command(visible_alias = "s")]
     Status {
-        /// Expand tree to show all dependencies recursively
-        #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+        /// Expand tree to show all dependencies recursively (default: true)
+        #[arg(long, default_value_t = true, num_args = 0..=1, default_missing_value = "true", action = clap::ArgAction::Set)]
         expand: bool,
+        /// Collapse the dependency tree; modules with dependencies show a `(+N deps)` marker
+        #[arg(long)]
+        no_expand: bool,
         /// Show all modules including up-to-date ones
         #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
         all: bool,
+        /// Only show modules whose id matches this glob (e.g. 'llvm*')
+        #[arg(long)]
+        filter: Option<String>,
+        /// Only show modules carrying this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Limit how many dependency levels are expanded below each module
+        #[arg(long)]
+        depth: Option<usize>,
     },
 
     /// Compute and display/update module hashes
@@ -265,6 +462,101 @@ pub enum ModulesCommand {
         build: bool,
     },
 
+    /// Explain why a module is pulled in
+    ///
+    /// Prints every dependency chain (e.g. clang -> llvm -> cmake) that leads
+    /// from a root module to the target. Uses all root modules unless --from is given
+    Why {
+        /// Module to explain
+        target: String,
+        /// Only consider chains starting at this module
+        #[arg(long)]
+        from: Option<String>,
+    },
+
+    /// List a module's dependencies, one id per line
+    ///
+    /// Prints every transitive dependency in build order (dependencies
+    /// first), or with --direct only its own depends_on. Meant for scripting,
+    /// e.g. `sprout modules build $(sprout modules deps clang)`
+    Deps {
+        /// Module whose dependencies to list
+        package: String,
+        /// Only list the modules in its depends_on
+        #[arg(long)]
+        direct: bool,
+    },
+
+    /// Print the module dependency graph
+    ///
+    /// Lists one `module -> dependency` edge per line, or with --dot emits
+    /// Graphviz DOT with nodes colored by build state (pipe to `dot -Tpng`)
+    Graph {
+        /// Emit Graphviz DOT
+        #[arg(long)]
+        dot: bool,
+    },
+
+    /// Show recent builds from the build history
+    ///
+    /// Every build script run is appended to history.jsonl in the sprout
+    /// directory with its outcome, duration, log file and build hash
+    History {
+        /// Only show builds of this module
+        package: Option<String>,
+        /// Number of most recent entries to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+
+    /// Check git modules pinned to a tag for newer upstream tags
+    ///
+    /// Queries each remote with git ls-remote and compares the pinned ref
+    /// against the latest version tag. Does not modify anything
+    Outdated,
+
+    /// Run modules' update scripts
+    ///
+    /// Executes the `update` block of each module in its source dir. Git
+    /// sources are cloned (or deepened) with full history and tags first
+    Update {
+        /// Modules to update (globs allowed); defaults to every module with an update block
+        packages: Vec<String>,
+        /// Print the update scripts without running them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Re-check the sha256 of cached HTTP downloads
+    ///
+    /// Recomputes the digest of every cached archive whose module pins a sha256
+    /// and reports mismatches. Nothing is downloaded
+    Verify {
+        /// Delete corrupt cache files so the next fetch downloads them again
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Freeze a git module to the commit it is currently fetched at
+    ///
+    /// Fetches the module if needed and rewrites its `ref` in the manifest to
+    /// the resolved commit SHA, so later fetches reproduce exactly that tree
+    Pin {
+        /// Module name or id
+        package: String,
+    },
+
+    /// Rename a module, carrying its build state along
+    ///
+    /// Updates the manifest (including depends_on and environment references),
+    /// moves the lockfile entry and renames the dist, source and cache directories
+    Rename {
+        /// Current module name or id
+        old: String,
+        /// New module name (or name@version)
+        new: String,
+    },
+
     /// Remove unused cache/source directories
     ///
     /// Cleans up old source and cache directories that don't match
@@ -274,7 +566,22 @@ pub enum ModulesCommand {
         /// Show what would be removed without removing
         #[arg(long)]
         dry_run: bool,
+        /// Only consider directories of modules whose id matches this glob
+        #[arg(long)]
+        filter: Option<String>,
+        /// Also remove dist dirs and lockfile entries of modules no longer in the manifest
+        #[arg(long)]
+        orphans: bool,
+        /// Delete without asking (required when stdin is not a terminal)
+        #[arg(short, long)]
+        yes: bool,
     },
+
+    /// Show disk usage per module
+    ///
+    /// Sums each module's sources/, cache/ and dist/ directories (all
+    /// fetched versions, not just the current one), largest first
+    Size,
 }
 
 #[derive(Subcommand, Debug)]
@@ -285,11 +592,18 @@ pub enum SymlinksCommand {
     /// back to the original location. Tracks it in the lockfile
     #[command(visible_alias = "a")]
     Add {
-        /// Path to file or directory to add (e.g., ~/.bashrc)
-        path: PathBuf,
+        /// Paths to files or directories to add (e.g., ~/.bashrc ~/.zshrc)
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
         /// Add directory recursively (required for directories)
         #[arg(short, long)]
         recursive: bool,
+        /// Keep the original as a regular file and track a copy instead of symlinking
+        #[arg(long)]
+        copy: bool,
+        /// Replace a path that is already tracked with its current on-disk content
+        #[arg(short, long)]
+        force: bool,
         /// Show what would be done without doing it
         #[arg(long)]
         dry_run: bool,
@@ -306,6 +620,16 @@ pub enum SymlinksCommand {
         all: bool,
     },
 
+    /// List every managed entry and where it points
+    ///
+    /// Reads the lockfile only; unlike `status` nothing is stat'ed or hashed
+    #[command(visible_alias = "ls")]
+    List {
+        /// Print the entries as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
     /// Restore broken or missing symlinks
     ///
     /// Recreates symlinks based on lockfile. Use after fresh clone or
@@ -314,6 +638,9 @@ pub enum SymlinksCommand {
         /// Show what would be restored without restoring
         #[arg(long)]
         dry_run: bool,
+        /// Ask whether to overwrite, back up or skip each real file in the way
+        #[arg(short, long)]
+        interactive: bool,
     },
 
     /// Rehash symlinks or discover managed symlinks
@@ -325,11 +652,24 @@ pub enum SymlinksCommand {
         /// Discover and add managed symlinks not in lockfile
         #[arg(long)]
         discover: bool,
+        /// With --discover, also pick up symlinks into the symlinks/ dir of a
+        /// previous sprout location and repoint them at the current one
+        #[arg(long, requires = "discover")]
+        include_untracked: bool,
+        /// Remove lockfile entries whose symlink no longer exists
+        #[arg(long, conflicts_with = "discover")]
+        prune: bool,
         /// Show what would be done without doing it
         #[arg(long)]
         dry_run: bool,
     },
 
+    /// Check the lockfile for entries that share a target
+    ///
+    /// Reports lockfile entries resolving to the same file in /sprout/symlinks
+    /// or the same path in the tracking directory, e.g. after a migration
+    Verify,
+
     /// Undo symlink management for a path
     ///
     /// Moves file/directory back from /sprout/symlinks to original location,
@@ -341,6 +681,31 @@ pub enum SymlinksCommand {
         #[arg(long)]
         dry_run: bool,
     },
+
+    /// Move a tracked path to a new location
+    ///
+    /// Renames the entry in /sprout/symlinks, replaces the old symlink with
+    /// one at the new location and re-keys the lockfile entry
+    #[command(visible_alias = "mv")]
+    Move {
+        /// Currently tracked path (e.g., ~/.vimrc)
+        old: PathBuf,
+        /// New path within the tracking directory (e.g., ~/.config/vim/vimrc)
+        new: PathBuf,
+        /// Show what would be done without doing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Show content changes of tracked files since the last commit
+    ///
+    /// Runs git diff on the stored files in /sprout/symlinks; copies are
+    /// compared against their file in the tracking directory
+    #[command(visible_alias = "d")]
+    Diff {
+        /// Only diff this tracked path (or the tracked entries below it)
+        path: Option<PathBuf>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -349,11 +714,18 @@ pub enum EnvCommand {
     ///
     /// Opens interactive menu to select which built modules should be
     /// included in the environment. Modules declare what they `provides`
-    /// for PATH, LD_LIBRARY_PATH, etc.
+    /// for PATH, LD_LIBRARY_PATH, etc. With --add/--remove the environment
+    /// is changed directly, without prompting
     Edit {
         /// Environment name (default: "default")
         #[arg(default_value = "default")]
         environment: String,
+        /// Add a built module to the environment (repeatable)
+        #[arg(long, value_name = "MODULE")]
+        add: Vec<String>,
+        /// Remove a module from the environment (repeatable)
+        #[arg(long, value_name = "MODULE")]
+        remove: Vec<String>,
     },
 
     /// List environments and their modules
@@ -374,154 +746,479 @@ pub enum EnvCommand {
         /// Generate for all built dependencies (ignores environment sets)
         #[arg(long)]
         all: bool,
+        /// Write the script to this file (for `source` in a shell rc) instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
 }
 
+impl ModulesCommand {
+    /// Fold the global --dry-run into this command's own flag
+    pub(crate) fn apply_dry_run(&mut self, global: bool) {
+        match self {
+            Self::Fetch { dry_run, .. }
+            | Self::Build { dry_run, .. }
+            | Self::Install { dry_run, .. }
+            | Self::Update { dry_run, .. }
+            | Self::Clean { dry_run, .. } => *dry_run |= global,
+            _ => {}
+        }
+    }
+}
+
+impl SymlinksCommand {
+    /// Fold the global --dry-run into this command's own flag
+    pub(crate) fn apply_dry_run(&mut self, global: bool) {
+        match self {
+            Self::Add { dry_run, .. }
+            | Self::Restore { dry_run, .. }
+            | Self::Rehash { dry_run, .. }
+            | Self::Undo { dry_run, .. }
+            | Self::Move { dry_run, .. } => *dry_run |= global,
+            _ => {}
+        }
+    }
+}
+
+/// Sections printed by `sprout status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct StatusSections {
+    pub modules: bool,
+    pub symlinks: bool,
+    pub git: bool,
+}
+
+impl StatusSections {
+    /// Select the requested sections, or every section when none was requested
+    pub(crate) fn from_flags(modules: bool, symlinks: bool, git: bool) -> Self {
+        if !modules && !symlinks && !git {
+            Self { modules: true, symlinks: true, git: true }
+        } else {
+            Self { modules, symlinks, git }
+        }
+    }
+}
+
+/// "origin/main"-style label for a pull/push target, defaulting like git does
+fn remote_description(remote: Option<&str>, branch: Option<&str>) -> String {
+    match (remote, branch) {
+        (Some(remote), Some(branch)) => format!("{}/{}", remote, branch),
+        (Some(remote), None) => remote.to_string(),
+        (None, _) => "the default remote".to_string(),
+    }
+}
+
+/// Disable ANSI colors for --no-color, a non-empty NO_COLOR, or a non-TTY stdout
+pub(crate) fn configure_color(choice: ColorChoice) {
+    match choice {
+        ColorChoice::Always => colored::control::set_override(true),
+        ColorChoice::Never => colored::control::set_override(false),
+        ColorChoice::Auto => {
+            let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+            if no_color_env || !atty::is(atty::Stream::Stdout) {
+                colored::control::set_override(false);
+            }
+        }
+    }
+}
+
+/// The global log subscriber for `--log-format`, writing to `writer`
+pub(crate) fn log_subscriber<W>(format: LogFormat, level: tracing_subscriber::filter::LevelFilter, writer: W) -> Box<dyn tracing::Subscriber + Send + Sync>
+where
+    W: for<'a> tracing_subscriber::fmt::MakeWriter<'a> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::FmtSubscriber::builder()
+        .with_max_level(level)
+        .with_writer(writer)
+        .with_target(false);
+
+    match format {
+        LogFormat::Text => Box::new(builder.without_time().finish()),
+        LogFormat::Json => Box::new(builder.json().finish()),
+    }
+}
+
 pub async fn run_cli(cli: Cli) -> Result<()> {
+    configure_color(if cli.no_color { ColorChoice::Never } else { cli.color });
+    let progress = Progress {
+        quiet: cli.quiet,
+        plain: cli.plain || std::env::var("CI").is_ok_and(|v| !v.is_empty() && v != "false"),
+    };
+
     let sprout_path = cli.sprout_path
         .map(|p| p.to_string_lossy().to_string())
         .or_else(|| std::env::var("SPROUT_PATH").ok())
         .unwrap_or_else(|| DEFAULT_SPROUT_PATH.to_string());
+    let manifest_path = resolve_manifest_path(&sprout_path, cli.manifest_path.as_deref());
 
     let verbose = cli.verbose > 0;
+    let dry_run = cli.dry_run;
 
     match cli.command {
-        Commands::Init { path, from, empty } => {
+        Commands::Init { path, from, empty, template } => {
             let init_path = if sprout_path != "/sprout" {
                 &sprout_path
             } else {
                 path.to_str().unwrap()
             };
             
-            if let Some(git_url) = from {
+            if dry_run {
+                println!("Would initialize a sprout directory at {}", init_path);
+            } else if let Some(git_url) = from {
                 init_sprout_from_git(init_path, &git_url)?;
             } else {
-                init_sprout(init_path, empty)?;
+                let template = template.as_deref().unwrap_or("default");
+                init_sprout_with_template(init_path, (!empty).then_some(template))?;
             }
         }
         Commands::Modules { command } => {
-            handle_modules_command(&sprout_path, command, verbose)?;
+            handle_modules_command(&sprout_path, &manifest_path, command, verbose, dry_run, progress)?;
         }
         Commands::Symlinks { command } => {
             let tracking_path = cli.tracking_path
                 .map(|p| p.to_string_lossy().to_string())
                 .or_else(|| dirs::home_dir().map(|p| p.to_string_lossy().to_string()))
                 .context("Could not determine tracking path (HOME directory)")?;
-            handle_symlinks_command(&sprout_path, command, &tracking_path)?;
+            handle_symlinks_command(&sprout_path, command, &tracking_path, dry_run)?;
         }
         Commands::Env { command } => {
-            handle_env_command(&sprout_path, command)?;
+            handle_env_command(&sprout_path, &manifest_path, command, dry_run)?;
         }
-        Commands::Status { all, expand } => {
+        Commands::Status { all, expand, no_expand, modules, symlinks, git } => {
             use colored::Colorize;
 
-            let tracking_path = cli.tracking_path
-                .map(|p| p.to_string_lossy().to_string())
-                .or_else(|| dirs::home_dir().map(|p| p.to_string_lossy().to_string()))
-                .context("Could not determine tracking path (HOME directory)")?;
+            let sections = StatusSections::from_flags(modules, symlinks, git);
+            let mut first = true;
+            let mut header = |title: &str| {
+                let separator = if first { "" } else { "\n" };
+                first = false;
+                println!("{}{}", separator, title.bold());
+            };
 
-            println!("{}", "=== Modules ===".bold());
-            show_status_tree(&sprout_path, expand, all)?;
+            if sections.modules {
+                header("=== Modules ===");
+                show_status_tree(&sprout_path, &manifest_path, expand && !no_expand, all, None, None, None)?;
+            }
+
+            if sections.symlinks {
+                let tracking_path = cli.tracking_path
+                    .map(|p| p.to_string_lossy().to_string())
+                    .or_else(|| dirs::home_dir().map(|p| p.to_string_lossy().to_string()))
+                    .context("Could not determine tracking path (HOME directory)")?;
 
-            println!("\n{}", "=== Symlinks ===".bold());
-            check_symlinks(&sprout_path, all, &tracking_path)?;
+                header("=== Symlinks ===");
+                check_symlinks(&sprout_path, all, &tracking_path)?;
+            }
 
-            println!("\n{}", "=== Git Status ===".bold());
-            crate::core::git_status(&sprout_path)?;
+            if sections.git {
+                header("=== Git Status ===");
+                crate::core::git_status(&sprout_path)?;
+            }
         }
-        Commands::Commit { message, ai } => {
+        Commands::Commit { message, ai, message_template, tag, force_tag, push } => {
+            if dry_run {
+                crate::core::git_status(&sprout_path)?;
+                println!("Would commit the changes above{}{}",
+                    tag.map(|t| format!(", tag them as {}", t)).unwrap_or_default(),
+                    if push { " and push" } else { "" });
+                return Ok(());
+            }
+
+            // Refuse before committing, so a taken tag does not leave an untagged commit behind
+            if let Some(tag) = &tag
+                && !force_tag
+                && crate::core::git_tag_exists(&sprout_path, tag)? {
+                return Err(anyhow::anyhow!("Tag '{}' already exists (use --force-tag to move it)", tag));
+            }
+
             if ai {
-                crate::core::git_commit_ai(&sprout_path).await?;
-            } else if let Some(msg) = message {
-                crate::core::git_commit(&sprout_path, &msg)?;
+                crate::core::git_commit_ai(&sprout_path, message_template.as_deref()).await?;
+            } else if let Some(msg) = &message {
+                crate::core::git_commit(&sprout_path, msg)?;
             } else {
                 crate::core::git_commit_interactive(&sprout_path)?;
             }
+
+            if let Some(tag) = tag {
+                crate::core::git_tag(&sprout_path, &tag, message.as_deref(), force_tag)?;
+            }
+
+            if push {
+                crate::core::git_push(&sprout_path, None, None)?;
+            }
         }
         Commands::Pull { remote, branch } => {
-            crate::core::git_pull(&sprout_path, remote, branch)?;
+            if dry_run {
+                println!("Would pull {} into {}", remote_description(remote.as_deref(), branch.as_deref()), sprout_path);
+            } else {
+                crate::core::git_pull(&sprout_path, remote, branch)?;
+            }
         }
         Commands::Push { remote, branch } => {
-            crate::core::git_push(&sprout_path, remote, branch)?;
+            if dry_run {
+                println!("Would push {} to {}", sprout_path, remote_description(remote.as_deref(), branch.as_deref()));
+            } else {
+                crate::core::git_push(&sprout_path, remote, branch)?;
+            }
         }
-        Commands::Edit { path } => {
+        Commands::Edit { path, no_validate } => {
             let edit_path = path.to_string_lossy();
-            edit_manifest(&edit_path)?;
+            let manifest_path = resolve_manifest_path(&edit_path, cli.manifest_path.as_deref());
+            let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+            if dry_run {
+                println!("Would open {} in {}", manifest_path.display(), editor);
+            } else if no_validate || !atty::is(atty::Stream::Stdin) {
+                // Nobody to ask; a broken edit is reverted
+                edit_manifest(&edit_path, &manifest_path, &editor, !no_validate, &mut |_| Ok(EditRecovery::Restore))?;
+            } else {
+                edit_manifest(&edit_path, &manifest_path, &editor, true, &mut prompt_edit_recovery)?;
+            }
         }
         Commands::Format { path, i } => {
             let format_path = path.to_string_lossy();
-            format_manifest(&format_path, i)?;
+            let manifest_path = resolve_manifest_path(&format_path, cli.manifest_path.as_deref());
+            if let Some(formatted) = format_manifest(&format_path, &manifest_path, i, dry_run)? {
+                print!("{}", formatted);
+            }
+        }
+        Commands::Lint => {
+            lint(&sprout_path, &manifest_path)?;
+        }
+        Commands::Export { file } => {
+            if dry_run {
+                println!("Would export {} to {}", sprout_path, file.display());
+            } else {
+                export_bundle(&sprout_path, &file)?;
+            }
+        }
+        Commands::Import { file, path } => {
+            let target = path
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_else(|| sprout_path.clone());
+            let tracking_path = cli.tracking_path
+                .map(|p| p.to_string_lossy().to_string())
+                .or_else(|| dirs::home_dir().map(|p| p.to_string_lossy().to_string()))
+                .context("Could not determine tracking path (HOME directory)")?;
+
+            if dry_run {
+                println!("Would import {} into {} and restore its symlinks", file.display(), target);
+            } else {
+                import_bundle(&file, &target)?;
+                let summary = restore_symlinks(&target, false, &tracking_path)?;
+                if !summary.failed.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "Imported {} but failed to restore {} symlink(s): {}",
+                        target, summary.failed.len(), summary.failed.join(", ")
+                    ));
+                }
+            }
         }
     }
 
     Ok(())
 }
 
-fn handle_modules_command(sprout_path: &str, command: ModulesCommand, verbose: bool) -> Result<()> {
-    match command {
+/// Modules an `install` batch gave up on, reported once the batch is done
+#[derive(Default)]
+struct InstallFailures {
+    fetch: Vec<String>,
+    build: Vec<String>,
+}
+
+impl InstallFailures {
+    /// Fail with the fetch exit code if any fetch failed, since those modules
+    /// never got to build, otherwise with the build code if any build failed
+    fn into_result(self) -> Result<()> {
+        let mut parts = Vec::new();
+        if !self.fetch.is_empty() {
+            parts.push(format!("Failed to fetch: {}", self.fetch.join(", ")));
+        }
+        if !self.build.is_empty() {
+            parts.push(format!("Failed to build: {}", self.build.join(", ")));
+        }
+        if parts.is_empty() {
+            return Ok(());
+        }
+        let kind = if self.fetch.is_empty() { ErrorKind::Build } else { ErrorKind::Fetch };
+        Err(anyhow::anyhow!(parts.join("\n"))).categorize(kind)
+    }
+}
 
-        ModulesCommand::Fetch { all, packages, dry_run } => {
-            let manifest = load_manifest(sprout_path)?;
+pub(crate) fn handle_modules_command(
+    sprout_path: &str,
+    manifest_path: &Path,
+    mut command: ModulesCommand,
+    verbose: bool,
+    dry_run: bool,
+    progress: Progress,
+) -> Result<()> {
+    command.apply_dry_run(dry_run);
+    match command {
 
-            if all {
-                info!("Fetching all dependencies");
-                for package in &manifest.modules {
-                    if let Err(e) = fetch_package(sprout_path, package, dry_run) {
-                        info!("Skipping {}: {}", package.id(), e);
+        ModulesCommand::Fetch { all, packages, dry_run, force, stop_on_error, no_verify, ref_, print_hash } => {
+            let options = FetchOptions {
+                dry_run,
+                force,
+                from_lock: false,
+                no_verify,
+                manifest_path: Some(manifest_path.to_path_buf()),
+                progress,
+            };
+            let manifest = load_manifest_from(manifest_path, sprout_path)?;
+            let packages = expand_package_patterns(&manifest, &packages)?;
+
+            if print_hash {
+                let selected: Vec<&crate::ast::ModuleBlock> = if all {
+                    manifest.modules.iter().filter(|m| crate::core::deps::has_archive_source(sprout_path, m)).collect()
+                } else if !packages.is_empty() {
+                    packages.iter()
+                        .map(|id| manifest.find_module(id).ok_or_else(|| anyhow::anyhow!("Package not found: {}", id)))
+                        .collect::<Result<_>>()?
+                } else {
+                    return Err(anyhow::anyhow!("Specify --all or one or more package names"));
+                };
+                for package in selected {
+                    if dry_run {
+                        println!("Would {} the archive of {} and print its sha256", if force { "re-download" } else { "read" }, package.id());
+                        continue;
                     }
+                    let hash = print_fetch_hash(sprout_path, package, &options)?;
+                    println!("{}: sha256 = {}", package.id(), hash);
+                }
+            } else if let Some(ref_) = ref_ {
+                let [module_id] = packages.as_slice() else {
+                    return Err(anyhow::anyhow!("--ref needs exactly one package name"));
+                };
+                let package = manifest.find_module(module_id)
+                    .ok_or_else(|| anyhow::anyhow!("Package not found: {}", module_id))?;
+                let source_path = fetch_with_ref(sprout_path, package, &ref_, &options)?;
+                if !dry_run {
+                    println!("Fetched {} at {} into {}", package.id(), ref_, source_path.display());
+                }
+            } else if all {
+                info!("Fetching all dependencies");
+                let summary = fetch_all(sprout_path, &manifest, &options, stop_on_error)?;
+                println!(
+                    "Fetched {}, failed {}, skipped {} (no fetch block)",
+                    summary.succeeded, summary.failed.len(), summary.skipped
+                );
+                if !summary.failed.is_empty() {
+                    return Err(anyhow::anyhow!("Failed to fetch: {}", summary.failed.join(", "))).categorize(ErrorKind::Fetch);
                 }
             } else if !packages.is_empty() {
                 for module_id in packages {
-                    let package = manifest.modules.iter()
-                        .find(|p| p.id() == module_id || p.name == module_id)
+                    let package = manifest.find_module(&module_id)
                         .ok_or_else(|| anyhow::anyhow!("Package not found: {}", module_id))?;
 
-                    fetch_package(sprout_path, package, dry_run)?;
+                    fetch_package(sprout_path, package, &options)?;
                 }
             } else {
                 return Err(anyhow::anyhow!("Specify --all or one or more package names"));
             }
         }
-        ModulesCommand::Build { all, packages, rebuild, dry_run } => {
-            let manifest = load_manifest(sprout_path)?;
+        ModulesCommand::Build { all, packages, tag, rebuild, dry_run, build_jobs, continue_from, check_sources, thorough, memory_limit, cpu_limit, keep_broken, isolated_env } => {
+            let options = BuildOptions {
+                dry_run,
+                rebuild,
+                verbose,
+                jobs: build_jobs,
+                source_check: source_check_mode(check_sources, thorough),
+                limits: ResourceLimits { memory_kb: memory_limit, niceness: cpu_limit },
+                keep_broken,
+                isolated_env,
+                build_cache: build_cache_from_env(),
+                manifest_path: Some(manifest_path.to_path_buf()),
+                progress,
+            };
+            let manifest = load_manifest_from(manifest_path, sprout_path)?;
+            let packages = select_modules(&manifest, &packages, tag.as_deref())?;
 
             if all {
                 info!("Building all dependencies");
-                let ordered_modules = resolve_dependency_order(&manifest)?;
+                let mut ordered_modules = resolve_dependency_order(&manifest)?;
+                if let Some(start) = &continue_from {
+                    ordered_modules = crate::core::deps::continue_from(sprout_path, &manifest, ordered_modules, start, &options)?;
+                    info!("Continuing from {}", start);
+                }
 
-                for package in ordered_modules {
-                    if let Err(e) = build_package(sprout_path, package, dry_run, rebuild, verbose) {
-                        warn!("Failed to build {}: {}", package.id(), e);
+                if dry_run {
+                    for (package, decision) in plan_builds(sprout_path, &manifest, &ordered_modules, &options)? {
+                        println!("{} {}", decision, package.id());
+                    }
+                } else {
+                    let failed = build_queue(sprout_path, ordered_modules, &options);
+                    if !failed.is_empty() {
+                        let ids: Vec<String> = failed.into_iter().map(|(id, _)| id).collect();
+                        return Err(anyhow::anyhow!("Failed to build: {}", ids.join(", "))).categorize(ErrorKind::Build);
                     }
                 }
             } else if !packages.is_empty() {
                 for module_id in packages {
-                    let package = manifest.modules.iter()
-                        .find(|p| p.id() == module_id || p.name == module_id)
+                    let package = manifest.find_module(&module_id)
                         .ok_or_else(|| anyhow::anyhow!("Package not found: {}", module_id))?;
 
-                    build_package(sprout_path, package, dry_run, rebuild, verbose)?;
+                    build_package(sprout_path, package, &options)?;
                 }
             } else {
                 return Err(anyhow::anyhow!("Specify --all or one or more package names"));
             }
         }
-        ModulesCommand::Install { all, packages, with_deps, rebuild, dry_run } => {
-            let manifest = load_manifest(sprout_path)?;
+        ModulesCommand::Install { all, packages, tag, with_deps, rebuild, dry_run, build_jobs, from_lock, no_verify, only_fetch, only_build, check_sources, thorough, memory_limit, cpu_limit, keep_broken, isolated_env } => {
+            let fetch_options = FetchOptions {
+                dry_run,
+                force: false,
+                from_lock,
+                no_verify,
+                manifest_path: Some(manifest_path.to_path_buf()),
+                progress,
+            };
+            let build_options = BuildOptions {
+                dry_run,
+                rebuild,
+                verbose,
+                jobs: build_jobs,
+                source_check: source_check_mode(check_sources, thorough),
+                limits: ResourceLimits { memory_kb: memory_limit, niceness: cpu_limit },
+                keep_broken,
+                isolated_env,
+                build_cache: build_cache_from_env(),
+                manifest_path: Some(manifest_path.to_path_buf()),
+                progress,
+            };
+            let manifest = load_manifest_from(manifest_path, sprout_path)?;
+            let packages = select_modules(&manifest, &packages, tag.as_deref())?;
+            let (fetch, build) = (!only_build, !only_fetch);
+
+            if from_lock {
+                let requested: Vec<String> = if all {
+                    manifest.modules.iter().map(|m| m.id()).collect()
+                } else if with_deps {
+                    packages.iter().flat_map(|p| manifest.get_all_dependencies(p)).collect()
+                } else {
+                    packages.clone()
+                };
+                check_locked_commits(sprout_path, &manifest, &requested)?;
+            }
 
             if all {
                 info!("Installing all dependencies");
                 let ordered_modules = resolve_dependency_order(&manifest)?;
+                let mut batch = BatchProgress::start(ordered_modules.len(), progress);
+                let mut failures = InstallFailures::default();
 
                 for package in ordered_modules {
-                    if package.fetch.is_some() && let Err(e) = fetch_package(sprout_path, package, dry_run) {
+                    batch.next(&package.id());
+                    if fetch && package.fetch.is_some() && let Err(e) = fetch_package(sprout_path, package, &fetch_options) {
                         warn!("Failed to fetch {}: {}", package.id(), e);
+                        failures.fetch.push(package.id());
                         continue;
                     }
-                    if let Err(e) = build_package(sprout_path, package, dry_run, rebuild, verbose) {
+                    if build && let Err(e) = build_package(sprout_path, package, &build_options) {
                         warn!("Failed to build {}: {}", package.id(), e);
+                        failures.build.push(package.id());
                     }
                 }
+                failures.into_result()?;
             } else if !packages.is_empty() {
                 if with_deps {
                     // Collect all packages and their dependencies
@@ -540,13 +1237,16 @@ fn handle_modules_command(sprout_path: &str, command: ModulesCommand, verbose: b
                         .collect();
 
                     let lock = crate::lockfile::SproutLock::load(sprout_path)?;
+                    let mut batch = BatchProgress::start(packages_to_install.len(), progress);
+                    let mut failures = InstallFailures::default();
                     for package in packages_to_install {
+                        batch.next(&package.id());
                         // Skip if already built and not rebuilding
                         if !rebuild {
                             let dist_path = Path::new(sprout_path).join("dist").join(package.id());
                             if dist_path.exists() {
                                 if let Some(state) = lock.get_module_state(&package.id()) {
-                                    let current_hash = crate::core::deps::compute_build_hash(package);
+                                    let current_hash = build_options.build_hash(package);
                                     if current_hash == state.build_hash {
                                         info!("Package {} is already up-to-date, skipping", package.id());
                                         continue;
@@ -555,34 +1255,36 @@ fn handle_modules_command(sprout_path: &str, command: ModulesCommand, verbose: b
                             }
                         }
 
-                        if package.fetch.is_some() {
-                            if let Err(e) = fetch_package(sprout_path, package, dry_run) {
+                        if fetch && package.fetch.is_some() {
+                            if let Err(e) = fetch_package(sprout_path, package, &fetch_options) {
                                 warn!("Failed to fetch {}: {}", package.id(), e);
+                                failures.fetch.push(package.id());
                                 continue;
                             }
                         }
-                        if let Err(e) = build_package(sprout_path, package, dry_run, rebuild, verbose) {
+                        if build && let Err(e) = build_package(sprout_path, package, &build_options) {
                             warn!("Failed to build {}: {}", package.id(), e);
+                            failures.build.push(package.id());
                         }
                     }
+                    failures.into_result()?;
                 } else {
                     // Install only specified packages without dependencies
                     let lock = crate::lockfile::SproutLock::load(sprout_path)?;
                     
                     for module_id in &packages {
-                        let package = manifest.modules.iter()
-                            .find(|p| p.id() == *module_id || p.name == *module_id)
+                        let package = manifest.find_module(module_id)
                             .ok_or_else(|| anyhow::anyhow!("Package not found: {}", module_id))?;
 
-                        // Check all dependencies are built
-                        for dep_id in &package.depends_on {
-                            let dist_path = Path::new(sprout_path).join("dist").join(dep_id);
+                        // Check all dependencies are built (fetching alone does not need them)
+                        for dep_ref in package.depends_on.iter().filter(|_| build) {
+                            let dep_module = manifest.find_module(dep_ref)
+                                .ok_or_else(|| anyhow::anyhow!("Dependency not found: {}", dep_ref))?;
+                            let dep_id = dep_module.id();
+                            let dist_path = Path::new(sprout_path).join("dist").join(&dep_id);
                             let dep_built = if dist_path.exists() {
-                                if let Some(state) = lock.get_module_state(dep_id) {
-                                    let dep_module = manifest.modules.iter()
-                                        .find(|m| m.id() == *dep_id)
-                                        .ok_or_else(|| anyhow::anyhow!("Dependency not found: {}", dep_id))?;
-                                    let current_hash = crate::core::deps::compute_build_hash(dep_module);
+                                if let Some(state) = lock.get_module_state(&dep_id) {
+                                    let current_hash = build_options.build_hash(dep_module);
                                     current_hash == state.build_hash && state.build_hash.is_some()
                                 } else {
                                     false
@@ -601,27 +1303,28 @@ fn handle_modules_command(sprout_path: &str, command: ModulesCommand, verbose: b
                     }
 
                     for module_id in packages {
-                        let package = manifest.modules.iter()
-                            .find(|p| p.id() == module_id || p.name == module_id)
+                        let package = manifest.find_module(&module_id)
                             .ok_or_else(|| anyhow::anyhow!("Package not found: {}", module_id))?;
 
-                        if package.fetch.is_some() {
-                            fetch_package(sprout_path, package, dry_run)?;
+                        if fetch && package.fetch.is_some() {
+                            fetch_package(sprout_path, package, &fetch_options)?;
+                        }
+                        if build {
+                            build_package(sprout_path, package, &build_options)?;
                         }
-                        build_package(sprout_path, package, dry_run, rebuild, verbose)?;
                     }
                 }
             } else {
                 return Err(anyhow::anyhow!("Specify --all or one or more package names"));
             }
         }
-        ModulesCommand::Status { expand, all } => {
-            show_status_tree(sprout_path, expand, all)?;
+        ModulesCommand::Status { expand, no_expand, all, filter, tag, depth } => {
+            show_status_tree(sprout_path, manifest_path, expand && !no_expand, all, filter.as_deref(), tag.as_deref(), depth)?;
         }
         ModulesCommand::Hash { i, fetch, build } => {
             use crate::core::deps::{compute_fetch_hash, compute_build_hash};
 
-            let manifest = load_manifest(sprout_path)?;
+            let manifest = load_manifest_from(manifest_path, sprout_path)?;
             let mut lock = SproutLock::load(sprout_path)?;
 
             let compute_fetch = fetch || !build;
@@ -634,7 +1337,10 @@ fn handle_modules_command(sprout_path: &str, command: ModulesCommand, verbose: b
                     && let Some(hash) = compute_fetch_hash(module) {
                         if i {
                             let mut state = lock.get_module_state(&module_id).cloned()
-                                .unwrap_or(PackageState { fetch_hash: None, build_hash: None });
+                                .unwrap_or(PackageState { fetch_hash: None, build_hash: None, resolved_commit: None, source_hash: None });
+                            if dry_run {
+                                println!("Would record {} fetch_hash: {}", module_id, hash);
+                            }
                             state.fetch_hash = Some(hash);
                             lock.set_module_state(module_id.clone(), state);
                         } else {
@@ -646,7 +1352,10 @@ fn handle_modules_command(sprout_path: &str, command: ModulesCommand, verbose: b
                     && let Some(hash) = compute_build_hash(module) {
                         if i {
                             let mut state = lock.get_module_state(&module_id).cloned()
-                                .unwrap_or(PackageState { fetch_hash: None, build_hash: None });
+                                .unwrap_or(PackageState { fetch_hash: None, build_hash: None, resolved_commit: None, source_hash: None });
+                            if dry_run {
+                                println!("Would record {} build_hash: {}", module_id, hash);
+                            }
                             state.build_hash = Some(hash);
                             lock.set_module_state(module_id.clone(), state);
                         } else {
@@ -655,60 +1364,189 @@ fn handle_modules_command(sprout_path: &str, command: ModulesCommand, verbose: b
                     }
             }
 
-            if i {
+            if i && dry_run {
+                println!("Would update lockfile.");
+            } else if i {
                 lock.save(sprout_path)?;
                 println!("Updated lockfile.");
             }
         }
-        ModulesCommand::Clean { dry_run } => {
-            clean_unused_directories(sprout_path, dry_run)?;
+        ModulesCommand::Why { target, from } => {
+            explain_module(sprout_path, manifest_path, &target, from.as_deref())?;
+        }
+        ModulesCommand::Deps { package, direct } => {
+            let manifest = load_manifest_from(manifest_path, sprout_path)?;
+            for id in module_dependencies(&manifest, &package, direct)? {
+                println!("{}", id);
+            }
+        }
+        ModulesCommand::Graph { dot } => {
+            let manifest = load_manifest_from(manifest_path, sprout_path)?;
+            let lock = SproutLock::load(sprout_path)?;
+            print!("{}", render_dependency_graph(sprout_path, &manifest, &lock, dot)?);
+        }
+        ModulesCommand::History { package, limit } => {
+            // Modules removed from the manifest still have history under their id
+            let module_id = match &package {
+                Some(reference) => Some(load_manifest_from(manifest_path, sprout_path)?
+                    .find_module(reference)
+                    .map(|m| m.id())
+                    .unwrap_or_else(|| reference.clone())),
+                None => None,
+            };
+            let history = read_build_history(sprout_path, module_id.as_deref())?;
+            if history.is_empty() {
+                println!("No builds recorded yet.");
+            }
+            print!("{}", render_build_history(&history[history.len().saturating_sub(limit)..]));
+        }
+        ModulesCommand::Outdated => {
+            check_outdated(sprout_path, manifest_path)?;
+        }
+        ModulesCommand::Update { packages, dry_run } => {
+            let manifest = load_manifest_from(manifest_path, sprout_path)?;
+            let packages = if packages.is_empty() {
+                manifest.modules.iter().filter(|m| m.update.is_some()).map(|m| m.id()).collect()
+            } else {
+                expand_package_patterns(&manifest, &packages)?
+            };
+            for package in &packages {
+                let module = manifest.find_module(package)
+                    .ok_or_else(|| anyhow::anyhow!("Package not found: {}", package))?;
+                update_package(sprout_path, module, dry_run, progress)?;
+            }
+        }
+        ModulesCommand::Verify { fix } => {
+            let manifest = load_manifest_from(manifest_path, sprout_path)?;
+            let corrupt = verify_caches(sprout_path, &manifest, fix && !dry_run)?;
+            if corrupt.is_empty() {
+                println!("All cached archives match their sha256.");
+            } else if fix && dry_run {
+                println!("Would remove {} corrupt cache(s): {}", corrupt.len(), corrupt.join(", "));
+            } else if fix {
+                println!("Removed {} corrupt cache(s): {}", corrupt.len(), corrupt.join(", "));
+            } else {
+                return Err(anyhow::anyhow!(
+                    "{} corrupt cache(s): {} (rerun with --fix to remove them)",
+                    corrupt.len(),
+                    corrupt.join(", ")
+                ));
+            }
+        }
+        ModulesCommand::Pin { package } => {
+            if dry_run {
+                println!("Would pin {} to the commit it is fetched at", package);
+            } else {
+                pin_module(sprout_path, manifest_path, &package, progress)?;
+            }
+        }
+        ModulesCommand::Rename { old, new } => {
+            rename_module(sprout_path, manifest_path, &old, &new, dry_run)?;
+        }
+        ModulesCommand::Clean { dry_run, filter, orphans, yes } => {
+            clean_unused_directories(sprout_path, manifest_path, dry_run, filter.as_deref(), orphans, yes, atty::is(atty::Stream::Stdin))?;
+        }
+        ModulesCommand::Size => {
+            let manifest = load_manifest_from(manifest_path, sprout_path)?;
+            print!("{}", render_module_sizes(&module_sizes(sprout_path, &manifest)?));
         }
     }
 
     Ok(())
 }
 
-fn handle_symlinks_command(sprout_path: &str, command: SymlinksCommand, tracking_path: &str) -> Result<()> {
+fn handle_symlinks_command(sprout_path: &str, mut command: SymlinksCommand, tracking_path: &str, dry_run: bool) -> Result<()> {
+    command.apply_dry_run(dry_run);
     match command {
-        SymlinksCommand::Add { path, recursive, dry_run } => {
-            info!("Adding symlink: {} (recursive: {}, dry_run: {})", path.display(), recursive, dry_run);
-            add_file(sprout_path, path, recursive, dry_run, tracking_path)?;
+        SymlinksCommand::Add { paths, recursive, copy, force, dry_run } => {
+            info!("Adding {} symlink(s) (recursive: {}, copy: {}, force: {}, dry_run: {})", paths.len(), recursive, copy, force, dry_run);
+            add_files(sprout_path, paths, recursive, copy, force, dry_run, tracking_path)?;
         }
         SymlinksCommand::Status { all } => {
             info!("Checking symlinks (show_all: {})", all);
             check_symlinks(sprout_path, all, tracking_path)?;
         }
-        SymlinksCommand::Restore { dry_run } => {
-            info!("Restoring symlinks (dry_run: {})", dry_run);
-            restore_symlinks(sprout_path, dry_run, tracking_path)?;
+        SymlinksCommand::List { json } => {
+            list_symlinks(sprout_path, json)?;
+        }
+        SymlinksCommand::Restore { dry_run, interactive } => {
+            info!("Restoring symlinks (dry_run: {}, interactive: {})", dry_run, interactive);
+            let summary = if interactive {
+                restore_symlinks_with(sprout_path, dry_run, tracking_path, &mut prompt_restore_conflict)?
+            } else {
+                restore_symlinks(sprout_path, dry_run, tracking_path)?
+            };
+            if !summary.failed.is_empty() {
+                return Err(anyhow::anyhow!("Failed to restore {} symlink(s)", summary.failed.len()));
+            }
+        }
+        SymlinksCommand::Rehash { discover, include_untracked, prune, dry_run } => {
+            info!("Rehashing symlinks (discover: {}, prune: {}, dry_run: {})", discover, prune, dry_run);
+            if prune && !dry_run {
+                let dead = find_dead_symlinks(sprout_path, tracking_path)?;
+                if !dead.is_empty() {
+                    println!("The following entries no longer exist and will be removed from the lockfile:");
+                    for path in &dead {
+                        println!("  {}", path);
+                    }
+                    let confirmed = dialoguer::Confirm::new()
+                        .with_prompt("Prune these entries?")
+                        .default(false)
+                        .interact()?;
+                    if !confirmed {
+                        println!("Aborted.");
+                        return Ok(());
+                    }
+                }
+            }
+            rehash_symlinks(sprout_path, tracking_path, discover, include_untracked, prune, dry_run)?;
         }
-        SymlinksCommand::Rehash { discover, dry_run } => {
-            info!("Rehashing symlinks (discover: {}, dry_run: {})", discover, dry_run);
-            rehash_symlinks(sprout_path, tracking_path, discover, dry_run)?;
+        SymlinksCommand::Verify => {
+            verify_symlinks(sprout_path, tracking_path)?;
         }
         SymlinksCommand::Undo { path, dry_run } => {
             info!("Undoing symlink: {} (dry_run: {})", path.display(), dry_run);
             undo_symlink(sprout_path, path, dry_run, tracking_path)?;
         }
+        SymlinksCommand::Move { old, new, dry_run } => {
+            move_symlink(sprout_path, &old, &new, dry_run, tracking_path)?;
+        }
+        SymlinksCommand::Diff { path } => {
+            let diff = diff_symlinks(sprout_path, path.as_deref(), tracking_path)?;
+            if diff.is_empty() {
+                println!("No changes to tracked files.");
+            } else {
+                print!("{}", diff);
+            }
+        }
     }
 
     Ok(())
 }
 
-fn handle_env_command(sprout_path: &str, command: EnvCommand) -> Result<()> {
+fn handle_env_command(sprout_path: &str, manifest_path: &Path, command: EnvCommand, dry_run: bool) -> Result<()> {
     match command {
-        EnvCommand::Edit { environment } => {
-            env_edit_interactive(sprout_path, &environment)?;
+        EnvCommand::Edit { environment, add, remove } => {
+            if add.is_empty() && remove.is_empty() {
+                if dry_run {
+                    println!("Would open environment '{}' for editing", environment);
+                } else {
+                    env_edit_interactive(sprout_path, manifest_path, &environment)?;
+                }
+            } else {
+                env_edit_modules(sprout_path, manifest_path, &environment, &add, &remove, dry_run)?;
+            }
         }
         EnvCommand::List { environment } => {
-            let manifest = load_manifest(sprout_path)?;
+            let manifest = load_manifest_from(manifest_path, sprout_path)?;
 
             if let Some(environments) = &manifest.environments {
                 if let Some(env_name) = environment {
                     if let Some(modules) = environments.environments.get(&env_name) {
                         println!("Environment '{}':", env_name);
-                        for package in modules {
-                            println!("  {}", package);
+                        for package in &expand_environment_modules(sprout_path, &manifest, modules)? {
+                            let suffix = manifest.find_module(package).map(description_suffix).unwrap_or_default();
+                            println!("  {}{}", package, suffix);
                         }
                     } else {
                         println!("Environment '{}' not found.", env_name);
@@ -717,8 +1555,9 @@ fn handle_env_command(sprout_path: &str, command: EnvCommand) -> Result<()> {
                     println!("Environments:");
                     for (name, modules) in &environments.environments {
                         println!("  {}:", name);
-                        for package in modules {
-                            println!("    {}", package);
+                        for package in &expand_environment_modules(sprout_path, &manifest, modules)? {
+                            let suffix = manifest.find_module(package).map(description_suffix).unwrap_or_default();
+                            println!("    {}{}", package, suffix);
                         }
                     }
                 }
@@ -726,8 +1565,8 @@ fn handle_env_command(sprout_path: &str, command: EnvCommand) -> Result<()> {
                 println!("No environments defined.");
             }
         }
-        EnvCommand::Generate { environment, all } => {
-            let manifest = load_manifest(sprout_path)?;
+        EnvCommand::Generate { environment, all, output } => {
+            let manifest = load_manifest_from(manifest_path, sprout_path)?;
             let env_name = environment.as_deref().unwrap_or("default");
 
             if all {
@@ -735,86 +1574,18 @@ fn handle_env_command(sprout_path: &str, command: EnvCommand) -> Result<()> {
                 info!("Generating environment for all built modules");
                 // TODO: Implement environment generation for all modules
                 warn!("env generate --all not yet implemented");
-            } else if let Some(environments) = &manifest.environments {
-                if let Some(modules) = environments.environments.get(env_name) {
-                    println!("# Environment: {}", env_name);
-                    
-                    // Guard to prevent loading environment multiple times in nested shells.
-                    // Without this, each time the shell config is sourced (e.g., exec zsh),
-                    // every `prepend`/`append` entry would re-accumulate onto the existing
-                    // value, growing PATH-like variables with duplicate segments. (Scalar
-                    // `set` entries are idempotent and unaffected.)
-                    println!("# Guard to prevent loading multiple times");
-                    println!("if [ -n \"$SPROUT_ENV_LOADED\" ]; then");
-                    println!("  return 0 2>/dev/null || :");
-                    println!("fi");
-                    println!("export SPROUT_ENV_LOADED=1");
-                    println!();
-
-                    // Collect contributions per variable, carrying the mode each
-                    // module declared for it. A variable may be contributed by several
-                    // modules (e.g. every module adds to PATH); their modes are expected
-                    // to agree, and a conflict is surfaced rather than silently resolved.
-                    use std::collections::HashMap;
-                    use crate::ast::ExportMode;
-                    let mut contributions: HashMap<String, (ExportMode, Vec<String>)> = HashMap::new();
-
-                    for module_id in modules {
-                        if let Some(package) = manifest.modules.iter().find(|p| p.id() == *module_id) {
-                            let dist_path = std::path::Path::new(sprout_path).join("dist").join(package.id());
-
-                            for export in &package.provides {
-                                let full_path = dist_path.join(export.value.trim_start_matches('/'));
-                                let entry = contributions
-                                    .entry(export.name.clone())
-                                    .or_insert_with(|| (export.mode, Vec::new()));
-                                if entry.0 != export.mode {
-                                    warn!(
-                                        "Conflicting modes for env var '{}'; keeping '{}', ignoring '{}'",
-                                        export.name, entry.0.keyword(), export.mode.keyword()
-                                    );
-                                }
-                                entry.1.push(full_path.display().to_string());
-                            }
-                        }
+            } else {
+                let script = generate_environment(sprout_path, &manifest, env_name)?;
+                match output {
+                    Some(path) if dry_run => {
+                        println!("Would write environment '{}' to {}", env_name, path.display());
                     }
-
-                    // Generate one export statement per variable, shaped by its mode.
-                    let mut sorted_vars: Vec<_> = contributions.keys().cloned().collect();
-                    sorted_vars.sort();
-
-                    for var in &sorted_vars {
-                        let (mode, values) = &contributions[var];
-                        debug_assert!(!values.is_empty(), "every collected var has at least one value");
-
-                        match mode {
-                            // Scalar: assign directly. Re-sourcing is idempotent and can
-                            // never accumulate into "value:value". Multiple definitions are
-                            // a conflict; the last deterministically wins.
-                            ExportMode::Set => {
-                                if values.len() > 1 {
-                                    warn!("Multiple 'set' definitions for env var '{}'; using the last", var);
-                                }
-                                let value = values.last().expect("non-empty checked above");
-                                println!("export {}=\"{}\"", var, value);
-                            }
-                            // Search path, this activation's entries take precedence.
-                            ExportMode::Prepend => {
-                                let joined = values.join(":");
-                                println!("export {}=\"{}${{{}:+:${{{}}}}}\"", var, joined, var, var);
-                            }
-                            // Search path, existing entries take precedence.
-                            ExportMode::Append => {
-                                let joined = values.join(":");
-                                println!("export {}=\"${{{}:+${{{}}}:}}{}\"", var, var, var, joined);
-                            }
-                        }
+                    Some(path) => {
+                        write_environment_file(&path, &script)?;
+                        info!("Wrote environment '{}' to {}", env_name, path.display());
                     }
-                } else {
-                    return Err(anyhow::anyhow!("Environment '{}' not found", env_name));
+                    None => print!("{}", script),
                 }
-            } else {
-                return Err(anyhow::anyhow!("No environments defined"));
             }
         }
     }
@@ -822,171 +1593,828 @@ fn handle_env_command(sprout_path: &str, command: EnvCommand) -> Result<()> {
     Ok(())
 }
 
-fn edit_manifest(sprout_path: &str) -> Result<()> {
-    use std::process::Command;
+/// What to do with an edit that left the manifest unparseable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EditRecovery {
+    /// Open the editor again on the broken file
+    Reopen,
+    /// Put the pre-edit manifest back
+    Restore,
+    /// Leave the broken file, with the pre-edit manifest saved next to it
+    Keep,
+}
+
+impl EditRecovery {
+    const ALL: [EditRecovery; 3] = [EditRecovery::Reopen, EditRecovery::Restore, EditRecovery::Keep];
+
+    fn label(self) -> &'static str {
+        match self {
+            EditRecovery::Reopen => "Re-open the editor",
+            EditRecovery::Restore => "Discard the edit and restore the previous manifest",
+            EditRecovery::Keep => "Keep the broken manifest",
+        }
+    }
+}
 
-    let manifest_path = Path::new(sprout_path).join("manifest.sprout");
+/// Open the manifest in `editor`. Unless `validate` is off, an edit that does
+/// not parse is handed to `recover` until it is fixed, reverted or kept
+pub(crate) fn edit_manifest(
+    sprout_path: &str,
+    manifest_path: &Path,
+    editor: &str,
+    validate: bool,
+    recover: &mut dyn FnMut(&anyhow::Error) -> Result<EditRecovery>,
+) -> Result<()> {
+    use std::process::Command;
 
     if !manifest_path.exists() {
         return Err(anyhow::anyhow!("Manifest not found: {}", manifest_path.display()));
     }
+    let original = std::fs::read(manifest_path)?;
+    let mut reopened = false;
+
+    loop {
+        let status = Command::new(editor)
+            .arg(manifest_path)
+            .status()
+            .with_context(|| format!("Failed to launch editor: {}", editor))?;
+
+        if !status.success() {
+            if reopened {
+                // What is on disk is the broken edit the editor was re-opened for
+                std::fs::write(manifest_path, &original)?;
+                eprintln!("Restored the manifest as it was before editing.");
+            }
+            return Err(anyhow::anyhow!("Editor exited with error"));
+        }
+        if !validate {
+            println!("Manifest saved without validation.");
+            return Ok(());
+        }
 
-    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+        // Validate the manifest after editing
+        let e = match load_manifest_from(manifest_path, sprout_path) {
+            Ok(_) => {
+                println!("Manifest edited successfully.");
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+        eprintln!("Warning: Manifest has syntax errors:\n{}", e.root_cause());
+
+        match recover(&e)? {
+            EditRecovery::Reopen => {
+                reopened = true;
+                continue;
+            }
+            EditRecovery::Restore => {
+                std::fs::write(manifest_path, &original)?;
+                eprintln!("Restored the manifest as it was before editing.");
+            }
+            EditRecovery::Keep => {
+                let mut backup = manifest_path.as_os_str().to_os_string();
+                backup.push(".bak");
+                std::fs::write(&backup, &original)?;
+                eprintln!("Kept the broken manifest; the previous version is at {}", PathBuf::from(backup).display());
+            }
+        }
+        return Err(e);
+    }
+}
 
-    let status = Command::new(&editor)
-        .arg(&manifest_path)
-        .status()
-        .with_context(|| format!("Failed to launch editor: {}", editor))?;
+fn prompt_edit_recovery(_error: &anyhow::Error) -> Result<EditRecovery> {
+    let labels: Vec<&str> = EditRecovery::ALL.iter().map(|c| c.label()).collect();
+    let selection = dialoguer::Select::new()
+        .with_prompt("The edited manifest does not parse")
+        .items(&labels)
+        .default(0)
+        .interact()?;
+    Ok(EditRecovery::ALL[selection])
+}
 
-    if !status.success() {
-        return Err(anyhow::anyhow!("Editor exited with error"));
-    }
+/// A SHA256 computed by `format`, with the directory rename it implies
+pub(crate) struct PendingHash {
+    pub module_id: String,
+    pub sha256: String,
+    pub old_dir_name: String,
+    pub new_dir_name: String,
+}
 
-    // Validate the manifest after editing
-    match load_manifest(sprout_path) {
-        Ok(_) => {
-            println!("Manifest edited successfully.");
-            Ok(())
+/// Fill in missing HTTP and GitHub SHA256 hashes from cached archives, without touching disk
+///
+/// Returns the raw manifest (so `${VAR}` references survive formatting) with
+/// the hashes applied, plus the cache/source renames needed to match them.
+pub(crate) fn compute_missing_hashes(sprout_path: &str, manifest_path: &Path) -> Result<(crate::ast::SproutManifest, Vec<PendingHash>)> {
+    // Paths and hashes derive from the expanded manifest, but the raw one is
+    // what gets written back
+    let expanded = load_manifest_from(manifest_path, sprout_path)?;
+    let mut manifest = load_manifest_raw_from(manifest_path)?;
+    let mut pending = Vec::new();
+
+    for (raw_module, expanded_module) in manifest.modules.iter_mut().zip(&expanded.modules) {
+        let mut module = expanded_module.clone();
+        let module_id = module.id();
+        // A github release's file is named after the asset URL its fetch resolved to
+        let (url, sha256) = match module.fetch.as_mut().map(|f| &mut f.spec) {
+            Some(crate::ast::FetchSpec::Http(http_spec)) => (Some(http_spec.url.clone()), &mut http_spec.sha256),
+            Some(crate::ast::FetchSpec::Github(github_spec)) => {
+                (crate::core::deps::cached_github_url(sprout_path, github_spec), &mut github_spec.sha256)
+            }
+            _ => continue,
+        };
+        let Some(url) = url.filter(|_| sha256.is_none()) else {
+            continue;
+        };
+
+        let original_filename = url.split('/').next_back().unwrap_or("archive").to_string();
+
+        // Compute old hash before adding SHA256
+        let old_fetch_hash = crate::core::deps::compute_fetch_hash(expanded_module)
+            .map(|h| h[..8].to_string())
+            .unwrap_or_else(|| "no-fetch".to_string());
+        let old_dir_name = format!("{}-{}", module_id, old_fetch_hash);
+        let cache_path = Path::new(sprout_path).join("cache/http").join(&old_dir_name).join(&original_filename);
+
+        if !cache_path.exists() {
+            continue;
         }
-        Err(e) => {
-            eprintln!("Warning: Manifest has syntax errors: {}", e);
-            eprintln!("Please fix the errors and try again.");
-            Err(e)
+
+        use sha2::{Sha256, Digest};
+        let mut file = std::fs::File::open(&cache_path)?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        let hash = format!("{:x}", hasher.finalize());
+
+        *sha256 = Some(hash.clone());
+        match raw_module.fetch.as_mut().map(|f| &mut f.spec) {
+            Some(crate::ast::FetchSpec::Http(raw_http_spec)) => raw_http_spec.sha256 = Some(hash.clone()),
+            Some(crate::ast::FetchSpec::Github(raw_github_spec)) => raw_github_spec.sha256 = Some(hash.clone()),
+            _ => {}
         }
+
+        // Compute new hash after adding SHA256
+        let new_fetch_hash = crate::core::deps::compute_fetch_hash(&module)
+            .map(|h| h[..8].to_string())
+            .unwrap_or_else(|| "no-fetch".to_string());
+
+        pending.push(PendingHash {
+            module_id: module_id.clone(),
+            sha256: hash,
+            old_dir_name,
+            new_dir_name: format!("{}-{}", module_id, new_fetch_hash),
+        });
     }
+
+    Ok((manifest, pending))
 }
 
-fn format_manifest(sprout_path: &str, in_place: bool) -> Result<()> {
-    let mut manifest = load_manifest(sprout_path)?;
-    let mut updated_modules = Vec::new();
+/// Reformat the manifest and fill in missing SHA256 hashes. Without
+/// `in_place` the formatted manifest is returned for stdout and nothing on
+/// disk changes
+pub(crate) fn format_manifest(sprout_path: &str, manifest_path: &Path, in_place: bool, dry_run: bool) -> Result<Option<String>> {
+    let (manifest, pending) = compute_missing_hashes(sprout_path, manifest_path)?;
 
-    // Compute and add missing SHA256 hashes for HTTP archives
-    for module in &mut manifest.modules {
-        if let Some(fetch) = &module.fetch {
-            if let crate::ast::FetchSpec::Http(http_spec) = &fetch.spec {
-                if http_spec.sha256.is_none() {
-                    // Compute old hash before adding SHA256
-                    let old_fetch_hash = crate::core::deps::compute_fetch_hash(module)
-                        .map(|h| h[..8].to_string())
-                        .unwrap_or_else(|| "no-fetch".to_string());
-
-                    let module_id = module.id();
-                    let old_dir_name = format!("{}-{}", module_id, old_fetch_hash);
-                    let cache_dir = std::path::Path::new(sprout_path).join("cache/http").join(&old_dir_name);
-                    let original_filename = http_spec.url.split('/').next_back().unwrap_or("archive");
-                    let cache_path = cache_dir.join(original_filename);
-
-                    if cache_path.exists() {
-                        use sha2::{Sha256, Digest};
-                        let mut file = std::fs::File::open(&cache_path)?;
-                        let mut hasher = Sha256::new();
-                        std::io::copy(&mut file, &mut hasher)?;
-                        let hash = format!("{:x}", hasher.finalize());
-
-                        println!("Adding SHA256 for {}: {}.", module_id, hash);
-
-                        // Update the module
-                        if let Some(fetch_mut) = &mut module.fetch {
-                            if let crate::ast::FetchSpec::Http(http_spec_mut) = &mut fetch_mut.spec {
-                                http_spec_mut.sha256 = Some(hash);
-
-                                // Compute new hash after adding SHA256
-                                let new_fetch_hash = crate::core::deps::compute_fetch_hash(module)
-                                    .map(|h| h[..8].to_string())
-                                    .unwrap_or_else(|| "no-fetch".to_string());
-                                let new_dir_name = format!("{}-{}", module_id, new_fetch_hash);
-
-                                // Rename directories only in in-place mode
-                                if in_place {
-                                    // Rename cache directory
-                                    let old_cache_dir = std::path::Path::new(sprout_path).join("cache/http").join(&old_dir_name);
-                                    let new_cache_dir = std::path::Path::new(sprout_path).join("cache/http").join(&new_dir_name);
-                                    if old_cache_dir.exists() && !new_cache_dir.exists() {
-                                        std::fs::rename(&old_cache_dir, &new_cache_dir)?;
-                                        println!("  Renamed cache: {} -> {}.", old_dir_name, new_dir_name);
-                                    }
+    if in_place && dry_run {
+        for hash in &pending {
+            println!("Would add SHA256 for {}: {} ({} -> {}).", hash.module_id, hash.sha256, hash.old_dir_name, hash.new_dir_name);
+        }
+        println!("Would write the formatted manifest to {}.", manifest_path.display());
+        return Ok(None);
+    }
 
-                                    // Rename source directory
-                                    let old_source_dir = std::path::Path::new(sprout_path).join("sources/http").join(&old_dir_name);
-                                    let new_source_dir = std::path::Path::new(sprout_path).join("sources/http").join(&new_dir_name);
-                                    if old_source_dir.exists() && !new_source_dir.exists() {
-                                        std::fs::rename(&old_source_dir, &new_source_dir)?;
-                                        println!("  Renamed source: {} -> {}.", old_dir_name, new_dir_name);
-                                    }
-                                }
+    if !in_place {
+        // Only describe what -i would change on disk
+        if !pending.is_empty() {
+            eprintln!("Note: computed SHA256 for {} module(s); run with -i to rename their cache/source directories and update the lockfile:", pending.len());
+            for hash in &pending {
+                eprintln!("  {}: {} -> {}", hash.module_id, hash.old_dir_name, hash.new_dir_name);
+            }
+        }
+        return Ok(Some(manifest.pretty_print()));
+    }
 
-                                updated_modules.push(module_id);
-                            }
-                        }
-                    }
-                }
+    for hash in &pending {
+        println!("Adding SHA256 for {}: {}.", hash.module_id, hash.sha256);
+
+        // Rename cache and source directories to match the new fetch hash
+        for kind in ["cache/http", "sources/http"] {
+            let old_dir = Path::new(sprout_path).join(kind).join(&hash.old_dir_name);
+            let new_dir = Path::new(sprout_path).join(kind).join(&hash.new_dir_name);
+            if old_dir.exists() && !new_dir.exists() {
+                std::fs::rename(&old_dir, &new_dir)?;
+                println!("  Renamed {}: {} -> {}.", kind, hash.old_dir_name, hash.new_dir_name);
             }
         }
     }
 
-    let updated = !updated_modules.is_empty();
+    save_manifest_to(manifest_path, &manifest)?;
+
+    // Update lockfile with new fetch hashes for updated modules
+    if !pending.is_empty() {
+        let mut lock = SproutLock::load(sprout_path)?;
+        let manifest = load_manifest_from(manifest_path, sprout_path)?;
+        for hash in &pending {
+            if let Some(module) = manifest.modules.iter().find(|m| m.id() == hash.module_id) {
+                let new_fetch_hash = crate::core::deps::compute_fetch_hash(module);
+                let mut state = lock.get_module_state(&hash.module_id)
+                    .cloned()
+                    .unwrap_or(PackageState {
+                        fetch_hash: None,
+                        build_hash: None,
+                        resolved_commit: None,
+                        source_hash: None,
+                    });
+                state.fetch_hash = new_fetch_hash;
+                lock.set_module_state(hash.module_id.clone(), state);
+            }
+        }
+        lock.save(sprout_path)?;
+        println!("Formatted manifest.sprout and added missing SHA256 hashes.");
+    } else {
+        println!("Formatted manifest.sprout.");
+    }
+    Ok(None)
+}
 
-    if in_place {
-        save_manifest(sprout_path, &manifest)?;
+/// Atomically write a generated environment script: the content goes to a
+/// temporary file next to the target which is then renamed over it, so a
+/// shell sourcing the file never sees a partial script
+pub(crate) fn write_environment_file(path: &Path, script: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
 
-        // Update lockfile with new fetch hashes for updated modules
-        if updated {
-            let mut lock = SproutLock::load(sprout_path)?;
-            for module_id in &updated_modules {
-                if let Some(module) = manifest.modules.iter().find(|m| m.id() == *module_id) {
-                    let new_fetch_hash = crate::core::deps::compute_fetch_hash(module);
-                    let mut state = lock.get_module_state(module_id)
-                        .cloned()
-                        .unwrap_or(PackageState {
-                            fetch_hash: None,
-                            build_hash: None,
-                        });
-                    state.fetch_hash = new_fetch_hash;
-                    lock.set_module_state(module_id.clone(), state);
+    let file_name = path.file_name()
+        .ok_or_else(|| anyhow::anyhow!("Invalid output path: {}", path.display()))?;
+    let tmp_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+
+    std::fs::write(&tmp_path, script)
+        .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to move {} into place", path.display()))?;
+    Ok(())
+}
+
+/// Render the shell exports for one environment
+///
+/// Modules contribute in dependency order (dependencies first), so the
+/// resulting PATH-like variables are deterministic regardless of how the
+/// environment lists them.
+pub(crate) fn generate_environment(sprout_path: &str, manifest: &crate::ast::SproutManifest, env_name: &str) -> Result<String> {
+    use std::fmt::Write;
+
+    let environments = manifest.environments.as_ref()
+        .ok_or_else(|| anyhow::anyhow!("No environments defined"))?;
+    let modules = environments.environments.get(env_name)
+        .ok_or_else(|| anyhow::anyhow!("Environment '{}' not found", env_name))?;
+    let modules = expand_environment_modules(sprout_path, manifest, modules)?;
+
+    let order: Vec<String> = crate::core::deps::resolve_dependency_order(manifest)?
+        .iter()
+        .map(|m| m.id())
+        .collect();
+    let mut packages: Vec<_> = modules.iter().filter_map(|m| manifest.find_module(m)).collect();
+    packages.sort_by_key(|p| order.iter().position(|id| *id == p.id()));
+    packages.dedup_by_key(|p| p.id());
+
+    let mut out = String::new();
+    writeln!(out, "# Environment: {}", env_name)?;
+
+    // Guard to prevent loading environment multiple times in nested shells.
+    // Without this, each time the shell config is sourced (e.g., exec zsh),
+    // every `prepend`/`append` entry would re-accumulate onto the existing
+    // value, growing PATH-like variables with duplicate segments. (Scalar
+    // `set` entries are idempotent and unaffected.)
+    writeln!(out, "# Guard to prevent loading multiple times")?;
+    writeln!(out, "if [ -n \"$SPROUT_ENV_LOADED\" ]; then")?;
+    writeln!(out, "  return 0 2>/dev/null || :")?;
+    writeln!(out, "fi")?;
+    writeln!(out, "export SPROUT_ENV_LOADED=1")?;
+    writeln!(out)?;
+
+    // Collect contributions per variable, carrying the mode each
+    // module declared for it. A variable may be contributed by several
+    // modules (e.g. every module adds to PATH); their modes are expected
+    // to agree, and a conflict is surfaced rather than silently resolved.
+    use crate::ast::ExportMode;
+    let mut contributions: HashMap<String, (ExportMode, Vec<String>)> = HashMap::new();
+
+    for package in packages {
+        let dist_path = Path::new(sprout_path).join("dist").join(package.id());
+
+        for export in &package.provides {
+            // Values are relative to the module's dist dir, except those that
+            // ${SPROUT_PATH}, ${DIST_PATH} and the like already expanded into the sprout dir
+            let full_path = if Path::new(&export.value).starts_with(sprout_path) {
+                PathBuf::from(&export.value)
+            } else {
+                dist_path.join(export.value.trim_start_matches('/'))
+            };
+            let entry = contributions
+                .entry(export.name.clone())
+                .or_insert_with(|| (export.mode, Vec::new()));
+            if entry.0 != export.mode {
+                warn!(
+                    "Conflicting modes for env var '{}'; keeping '{}', ignoring '{}'",
+                    export.name, entry.0.keyword(), export.mode.keyword()
+                );
+            }
+            entry.1.push(full_path.display().to_string());
+        }
+    }
+
+    // Generate one export statement per variable, shaped by its mode.
+    let mut sorted_vars: Vec<_> = contributions.keys().cloned().collect();
+    sorted_vars.sort();
+
+    for var in &sorted_vars {
+        let (mode, values) = &contributions[var];
+        debug_assert!(!values.is_empty(), "every collected var has at least one value");
+
+        match mode {
+            // Scalar: assign directly. Re-sourcing is idempotent and can
+            // never accumulate into "value:value". Multiple definitions are
+            // a conflict; the last deterministically wins.
+            ExportMode::Set => {
+                if values.len() > 1 {
+                    warn!("Multiple 'set' definitions for env var '{}'; using the last", var);
                 }
+                let value = values.last().expect("non-empty checked above");
+                writeln!(out, "export {}=\"{}\"", var, value)?;
+            }
+            // Search path, this activation's entries take precedence.
+            ExportMode::Prepend => {
+                let joined = values.join(":");
+                writeln!(out, "export {}=\"{}${{{}:+:${{{}}}}}\"", var, joined, var, var)?;
+            }
+            // Search path, existing entries take precedence.
+            ExportMode::Append => {
+                let joined = values.join(":");
+                writeln!(out, "export {}=\"${{{}:+${{{}}}:}}{}\"", var, var, var, joined)?;
             }
-            lock.save(sprout_path)?;
-            println!("Formatted manifest.sprout and added missing SHA256 hashes.");
-        } else {
-            println!("Formatted manifest.sprout.");
         }
+    }
+
+    Ok(out)
+}
+
+/// Refuse `--from-lock` unless every requested git module has a resolved commit
+pub(crate) fn check_locked_commits(sprout_path: &str, manifest: &crate::ast::SproutManifest, requested: &[String]) -> Result<()> {
+    let lock = SproutLock::load(sprout_path)?;
+
+    let mut missing: Vec<String> = requested.iter()
+        .filter_map(|id| manifest.find_module(id))
+        .filter(|m| matches!(m.fetch.as_ref().map(|f| &f.spec), Some(crate::ast::FetchSpec::Git(_))))
+        .filter(|m| lock.get_module_state(&m.id()).and_then(|s| s.resolved_commit.as_ref()).is_none())
+        .map(|m| m.id())
+        .collect();
+    missing.sort();
+    missing.dedup();
+
+    if !missing.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Lockfile has no resolved commit for: {}. Fetch them once without --from-lock first.",
+            missing.join(", ")
+        )).categorize(ErrorKind::LockMismatch);
+    }
+    Ok(())
+}
+
+/// Lint issues of the manifest as written. Expanding `${DIST_PATH}` and the
+/// like would turn paths inside the sprout dir into absolute system paths
+pub(crate) fn manifest_lint_issues(sprout_path: &str, manifest_path: &Path) -> Result<Vec<crate::lint::LintIssue>> {
+    // Still expand once so undefined variables fail the lint
+    load_manifest_from(manifest_path, sprout_path)?;
+    let manifest = load_manifest_raw_from(manifest_path)?;
+    Ok(crate::lint::lint_manifest(&manifest))
+}
+
+fn lint(sprout_path: &str, manifest_path: &Path) -> Result<()> {
+    use colored::Colorize;
+    use crate::lint::Severity;
+
+    let issues = manifest_lint_issues(sprout_path, manifest_path)?;
+
+    for issue in &issues {
+        let severity = match issue.severity {
+            Severity::Warning => issue.severity.to_string().yellow(),
+            Severity::Error => issue.severity.to_string().red(),
+        };
+        println!("{}: {} {}", severity, issue.module.bold(), issue.message);
+    }
+
+    let errors = issues.iter().filter(|i| i.severity == Severity::Error).count();
+    if issues.is_empty() {
+        println!("No issues found.");
     } else {
-        // Print to stdout
-        print!("{}", manifest.pretty_print());
+        println!("\n{} error(s), {} warning(s).", errors, issues.len() - errors);
+    }
+
+    if errors > 0 {
+        return Err(anyhow::anyhow!("Lint found {} error(s)", errors));
+    }
+    Ok(())
+}
+
+/// Rename a module in the manifest and migrate everything keyed by its id:
+/// references from other modules and environments, the lockfile entry and the
+/// dist, source and cache directories
+pub(crate) fn rename_module(sprout_path: &str, manifest_path: &Path, old: &str, new: &str, dry_run: bool) -> Result<()> {
+    let expanded = load_manifest_from(manifest_path, sprout_path)?;
+    let mut manifest = load_manifest_raw_from(manifest_path)?;
+
+    let index = expanded.modules.iter()
+        .position(|m| Some(m.id()) == expanded.find_module(old).map(|f| f.id()))
+        .ok_or_else(|| anyhow::anyhow!("Package not found: {}", old))?;
+    let old_module = expanded.modules[index].clone();
+    let old_id = old_module.id();
+    let old_name = old_module.name.clone();
+
+    let mut new_module = old_module.clone();
+    match new.split_once('@') {
+        Some((name, version)) => {
+            new_module.name = name.to_string();
+            new_module.version = Some(version.to_string());
+        }
+        None => new_module.name = new.to_string(),
+    }
+    let new_id = new_module.id();
+    if expanded.find_module(&new_id).is_some_and(|m| m.id() == new_id) {
+        return Err(anyhow::anyhow!("Module '{}' already exists", new_id));
+    }
+
+    // References by full id or by bare name both follow the module
+    let rewrite = |reference: &mut String| {
+        if *reference == old_id {
+            *reference = new_id.clone();
+        } else if *reference == old_name && expanded.find_module(reference).map(|m| m.id()) == Some(old_id.clone()) {
+            *reference = new_module.name.clone();
+        }
+    };
+
+    let raw_index = manifest.modules.iter().position(|m| m.id() == old_id)
+        .ok_or_else(|| anyhow::anyhow!("Package not found: {}", old))?;
+    manifest.modules[raw_index].name = new_module.name.clone();
+    manifest.modules[raw_index].version = new_module.version.clone();
+    for module in &mut manifest.modules {
+        module.depends_on.iter_mut().for_each(rewrite);
+    }
+    if let Some(environments) = &mut manifest.environments {
+        environments.environments.values_mut().flatten().for_each(rewrite);
+    }
+
+    // Move on-disk state before touching the manifest so a failure leaves it consistent
+    let fetch_hash = crate::core::deps::compute_fetch_hash(&old_module)
+        .map(|h| h[..8].to_string())
+        .unwrap_or_else(|| "no-fetch".to_string());
+    let moves = [
+        (get_dist_path(sprout_path, &old_module), get_dist_path(sprout_path, &new_module)),
+        (get_source_path(sprout_path, &old_module), get_source_path(sprout_path, &new_module)),
+        (
+            Path::new(sprout_path).join("cache/http").join(format!("{}-{}", old_id, fetch_hash)),
+            Path::new(sprout_path).join("cache/http").join(format!("{}-{}", new_id, fetch_hash)),
+        ),
+    ];
+    if dry_run {
+        for (from, to) in moves.iter().filter(|(from, _)| from.exists()) {
+            println!("Would rename {} -> {}", from.display(), to.display());
+        }
+        println!("Would rename module {} -> {}", old_id, new_id);
+        return Ok(());
     }
+    for (from, to) in &moves {
+        if from.exists() {
+            std::fs::rename(from, to)
+                .with_context(|| format!("Failed to rename {} to {}", from.display(), to.display()))?;
+            info!("Renamed {} -> {}", from.display(), to.display());
+        }
+    }
+
+    let mut lock = SproutLock::load(sprout_path)?;
+    if let Some(state) = lock.modules.remove(&old_id) {
+        lock.set_module_state(new_id.clone(), state);
+        lock.save(sprout_path)?;
+    }
+
+    save_manifest_to(manifest_path, &manifest)?;
+    println!("Renamed module {} -> {}", old_id, new_id);
     Ok(())
 }
 
+/// Rewrite a git module's `ref` to the commit it is fetched at and return that
+/// commit. The source dir and lockfile entry move to the new fetch hash, so the
+/// module stays fetched and built
+pub(crate) fn pin_module(sprout_path: &str, manifest_path: &Path, reference: &str, progress: Progress) -> Result<String> {
+    use crate::ast::FetchSpec;
+
+    let expanded = load_manifest_from(manifest_path, sprout_path)?;
+    let package = expanded.find_module(reference)
+        .ok_or_else(|| anyhow::anyhow!("Package not found: {}", reference))?;
+    let module_id = package.id();
+    let Some(FetchSpec::Git(git)) = package.fetch.as_ref().map(|f| &f.spec) else {
+        return Err(anyhow::anyhow!("Only git modules can be pinned: {}", module_id));
+    };
+    if let Some(ref_) = git.ref_.as_deref().filter(|r| is_commit_sha(r)) {
+        println!("{} is already pinned to {}", module_id, ref_);
+        return Ok(ref_.to_string());
+    }
+
+    let recorded = |lock: &SproutLock| lock.get_module_state(&module_id).and_then(|s| s.resolved_commit.clone());
+    let source_path = get_source_path(sprout_path, package);
+    let commit = match recorded(&SproutLock::load(sprout_path)?) {
+        Some(commit) if source_path.exists() => commit,
+        _ => {
+            let options = FetchOptions { manifest_path: Some(manifest_path.to_path_buf()), progress, ..Default::default() };
+            fetch_package(sprout_path, package, &options)?;
+            recorded(&SproutLock::load(sprout_path)?)
+                .ok_or_else(|| anyhow::anyhow!("No resolved commit recorded for {}", module_id))?
+        }
+    };
+
+    let mut pinned = package.clone();
+    if let Some(FetchSpec::Git(git)) = pinned.fetch.as_mut().map(|f| &mut f.spec) {
+        git.ref_ = Some(commit.clone());
+    }
+
+    // Same tree, new fetch hash: carry the checkout and lock state over
+    let pinned_source = get_source_path(sprout_path, &pinned);
+    if source_path.exists() && !pinned_source.exists() {
+        std::fs::rename(&source_path, &pinned_source)
+            .with_context(|| format!("Failed to rename {} to {}", source_path.display(), pinned_source.display()))?;
+    }
+    let mut lock = SproutLock::load(sprout_path)?;
+    if let Some(state) = lock.modules.get_mut(&module_id) {
+        state.fetch_hash = crate::core::deps::compute_fetch_hash(&pinned);
+        lock.save(sprout_path)?;
+    }
+
+    let mut manifest = load_manifest_raw_from(manifest_path)?;
+    let raw = manifest.modules.iter_mut().find(|m| m.id() == module_id)
+        .ok_or_else(|| anyhow::anyhow!("Package not found: {}", reference))?;
+    if let Some(FetchSpec::Git(git)) = raw.fetch.as_mut().map(|f| &mut f.spec) {
+        git.ref_ = Some(commit.clone());
+    }
+    save_manifest_to(manifest_path, &manifest)?;
+
+    println!("Pinned {} to {}", module_id, commit);
+    Ok(commit)
+}
+
+fn explain_module(sprout_path: &str, manifest_path: &Path, target: &str, from: Option<&str>) -> Result<()> {
+    let manifest = load_manifest_from(manifest_path, sprout_path)?;
 
+    let target = manifest.find_module(target)
+        .map(|m| m.id())
+        .ok_or_else(|| anyhow::anyhow!("Package not found: {}", target))?;
 
-fn show_status_tree(sprout_path: &str, expand: bool, show_all: bool) -> Result<()> {
+    let roots = match from {
+        Some(from) => {
+            let from = manifest.find_module(from)
+                .ok_or_else(|| anyhow::anyhow!("Package not found: {}", from))?;
+            vec![from.id()]
+        }
+        None => manifest.get_root_modules().into_iter().filter(|r| *r != target).collect(),
+    };
+
+    let mut found = false;
+    for root in &roots {
+        for chain in manifest.get_dependency_paths(root, &target) {
+            println!("{}", chain.join(" -> "));
+            found = true;
+        }
+    }
+
+    if !found {
+        match from {
+            Some(from) => println!("'{}' is not reachable from '{}'.", target, from),
+            None => println!("'{}' is not required by any other module.", target),
+        }
+    }
+
+    Ok(())
+}
+
+/// Dependency ids of a module: all of them in build order, or only the
+/// modules its `depends_on` names (resolved to ids) with `direct`
+pub(crate) fn module_dependencies(manifest: &crate::ast::SproutManifest, reference: &str, direct: bool) -> Result<Vec<String>> {
+    let module = manifest.find_module(reference)
+        .ok_or_else(|| anyhow::anyhow!("Package not found: {}", reference))?;
+
+    if direct {
+        return module.depends_on.iter()
+            .map(|dep| manifest.find_module(dep)
+                .map(|m| m.id())
+                .ok_or_else(|| anyhow::anyhow!("{} depends on unknown module: {}", module.id(), dep)))
+            .collect();
+    }
+
+    // get_all_dependencies ends with the module itself
+    let id = module.id();
+    Ok(manifest.get_all_dependencies(&id).into_iter().filter(|dep| *dep != id).collect())
+}
+
+fn check_outdated(sprout_path: &str, manifest_path: &Path) -> Result<()> {
     use colored::Colorize;
 
-    let manifest = load_manifest(sprout_path)?;
-    let lock = SproutLock::load(sprout_path)?;
+    let manifest = load_manifest_from(manifest_path, sprout_path)?;
+    let mut outdated = 0;
 
-    let mut module_map: HashMap<String, &crate::ast::ModuleBlock> = HashMap::new();
     for module in &manifest.modules {
-        module_map.insert(module.id(), module);
+        let Some(crate::ast::FetchSpec::Git(git)) = module.fetch.as_ref().map(|f| &f.spec) else {
+            continue;
+        };
+        let Some(pinned) = &git.ref_ else {
+            continue;
+        };
+        // Only version tags can be compared with newer tags
+        if !crate::core::deps::is_version_ref(pinned) {
+            println!("{}: {} ({})", module.id(), pinned, "unknown, not a version tag".yellow());
+            continue;
+        }
+
+        match crate::core::deps::latest_remote_tag(&git.url) {
+            Ok(Some(latest)) if crate::core::deps::is_newer_version(&latest, pinned) => {
+                println!("{}: {} -> {}", module.id(), pinned, latest.green());
+                outdated += 1;
+            }
+            Ok(_) => println!("{}: {} (up-to-date)", module.id(), pinned),
+            Err(e) => {
+                tracing::debug!("Failed to query tags for {}: {}", module.id(), e);
+                println!("{}: {} ({})", module.id(), pinned, "unknown".yellow());
+            }
+        }
+    }
+
+    if outdated > 0 {
+        println!("\n{} module(s) have newer tags available.", outdated);
+    }
+
+    Ok(())
+}
+
+/// Render the `depends_on` edges as `module -> dependency` lines, or as DOT
+/// where up-to-date modules are green, built but stale ones yellow and
+/// unbuilt ones gray
+pub(crate) fn render_dependency_graph(
+    sprout_path: &str,
+    manifest: &crate::ast::SproutManifest,
+    lock: &SproutLock,
+    dot: bool,
+) -> Result<String> {
+    use std::fmt::Write;
+
+    let edges = crate::core::deps::dependency_edges(manifest)?;
+    let mut out = String::new();
+
+    if !dot {
+        for (module, dep) in &edges {
+            writeln!(out, "{} -> {}", module, dep)?;
+        }
+        return Ok(out);
+    }
+
+    writeln!(out, "digraph sprout {{")?;
+    writeln!(out, "    rankdir=LR;")?;
+    writeln!(out, "    node [shape=box, style=filled];")?;
+    for module in &manifest.modules {
+        let id = module.id();
+        let state = lock.get_module_state(&id)
+            .filter(|_| get_dist_path(sprout_path, module).exists());
+        let color = match state {
+            Some(state) if state.build_hash == compute_build_hash(module)
+                && state.fetch_hash == compute_fetch_hash(module) => "palegreen",
+            Some(_) => "khaki",
+            None => "lightgray",
+        };
+        writeln!(out, "    \"{}\" [fillcolor={}];", id, color)?;
+    }
+    for (module, dep) in &edges {
+        writeln!(out, "    \"{}\" -> \"{}\";", module, dep)?;
+    }
+    writeln!(out, "}}")?;
+
+    Ok(out)
+}
+
+/// One line per build record: time, outcome, module, duration, hash and log
+pub(crate) fn render_build_history(records: &[BuildRecord]) -> String {
+    use colored::Colorize;
+
+    records.iter()
+        .map(|record| {
+            let outcome = if record.success { "✓".green() } else { "✗".red() };
+            let hash = record.build_hash.as_ref().map(|h| &h[..8]).unwrap_or("-");
+            let log = record.log.as_ref()
+                .map(|log| format!(" {}", log.display().to_string().bright_black()))
+                .unwrap_or_default();
+            format!("{} {} {} {:.1}s [{}]{}\n",
+                record.timestamp.format("%Y-%m-%d %H:%M:%S"), outcome, record.module,
+                record.duration_secs, hash, log)
+        })
+        .collect()
+}
+
+/// Compile an optional module id glob; `None` matches every module
+pub(crate) fn module_filter(filter: Option<&str>) -> Result<Option<globset::GlobMatcher>> {
+    filter
+        .map(|pattern| {
+            globset::Glob::new(pattern)
+                .map(|glob| glob.compile_matcher())
+                .with_context(|| format!("Invalid filter glob: {}", pattern))
+        })
+        .transpose()
+}
+
+/// Expand glob patterns among the positional package names against the
+/// manifest's module ids, in dependency order. Plain names pass through
+/// untouched so exact and bare-name lookups keep working
+pub(crate) fn expand_package_patterns(manifest: &crate::ast::SproutManifest, packages: &[String]) -> Result<Vec<String>> {
+    let mut expanded: Vec<String> = Vec::new();
+    for pattern in packages {
+        if !pattern.contains(['*', '?', '[', '{']) {
+            if !expanded.contains(pattern) {
+                expanded.push(pattern.clone());
+            }
+            continue;
+        }
+
+        let matcher = globset::Glob::new(pattern)
+            .with_context(|| format!("Invalid package glob: {}", pattern))?
+            .compile_matcher();
+        let matches: Vec<String> = resolve_dependency_order(manifest)?.into_iter()
+            .map(|m| m.id())
+            .filter(|id| matcher.is_match(id))
+            .collect();
+        if matches.is_empty() {
+            return Err(anyhow::anyhow!("No modules match pattern: {}", pattern));
+        }
+        for id in matches {
+            if !expanded.contains(&id) {
+                expanded.push(id);
+            }
+        }
     }
+    Ok(expanded)
+}
 
-    let mut roots: Vec<String> = manifest.modules.iter().map(|m| m.id()).collect();
+/// `expand_package_patterns`, followed by every module tagged `tag` in dependency order
+pub(crate) fn select_modules(manifest: &crate::ast::SproutManifest, packages: &[String], tag: Option<&str>) -> Result<Vec<String>> {
+    let mut selected = expand_package_patterns(manifest, packages)?;
+    if let Some(tag) = tag {
+        let tagged: Vec<String> = resolve_dependency_order(manifest)?.into_iter()
+            .filter(|m| m.tags.iter().any(|t| t == tag))
+            .map(|m| m.id())
+            .collect();
+        if tagged.is_empty() {
+            return Err(anyhow::anyhow!("No modules tagged '{}'", tag));
+        }
+        for id in tagged {
+            if !selected.contains(&id) {
+                selected.push(id);
+            }
+        }
+    }
+    Ok(selected)
+}
+
+/// Root module ids shown by `modules status`, sorted and restricted to `filter` and `tag`
+pub(crate) fn status_roots(manifest: &crate::ast::SproutManifest, filter: Option<&str>, tag: Option<&str>) -> Result<Vec<String>> {
+    let matcher = module_filter(filter)?;
+    let mut roots: Vec<String> = manifest.modules.iter()
+        .filter(|m| tag.is_none_or(|tag| m.tags.iter().any(|t| t == tag)))
+        .map(|m| m.id())
+        .filter(|id| matcher.as_ref().is_none_or(|m| m.is_match(id)))
+        .collect();
     roots.sort();
+    Ok(roots)
+}
+
+fn show_status_tree(sprout_path: &str, manifest_path: &Path, expand: bool, show_all: bool, filter: Option<&str>, tag: Option<&str>, depth: Option<usize>) -> Result<()> {
+    use colored::Colorize;
+
+    let manifest = load_manifest_from(manifest_path, sprout_path)?;
+    let lock = SproutLock::load(sprout_path)?;
+
+    let roots = status_roots(&manifest, filter, tag)?;
 
-    let mut processed = HashSet::new();
+    let mut tree = StatusTree::new(sprout_path, &manifest, &lock, expand, show_all);
     let mut has_issues = false;
 
     for root in &roots {
-        let node_has_issues = print_tree_node(root, &module_map, &lock, sprout_path, "", true, &mut processed, expand, show_all)?;
+        let node_has_issues = print_tree_node(&mut tree, root, "", true, depth)?;
         has_issues = has_issues || node_has_issues;
     }
+    print!("{}", tree.out);
+    tracing::debug!("Computed hashes for {} modules", tree.hash_computations);
 
     if !has_issues && !show_all {
-        let manifest_path = Path::new(sprout_path).join("manifest.sprout");
         println!("Your modules are up to date with '{}'.", manifest_path.display());
+        println!("{}", tree.summary);
         return Ok(());
     }
 
+    println!("\n{}", tree.summary.to_string().bold());
     println!("\n{}:", "Legend".bold());
     println!("  Name: green=up-to-date, red=needs rebuild.");
     println!("  Hashes: green=done, red=not done.");
@@ -995,43 +2423,159 @@ fn show_status_tree(sprout_path: &str, expand: bool, show_all: bool) -> Result<(
     Ok(())
 }
 
-fn print_tree_node(
+/// State shared while rendering the module status tree
+pub(crate) struct StatusTree<'a> {
+    manifest: &'a crate::ast::SproutManifest,
+    module_map: HashMap<String, &'a crate::ast::ModuleBlock>,
+    lock: &'a SproutLock,
+    sprout_path: &'a str,
+    expand: bool,
+    show_all: bool,
+    /// Fetch and build hash per module id, computed on first use since shared
+    /// dependencies appear many times in the tree
+    hashes: HashMap<String, (Option<String>, Option<String>)>,
+    /// How many modules had their hashes computed
+    pub(crate) hash_computations: usize,
+    /// Per-state counts of the distinct modules visited
+    pub(crate) summary: StatusSummary,
+    /// State of every module visited so far
+    pub(crate) states: HashMap<String, ModuleState>,
+    /// Rendered tree lines
+    pub(crate) out: String,
+}
+
+/// How `modules status` classifies a module
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ModuleState {
+    UpToDate,
+    NeedsRebuild,
+    NotFetched,
+    /// dist/<id> exists but the lockfile records no build for it; `modules hash -i` fixes it
+    Unlocked,
+}
+
+/// Aggregate module states for the footer of `modules status`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct StatusSummary {
+    pub total: usize,
+    pub up_to_date: usize,
+    pub needs_rebuild: usize,
+    pub not_fetched: usize,
+    pub unlocked: usize,
+}
+
+impl std::fmt::Display for StatusSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} module{}: {} up to date, {} need{} rebuild, {} not fetched",
+            self.total,
+            if self.total == 1 { "" } else { "s" },
+            self.up_to_date,
+            self.needs_rebuild,
+            if self.needs_rebuild == 1 { "s" } else { "" },
+            self.not_fetched
+        )?;
+        if self.unlocked > 0 {
+            write!(f, ", {} built but unlocked", self.unlocked)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> StatusTree<'a> {
+    pub(crate) fn new(
+        sprout_path: &'a str,
+        manifest: &'a crate::ast::SproutManifest,
+        lock: &'a SproutLock,
+        expand: bool,
+        show_all: bool,
+    ) -> Self {
+        let module_map = manifest.modules.iter().map(|m| (m.id(), m)).collect();
+        Self {
+            manifest,
+            module_map,
+            lock,
+            sprout_path,
+            expand,
+            show_all,
+            hashes: HashMap::new(),
+            hash_computations: 0,
+            summary: StatusSummary::default(),
+            states: HashMap::new(),
+            out: String::new(),
+        }
+    }
+
+    /// Count a module once, however often it appears in the tree
+    fn count(&mut self, id: &str, state: ModuleState) {
+        if self.states.insert(id.to_string(), state).is_some() {
+            return;
+        }
+        self.summary.total += 1;
+        match state {
+            ModuleState::UpToDate => self.summary.up_to_date += 1,
+            ModuleState::NeedsRebuild => self.summary.needs_rebuild += 1,
+            ModuleState::NotFetched => self.summary.not_fetched += 1,
+            ModuleState::Unlocked => self.summary.unlocked += 1,
+        }
+    }
+
+    fn module_hashes(&mut self, module: &crate::ast::ModuleBlock) -> (Option<String>, Option<String>) {
+        if let Some(hashes) = self.hashes.get(&module.id()) {
+            return hashes.clone();
+        }
+        self.hash_computations += 1;
+        let hashes = (compute_fetch_hash(module), compute_build_hash(module));
+        self.hashes.insert(module.id(), hashes.clone());
+        hashes
+    }
+}
+
+/// Render one module and, when expanding, its dependencies. `remaining_depth`
+/// limits how many dependency levels are shown below this node (None = all)
+pub(crate) fn print_tree_node(
+    tree: &mut StatusTree,
     id: &str,
-    module_map: &HashMap<String, &crate::ast::ModuleBlock>,
-    lock: &SproutLock,
-    sprout_path: &str,
     prefix: &str,
     is_last: bool,
-    processed: &mut HashSet<String>,
-    expand: bool,
-    show_all: bool,
+    remaining_depth: Option<usize>,
 ) -> Result<bool> {
     use colored::Colorize;
+    use std::fmt::Write;
+
+    let module_map = &tree.module_map;
+    let lock = tree.lock;
+    let sprout_path = tree.sprout_path;
+    let (expand, show_all) = (tree.expand, tree.show_all);
 
     let module = match module_map.get(id) {
-        Some(m) => m,
+        Some(m) => *m,
         None => {
-            println!("{}{}─ {} ({})", prefix, if is_last { "└" } else { "├" }, id, "not found".red());
+            writeln!(tree.out, "{}{}─ {} ({})", prefix, if is_last { "└" } else { "├" }, id, "not found".red())?;
             return Ok(true);
         }
     };
+    let (current_fetch_hash, current_build_hash) = tree.module_hashes(module);
 
     // Calculate status flags
-    let source_path = get_source_path(sprout_path, module);
+    let source_path = crate::core::deps::source_path_with_hash(sprout_path, module, current_fetch_hash.as_deref());
     let dist_path = get_dist_path(sprout_path, module);
 
     let has_source = source_path.exists();
     let has_dist = dist_path.exists();
 
+    let mut stale_caches = 0;
     let has_cache = if let Some(fetch) = &module.fetch {
         match &fetch.spec {
             crate::ast::FetchSpec::Http(_) => {
-                let fetch_hash = compute_fetch_hash(module)
+                let fetch_hash = current_fetch_hash.as_ref()
                     .map(|h| h[..8].to_string())
                     .unwrap_or_else(|| "no-fetch".to_string());
                 let cache_dir = Path::new(sprout_path)
                     .join("cache/http")
                     .join(format!("{}-{}", id, fetch_hash));
+                stale_caches = count_stale_caches(sprout_path, id, &fetch_hash);
                 Some(cache_dir.exists())
             }
             _ => None
@@ -1045,7 +2589,6 @@ fn print_tree_node(
     // Check if build completed successfully by verifying lock file has matching hash
     let built = if has_dist {
         if let Some(state) = lock.get_module_state(id) {
-            let current_build_hash = compute_build_hash(module);
             current_build_hash == state.build_hash && state.build_hash.is_some()
         } else {
             false
@@ -1057,15 +2600,25 @@ fn print_tree_node(
     let mut up_to_date = false;
 
     if has_dist && let Some(state) = lock.get_module_state(id) {
-        let current_fetch_hash = compute_fetch_hash(module);
-        let current_build_hash = compute_build_hash(module);
-
         let fetch_changed = current_fetch_hash != state.fetch_hash;
         let build_changed = current_build_hash != state.build_hash;
 
         up_to_date = !fetch_changed && !build_changed && state.build_hash.is_some();
     }
 
+    // A dist the lockfile knows nothing about needs its hashes recorded, not a rebuild
+    let unlocked = has_dist && current_build_hash.is_some()
+        && lock.get_module_state(id).is_none_or(|state| state.build_hash.is_none());
+    let state = if up_to_date {
+        ModuleState::UpToDate
+    } else if !fetched {
+        ModuleState::NotFetched
+    } else if unlocked {
+        ModuleState::Unlocked
+    } else {
+        ModuleState::NeedsRebuild
+    };
+
     let check = |b: bool| if b { "✓".green() } else { "✗".red() };
     let check_opt = |opt: Option<bool>| match opt {
         Some(true) => "✓".green(),
@@ -1073,11 +2626,11 @@ fn print_tree_node(
         None => "-".bright_black(),
     };
 
-    let fetch_hash_str = compute_fetch_hash(module)
+    let fetch_hash_str = current_fetch_hash.as_ref()
         .map(|h| h[..8].to_string())
         .unwrap_or_else(|| "-".to_string());
 
-    let build_hash_str = compute_build_hash(module)
+    let build_hash_str = current_build_hash.as_ref()
         .map(|h| h[..8].to_string())
         .unwrap_or_else(|| "-".to_string());
 
@@ -1097,49 +2650,201 @@ fn print_tree_node(
         fetch_hash_colored, build_hash_colored,
         check(has_source), check_opt(has_cache));
 
+    let status_line = if stale_caches > 0 {
+        let hint = format!("({} stale cache{} — run clean)", stale_caches, if stale_caches == 1 { "" } else { "s" });
+        format!("{}] {}", status_line, hint.yellow())
+    } else {
+        format!("{}]", status_line)
+    };
+
+    let status_line = if state == ModuleState::Unlocked {
+        format!("{} {}", status_line, "(built but unlocked — run `modules hash -i`)".yellow())
+    } else {
+        status_line
+    };
+
     let colored_id = if up_to_date { id.green() } else { id.red() };
 
     let has_issues = !up_to_date;
+    tree.count(id, state);
+
+    // A collapsed tree still says how many dependencies are hidden
+    let status_line = if !expand && !module.depends_on.is_empty() {
+        let n = module.depends_on.len();
+        let marker = format!("(+{} dep{})", n, if n == 1 { "" } else { "s" });
+        format!("{} {}", status_line, marker.bright_black())
+    } else {
+        status_line
+    };
 
     if show_all || has_issues {
-        println!("{}{}─ {} [{}]", prefix, if is_last { "└" } else { "├" }, colored_id, status_line);
+        writeln!(tree.out, "{}{}─ {} [{}{}", prefix, if is_last { "└" } else { "├" }, colored_id, status_line, description_suffix(module))?;
     }
 
     // Print dependencies (not dependents)
     let mut child_has_issues = false;
     if expand && !module.depends_on.is_empty() {
-        let mut sorted_deps = module.depends_on.clone();
+        // Dependencies may name a module without its version (`gcc` for `gcc@13`)
+        let mut sorted_deps: Vec<String> = module.depends_on.iter()
+            .map(|dep_ref| tree.manifest.find_module(dep_ref).map_or_else(|| dep_ref.clone(), |m| m.id()))
+            .collect();
         sorted_deps.sort();
 
         let child_prefix = format!("{}{}  ", prefix, if is_last { " " } else { "│" });
 
-        for (i, dep_id) in sorted_deps.iter().enumerate() {
-            let is_last_child = i == sorted_deps.len() - 1;
-            let dep_has_issues = print_tree_node(dep_id, module_map, lock, sprout_path, &child_prefix, is_last_child, processed, expand, show_all)?;
-            child_has_issues = child_has_issues || dep_has_issues;
+        if remaining_depth == Some(0) {
+            if show_all || has_issues {
+                writeln!(tree.out, "{}└─ {}", child_prefix, "…".bright_black())?;
+            }
+        } else {
+            let child_depth = remaining_depth.map(|d| d - 1);
+            for (i, dep_id) in sorted_deps.iter().enumerate() {
+                let is_last_child = i == sorted_deps.len() - 1;
+                let dep_has_issues = print_tree_node(tree, dep_id, &child_prefix, is_last_child, child_depth)?;
+                child_has_issues = child_has_issues || dep_has_issues;
+            }
         }
     }
 
     Ok(has_issues || child_has_issues)
 }
 
+/// `--thorough` implies `--check-sources`
+fn source_check_mode(check_sources: bool, thorough: bool) -> crate::core::deps::SourceCheck {
+    use crate::core::deps::SourceCheck;
+    match (check_sources, thorough) {
+        (_, true) => SourceCheck::Content,
+        (true, false) => SourceCheck::Metadata,
+        (false, false) => SourceCheck::Off,
+    }
+}
+
+/// Counts `cache/http/<id>-<hash8>` directories left behind by earlier fetch specs
+fn count_stale_caches(sprout_path: &str, id: &str, current_hash: &str) -> usize {
+    module_dirs(&Path::new(sprout_path).join("cache/http"), id)
+        .iter()
+        .filter(|(_, hash)| hash != current_hash)
+        .count()
+}
+
+/// `<id>-<hash8>` directories directly below `parent`, with their hash part
+fn module_dirs(parent: &Path, id: &str) -> Vec<(PathBuf, String)> {
+    let Ok(entries) = std::fs::read_dir(parent) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}-", id);
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // Match the exact `<id>-<hash8>` shape so `tool` never claims `tool2-…`
+            let hash = name.strip_prefix(&prefix)?;
+            (hash == "no-fetch" || (hash.len() == 8 && hash.chars().all(|c| c.is_ascii_hexdigit())))
+                .then(|| (entry.path(), hash.to_string()))
+        })
+        .collect()
+}
+
+/// Disk usage of one module, in bytes
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ModuleSize {
+    pub id: String,
+    pub sources: u64,
+    pub cache: u64,
+    pub dist: u64,
+}
+
+impl ModuleSize {
+    pub fn total(&self) -> u64 {
+        self.sources + self.cache + self.dist
+    }
+}
+
+/// Sizes of every manifest module's sources, caches and dist dir, largest first
+pub(crate) fn module_sizes(sprout_path: &str, manifest: &crate::ast::SproutManifest) -> Result<Vec<ModuleSize>> {
+    let root = Path::new(sprout_path);
+    let sum_dirs = |parent: &str, id: &str| -> Result<u64> {
+        module_dirs(&root.join(parent), id)
+            .iter()
+            .map(|(path, _)| dir_size(path))
+            .sum()
+    };
+
+    let mut sizes = Vec::new();
+    for module in &manifest.modules {
+        let id = module.id();
+        let mut sources = 0;
+        for subdir in ["sources/git", "sources/http", "sources/rsync", "sources/archive"] {
+            sources += sum_dirs(subdir, &id)?;
+        }
+        let cache = sum_dirs("cache/http", &id)?;
+        let dist_path = get_dist_path(sprout_path, module);
+        let dist = if dist_path.exists() { dir_size(&dist_path)? } else { 0 };
+        sizes.push(ModuleSize { id, sources, cache, dist });
+    }
+
+    sizes.sort_by(|a, b| b.total().cmp(&a.total()).then_with(|| a.id.cmp(&b.id)));
+    Ok(sizes)
+}
+
+/// Table of `module_sizes` output followed by a grand total row
+pub(crate) fn render_module_sizes(sizes: &[ModuleSize]) -> String {
+    let width = sizes.iter().map(|s| s.id.len()).chain(["MODULE".len()]).max().unwrap_or(0);
+    let row = |id: &str, sources: &str, cache: &str, dist: &str, total: &str| {
+        format!("{:<width$}  {:>10}  {:>10}  {:>10}  {:>10}\n", id, sources, cache, dist, total, width = width)
+    };
+
+    let mut out = row("MODULE", "SOURCES", "CACHE", "DIST", "TOTAL");
+    for size in sizes {
+        out += &row(
+            &size.id,
+            &format_size(size.sources),
+            &format_size(size.cache),
+            &format_size(size.dist),
+            &format_size(size.total()),
+        );
+    }
 
-fn env_edit_interactive(sprout_path: &str, env_name: &str) -> Result<()> {
+    let sum = |field: fn(&ModuleSize) -> u64| sizes.iter().map(field).sum::<u64>();
+    out += &row(
+        "TOTAL",
+        &format_size(sum(|s| s.sources)),
+        &format_size(sum(|s| s.cache)),
+        &format_size(sum(|s| s.dist)),
+        &format_size(sum(ModuleSize::total)),
+    );
+    out
+}
+
+/// Dimmed ` — description` suffix for module listings, empty without a description
+fn description_suffix(module: &crate::ast::ModuleBlock) -> String {
+    use colored::Colorize;
+
+    module.description.as_ref()
+        .map(|description| format!(" {}", format!("— {}", description).dimmed()))
+        .unwrap_or_default()
+}
+
+/// Ask what to do with a real file or directory where a symlink should be restored
+fn prompt_restore_conflict(path: &Path) -> Result<ConflictChoice> {
+    let labels: Vec<&str> = ConflictChoice::ALL.iter().map(|c| c.label()).collect();
+    let selection = dialoguer::Select::new()
+        .with_prompt(format!("{} exists and is not a symlink", path.display()))
+        .items(&labels)
+        .default(0)
+        .interact()?;
+    Ok(ConflictChoice::ALL[selection])
+}
+
+fn env_edit_interactive(sprout_path: &str, manifest_path: &Path, env_name: &str) -> Result<()> {
     use dialoguer::MultiSelect;
 
-    let manifest = load_manifest(sprout_path)?;
+    let manifest = load_manifest_from(manifest_path, sprout_path)?;
     let lock = SproutLock::load(sprout_path)?;
 
-    // Get all built modules
-    let mut available_modules: Vec<String> = manifest.modules.iter()
-        .filter(|m| {
-            let module_id = m.id();
-            lock.get_module_state(&module_id)
-                .map(|s| s.build_hash.is_some())
-                .unwrap_or(false)
-        })
-        .map(|m| m.id())
-        .collect();
+    let mut available_modules = built_module_ids(&manifest, &lock);
 
     if available_modules.is_empty() {
         return Err(anyhow::anyhow!("No built modules available"));
@@ -1167,9 +2872,10 @@ fn env_edit_interactive(sprout_path: &str, env_name: &str) -> Result<()> {
         .interact()?;
 
     // Update manifest
-    let mut manifest = load_manifest(sprout_path)?;
-    let new_modules: Vec<String> = selections.iter()
-        .map(|&i| available_modules[i].clone())
+    let mut manifest = load_manifest_raw_from(manifest_path)?;
+    // A wildcard entry is not one of the choices and survives the edit
+    let new_modules: Vec<String> = current_modules.get(crate::ast::ALL_BUILT_MODULES).cloned().into_iter()
+        .chain(selections.iter().map(|&i| available_modules[i].clone()))
         .collect();
 
     if manifest.environments.is_none() {
@@ -1181,17 +2887,105 @@ fn env_edit_interactive(sprout_path: &str, env_name: &str) -> Result<()> {
     manifest.environments.as_mut().unwrap()
         .environments.insert(env_name.to_string(), new_modules);
 
-    save_manifest(sprout_path, &manifest)?;
+    save_manifest_to(manifest_path, &manifest)?;
     println!("✓ Updated environment '{}'.", env_name);
 
     Ok(())
 }
 
-fn clean_unused_directories(sprout_path: &str, dry_run: bool) -> Result<()> {
+/// An environment's module list with `*` replaced by every built module,
+/// without duplicates and in listed order
+fn expand_environment_modules(sprout_path: &str, manifest: &crate::ast::SproutManifest, modules: &[String]) -> Result<Vec<String>> {
+    let mut expanded: Vec<String> = Vec::new();
+    for module in modules {
+        let ids = if module == crate::ast::ALL_BUILT_MODULES {
+            built_module_ids(manifest, &SproutLock::load(sprout_path)?)
+        } else {
+            vec![module.clone()]
+        };
+        for id in ids {
+            if !expanded.contains(&id) {
+                expanded.push(id);
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+/// Ids of the modules the lockfile records as built; only these can join an environment
+fn built_module_ids(manifest: &crate::ast::SproutManifest, lock: &SproutLock) -> Vec<String> {
+    manifest.modules.iter()
+        .map(|m| m.id())
+        .filter(|id| lock.get_module_state(id).is_some_and(|s| s.build_hash.is_some()))
+        .collect()
+}
+
+/// Non-interactive `env edit`: add and remove modules, then save the manifest
+pub(crate) fn env_edit_modules(sprout_path: &str, manifest_path: &Path, env_name: &str, add: &[String], remove: &[String], dry_run: bool) -> Result<()> {
+    let manifest = load_manifest_from(manifest_path, sprout_path)?;
+    let lock = SproutLock::load(sprout_path)?;
+    let built = built_module_ids(&manifest, &lock);
+
+    // Validate every addition before changing anything
+    let mut to_add = Vec::new();
+    for reference in add {
+        if reference == crate::ast::ALL_BUILT_MODULES {
+            to_add.push(reference.clone());
+            continue;
+        }
+        let id = manifest.find_module(reference)
+            .map(|m| m.id())
+            .ok_or_else(|| anyhow::anyhow!("Module not found: {}", reference))?;
+        if !built.contains(&id) {
+            return Err(anyhow::anyhow!("Module {} is not built; build it before adding it to '{}'", id, env_name));
+        }
+        to_add.push(id);
+    }
+    // Removal also works for modules already deleted from the manifest
+    let to_remove: Vec<String> = remove.iter()
+        .map(|reference| manifest.find_module(reference).map_or_else(|| reference.clone(), |m| m.id()))
+        .collect();
+
+    let mut manifest = load_manifest_raw_from(manifest_path)?;
+    let environments = &mut manifest.environments
+        .get_or_insert_with(|| crate::ast::EnvironmentsBlock { environments: HashMap::new() })
+        .environments;
+    let modules = environments.entry(env_name.to_string()).or_default();
+
+    for id in to_remove {
+        let before = modules.len();
+        modules.retain(|m| *m != id);
+        if modules.len() == before {
+            warn!("{} is not in environment '{}'", id, env_name);
+        }
+    }
+    for id in to_add {
+        if !modules.contains(&id) {
+            modules.push(id);
+        }
+    }
+
+    if dry_run {
+        println!("Would set environment '{}' to [{}]", env_name, modules.join(", "));
+        return Ok(());
+    }
+    save_manifest_to(manifest_path, &manifest)?;
+    println!("✓ Updated environment '{}'.", env_name);
+
+    Ok(())
+}
+
+/// Remove source and cache dirs no current fetch hash refers to; with
+/// `orphans`, also the dist dirs and lockfile entries of modules that are no
+/// longer in the manifest. A module whose hash merely changed keeps its dist.
+/// Lists what goes and asks first unless `yes`; without an `interactive`
+/// terminal to ask on, `yes` is required
+pub(crate) fn clean_unused_directories(sprout_path: &str, manifest_path: &Path, dry_run: bool, filter: Option<&str>, orphans: bool, yes: bool, interactive: bool) -> Result<()> {
     use crate::core::deps::compute_fetch_hash;
     use std::fs;
 
-    let manifest = load_manifest(sprout_path)?;
+    let manifest = load_manifest_from(manifest_path, sprout_path)?;
+    let matcher = module_filter(filter)?;
 
     // Collect valid hashes from manifest
     let mut valid_hashes = HashSet::new();
@@ -1203,13 +2997,14 @@ fn clean_unused_directories(sprout_path: &str, dry_run: bool) -> Result<()> {
         }
     }
 
-    let mut removed_count = 0;
-    let mut freed_bytes = 0u64;
+    // (label, path, size) of every directory to delete
+    let mut doomed: Vec<(String, PathBuf, u64)> = Vec::new();
 
     // Clean all directories
     let dirs_to_clean = [
         ("sources/git", Path::new(sprout_path).join("sources/git")),
         ("sources/http", Path::new(sprout_path).join("sources/http")),
+        ("sources/rsync", Path::new(sprout_path).join("sources/rsync")),
         ("cache/http", Path::new(sprout_path).join("cache/http")),
     ];
 
@@ -1219,47 +3014,102 @@ fn clean_unused_directories(sprout_path: &str, dry_run: bool) -> Result<()> {
                 let entry = entry?;
                 let dir_name = entry.file_name().to_string_lossy().to_string();
 
+                // Directories are named <module-id>-<short-hash>
+                if let Some(matcher) = &matcher {
+                    let module_id = dir_name.rsplit_once('-').map_or(dir_name.as_str(), |(id, _)| id);
+                    if !matcher.is_match(module_id) {
+                        continue;
+                    }
+                }
+
                 if !valid_hashes.contains(&dir_name) {
-                    let size = dir_size(&entry.path())?;
-                    freed_bytes += size;
+                    doomed.push((format!("{}/{}", label, dir_name), entry.path(), dir_size(&entry.path())?));
+                }
+            }
+        }
+    }
 
-                    if dry_run {
-                        println!("Would remove: {}/{} ({} MB)", label, dir_name, size / 1_000_000);
-                    } else {
-                        println!("Removing: {}/{} ({} MB)", label, dir_name, size / 1_000_000);
-                        fs::remove_dir_all(entry.path())?;
-                    }
-                    removed_count += 1;
+    let mut lock = SproutLock::load(sprout_path)?;
+    let mut stale = Vec::new();
+    if orphans {
+        let module_ids: HashSet<String> = manifest.modules.iter().map(|m| m.id()).collect();
+        let is_orphan = |id: &str| !module_ids.contains(id) && matcher.as_ref().is_none_or(|m| m.is_match(id));
+
+        let dist_dir = Path::new(sprout_path).join("dist");
+        if dist_dir.exists() {
+            for entry in fs::read_dir(&dist_dir)? {
+                let entry = entry?;
+                let id = entry.file_name().to_string_lossy().to_string();
+                if is_orphan(&id) {
+                    doomed.push((format!("dist/{}", id), entry.path(), dir_size(&entry.path())?));
                 }
             }
         }
+
+        stale = lock.modules.keys().filter(|id| is_orphan(id)).cloned().collect();
     }
 
-    if removed_count == 0 {
+    let freed_bytes: u64 = doomed.iter().map(|(_, _, size)| size).sum();
+    if doomed.is_empty() && stale.is_empty() {
         println!("No unused directories found.");
-    } else if dry_run {
-        println!("\nWould remove {} directory(ies), freeing {} MB.", removed_count, freed_bytes / 1_000_000);
-    } else {
-        println!("\nRemoved {} directory(ies), freed {} MB.", removed_count, freed_bytes / 1_000_000);
+        return Ok(());
+    }
+
+    if dry_run {
+        for (label, _, size) in &doomed {
+            println!("Would remove: {} ({} MB)", label, size / 1_000_000);
+        }
+        for id in &stale {
+            println!("Would drop lockfile entry: {}", id);
+        }
+        println!("\nWould remove {} directory(ies), freeing {} MB.", doomed.len(), freed_bytes / 1_000_000);
+        return Ok(());
+    }
+
+    let mut listing = String::new();
+    for (label, _, size) in &doomed {
+        listing.push_str(&format!("  {} ({})\n", label, format_size(*size)));
+    }
+    for id in &stale {
+        listing.push_str(&format!("  lockfile entry {}\n", id));
+    }
+    let question = format!("Delete {} directory(ies), freeing {}?", doomed.len(), format_size(freed_bytes));
+    if !confirm_deletion(&listing, &question, yes, interactive)? {
+        println!("Aborted.");
+        return Ok(());
+    }
+
+    for (label, path, size) in &doomed {
+        println!("Removing: {} ({} MB)", label, size / 1_000_000);
+        fs::remove_dir_all(path)?;
+    }
+    for id in &stale {
+        println!("Dropping lockfile entry: {}", id);
+        lock.modules.remove(id);
+    }
+    if !stale.is_empty() {
+        lock.save(sprout_path)?;
+    }
+
+    if !doomed.is_empty() {
+        println!("\nRemoved {} directory(ies), freed {} MB.", doomed.len(), freed_bytes / 1_000_000);
     }
 
     Ok(())
 }
 
-fn dir_size(path: &Path) -> Result<u64> {
-    let mut size = 0u64;
-    if path.is_dir() {
-        for entry in std::fs::read_dir(path)? {
-            let entry = entry?;
-            let metadata = entry.metadata()?;
-            if metadata.is_dir() {
-                size += dir_size(&entry.path())?;
-            } else {
-                size += metadata.len();
-            }
-        }
-    } else {
-        size = path.metadata()?.len();
+/// Gate for irreversible deletes: `yes` (--yes) proceeds, an interactive terminal
+/// is asked after seeing `listing`, and anything else is refused
+pub(crate) fn confirm_deletion(listing: &str, question: &str, yes: bool, interactive: bool) -> Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    if !interactive {
+        return Err(anyhow::anyhow!("{} Not a terminal, so nothing was deleted; rerun with --yes to confirm", question));
     }
-    Ok(size)
+    print!("{}", listing);
+    Ok(dialoguer::Confirm::new()
+        .with_prompt(question)
+        .default(false)
+        .interact()?)
 }
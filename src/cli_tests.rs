@@ -1,7 +1,10 @@
 #[cfg(test)]
 mod tests {
-    use crate::core::init_sprout;
-    use crate::manifest::{load_manifest, save_manifest};
+    use crate::cli::{compute_missing_hashes, format_manifest, clean_unused_directories, module_sizes, render_module_sizes, configure_color, ColorChoice, edit_manifest, EditRecovery, env_edit_modules, expand_package_patterns, generate_environment, log_subscriber, LogFormat, handle_modules_command, manifest_lint_issues, module_dependencies, pin_module, print_tree_node, render_dependency_graph, Cli, Commands, ModulesCommand, rename_module, select_modules, write_environment_file, status_roots, StatusSections, StatusSummary, StatusTree, ModuleState};
+    use crate::parser::parse_manifest;
+    use crate::core::{add_file, build_commit_prompt, init_sprout_with_template, INIT_TEMPLATES, export_bundle, git_commit, git_push, git_tag, import_bundle, init_sprout, restore_symlinks};
+    use crate::core::deps::Progress;
+    use crate::manifest::{load_manifest, manifest_path, save_manifest};
     use std::fs;
     use tempfile::TempDir;
 
@@ -20,4 +23,1407 @@ mod tests {
             fs::read_to_string(format!("{}/manifest.sprout", sprout_path)).unwrap();
         insta::assert_snapshot!(manifest_content);
     }
+
+    #[test]
+    fn test_init_template_writes_bundled_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("minimal");
+
+        init_sprout_with_template(&sprout_path, Some("minimal")).unwrap();
+        assert_eq!(
+            fs::read_to_string(sprout_path.join("manifest.sprout")).unwrap(),
+            include_str!("templates/minimal.sprout")
+        );
+
+        // Every bundled template must be a valid manifest
+        for (name, content) in INIT_TEMPLATES {
+            assert!(parse_manifest(content).is_ok(), "template {} does not parse", name);
+        }
+
+        let err = init_sprout_with_template(temp_dir.path().join("bad"), Some("nope")).unwrap_err().to_string();
+        assert!(err.contains("Unknown template 'nope'") && err.contains("rust-tools"), "{}", err);
+        assert!(!temp_dir.path().join("bad").exists());
+    }
+
+    /// Serializes tests that flip the process-wide color override
+    static COLOR_OVERRIDE: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    #[test]
+    fn test_no_color_strips_escape_sequences() {
+        use clap::Parser;
+        use crate::lockfile::SproutLock;
+        let _guard = COLOR_OVERRIDE.lock().unwrap_or_else(|e| e.into_inner());
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest = parse_manifest(r#"
+module tool {
+    depends_on = []
+    fetch {
+        http = {
+            url = "https://example.com/tool-2.0.tar.gz"
+        }
+    }
+}
+"#).unwrap();
+        let lock = SproutLock::default();
+        let render = || {
+            let mut tree = StatusTree::new(sprout_path, &manifest, &lock, false, true);
+            print_tree_node(&mut tree, "tool", "", true, None).unwrap();
+            tree.out
+        };
+
+        configure_color(ColorChoice::Always);
+        let colored = render();
+        // Applied the way run_cli applies the flag
+        let cli = Cli::try_parse_from(["sprout", "--no-color", "modules", "status"]).unwrap();
+        configure_color(if cli.no_color { ColorChoice::Never } else { cli.color });
+        let plain = render();
+
+        assert!(colored.contains('\x1b'), "expected an escape sequence in {:?}", colored);
+        assert!(!plain.contains('\x1b'), "unexpected escape sequence in {:?}", plain);
+        assert!(plain.contains("tool ["), "{}", plain);
+    }
+
+    #[test]
+    fn test_color_always_keeps_escape_sequences_off_terminal() {
+        use clap::Parser;
+        use colored::Colorize;
+        let _guard = COLOR_OVERRIDE.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Test output is captured, so stdout is not a terminal here
+        let cli = Cli::try_parse_from(["sprout", "--color=always", "status"]).unwrap();
+        assert_eq!(cli.color, ColorChoice::Always);
+        configure_color(cli.color);
+        let forced = format!("{}", "✓".green());
+        configure_color(ColorChoice::Never);
+
+        assert!(forced.contains('\x1b'), "expected an escape sequence in {:?}", forced);
+        assert!(Cli::try_parse_from(["sprout", "--color", "sometimes", "status"]).is_err());
+        assert!(Cli::try_parse_from(["sprout", "status", "--no-color"]).unwrap().no_color);
+    }
+
+    #[test]
+    fn test_status_sections_scoping() {
+        let symlinks_only = StatusSections::from_flags(false, true, false);
+        assert_eq!(symlinks_only, StatusSections { modules: false, symlinks: true, git: false });
+
+        let everything = StatusSections::from_flags(false, false, false);
+        assert_eq!(everything, StatusSections { modules: true, symlinks: true, git: true });
+    }
+
+    #[test]
+    fn test_status_filter_matches_glob() {
+        let manifest = parse_manifest(r#"
+module llvm-core { depends_on = [] }
+module llvm-tools { depends_on = [llvm-core] }
+module cmake { depends_on = [] }
+"#).unwrap();
+
+        let roots = status_roots(&manifest, Some("llvm*"), None).unwrap();
+        assert_eq!(roots, vec!["llvm-core".to_string(), "llvm-tools".to_string()]);
+
+        assert_eq!(status_roots(&manifest, None, None).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_status_tree_depth_limits_expansion() {
+        use crate::lockfile::SproutLock;
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest = parse_manifest(r#"
+module zlib { depends_on = [] }
+module openssl { depends_on = [zlib] }
+module curl { depends_on = [openssl] }
+"#).unwrap();
+        let lock = SproutLock::default();
+
+        let mut tree = StatusTree::new(sprout_path, &manifest, &lock, true, true);
+        print_tree_node(&mut tree, "curl", "", true, Some(1)).unwrap();
+
+        let lines: Vec<&str> = tree.out.lines().collect();
+        assert_eq!(lines.len(), 3, "unexpected tree:\n{}", tree.out);
+        assert!(lines[0].contains("curl"));
+        assert!(lines[1].contains("openssl"));
+        assert!(lines[2].contains('…'));
+        assert!(!tree.out.contains("zlib"));
+
+        let mut tree = StatusTree::new(sprout_path, &manifest, &lock, true, true);
+        print_tree_node(&mut tree, "curl", "", true, None).unwrap();
+        assert!(tree.out.contains("zlib"));
+        assert!(!tree.out.contains('…'));
+    }
+
+    #[test]
+    fn test_status_tree_resolves_unversioned_dependency() {
+        use crate::lockfile::SproutLock;
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest = parse_manifest(r#"
+module gcc@13 { depends_on = [] }
+module app { depends_on = [gcc] }
+"#).unwrap();
+        let lock = SproutLock::default();
+
+        let mut tree = StatusTree::new(sprout_path, &manifest, &lock, true, true);
+        print_tree_node(&mut tree, "app", "", true, None).unwrap();
+        assert!(tree.out.contains("gcc@13"), "unexpected tree:\n{}", tree.out);
+        assert!(!tree.out.contains("not found"), "unexpected tree:\n{}", tree.out);
+        assert!(tree.states.contains_key("gcc@13"));
+    }
+
+    #[test]
+    fn test_status_tree_hashes_each_module_once() {
+        use crate::lockfile::SproutLock;
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        // zlib is shared by both branches of the diamond
+        let manifest = parse_manifest(r#"
+module zlib {
+    depends_on = []
+    build {
+        make
+    }
+}
+module openssl {
+    depends_on = [zlib]
+    build {
+        make
+    }
+}
+module libssh {
+    depends_on = [zlib]
+    build {
+        make
+    }
+}
+module curl {
+    depends_on = [openssl, libssh]
+    build {
+        make
+    }
+}
+"#).unwrap();
+        let lock = SproutLock::default();
+
+        let mut tree = StatusTree::new(sprout_path, &manifest, &lock, true, true);
+        for root in ["curl", "openssl", "zlib"] {
+            print_tree_node(&mut tree, root, "", true, None).unwrap();
+        }
+
+        assert_eq!(tree.out.matches("zlib").count(), 4);
+        assert_eq!(tree.hash_computations, manifest.modules.len());
+    }
+
+    #[test]
+    fn test_clean_orphans_removes_deleted_module_dist_and_lock_entry() {
+        use crate::lockfile::{PackageState, SproutLock};
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let state = |hash: &str| PackageState {
+            fetch_hash: None,
+            build_hash: Some(hash.to_string()),
+            resolved_commit: None,
+            source_hash: None,
+        };
+        let mut lock = SproutLock::default();
+        lock.set_module_state("kept".to_string(), state("old-hash"));
+        lock.set_module_state("gone".to_string(), state("abc"));
+        lock.save(sprout_path).unwrap();
+        for id in ["kept", "gone"] {
+            fs::create_dir_all(temp_dir.path().join("dist").join(id).join("bin")).unwrap();
+        }
+        // "gone" was deleted from the manifest; "kept" only changed its build
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module kept {
+    depends_on = []
+    build {
+        echo changed
+    }
+}
+"#).unwrap();
+
+        clean_unused_directories(sprout_path, &manifest_path(sprout_path), true, None, true, false, false).unwrap();
+        assert!(temp_dir.path().join("dist/gone").exists(), "dry run must not remove");
+
+        clean_unused_directories(sprout_path, &manifest_path(sprout_path), false, None, false, true, false).unwrap();
+        assert!(temp_dir.path().join("dist/gone").exists(), "dist is only cleaned with --orphans");
+
+        // Without --yes and without a terminal to ask on, nothing is deleted
+        let err = clean_unused_directories(sprout_path, &manifest_path(sprout_path), false, None, true, false, false).unwrap_err();
+        assert!(err.to_string().contains("--yes"), "{}", err);
+        assert!(temp_dir.path().join("dist/gone").exists());
+        assert!(SproutLock::load(sprout_path).unwrap().get_module_state("gone").is_some());
+
+        clean_unused_directories(sprout_path, &manifest_path(sprout_path), false, None, true, true, false).unwrap();
+        assert!(!temp_dir.path().join("dist/gone").exists());
+        assert!(temp_dir.path().join("dist/kept").exists());
+        let lock = SproutLock::load(sprout_path).unwrap();
+        assert!(lock.get_module_state("gone").is_none());
+        assert!(lock.get_module_state("kept").is_some());
+    }
+
+    #[test]
+    fn test_module_sizes_sum_sources_cache_and_dist() {
+        use crate::core::compute_fetch_hash;
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest = parse_manifest(r#"
+module tool {
+    depends_on = []
+    fetch {
+        http = {
+            url = "https://example.com/tool.tar.gz"
+        }
+    }
+}
+module small {
+    depends_on = []
+}
+"#).unwrap();
+        let hash = &compute_fetch_hash(manifest.find_module("tool").unwrap()).unwrap()[..8];
+        let write = |rel: String, len: usize| {
+            let path = temp_dir.path().join(rel);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, vec![b'x'; len]).unwrap();
+        };
+        write(format!("sources/http/tool-{}/src/main.c", hash), 3000);
+        write(format!("sources/http/tool-{}/README", hash), 500);
+        write("sources/http/tool-0badc0de/old.c".to_string(), 1000);
+        write(format!("cache/http/tool-{}/tool.tar.gz", hash), 2000);
+        write("dist/tool/bin/tool".to_string(), 4000);
+        write("dist/small/lib.so".to_string(), 10);
+        // Another module's directories must not be attributed to `tool`
+        write("sources/http/tool2-deadbeef/x".to_string(), 9999);
+
+        let sizes = module_sizes(sprout_path, &manifest).unwrap();
+        assert_eq!(sizes.iter().map(|s| s.id.as_str()).collect::<Vec<_>>(), ["tool", "small"]);
+        assert_eq!((sizes[0].sources, sizes[0].cache, sizes[0].dist), (4500, 2000, 4000));
+        assert_eq!((sizes[1].sources, sizes[1].cache, sizes[1].dist), (0, 0, 10));
+
+        let table = render_module_sizes(&sizes);
+        let total = table.lines().last().unwrap();
+        assert!(total.starts_with("TOTAL"), "{}", table);
+        assert!(total.ends_with("10.5 KB"), "{}", table);
+    }
+
+    #[test]
+    fn test_build_tag_selects_only_tagged_modules() {
+        use clap::Parser;
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        init_sprout(sprout_path, true).unwrap();
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module ripgrep {
+    tags = ["rust", "cli"]
+    depends_on = []
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+module fd {
+    tags = ["rust"]
+    depends_on = []
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+module jq {
+    tags = ["cli"]
+    depends_on = []
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+"#).unwrap();
+
+        let manifest = load_manifest(sprout_path).unwrap();
+        assert_eq!(select_modules(&manifest, &[], Some("cli")).unwrap(), ["jq", "ripgrep"]);
+        assert_eq!(status_roots(&manifest, None, Some("rust")).unwrap(), ["fd", "ripgrep"]);
+        assert!(select_modules(&manifest, &[], Some("python")).unwrap_err().to_string().contains("No modules tagged 'python'"));
+
+        // --all would silently ignore the tag
+        assert!(Cli::try_parse_from(["sprout", "modules", "build", "--all", "--tag", "rust"]).is_err());
+        assert!(Cli::try_parse_from(["sprout", "modules", "install", "--all", "--tag", "rust"]).is_err());
+
+        let cli = Cli::try_parse_from(["sprout", "modules", "build", "--tag", "rust"]).unwrap();
+        let Commands::Modules { command } = cli.command else { unreachable!() };
+        handle_modules_command(sprout_path, &manifest_path(sprout_path), command, false, false, Progress::default()).unwrap();
+        assert!(temp_dir.path().join("dist/ripgrep").exists());
+        assert!(temp_dir.path().join("dist/fd").exists());
+        assert!(!temp_dir.path().join("dist/jq").exists());
+    }
+
+    #[test]
+    fn test_status_hints_at_stale_caches() {
+        use crate::lockfile::SproutLock;
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest = parse_manifest(r#"
+module tool {
+    depends_on = []
+    fetch {
+        http = {
+            url = "https://example.com/tool-2.0.tar.gz"
+        }
+    }
+}
+"#).unwrap();
+        let lock = SproutLock::default();
+        let fetch_hash = crate::core::deps::compute_fetch_hash(&manifest.modules[0]).unwrap();
+
+        let cache = temp_dir.path().join("cache/http");
+        fs::create_dir_all(cache.join(format!("tool-{}", &fetch_hash[..8]))).unwrap();
+        let mut tree = StatusTree::new(sprout_path, &manifest, &lock, false, true);
+        print_tree_node(&mut tree, "tool", "", true, None).unwrap();
+        assert!(!tree.out.contains("stale cache"), "unexpected hint:\n{}", tree.out);
+
+        // A cache from an earlier spec plus an unrelated module sharing the prefix
+        let stale = if fetch_hash.starts_with("deadbeef") { "0badcafe" } else { "deadbeef" };
+        fs::create_dir_all(cache.join(format!("tool-{}", stale))).unwrap();
+        fs::create_dir_all(cache.join("tool2-0badcafe")).unwrap();
+        let mut tree = StatusTree::new(sprout_path, &manifest, &lock, false, true);
+        print_tree_node(&mut tree, "tool", "", true, None).unwrap();
+        assert!(tree.out.contains("(1 stale cache — run clean)"), "missing hint:\n{}", tree.out);
+    }
+
+    #[test]
+    fn test_status_flags_dist_missing_from_lockfile_as_unlocked() {
+        use crate::lockfile::SproutLock;
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest = parse_manifest(r#"
+module tool {
+    depends_on = []
+    build {
+        make install
+    }
+}
+"#).unwrap();
+        fs::create_dir_all(temp_dir.path().join("dist/tool/bin")).unwrap();
+        let lock = SproutLock::default();
+
+        let mut tree = StatusTree::new(sprout_path, &manifest, &lock, true, true);
+        print_tree_node(&mut tree, "tool", "", true, None).unwrap();
+        assert_eq!(tree.states.get("tool"), Some(&ModuleState::Unlocked));
+        assert!(tree.out.contains("built but unlocked — run `modules hash -i`"), "{}", tree.out);
+        assert_eq!(tree.summary.to_string(), "1 module: 0 up to date, 0 need rebuild, 0 not fetched, 1 built but unlocked");
+
+        // Without a dist it is an ordinary rebuild
+        fs::remove_dir_all(temp_dir.path().join("dist/tool")).unwrap();
+        let mut tree = StatusTree::new(sprout_path, &manifest, &lock, true, true);
+        print_tree_node(&mut tree, "tool", "", true, None).unwrap();
+        assert_eq!(tree.states.get("tool"), Some(&ModuleState::NeedsRebuild));
+    }
+
+    #[test]
+    fn test_collapsed_status_marks_hidden_dependencies() {
+        use crate::lockfile::SproutLock;
+        use clap::Parser;
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest = parse_manifest(r#"
+module zlib {
+    depends_on = []
+}
+module cmake {
+    depends_on = []
+}
+module app {
+    depends_on = [zlib, cmake]
+}
+"#).unwrap();
+        let lock = SproutLock::default();
+
+        let mut tree = StatusTree::new(sprout_path, &manifest, &lock, false, true);
+        print_tree_node(&mut tree, "app", "", true, None).unwrap();
+        assert!(tree.out.contains("(+2 deps)"), "missing marker:\n{}", tree.out);
+        assert!(!tree.out.contains("zlib"), "dependencies should be collapsed:\n{}", tree.out);
+
+        let mut tree = StatusTree::new(sprout_path, &manifest, &lock, false, true);
+        print_tree_node(&mut tree, "zlib", "", true, None).unwrap();
+        assert!(!tree.out.contains("deps)"), "leaf needs no marker:\n{}", tree.out);
+
+        let mut tree = StatusTree::new(sprout_path, &manifest, &lock, true, true);
+        print_tree_node(&mut tree, "app", "", true, None).unwrap();
+        assert!(!tree.out.contains("(+2 deps)") && tree.out.contains("zlib"), "expanded view:\n{}", tree.out);
+
+        // Both status views expand by default and collapse with --no-expand
+        for args in [&["sprout", "status"][..], &["sprout", "modules", "status"]] {
+            let cli = Cli::try_parse_from(args).unwrap();
+            let expand = match cli.command {
+                Commands::Status { expand, no_expand, .. } => expand && !no_expand,
+                Commands::Modules { command: ModulesCommand::Status { expand, no_expand, .. } } => expand && !no_expand,
+                _ => unreachable!(),
+            };
+            assert!(expand);
+        }
+        assert!(matches!(
+            Cli::try_parse_from(["sprout", "status", "--no-expand"]).unwrap().command,
+            Commands::Status { no_expand: true, .. }
+        ));
+        assert!(Cli::try_parse_from(["sprout", "status", "--expand"]).is_ok());
+    }
+
+    #[test]
+    fn test_status_summary_counts_each_module_once() {
+        use crate::lockfile::{PackageState, SproutLock};
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest = parse_manifest(r#"
+module zlib {
+    depends_on = []
+    build {
+        make install
+    }
+}
+module stale {
+    depends_on = [zlib]
+    build {
+        make install
+    }
+}
+module remote {
+    depends_on = [zlib]
+    fetch {
+        git = {
+            url = "https://example.com/remote.git"
+        }
+    }
+}
+module app {
+    depends_on = [stale, remote]
+    build {
+        make install
+    }
+}
+"#).unwrap();
+
+        let mut lock = SproutLock::default();
+        for id in ["zlib", "app"] {
+            let module = manifest.find_module(id).unwrap();
+            fs::create_dir_all(crate::core::get_dist_path(sprout_path, module)).unwrap();
+            lock.set_module_state(id.to_string(), PackageState {
+                fetch_hash: None,
+                build_hash: crate::core::deps::compute_build_hash(module),
+                resolved_commit: None,
+                source_hash: None,
+            });
+        }
+
+        let mut tree = StatusTree::new(sprout_path, &manifest, &lock, true, true);
+        print_tree_node(&mut tree, "app", "", true, None).unwrap();
+        assert_eq!(tree.summary, StatusSummary { total: 4, up_to_date: 2, needs_rebuild: 1, not_fetched: 1, unlocked: 0 });
+        assert_eq!(tree.summary.to_string(), "4 modules: 2 up to date, 1 needs rebuild, 1 not fetched");
+    }
+
+    #[test]
+    fn test_format_without_in_place_leaves_directories_untouched() {
+        use crate::ast::PrettyPrint;
+        use sha2::{Digest, Sha256};
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module tool {
+    depends_on = []
+    fetch {
+        http = {
+            url = "https://example.com/tool.tar.gz"
+        }
+    }
+}
+"#).unwrap();
+
+        let manifest = load_manifest(sprout_path).unwrap();
+        let fetch_hash = crate::core::deps::compute_fetch_hash(&manifest.modules[0]).unwrap();
+        let old_dir = temp_dir.path().join(format!("cache/http/tool-{}", &fetch_hash[..8]));
+        fs::create_dir_all(&old_dir).unwrap();
+        fs::write(old_dir.join("tool.tar.gz"), b"archive").unwrap();
+        let expected = format!("{:x}", Sha256::digest(b"archive"));
+
+        let (formatted, pending) = compute_missing_hashes(sprout_path, &manifest_path(sprout_path)).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].sha256, expected);
+        assert!(formatted.pretty_print().contains(&expected));
+
+        // Nothing on disk changes until -i is given
+        assert!(old_dir.exists());
+        assert!(!temp_dir.path().join("cache/http").join(&pending[0].new_dir_name).exists());
+        assert!(!fs::read_to_string(temp_dir.path().join("manifest.sprout")).unwrap().contains(&expected));
+    }
+
+    #[test]
+    fn test_global_dry_run_leaves_manifest_unformatted() {
+        let _guard = COLOR_OVERRIDE.lock().unwrap_or_else(|e| e.into_inner());
+        use clap::Parser;
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest_path = temp_dir.path().join("manifest.sprout");
+        let original = "module tool {\n  depends_on = []\n}\n";
+        fs::write(&manifest_path, original).unwrap();
+
+        let run = |args: &[&str]| {
+            let cli = Cli::try_parse_from(args).unwrap();
+            tokio::runtime::Runtime::new().unwrap().block_on(crate::cli::run_cli(cli)).unwrap();
+        };
+        run(&["sprout", "--dry-run", "--sprout-path", sprout_path, "format", "-i", sprout_path]);
+        assert_eq!(fs::read_to_string(&manifest_path).unwrap(), original);
+
+        run(&["sprout", "--sprout-path", sprout_path, "format", "-i", sprout_path]);
+        assert_ne!(fs::read_to_string(&manifest_path).unwrap(), original);
+
+        // The global flag ORs with a subcommand's own --dry-run
+        let mut command = match Cli::try_parse_from(["sprout", "--dry-run", "modules", "clean"]).unwrap().command {
+            Commands::Modules { command } => command,
+            _ => unreachable!(),
+        };
+        command.apply_dry_run(true);
+        assert!(matches!(command, ModulesCommand::Clean { dry_run: true, .. }));
+        assert!(matches!(
+            Cli::try_parse_from(["sprout", "modules", "clean", "--dry-run"]).unwrap().command,
+            Commands::Modules { command: ModulesCommand::Clean { dry_run: true, .. } }
+        ));
+    }
+
+    #[test]
+    fn test_format_without_in_place_returns_result_and_leaves_disk_alone() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest_path = temp_dir.path().join("manifest.sprout");
+        let original = "module tool {\n  depends_on = []\n  fetch {\n    http = { url = \"https://example.com/tool.tar.gz\" }\n  }\n}\n";
+        fs::write(&manifest_path, original).unwrap();
+        let module = load_manifest(sprout_path).unwrap().modules[0].clone();
+        let fetch_hash = crate::core::deps::compute_fetch_hash(&module).unwrap();
+        let cache_dir = temp_dir.path().join(format!("cache/http/tool-{}", &fetch_hash[..8]));
+        fs::create_dir_all(&cache_dir).unwrap();
+        fs::write(cache_dir.join("tool.tar.gz"), "archive").unwrap();
+
+        let formatted = format_manifest(sprout_path, &manifest_path, false, false).unwrap().unwrap();
+        assert_ne!(formatted, original);
+        assert!(parse_manifest(&formatted).is_ok(), "{}", formatted);
+        let sha256 = compute_missing_hashes(sprout_path, &manifest_path).unwrap().1[0].sha256.clone();
+        assert!(formatted.contains(&sha256), "{}", formatted);
+
+        // The hash is only shown; manifest, cache dir and lockfile stay as they were
+        assert_eq!(fs::read_to_string(&manifest_path).unwrap(), original);
+        assert!(cache_dir.join("tool.tar.gz").exists());
+        assert!(!temp_dir.path().join("sprout.lock").exists());
+    }
+
+    #[test]
+    fn test_global_dry_run_reaches_every_mutating_handler() {
+        let _guard = COLOR_OVERRIDE.lock().unwrap_or_else(|e| e.into_inner());
+        use clap::Parser;
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest_path = temp_dir.path().join("manifest.sprout");
+        fs::write(&manifest_path, r#"
+module tool {
+    depends_on = []
+    provides = { prepend PATH = "/bin" }
+    fetch {
+        http = { url = "https://example.com/tool.tar.gz" sha256 = "0000000000000000000000000000000000000000000000000000000000000000" }
+    }
+    build {
+        make
+    }
+}
+environments {
+    default = [tool]
+}
+"#).unwrap();
+        let original = fs::read_to_string(&manifest_path).unwrap();
+        let module = load_manifest(sprout_path).unwrap().modules[0].clone();
+        let fetch_hash = crate::core::deps::compute_fetch_hash(&module).unwrap();
+        let cached = temp_dir.path().join(format!("cache/http/tool-{}/tool.tar.gz", &fetch_hash[..8]));
+        fs::create_dir_all(cached.parent().unwrap()).unwrap();
+        fs::write(&cached, "corrupt").unwrap();
+
+        let run = |args: &[&str]| {
+            let mut argv = vec!["sprout", "--dry-run", "--sprout-path", sprout_path];
+            argv.extend_from_slice(args);
+            let cli = Cli::try_parse_from(argv).unwrap();
+            tokio::runtime::Runtime::new().unwrap().block_on(crate::cli::run_cli(cli)).unwrap();
+        };
+        run(&["modules", "hash", "-i"]);
+        run(&["modules", "verify", "--fix"]);
+        run(&["env", "generate", "--output", temp_dir.path().join("env.sh").to_str().unwrap()]);
+        run(&["edit", sprout_path]);
+        run(&["modules", "fetch", "--print-hash", "--force", "tool"]);
+
+        assert!(!temp_dir.path().join("sprout.lock").exists(), "hash -i wrote the lockfile");
+        assert!(cached.exists(), "verify --fix or fetch --print-hash --force removed a cache");
+        assert!(!temp_dir.path().join("env.sh").exists(), "env generate wrote its output");
+        assert_eq!(fs::read_to_string(&manifest_path).unwrap(), original);
+    }
+
+    #[test]
+    fn test_env_generate_orders_dependencies_first() {
+        // b depends on a, but the environment lists b first
+        let manifest = parse_manifest(r#"
+module b {
+    depends_on = [a]
+    provides = { prepend PATH = "/bin" }
+}
+module a {
+    depends_on = []
+    provides = { prepend PATH = "/bin" }
+}
+environments {
+    default = [b, a]
+}
+"#).unwrap();
+
+        let script = generate_environment("/sprout", &manifest, "default").unwrap();
+        let path_line = script.lines().find(|l| l.starts_with("export PATH=")).unwrap();
+        let a = path_line.find("/sprout/dist/a/bin").unwrap();
+        let b = path_line.find("/sprout/dist/b/bin").unwrap();
+        assert!(a < b, "dependency path should come first: {}", path_line);
+    }
+
+    #[test]
+    fn test_env_generate_honors_prepend_and_append_per_variable() {
+        let manifest = parse_manifest(r#"
+module tool {
+    depends_on = []
+    provides = {
+        prepend PATH = "/bin"
+        append MANPATH = "/share/man"
+    }
+}
+environments {
+    default = [tool]
+}
+"#).unwrap();
+
+        let script = generate_environment("/sprout", &manifest, "default").unwrap();
+        assert!(script.contains("export PATH=\"/sprout/dist/tool/bin${PATH:+:${PATH}}\"\n"), "{}", script);
+        assert!(script.contains("export MANPATH=\"${MANPATH:+${MANPATH}:}/sprout/dist/tool/share/man\"\n"), "{}", script);
+    }
+
+    #[test]
+    fn test_env_generate_keeps_interpolated_provides_absolute() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module tool {
+    depends_on = []
+    provides = {
+        prepend PATH = "${SPROUT_PATH}/shared/bin"
+        append MANPATH = "${DIST_PATH}/share/man"
+        set TOOL_HOME = "/"
+    }
+}
+environments {
+    default = [tool]
+}
+"#).unwrap();
+        let manifest = load_manifest(sprout_path).unwrap();
+
+        let script = generate_environment(sprout_path, &manifest, "default").unwrap();
+        assert!(script.contains(&format!("export PATH=\"{}/shared/bin${{PATH:+:${{PATH}}}}\"\n", sprout_path)), "{}", script);
+        assert!(script.contains(&format!("export MANPATH=\"${{MANPATH:+${{MANPATH}}:}}{}/dist/tool/share/man\"\n", sprout_path)), "{}", script);
+        assert!(!script.contains(&format!("dist/tool{}", sprout_path)), "{}", script);
+    }
+
+    #[test]
+    fn test_env_edit_adds_and_removes_modules_without_prompting() {
+        use crate::lockfile::{PackageState, SproutLock};
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module rg {
+    depends_on = []
+}
+module fd {
+    depends_on = []
+}
+module unbuilt {
+    depends_on = []
+}
+environments {
+    default = [rg]
+}
+"#).unwrap();
+        let mut lock = SproutLock::default();
+        for id in ["rg", "fd"] {
+            lock.set_module_state(id.to_string(), PackageState {
+                fetch_hash: None,
+                build_hash: Some("built".to_string()),
+                resolved_commit: None,
+                source_hash: None,
+            });
+        }
+        lock.save(sprout_path).unwrap();
+        let environment = |name: &str| load_manifest(sprout_path).unwrap()
+            .environments.unwrap().environments.get(name).cloned();
+
+        env_edit_modules(sprout_path, &manifest_path(sprout_path), "default", &["fd".to_string()], &["rg".to_string()], false).unwrap();
+        assert_eq!(environment("default"), Some(vec!["fd".to_string()]));
+
+        // New environments are created; removing an absent module only warns
+        env_edit_modules(sprout_path, &manifest_path(sprout_path), "dev", &["rg".to_string(), "fd".to_string()], &["nope".to_string()], false).unwrap();
+        assert_eq!(environment("dev"), Some(vec!["rg".to_string(), "fd".to_string()]));
+
+        let err = env_edit_modules(sprout_path, &manifest_path(sprout_path), "default", &["unbuilt".to_string()], &[], false).unwrap_err();
+        assert!(err.to_string().contains("not built"), "unexpected error: {}", err);
+        assert_eq!(environment("default"), Some(vec!["fd".to_string()]));
+    }
+
+    #[test]
+    fn test_env_generate_expands_wildcard_to_built_modules() {
+        use crate::lockfile::{PackageState, SproutLock};
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest = parse_manifest(r#"
+module rg {
+    depends_on = []
+    provides = { prepend PATH = "/bin" }
+}
+module fd {
+    depends_on = []
+    provides = { prepend PATH = "/bin" }
+}
+module unbuilt {
+    depends_on = []
+    provides = { prepend PATH = "/bin" }
+}
+environments {
+    default = [rg, *]
+}
+"#).unwrap();
+        let mut lock = SproutLock::default();
+        for id in ["rg", "fd"] {
+            lock.set_module_state(id.to_string(), PackageState {
+                fetch_hash: None,
+                build_hash: Some("built".to_string()),
+                resolved_commit: None,
+                source_hash: None,
+            });
+        }
+        lock.save(sprout_path).unwrap();
+
+        let script = generate_environment(sprout_path, &manifest, "default").unwrap();
+        let path_line = script.lines().find(|l| l.starts_with("export PATH=")).unwrap();
+        assert!(path_line.contains("dist/rg/bin") && path_line.contains("dist/fd/bin"), "{}", path_line);
+        assert!(!path_line.contains("dist/unbuilt"), "{}", path_line);
+        assert_eq!(path_line.matches("dist/rg/bin").count(), 1, "rg listed twice: {}", path_line);
+    }
+
+    #[test]
+    fn test_json_log_format_emits_parseable_lines() {
+        use std::sync::{Arc, Mutex};
+        use tracing_subscriber::filter::LevelFilter;
+
+        #[derive(Clone, Default)]
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+        impl std::io::Write for Buffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = log_subscriber(LogFormat::Json, LevelFilter::INFO, move || writer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(module = "ripgrep", seconds = 3, "build finished");
+            tracing::debug!("filtered out");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 1, "unexpected output:\n{}", output);
+        let record: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(record["level"], "INFO");
+        assert_eq!(record["fields"]["message"], "build finished");
+        assert_eq!(record["fields"]["module"], "ripgrep");
+        assert_eq!(record["fields"]["seconds"], 3);
+    }
+
+    #[test]
+    fn test_env_generate_writes_activation_script() {
+        let temp_dir = TempDir::new().unwrap();
+        let manifest = parse_manifest(r#"
+module tool {
+    depends_on = []
+    provides = { prepend PATH = "/bin" }
+}
+environments {
+    default = [tool]
+}
+"#).unwrap();
+
+        let output = temp_dir.path().join("config/sprout/activate.sh");
+        let script = generate_environment("/sprout", &manifest, "default").unwrap();
+        write_environment_file(&output, &script).unwrap();
+
+        let written = fs::read_to_string(&output).unwrap();
+        assert!(written.contains("export SPROUT_ENV_LOADED=1"));
+        assert!(written.contains("export PATH=\"/sprout/dist/tool/bin"));
+
+        // Only the script is left behind, no temporary file
+        let entries: Vec<_> = fs::read_dir(output.parent().unwrap()).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn test_commit_tag_created_and_not_overwritten() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+
+        init_sprout(sprout_path, true).unwrap();
+        fs::write(temp_dir.path().join("manifest.sprout"), "module tool { depends_on = [] }\n").unwrap();
+        crate::core::git_commit(sprout_path, "Add tool").unwrap();
+
+        git_tag(sprout_path, "v1", None, false).unwrap();
+        let tags = std::process::Command::new("git")
+            .current_dir(sprout_path)
+            .args(["tag", "-n1", "--list", "v1"])
+            .output()
+            .unwrap();
+        let tags = String::from_utf8_lossy(&tags.stdout);
+        assert!(tags.contains("v1") && tags.contains("Add tool"), "unexpected tags: {}", tags);
+
+        // An existing tag is only replaced when forced
+        assert!(git_tag(sprout_path, "v1", Some("again"), false).is_err());
+        git_tag(sprout_path, "v1", Some("again"), true).unwrap();
+    }
+
+    #[test]
+    fn test_commit_with_taken_tag_commits_nothing() {
+        let _guard = COLOR_OVERRIDE.lock().unwrap_or_else(|e| e.into_inner());
+        use clap::Parser;
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        init_sprout(sprout_path, true).unwrap();
+        fs::write(temp_dir.path().join("manifest.sprout"), "module tool { depends_on = [] }\n").unwrap();
+        crate::core::git_commit(sprout_path, "Add tool").unwrap();
+        git_tag(sprout_path, "v1", None, false).unwrap();
+        let head = || {
+            let output = std::process::Command::new("git").current_dir(sprout_path).args(["rev-parse", "HEAD"]).output().unwrap();
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        let before = head();
+
+        fs::write(temp_dir.path().join("manifest.sprout"), "module tool { depends_on = [] }\nmodule other { depends_on = [] }\n").unwrap();
+        let cli = Cli::try_parse_from(["sprout", "--sprout-path", sprout_path, "commit", "-m", "Add other", "--tag", "v1"]).unwrap();
+        let err = tokio::runtime::Runtime::new().unwrap().block_on(crate::cli::run_cli(cli)).unwrap_err();
+        assert!(err.to_string().contains("already exists"), "{}", err);
+        assert_eq!(head(), before, "nothing may be committed when the tag is taken");
+    }
+
+    #[test]
+    fn test_commit_push_reaches_remote() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_dir = temp_dir.path().join("sprout");
+        let remote_dir = temp_dir.path().join("remote.git");
+        let sprout_path = sprout_dir.to_str().unwrap();
+
+        init_sprout(sprout_path, true).unwrap();
+        fs::write(sprout_dir.join("manifest.sprout"), "module tool { depends_on = [] }\n").unwrap();
+        git_commit(sprout_path, "Add tool").unwrap();
+
+        // Without a remote the push is refused with a clear message
+        let err = git_push(sprout_path, None, None).unwrap_err();
+        assert!(err.to_string().contains("No git remote 'origin'"), "unexpected error: {}", err);
+
+        let git = |dir: &std::path::Path, args: &[&str]| {
+            let output = std::process::Command::new("git").current_dir(dir).args(args).output().unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        git(temp_dir.path(), &["init", "--bare", "-q", remote_dir.to_str().unwrap()]);
+        git(&sprout_dir, &["remote", "add", "origin", remote_dir.to_str().unwrap()]);
+
+        git_push(sprout_path, None, None).unwrap();
+
+        let branch = git(&sprout_dir, &["branch", "--show-current"]);
+        let pushed = git(&remote_dir, &["log", "-1", "--format=%s", &branch]);
+        assert_eq!(pushed, "Add tool");
+    }
+
+    #[test]
+    fn test_rename_module_updates_dependents_and_dist() {
+        use crate::lockfile::{PackageState, SproutLock};
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module libz { depends_on = [] }
+module curl { depends_on = [libz] }
+environments {
+    default = [curl, libz]
+}
+"#).unwrap();
+        fs::create_dir_all(temp_dir.path().join("dist/libz/lib")).unwrap();
+        let mut lock = SproutLock::default();
+        lock.set_module_state("libz".to_string(), PackageState {
+            fetch_hash: None,
+            build_hash: Some("abc".to_string()),
+            resolved_commit: None,
+            source_hash: None,
+        });
+        lock.save(sprout_path).unwrap();
+
+        rename_module(sprout_path, &manifest_path(sprout_path), "libz", "zlib", false).unwrap();
+
+        let manifest = load_manifest(sprout_path).unwrap();
+        assert!(manifest.find_module("libz").is_none());
+        assert_eq!(manifest.find_module("curl").unwrap().depends_on, vec!["zlib".to_string()]);
+        let default_env = &manifest.environments.unwrap().environments["default"];
+        assert!(default_env.contains(&"zlib".to_string()));
+
+        assert!(!temp_dir.path().join("dist/libz").exists());
+        assert!(temp_dir.path().join("dist/zlib/lib").exists());
+
+        let lock = SproutLock::load(sprout_path).unwrap();
+        assert!(lock.get_module_state("libz").is_none());
+        assert_eq!(lock.get_module_state("zlib").unwrap().build_hash.as_deref(), Some("abc"));
+
+        // Renaming onto an existing module is refused
+        assert!(rename_module(sprout_path, &manifest_path(sprout_path), "zlib", "curl", false).is_err());
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let old_sprout = temp_dir.path().join("old/sprout");
+        let old_home = temp_dir.path().join("old/home");
+        let new_sprout = temp_dir.path().join("new/sprout");
+        let new_home = temp_dir.path().join("new/home");
+
+        init_sprout(&old_sprout, false).unwrap();
+        fs::create_dir_all(&old_home).unwrap();
+        fs::write(old_home.join(".bashrc"), "export EDITOR=vim\n").unwrap();
+        add_file(old_sprout.to_str().unwrap(), old_home.join(".bashrc"), false, false, false, false, old_home.to_str().unwrap()).unwrap();
+        fs::create_dir_all(old_sprout.join("dist/tool")).unwrap();
+
+        let bundle = temp_dir.path().join("sprout.tar.gz");
+        export_bundle(&old_sprout, &bundle).unwrap();
+
+        import_bundle(&bundle, &new_sprout).unwrap();
+        for file in ["manifest.sprout", "sprout.lock", "symlinks/.bashrc"] {
+            assert_eq!(
+                fs::read_to_string(old_sprout.join(file)).unwrap(),
+                fs::read_to_string(new_sprout.join(file)).unwrap(),
+                "{} differs", file
+            );
+        }
+        assert!(!new_sprout.join("dist/tool").exists());
+
+        // Restoring on the new machine links into the imported directory
+        fs::create_dir_all(&new_home).unwrap();
+        restore_symlinks(new_sprout.to_str().unwrap(), false, new_home.to_str().unwrap()).unwrap();
+        assert_eq!(fs::read_link(new_home.join(".bashrc")).unwrap(), new_sprout.join("symlinks/.bashrc"));
+
+        // An existing setup is never overwritten
+        assert!(import_bundle(&bundle, &new_sprout).is_err());
+    }
+
+    #[test]
+    fn test_import_fails_when_symlinks_cannot_be_restored() {
+        let _guard = COLOR_OVERRIDE.lock().unwrap_or_else(|e| e.into_inner());
+        use clap::Parser;
+
+        let temp_dir = TempDir::new().unwrap();
+        let old_sprout = temp_dir.path().join("old/sprout");
+        let old_home = temp_dir.path().join("old/home");
+        let new_sprout = temp_dir.path().join("new/sprout");
+        let new_home = temp_dir.path().join("new/home");
+
+        init_sprout(&old_sprout, false).unwrap();
+        fs::create_dir_all(old_home.join(".config/tool")).unwrap();
+        fs::write(old_home.join(".config/tool/config"), "key = value\n").unwrap();
+        add_file(old_sprout.to_str().unwrap(), old_home.join(".config/tool/config"), false, false, false, false, old_home.to_str().unwrap()).unwrap();
+        let bundle = temp_dir.path().join("sprout.tar.gz");
+        export_bundle(&old_sprout, &bundle).unwrap();
+
+        // A plain file where the link's parent directory has to go
+        fs::create_dir_all(&new_home).unwrap();
+        fs::write(new_home.join(".config"), "not a directory").unwrap();
+        let cli = Cli::try_parse_from([
+            "sprout", "--tracking-path", new_home.to_str().unwrap(),
+            "import", bundle.to_str().unwrap(), new_sprout.to_str().unwrap(),
+        ]).unwrap();
+        let err = tokio::runtime::Runtime::new().unwrap().block_on(crate::cli::run_cli(cli)).unwrap_err();
+        assert!(err.to_string().contains("failed to restore 1 symlink"), "{}", err);
+        assert!(new_sprout.join("symlinks/.config/tool/config").exists());
+    }
+
+    #[test]
+    fn test_commit_template_substitutes_diff() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path();
+        let diff = "+module tool { depends_on = [] }";
+
+        // Without a template file the built-in prompt is used
+        let prompt = build_commit_prompt(sprout_path, None, diff).unwrap();
+        assert!(prompt.starts_with("Generate a concise git commit message"));
+        assert!(prompt.ends_with(diff));
+
+        fs::write(sprout_path.join("commit_template.txt"), "Prefix the subject with [DOTFILES].\n\n{diff}\n").unwrap();
+        let prompt = build_commit_prompt(sprout_path, None, diff).unwrap();
+        assert_eq!(prompt, format!("Prefix the subject with [DOTFILES].\n\n{}\n", diff));
+
+        // An explicit template wins and must carry the placeholder
+        let custom = temp_dir.path().join("custom.txt");
+        fs::write(&custom, "Write the message in German.").unwrap();
+        let err = build_commit_prompt(sprout_path, Some(&custom), diff).unwrap_err();
+        assert!(err.to_string().contains("{diff}"));
+    }
+
+    #[test]
+    fn test_build_glob_selects_matching_modules() {
+        use clap::Parser;
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        init_sprout(sprout_path, true).unwrap();
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module libssl {
+    depends_on = [libz]
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+module libz {
+    depends_on = []
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+module app {
+    depends_on = [libssl]
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+"#).unwrap();
+
+        let build = |args: &[&str]| {
+            let cli = Cli::try_parse_from([&["sprout", "modules", "build"], args].concat()).unwrap();
+            let Commands::Modules { command } = cli.command else { unreachable!() };
+            handle_modules_command(sprout_path, &manifest_path(sprout_path), command, false, false, Progress::default())
+        };
+
+        let manifest = load_manifest(sprout_path).unwrap();
+        assert_eq!(expand_package_patterns(&manifest, &["lib*".to_string(), "app".to_string()]).unwrap(), ["libz", "libssl", "app"]);
+
+        let err = build(&["gcc*"]).unwrap_err();
+        assert!(err.to_string().contains("No modules match pattern: gcc*"), "{}", err);
+
+        build(&["lib*"]).unwrap();
+        assert!(temp_dir.path().join("dist/libz").exists());
+        assert!(temp_dir.path().join("dist/libssl").exists());
+        assert!(!temp_dir.path().join("dist/app").exists());
+    }
+
+    #[test]
+    fn test_lint_ignores_interpolated_exports() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        init_sprout(sprout_path, true).unwrap();
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module tool {
+    depends_on = []
+    provides = {
+        prepend MANPATH = "${DIST_PATH}/share/man"
+    }
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+"#).unwrap();
+
+        // The temp dir lives under /tmp, which expanded would count as a system path
+        let issues = manifest_lint_issues(sprout_path, &manifest_path(sprout_path)).unwrap();
+        assert!(issues.is_empty(), "unexpected issues: {:?}", issues.iter().map(|i| &i.message).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_build_all_fails_when_a_module_fails() {
+        use clap::Parser;
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        init_sprout(sprout_path, true).unwrap();
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module good {
+    depends_on = []
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+module broken {
+    depends_on = []
+    build {
+        false
+    }
+}
+"#).unwrap();
+
+        let cli = Cli::try_parse_from(["sprout", "modules", "build", "--all"]).unwrap();
+        let Commands::Modules { command } = cli.command else { unreachable!() };
+        let err = handle_modules_command(sprout_path, &manifest_path(sprout_path), command, false, false, Progress::default()).unwrap_err();
+        assert_eq!(err.to_string(), "Failed to build: broken");
+        assert_eq!(crate::error::exit_code(&err), 3);
+
+        // The rest of the queue still ran
+        assert!(temp_dir.path().join("dist/good").exists());
+    }
+
+    #[test]
+    fn test_fetch_and_install_all_fail_when_a_module_fails() {
+        use clap::Parser;
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        init_sprout(sprout_path, true).unwrap();
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module good {
+    depends_on = []
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+module gone {
+    depends_on = []
+    fetch {
+        local = {
+            path = vendor/gone
+        }
+    }
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+module broken {
+    depends_on = []
+    build {
+        false
+    }
+}
+"#).unwrap();
+
+        let run = |args: &[&str]| {
+            let cli = Cli::try_parse_from([&["sprout", "modules"], args].concat()).unwrap();
+            let Commands::Modules { command } = cli.command else { unreachable!() };
+            handle_modules_command(sprout_path, &manifest_path(sprout_path), command, false, false, Progress::default())
+        };
+
+        let err = run(&["fetch", "--all"]).unwrap_err();
+        assert_eq!(err.to_string(), "Failed to fetch: gone");
+        assert_eq!(crate::error::exit_code(&err), 4);
+
+        let err = run(&["install", "--all"]).unwrap_err();
+        assert_eq!(err.to_string(), "Failed to fetch: gone\nFailed to build: broken");
+        assert_eq!(crate::error::exit_code(&err), 4);
+        assert!(temp_dir.path().join("dist/good").exists());
+
+        // Only build failures left: the build exit code
+        fs::create_dir_all(temp_dir.path().join("vendor/gone")).unwrap();
+        let err = run(&["install", "--all"]).unwrap_err();
+        assert_eq!(err.to_string(), "Failed to build: broken");
+        assert_eq!(crate::error::exit_code(&err), 3);
+    }
+
+    #[test]
+    fn test_pin_writes_current_head_into_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout");
+        let upstream = temp_dir.path().join("upstream");
+        fs::create_dir_all(&upstream).unwrap();
+        init_sprout(sprout_path.to_str().unwrap(), true).unwrap();
+        let sprout_path = sprout_path.to_str().unwrap();
+
+        let git = |args: &[&str]| {
+            let output = std::process::Command::new("git").current_dir(&upstream).args(args).output().unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        git(&["init", "-q", "-b", "main"]);
+        fs::write(upstream.join("VERSION"), "1").unwrap();
+        git(&["add", "VERSION"]);
+        git(&["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "-q", "-m", "1"]);
+        let head = git(&["rev-parse", "HEAD"]);
+
+        fs::write(temp_dir.path().join("sprout/manifest.sprout"), format!(r#"
+module tool {{
+    depends_on = []
+    fetch {{
+        git = {{
+            url = {}
+            ref = main
+        }}
+    }}
+}}
+"#, upstream.display())).unwrap();
+
+        assert_eq!(pin_module(sprout_path, &manifest_path(sprout_path), "tool", Progress::default()).unwrap(), head);
+
+        let manifest = load_manifest(sprout_path).unwrap();
+        let Some(crate::ast::FetchSpec::Git(spec)) = manifest.modules[0].fetch.as_ref().map(|f| &f.spec) else { unreachable!() };
+        assert_eq!(spec.ref_.as_deref(), Some(head.as_str()));
+
+        // The checkout follows the new fetch hash, so nothing needs refetching
+        let source = crate::core::get_source_path(sprout_path, &manifest.modules[0]);
+        assert_eq!(fs::read_to_string(source.join("VERSION")).unwrap(), "1");
+        let lock = crate::lockfile::SproutLock::load(sprout_path).unwrap();
+        assert_eq!(lock.modules["tool"].fetch_hash, crate::core::deps::compute_fetch_hash(&manifest.modules[0]));
+
+        // Pinning again is a no-op
+        assert_eq!(pin_module(sprout_path, &manifest_path(sprout_path), "tool", Progress::default()).unwrap(), head);
+    }
+
+    #[test]
+    fn test_install_only_fetch_leaves_dist_empty() {
+        use clap::Parser;
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        init_sprout(sprout_path, true).unwrap();
+        fs::create_dir_all(temp_dir.path().join("vendor/tool")).unwrap();
+        fs::write(temp_dir.path().join("vendor/tool/Makefile"), "all:\n").unwrap();
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module tool {
+    depends_on = []
+    fetch {
+        local = {
+            path = vendor/tool
+        }
+    }
+    build {
+        mkdir -p ${DIST_PATH}/bin
+    }
+}
+"#).unwrap();
+
+        let install = |args: &[&str]| {
+            let cli = Cli::try_parse_from([&["sprout", "modules", "install"], args].concat()).unwrap();
+            let Commands::Modules { command } = cli.command else { unreachable!() };
+            handle_modules_command(sprout_path, &manifest_path(sprout_path), command, false, false, Progress::default())
+        };
+
+        assert!(Cli::try_parse_from(["sprout", "modules", "install", "--all", "--only-fetch", "--only-build"]).is_err());
+
+        install(&["--all", "--only-fetch"]).unwrap();
+        let manifest = load_manifest(sprout_path).unwrap();
+        let source = crate::core::get_source_path(sprout_path, &manifest.modules[0]);
+        assert!(source.join("Makefile").exists());
+        assert!(!temp_dir.path().join("dist/tool").exists());
+
+        install(&["--all", "--only-build"]).unwrap();
+        assert!(temp_dir.path().join("dist/tool/bin").exists());
+    }
+
+    #[test]
+    fn test_graph_dot_has_node_per_module_and_edge_per_dependency() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest = parse_manifest(r#"
+module cmake { depends_on = [] }
+module zlib { depends_on = [] }
+module llvm { depends_on = [cmake, zlib] }
+"#).unwrap();
+        let lock = crate::lockfile::SproutLock::default();
+
+        let dot = render_dependency_graph(sprout_path, &manifest, &lock, true).unwrap();
+        assert!(dot.starts_with("digraph sprout {"));
+        for id in ["cmake", "zlib", "llvm"] {
+            assert!(dot.contains(&format!("\"{}\" [fillcolor=lightgray];", id)), "{}", dot);
+        }
+        assert!(dot.contains("\"llvm\" -> \"cmake\";"));
+        assert!(dot.contains("\"llvm\" -> \"zlib\";"));
+        assert_eq!(dot.matches(" -> ").count(), 2);
+
+        let plain = render_dependency_graph(sprout_path, &manifest, &lock, false).unwrap();
+        assert_eq!(plain, "llvm -> cmake\nllvm -> zlib\n");
+    }
+
+    #[test]
+    fn test_deps_lists_transitive_dependencies_in_build_order() {
+        let manifest = parse_manifest(r#"
+module clang { depends_on = [llvm] }
+module llvm { depends_on = [cmake, zlib] }
+module zlib { depends_on = [cmake] }
+module cmake { depends_on = [] }
+"#).unwrap();
+
+        assert_eq!(module_dependencies(&manifest, "clang", false).unwrap(), vec!["cmake", "zlib", "llvm"]);
+        assert_eq!(module_dependencies(&manifest, "clang", true).unwrap(), vec!["llvm"]);
+        assert_eq!(module_dependencies(&manifest, "llvm", true).unwrap(), vec!["cmake", "zlib"]);
+        assert!(module_dependencies(&manifest, "cmake", false).unwrap().is_empty());
+        assert!(module_dependencies(&manifest, "gcc", false).is_err());
+    }
+
+    #[test]
+    fn test_edit_with_syntax_error_reopens_or_restores() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest_path = temp_dir.path().join("manifest.sprout");
+        let original = "module tool {\n    depends_on = []\n}\n";
+        // Breaks the manifest on its first run and fixes it on every later one
+        let editor = temp_dir.path().join("editor.sh");
+        fs::write(&editor, format!(r#"#!/bin/sh
+runs={runs}
+if [ -e "$runs" ]; then echo 'module fixed {{ depends_on = [] }}' > "$1"; else touch "$runs"; echo 'module broken {{' > "$1"; fi
+"#, runs = temp_dir.path().join("runs").display())).unwrap();
+        fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+        let editor = editor.to_str().unwrap();
+
+        fs::write(&manifest_path, original).unwrap();
+        let mut prompts = 0;
+        edit_manifest(sprout_path, &manifest_path, editor, true, &mut |_| { prompts += 1; Ok(EditRecovery::Reopen) }).unwrap();
+        assert_eq!(prompts, 1);
+        assert!(fs::read_to_string(&manifest_path).unwrap().contains("module fixed"));
+
+        fs::remove_file(temp_dir.path().join("runs")).unwrap();
+        fs::write(&manifest_path, original).unwrap();
+        let err = edit_manifest(sprout_path, &manifest_path, editor, true, &mut |_| Ok(EditRecovery::Restore)).unwrap_err();
+        assert!(err.root_cause().to_string().contains("line"), "{}", err.root_cause());
+        assert_eq!(fs::read_to_string(&manifest_path).unwrap(), original);
+
+        fs::remove_file(temp_dir.path().join("runs")).unwrap();
+        assert!(edit_manifest(sprout_path, &manifest_path, editor, true, &mut |_| Ok(EditRecovery::Keep)).is_err());
+        assert!(fs::read_to_string(&manifest_path).unwrap().contains("module broken"));
+        assert_eq!(fs::read_to_string(temp_dir.path().join("manifest.sprout.bak")).unwrap(), original);
+
+        // --no-validate saves the broken edit without asking
+        fs::remove_file(temp_dir.path().join("runs")).unwrap();
+        fs::write(&manifest_path, original).unwrap();
+        edit_manifest(sprout_path, &manifest_path, editor, false, &mut |_| panic!("no validation, no prompt")).unwrap();
+        assert!(fs::read_to_string(&manifest_path).unwrap().contains("module broken"));
+
+        // An editor that fails after a re-open does not leave the broken edit behind
+        let failing = temp_dir.path().join("failing.sh");
+        fs::write(&failing, format!(r#"#!/bin/sh
+runs={runs}
+if [ -e "$runs" ]; then exit 1; else touch "$runs"; echo 'module broken {{' > "$1"; fi
+"#, runs = temp_dir.path().join("failing-runs").display())).unwrap();
+        fs::set_permissions(&failing, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(&manifest_path, original).unwrap();
+        let err = edit_manifest(sprout_path, &manifest_path, failing.to_str().unwrap(), true, &mut |_| Ok(EditRecovery::Reopen)).unwrap_err();
+        assert!(err.to_string().contains("Editor exited with error"), "{}", err);
+        assert_eq!(fs::read_to_string(&manifest_path).unwrap(), original);
+    }
 }
@@ -1,15 +1,18 @@
-use anyhow::{anyhow, Result};
-use std::collections::HashMap;
+use anyhow::{anyhow, Context, Result};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
-use tracing::{debug, info};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex, Once};
+use std::time::Duration;
+use tracing::{debug, info, warn};
 use sha2::{Sha256, Digest};
 
-use crate::ast::{ModuleBlock, SproutManifest};
+use crate::ast::{ModuleBlock, ScriptBlock, SproutManifest};
+use crate::error::{Categorize, ErrorKind};
 use crate::lockfile::SproutLock;
-use crate::manifest::load_manifest;
+use crate::manifest::load_manifest_from;
 
 use std::hash::{Hash, Hasher};
 use std::collections::hash_map::DefaultHasher;
@@ -20,6 +23,16 @@ pub fn compute_fetch_hash(package: &ModuleBlock) -> Option<String> {
     package.fetch.as_ref().map(|fetch| {
         let mut hasher = DefaultHasher::new();
         fetch.spec.hash(&mut hasher);
+        // Only hashed when set so existing lockfiles stay valid
+        if let Some(timeout) = fetch.timeout {
+            timeout.hash(&mut hasher);
+        }
+        if !fetch.extract {
+            "no-extract".hash(&mut hasher);
+        }
+        if fetch.discard_git {
+            "discard-git".hash(&mut hasher);
+        }
         let hash_value = hasher.finish();
         
         let mut sha_hasher = Sha256::new();
@@ -30,9 +43,25 @@ pub fn compute_fetch_hash(package: &ModuleBlock) -> Option<String> {
 
 /// Compute hash of build block only
 pub fn compute_build_hash(package: &ModuleBlock) -> Option<String> {
+    build_hash_with(package, package.isolated)
+}
+
+/// `compute_build_hash` of a build that `--isolated-env` may isolate even
+/// though its module does not ask for it
+fn build_hash_with(package: &ModuleBlock, isolated: bool) -> Option<String> {
     package.build.as_ref().map(|build| {
         let mut hasher = DefaultHasher::new();
         build.hash(&mut hasher);
+        // Only hashed when set so existing lockfiles stay valid
+        if let Some(build_dir) = &package.build_dir {
+            build_dir.hash(&mut hasher);
+        }
+        if package.out_of_tree {
+            "out_of_tree".hash(&mut hasher);
+        }
+        if isolated {
+            "isolated".hash(&mut hasher);
+        }
         let hash_value = hasher.finish();
         
         let mut sha_hasher = Sha256::new();
@@ -41,6 +70,410 @@ pub fn compute_build_hash(package: &ModuleBlock) -> Option<String> {
     })
 }
 
+/// How `build` checks whether fetched sources were edited since the last build
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SourceCheck {
+    /// Only the fetch and build hashes decide (the default)
+    #[default]
+    Off,
+    /// Hash every file's path, size and mtime (`--check-sources`)
+    Metadata,
+    /// Hash every file's path and contents (`--thorough`); reads the whole tree
+    Content,
+}
+
+impl SourceCheck {
+    fn prefix(self) -> &'static str {
+        match self {
+            SourceCheck::Off => "",
+            SourceCheck::Metadata => "meta:",
+            SourceCheck::Content => "content:",
+        }
+    }
+}
+
+/// Hash the fetched source tree of a module: every file below `source_path`
+/// (`.git` excluded) in path order, by size and mtime or by full contents.
+/// The result carries the mode as a prefix so hashes of different modes are
+/// never compared
+pub fn compute_source_hash(source_path: &Path, check: SourceCheck) -> Result<Option<String>> {
+    if check == SourceCheck::Off || !source_path.is_dir() {
+        return Ok(None);
+    }
+
+    let mut files = Vec::new();
+    let mut pending = vec![source_path.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                if entry.file_name() != ".git" {
+                    pending.push(entry.path());
+                }
+            } else {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for path in files {
+        let relative = path.strip_prefix(source_path).unwrap_or(&path);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        let metadata = fs::symlink_metadata(&path)?;
+        if metadata.file_type().is_symlink() {
+            hasher.update(fs::read_link(&path)?.to_string_lossy().as_bytes());
+        } else if check == SourceCheck::Content {
+            let mut file = fs::File::open(&path)?;
+            std::io::copy(&mut file, &mut hasher)?;
+        } else {
+            use std::os::unix::fs::MetadataExt;
+            hasher.update(metadata.len().to_le_bytes());
+            hasher.update(metadata.mtime().to_le_bytes());
+            hasher.update(metadata.mtime_nsec().to_le_bytes());
+        }
+        hasher.update([0]);
+    }
+    Ok(Some(format!("{}{:x}", check.prefix(), hasher.finalize())))
+}
+
+/// Whether the module's sources differ from the tree recorded at its last
+/// build. Without a recorded hash of the same mode there is nothing to compare
+fn sources_changed(sprout_path: &str, lock: &SproutLock, package: &ModuleBlock, check: SourceCheck) -> bool {
+    if check == SourceCheck::Off || package.fetch.is_none() {
+        return false;
+    }
+    let Some(recorded) = lock.get_module_state(&package.id()).and_then(|s| s.source_hash.as_deref()) else {
+        return false;
+    };
+    if !recorded.starts_with(check.prefix()) {
+        return false;
+    }
+    match compute_source_hash(&get_source_path(sprout_path, package), check) {
+        Ok(current) => current.as_deref() != Some(recorded),
+        Err(e) => {
+            warn!("Failed to hash sources of {}: {}", package.id(), e);
+            true
+        }
+    }
+}
+
+/// How progress is reported, from `--quiet` and `--plain` (or a CI environment)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Progress {
+    /// Suppresses progress bars and their success lines
+    pub quiet: bool,
+    /// Replaces spinners with heartbeat lines
+    pub plain: bool,
+}
+
+impl Progress {
+    /// Progress bars are only drawn on a terminal and never under `--quiet` or `--plain`
+    fn bars(self) -> bool {
+        !self.quiet && !self.plain && atty::is(atty::Stream::Stderr)
+    }
+
+    /// Whether plain-mode counter and heartbeat lines are printed instead
+    fn lines(self) -> bool {
+        self.plain && !self.quiet
+    }
+}
+
+/// Command line settings a fetch runs with
+#[derive(Debug, Clone, Default)]
+pub struct FetchOptions {
+    pub dry_run: bool,
+    /// Discard any cached archive and source dir first
+    pub force: bool,
+    /// Git modules check out the lockfile's resolved commit instead of their ref
+    pub from_lock: bool,
+    /// Skip the sha256 check of archives (the pinned hash is kept in the manifest)
+    pub no_verify: bool,
+    /// `--manifest-path`; `None` reads manifest.sprout in the sprout dir
+    pub manifest_path: Option<PathBuf>,
+    pub progress: Progress,
+}
+
+impl FetchOptions {
+    fn manifest_path(&self, sprout_path: &str) -> PathBuf {
+        crate::manifest::resolve_manifest_path(sprout_path, self.manifest_path.as_deref())
+    }
+}
+
+/// Command line settings a build runs with
+#[derive(Debug, Clone, Default)]
+pub struct BuildOptions {
+    pub dry_run: bool,
+    pub rebuild: bool,
+    /// Stream build output to the console
+    pub verbose: bool,
+    /// Overrides the detected `SPROUT_JOBS` value
+    pub jobs: Option<usize>,
+    /// From `--check-sources` / `--thorough`
+    pub source_check: SourceCheck,
+    pub limits: ResourceLimits,
+    /// `--keep-broken`: failed builds leave their partial dist dir in place
+    pub keep_broken: bool,
+    /// `--isolated-env`: isolate every build as if it had `isolated = true`
+    pub isolated_env: bool,
+    /// From `SPROUT_BUILD_CACHE`; `None` disables the build cache
+    pub build_cache: Option<PathBuf>,
+    /// `--manifest-path`; `None` reads manifest.sprout in the sprout dir
+    pub manifest_path: Option<PathBuf>,
+    pub progress: Progress,
+}
+
+impl BuildOptions {
+    fn manifest_path(&self, sprout_path: &str) -> PathBuf {
+        crate::manifest::resolve_manifest_path(sprout_path, self.manifest_path.as_deref())
+    }
+
+    fn isolates(&self, package: &ModuleBlock) -> bool {
+        package.isolated || self.isolated_env
+    }
+
+    /// `compute_build_hash` as recorded by a build with these options
+    pub fn build_hash(&self, package: &ModuleBlock) -> Option<String> {
+        build_hash_with(package, self.isolates(package))
+    }
+}
+
+/// PATH of an isolated build; everything else comes from the script itself
+const ISOLATED_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+/// Remove the incomplete dist dir of a failed build so it reads as not built
+fn discard_broken_dist(module_id: &str, dist_path: &Path, keep_broken: bool) {
+    if keep_broken {
+        warn!("Kept incomplete dist of {} (--keep-broken): {}", module_id, dist_path.display());
+    } else if dist_path.exists()
+        && let Err(e) = fs::remove_dir_all(dist_path) {
+        warn!("Failed to remove incomplete dist of {}: {}", module_id, e);
+    }
+}
+
+/// Set while a batch runs; module spinners are drawn beneath its counter
+static BATCH: Mutex<Option<indicatif::MultiProgress>> = Mutex::new(None);
+
+/// Overall "[3/20] module" counter of `fetch --all`, `build --all` and `install --all`
+pub struct BatchProgress {
+    total: usize,
+    position: usize,
+    label: String,
+    bar: Option<indicatif::ProgressBar>,
+    progress: Progress,
+}
+
+impl BatchProgress {
+    pub fn start(total: usize, progress: Progress) -> Self {
+        let bar = progress.bars().then(|| {
+            let multi = indicatif::MultiProgress::new();
+            let bar = multi.add(indicatif::ProgressBar::new(total as u64));
+            bar.set_style(indicatif::ProgressStyle::default_bar()
+                .template("{msg}")
+                .expect("valid template"));
+            *BATCH.lock().unwrap() = Some(multi);
+            bar
+        });
+        Self { total, position: 0, label: String::new(), bar, progress }
+    }
+
+    /// Move the counter on to the next module
+    pub fn next(&mut self, module_id: &str) {
+        self.position = (self.position + 1).min(self.total);
+        self.label = format!("[{}/{}] {}", self.position, self.total, module_id);
+        match &self.bar {
+            Some(bar) => {
+                bar.set_position(self.position as u64);
+                bar.set_message(self.label.clone());
+            }
+            None if self.progress.lines() => eprintln!("{}", self.label),
+            None => info!("{}", self.label),
+        }
+    }
+}
+
+impl Drop for BatchProgress {
+    fn drop(&mut self) {
+        if let Some(bar) = self.bar.take() {
+            bar.finish_and_clear();
+            *BATCH.lock().unwrap() = None;
+        }
+    }
+}
+
+/// A progress bar drawn beneath the batch counter, if a batch is running
+fn attach_progress(pb: indicatif::ProgressBar) -> indicatif::ProgressBar {
+    match BATCH.lock().unwrap().as_ref() {
+        Some(multi) => multi.add(pb),
+        None => pb,
+    }
+}
+
+/// A finished-step line that does not tear through the batch counter
+fn progress_println(line: String) {
+    match BATCH.lock().unwrap().as_ref() {
+        Some(multi) => {
+            let _ = multi.println(line);
+        }
+        None => println!("{}", line),
+    }
+}
+
+/// How often a plain-mode build reports that it is still running
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Emits a "still building" line each time another `interval` has passed, so
+/// CI jobs see output during long builds
+pub struct Heartbeat {
+    module_id: String,
+    interval: Duration,
+    next: Duration,
+}
+
+impl Heartbeat {
+    pub fn new(module_id: &str, interval: Duration) -> Self {
+        Heartbeat { module_id: module_id.to_string(), interval, next: interval }
+    }
+
+    /// The line to print at `elapsed`, if one is due; `tail` is only asked for
+    /// the latest output line when it is
+    pub fn poll(&mut self, elapsed: Duration, tail: impl FnOnce() -> Option<String>) -> Option<String> {
+        if elapsed < self.next {
+            return None;
+        }
+        while self.next <= elapsed {
+            self.next += self.interval;
+        }
+        let mut line = format!("still building {}... {}s", self.module_id, elapsed.as_secs());
+        if let Some(tail) = tail().as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+            line.push_str(&format!(" | {}", tail));
+        }
+        Some(line)
+    }
+}
+
+/// Last non-empty line of a build log, reading only its end
+fn last_log_line(path: &Path) -> Option<String> {
+    use std::io::{Seek, SeekFrom};
+
+    let mut file = fs::File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(4096))).ok()?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer).ok()?;
+    String::from_utf8_lossy(&buffer).lines().rev().find(|l| !l.trim().is_empty()).map(str::to_string)
+}
+
+/// A build in progress that the Ctrl-C handler must tear down
+struct ActiveBuild {
+    child: Arc<Mutex<Child>>,
+    dist_path: PathBuf,
+}
+
+static ACTIVE_BUILD: Mutex<Option<ActiveBuild>> = Mutex::new(None);
+/// A timed fetch command the Ctrl-C handler must kill, since it runs in its own
+/// process group and would not see the terminal's interrupt
+static ACTIVE_FETCH: Mutex<Option<Arc<Mutex<Child>>>> = Mutex::new(None);
+static INTERRUPT_HANDLER: Once = Once::new();
+
+/// Spawn `cmd` as the leader of its own process group, so that a timeout or
+/// Ctrl-C can kill everything it started. Killing only the shell would leave
+/// its children holding the output pipes open
+fn spawn_in_group(cmd: &mut Command) -> std::io::Result<Child> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+    cmd.spawn()
+}
+
+/// Kill a child started by `spawn_in_group` together with its descendants
+fn kill_process_group(child: &mut Child) {
+    #[cfg(unix)]
+    // SAFETY: killpg only sends a signal; the group id is the child's pid
+    unsafe {
+        libc::killpg(child.id() as libc::pid_t, libc::SIGKILL);
+    }
+    let _ = child.kill();
+}
+
+/// Install (once per process) a Ctrl-C handler that kills the running build
+/// or timed fetch and removes an incomplete dist directory before exiting
+fn install_interrupt_handler() {
+    INTERRUPT_HANDLER.call_once(|| {
+        let result = ctrlc::set_handler(|| {
+            let fetch = ACTIVE_FETCH.lock().ok().and_then(|mut f| f.take());
+            if let Some(child) = fetch
+                && let Ok(mut child) = child.lock() {
+                kill_process_group(&mut child);
+                let _ = child.wait();
+            }
+            let active = ACTIVE_BUILD.lock().ok().and_then(|mut a| a.take());
+            if let Some(build) = active {
+                if let Ok(mut child) = build.child.lock() {
+                    kill_process_group(&mut child);
+                    let _ = child.wait();
+                }
+                eprintln!("Interrupted, removing incomplete {}", build.dist_path.display());
+                if let Err(e) = remove_incomplete_dist(&build.dist_path) {
+                    eprintln!("Failed to remove {}: {}", build.dist_path.display(), e);
+                }
+            }
+            std::process::exit(130);
+        });
+        if let Err(e) = result {
+            warn!("Failed to install Ctrl-C handler: {}", e);
+        }
+    });
+}
+
+/// Registers a child with the Ctrl-C handler and forgets it again when
+/// dropped, so every exit path clears the slot
+struct ActiveGuard<T: 'static>(&'static Mutex<Option<T>>);
+
+impl<T> ActiveGuard<T> {
+    fn register(slot: &'static Mutex<Option<T>>, value: T) -> Result<Self> {
+        *slot.lock().map_err(|_| anyhow!("interrupt state lock poisoned"))? = Some(value);
+        Ok(ActiveGuard(slot))
+    }
+}
+
+impl<T> Drop for ActiveGuard<T> {
+    fn drop(&mut self) {
+        if let Ok(mut active) = self.0.lock() {
+            *active = None;
+        }
+    }
+}
+
+/// Remove a dist directory left behind by an interrupted build
+pub fn remove_incomplete_dist(dist_path: &Path) -> Result<()> {
+    if dist_path.exists() {
+        fs::remove_dir_all(dist_path)?;
+    }
+    Ok(())
+}
+
+/// Every `(module id, dependency id)` pair, with dependencies resolved by full
+/// id or unambiguous name
+pub fn dependency_edges(manifest: &SproutManifest) -> Result<Vec<(String, String)>> {
+    let mut edges = Vec::new();
+    for package in &manifest.modules {
+        for dep in &package.depends_on {
+            let dep_id = manifest
+                .find_module(dep)
+                .map(|p| p.id())
+                .ok_or_else(|| anyhow!("Dependency not found: {}", dep))?;
+            edges.push((package.id(), dep_id));
+        }
+    }
+    Ok(edges)
+}
+
 /// Resolve dependency order using topological sort
 pub fn resolve_dependency_order(manifest: &SproutManifest) -> Result<Vec<&ModuleBlock>> {
     let mut graph: HashMap<String, Vec<String>> = HashMap::new();
@@ -56,26 +489,19 @@ pub fn resolve_dependency_order(manifest: &SproutManifest) -> Result<Vec<&Module
     }
 
     // Add edges for dependencies
-    for package in &manifest.modules {
-        let module_id = package.id();
-        for dep in &package.depends_on {
-            // Find the dependency by name or full ID
-            let dep_id = manifest.modules
-                .iter()
-                .find(|p| p.name == *dep || p.id() == *dep)
-                .map(|p| p.id())
-                .ok_or_else(|| anyhow!("Dependency not found: {}", dep))?;
-
-            graph.get_mut(&dep_id).unwrap().push(module_id.clone());
-            *in_degree.get_mut(&module_id).unwrap() += 1;
-        }
+    for (module_id, dep_id) in dependency_edges(manifest)? {
+        graph.get_mut(&dep_id).unwrap().push(module_id.clone());
+        *in_degree.get_mut(&module_id).unwrap() += 1;
     }
 
     // Topological sort using Kahn's algorithm
-    let mut queue: Vec<String> = in_degree
+    // Seeded in reverse manifest order so that, popping from the back,
+    // independent modules come out in the order they are declared
+    let mut queue: Vec<String> = manifest.modules
         .iter()
-        .filter(|(_, degree)| **degree == 0)
-        .map(|(id, _)| id.clone())
+        .rev()
+        .map(|p| p.id())
+        .filter(|id| in_degree[id] == 0)
         .collect();
 
     let mut result = Vec::new();
@@ -93,14 +519,236 @@ pub fn resolve_dependency_order(manifest: &SproutManifest) -> Result<Vec<&Module
     }
 
     if result.len() != manifest.modules.len() {
-        return Err(anyhow!("Circular dependency detected"));
+        let cycle = find_cycle(manifest, |id| in_degree[id] > 0);
+        return Err(anyhow!("Circular dependency detected: {}", cycle.join(" -> ")));
     }
 
     Ok(result)
 }
 
-/// Check which modules need to be rebuilt
-pub fn fetch_package(sprout_path: &str, package: &ModuleBlock, dry_run: bool) -> Result<()> {
+/// Follow dependencies among the modules Kahn's algorithm could not order
+/// until one repeats; every such module still has an unordered dependency
+fn find_cycle(manifest: &SproutManifest, unordered: impl Fn(&str) -> bool) -> Vec<String> {
+    let next = |id: &str| {
+        manifest.find_module(id)?
+            .depends_on.iter()
+            .filter_map(|dep| manifest.find_module(dep).map(|m| m.id()))
+            .find(|dep| unordered(dep))
+    };
+
+    let mut path: Vec<String> = Vec::new();
+    let mut current = manifest.modules.iter().map(|m| m.id()).find(|id| unordered(id));
+    while let Some(id) = current {
+        if let Some(start) = path.iter().position(|p| *p == id) {
+            let mut cycle = path.split_off(start);
+            cycle.push(id);
+            return cycle;
+        }
+        current = next(&id);
+        path.push(id);
+    }
+    path
+}
+
+/// Drop the modules before `start` from a dependency-ordered list so that a
+/// failed `build --all` can be resumed. Skipped modules that the remaining
+/// ones depend on must already be built
+pub fn continue_from<'a>(
+    sprout_path: &str,
+    manifest: &SproutManifest,
+    ordered: Vec<&'a ModuleBlock>,
+    start: &str,
+    options: &BuildOptions,
+) -> Result<Vec<&'a ModuleBlock>> {
+    let start_id = manifest
+        .find_module(start)
+        .map(|p| p.id())
+        .ok_or_else(|| anyhow!("Package not found: {}", start))?;
+    let position = ordered
+        .iter()
+        .position(|p| p.id() == start_id)
+        .ok_or_else(|| anyhow!("Package '{}' is not part of the build order", start_id))?;
+
+    let (skipped, remaining) = ordered.split_at(position);
+    let needed: std::collections::HashSet<String> = remaining
+        .iter()
+        .flat_map(|p| manifest.get_all_dependencies(&p.id()))
+        .collect();
+
+    let lock = SproutLock::load(sprout_path)?;
+    for dep in skipped.iter().filter(|p| p.build.is_some() && needed.contains(&p.id())) {
+        let dep_id = dep.id();
+        let built = get_dist_path(sprout_path, dep).exists()
+            && lock.get_module_state(&dep_id).is_some_and(|s| s.build_hash == options.build_hash(dep));
+        if !built {
+            return Err(anyhow!(
+                "Cannot continue from {}: dependency '{}' is not built. Build it first.",
+                start_id,
+                dep_id
+            ));
+        }
+    }
+
+    Ok(remaining.to_vec())
+}
+
+/// A dependency that keeps a module from building. `build_package` fails with
+/// this error so callers can tell an ordering problem from a broken build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockedDependency {
+    NotBuilt(String),
+    Changed(String),
+}
+
+impl BlockedDependency {
+    pub fn dependency(&self) -> &str {
+        match self {
+            BlockedDependency::NotBuilt(dep) | BlockedDependency::Changed(dep) => dep,
+        }
+    }
+}
+
+impl std::fmt::Display for BlockedDependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockedDependency::NotBuilt(dep) => write!(f, "Dependency '{}' is not built yet. Build it first.", dep),
+            BlockedDependency::Changed(dep) => write!(f, "Dependency '{}' has changed and needs rebuilding. Rebuild it first.", dep),
+        }
+    }
+}
+
+impl std::error::Error for BlockedDependency {}
+
+/// The first dependency of `package` that is neither built and current nor in
+/// `will_build` (modules a run builds before this one)
+fn blocking_dependency(
+    sprout_path: &str,
+    manifest: &SproutManifest,
+    lock: &SproutLock,
+    package: &ModuleBlock,
+    will_build: &HashSet<String>,
+    options: &BuildOptions,
+) -> Option<BlockedDependency> {
+    let all_deps = manifest.get_all_dependencies(&package.id());
+
+    // Skip the last one (it's the package itself)
+    for dep in all_deps.iter().take(all_deps.len().saturating_sub(1)) {
+        if will_build.contains(dep) {
+            continue;
+        }
+        let Some(dep_pkg) = manifest.modules.iter().find(|p| p.id() == *dep) else {
+            continue;
+        };
+
+        if !get_dist_path(sprout_path, dep_pkg).exists() {
+            return Some(BlockedDependency::NotBuilt(dep.clone()));
+        }
+
+        // Check if dependency is up to date
+        if let Some(dep_state) = lock.get_module_state(dep)
+            && options.build_hash(dep_pkg) != dep_state.build_hash {
+            return Some(BlockedDependency::Changed(dep.clone()));
+        }
+    }
+    None
+}
+
+/// Declared outputs missing from the dist dir when the lockfile's build hash
+/// (and, under a source check, the source tree) is current; `None` means the
+/// module needs building regardless
+fn missing_outputs_if_current(sprout_path: &str, lock: &SproutLock, package: &ModuleBlock, options: &BuildOptions) -> Option<Vec<String>> {
+    let dist_path = get_dist_path(sprout_path, package);
+    let state = lock.get_module_state(&package.id())?;
+    (dist_path.exists()
+        && options.build_hash(package) == state.build_hash
+        && !sources_changed(sprout_path, lock, package, options.source_check))
+        .then(|| find_missing_outputs(&dist_path, package))
+}
+
+/// What `build --all` would do with a module
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildDecision {
+    Build,
+    UpToDate,
+    Blocked(String),
+}
+
+impl std::fmt::Display for BuildDecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildDecision::Build => write!(f, "[build]"),
+            BuildDecision::UpToDate => write!(f, "[skip up-to-date]"),
+            BuildDecision::Blocked(reason) => write!(f, "[blocked: {}]", reason),
+        }
+    }
+}
+
+/// Predict the outcome of building `ordered` in sequence, using the same
+/// dependency and up-to-date checks as `build_package`. Modules planned for
+/// building count as built for the ones after them
+pub fn plan_builds<'a>(
+    sprout_path: &str,
+    manifest: &SproutManifest,
+    ordered: &[&'a ModuleBlock],
+    options: &BuildOptions,
+) -> Result<Vec<(&'a ModuleBlock, BuildDecision)>> {
+    let lock = SproutLock::load(sprout_path)?;
+    let mut will_build = HashSet::new();
+    let mut plan = Vec::new();
+
+    for &package in ordered {
+        let decision = match blocking_dependency(sprout_path, manifest, &lock, package, &will_build, options) {
+            Some(BlockedDependency::NotBuilt(dep)) => BuildDecision::Blocked(format!("dep {} not built", dep)),
+            Some(BlockedDependency::Changed(dep)) => BuildDecision::Blocked(format!("dep {} changed", dep)),
+            None if !options.rebuild && missing_outputs_if_current(sprout_path, &lock, package, options).is_some_and(|m| m.is_empty()) => {
+                BuildDecision::UpToDate
+            }
+            None => BuildDecision::Build,
+        };
+        if decision == BuildDecision::Build {
+            will_build.insert(package.id());
+        }
+        plan.push((package, decision));
+    }
+
+    Ok(plan)
+}
+
+/// Fetch a git module at `ref_` instead of its manifest ref, for trying out a
+/// branch without editing the manifest. The override changes the fetch hash, so
+/// it gets its own source dir; neither the manifest nor the lockfile is touched
+pub fn fetch_with_ref(sprout_path: &str, package: &ModuleBlock, ref_: &str, options: &FetchOptions) -> Result<PathBuf> {
+    let Some(crate::ast::FetchSpec::Git(git)) = package.fetch.as_ref().map(|f| &f.spec) else {
+        return Err(anyhow!("--ref only applies to git modules; {} is not fetched from git", package.id()));
+    };
+
+    let git = crate::ast::GitSpec { ref_: Some(ref_.to_string()), ..git.clone() };
+    let mut overridden = package.clone();
+    if let Some(fetch) = &mut overridden.fetch {
+        fetch.spec = crate::ast::FetchSpec::Git(git.clone());
+    }
+    let source_path = get_source_path(sprout_path, &overridden);
+
+    if options.dry_run {
+        println!("Would fetch: {} at {}", package.id(), ref_);
+        return Ok(source_path);
+    }
+
+    if options.force && source_path.exists() {
+        info!("Removing source directory (--force): {}", source_path.display());
+        fs::remove_dir_all(&source_path)?;
+    }
+
+    fetch_git(sprout_path, &overridden, &git, None, false, options.progress).categorize(ErrorKind::Fetch)?;
+    info!("Fetched {} at {} into {} (not recorded in the lockfile)", package.id(), ref_, source_path.display());
+    Ok(source_path)
+}
+
+/// Commit a `discard_git` checkout was taken at, written in place of its `.git`
+const DISCARDED_GIT_MARKER: &str = ".sprout-commit";
+
+/// Fetch a package's sources
+pub fn fetch_package(sprout_path: &str, package: &ModuleBlock, options: &FetchOptions) -> Result<()> {
     let Some(fetch) = &package.fetch else {
         return Err(anyhow!(
             "Package {} has no fetch configuration",
@@ -108,27 +756,90 @@ pub fn fetch_package(sprout_path: &str, package: &ModuleBlock, dry_run: bool) ->
         ));
     };
 
-    if dry_run {
+    if options.dry_run {
         println!("Would fetch: {}", package.id());
         return Ok(());
     }
 
     info!("Fetching package: {}", package.id());
 
+    if options.force {
+        let source_path = get_source_path(sprout_path, package);
+        if source_path.exists() {
+            info!("Removing source directory (--force): {}", source_path.display());
+            fs::remove_dir_all(&source_path)?;
+        }
+    }
+
+    let mut resolved_commit = None;
     match &fetch.spec {
         crate::ast::FetchSpec::Git(git_spec) => {
-            fetch_git(sprout_path, package, git_spec)?;
+            let locked_commit = if options.from_lock {
+                let commit = SproutLock::load(sprout_path)?
+                    .get_module_state(&package.id())
+                    .and_then(|s| s.resolved_commit.clone())
+                    .ok_or_else(|| anyhow!("No resolved commit recorded in the lockfile for {}", package.id()))
+                    .categorize(ErrorKind::LockMismatch)?;
+                Some(commit)
+            } else {
+                None
+            };
+            // An existing clean checkout of the expected commit is reused as is
+            let source_path = get_source_path(sprout_path, package);
+            let expected_commit = locked_commit.clone().or_else(|| {
+                SproutLock::load(sprout_path).ok()?
+                    .get_module_state(&package.id())?
+                    .resolved_commit.clone()
+            });
+            let is_current = |commit: &str| if fetch.discard_git {
+                // No .git left to ask; the marker written when it was discarded says what this is
+                !source_path.join(".git").exists()
+                    && fs::read_to_string(source_path.join(DISCARDED_GIT_MARKER)).is_ok_and(|marker| marker.trim() == commit)
+            } else {
+                git_checkout_is_current(&source_path, commit)
+            };
+            // Branches move: without a locked commit the ref must still name the recorded one
+            let commit = match expected_commit.filter(|c| is_current(c) && (locked_commit.is_some() || git_ref_is_at(git_spec, c))) {
+                Some(commit) => {
+                    info!("Source of {} is already at {}, skipping clone", package.id(), commit);
+                    commit
+                }
+                None => match shared_git_checkout(sprout_path, package, git_spec, locked_commit.as_deref(), options) {
+                    Some((sibling, commit)) => {
+                        clone_shared_checkout(&sibling, &source_path, git_spec, &commit).categorize(ErrorKind::Fetch)?;
+                        progress_println(format!("  ✓ Cloned {} from {}", package.id(), sibling.display()));
+                        commit
+                    }
+                    None => {
+                        fetch_git(sprout_path, package, git_spec, locked_commit.as_deref(), false, options.progress).categorize(ErrorKind::Fetch)?;
+                        git_head(&source_path)?
+                    }
+                },
+            };
+            if fetch.discard_git && source_path.join(".git").exists() {
+                info!("Discarding git metadata of {} at {}", package.id(), commit);
+                fs::remove_dir_all(source_path.join(".git"))?;
+                fs::write(source_path.join(DISCARDED_GIT_MARKER), format!("{}\n", commit))?;
+            }
+            resolved_commit = Some(commit);
         }
         crate::ast::FetchSpec::Http(archive_spec) => {
-            fetch_archive(sprout_path, package, archive_spec)?;
+            fetch_archive(sprout_path, package, archive_spec, options).categorize(ErrorKind::Fetch)?;
+        }
+        crate::ast::FetchSpec::Local(local_spec) => {
+            fetch_local(sprout_path, package, local_spec, options).categorize(ErrorKind::Fetch)?;
+        }
+        crate::ast::FetchSpec::Rsync(rsync_spec) => {
+            fetch_rsync(sprout_path, package, rsync_spec, options.progress).categorize(ErrorKind::Fetch)?;
         }
-        _ => {
-            return Err(anyhow!("Unsupported fetch type for package {}", package.id()));
+        crate::ast::FetchSpec::Github(github_spec) => {
+            let archive_spec = github_archive(sprout_path, GITHUB_API, github_spec, options.force).categorize(ErrorKind::Fetch)?;
+            fetch_archive(sprout_path, package, &archive_spec, options).categorize(ErrorKind::Fetch)?;
         }
     }
 
     // Reload package from manifest in case it was updated (e.g., SHA256 added)
-    let manifest = load_manifest(sprout_path)?;
+    let manifest = load_manifest_from(&options.manifest_path(sprout_path), sprout_path)?;
     let updated_package = manifest.modules.iter()
         .find(|m| m.id() == package.id())
         .ok_or_else(|| anyhow!("Package {} not found after fetch", package.id()))?;
@@ -141,93 +852,559 @@ pub fn fetch_package(sprout_path: &str, package: &ModuleBlock, dry_run: bool) ->
         .unwrap_or(crate::lockfile::PackageState {
             fetch_hash: None,
             build_hash: None,
+            resolved_commit: None,
+            source_hash: None,
         });
     state.fetch_hash = fetch_hash;
+    state.resolved_commit = resolved_commit;
     lock.set_module_state(package.id(), state);
     lock.save(sprout_path)?;
 
+    if let Some(max_bytes) = cache_limit()? {
+        let evicted = enforce_cache_limit(sprout_path, &manifest, max_bytes)?;
+        if !evicted.is_empty() {
+            info!("Evicted {} cached download(s) over the {} cap: {}", evicted.len(), CACHE_MAX_ENV, evicted.join(", "));
+        }
+    }
+
     info!("Successfully fetched: {}", package.id());
     Ok(())
 }
 
-/// Build a package
-pub fn build_package(
-    sprout_path: &str,
-    package: &ModuleBlock,
-    dry_run: bool,
-    rebuild: bool,
-    verbose: bool,
-) -> Result<()> {
-    use indicatif::{ProgressBar, ProgressStyle};
-    use std::time::Duration;
-
-    let module_id = package.id();
-
-    if dry_run {
-        println!("Would build: {}", module_id);
-        return Ok(());
+/// Upper bound for `cache/http` in GB (fractions allowed), enforced after every fetch
+pub const CACHE_MAX_ENV: &str = "SPROUT_CACHE_MAX_GB";
+
+fn cache_limit() -> Result<Option<u64>> {
+    match std::env::var(CACHE_MAX_ENV) {
+        Ok(value) if !value.trim().is_empty() => {
+            let gb: f64 = value.trim().parse()
+                .ok()
+                .filter(|gb: &f64| *gb >= 0.0)
+                .ok_or_else(|| anyhow!("{} must be a non-negative number of GB, got '{}'", CACHE_MAX_ENV, value))?;
+            Ok(Some((gb * 1e9) as u64))
+        }
+        _ => Ok(None),
     }
+}
 
-    let pb = if !verbose && atty::is(atty::Stream::Stderr) {
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(ProgressStyle::default_spinner()
-            .template("  {spinner} {msg}")?);
-        pb.set_message(format!("Building {}", module_id));
-        pb.enable_steady_tick(Duration::from_millis(100));
-        Some(pb)
-    } else {
-        info!("Building package: {}", module_id);
-        None
-    };
-
-    debug!("Package build block: {:?}", package.build);
+/// Evict least recently used `cache/http/<id>-<hash8>` dirs until the cache
+/// fits in `max_bytes`. Caches the current manifest still refers to are never
+/// evicted, even if that leaves the cache over the cap. Returns the evicted dir names
+pub fn enforce_cache_limit(sprout_path: &str, manifest: &SproutManifest, max_bytes: u64) -> Result<Vec<String>> {
+    let cache_root = Path::new(sprout_path).join("cache/http");
+    if !cache_root.exists() {
+        return Ok(Vec::new());
+    }
 
-    let source_path = get_source_path(sprout_path, package);
-    let dist_path = get_dist_path(sprout_path, package);
-    let lock = SproutLock::load(sprout_path)?;
+    let referenced: HashSet<String> = manifest.modules.iter()
+        .filter_map(|m| compute_fetch_hash(m).map(|hash| format!("{}-{}", m.id(), &hash[..8])))
+        .collect();
 
-    // Check all dependencies are built
-    if !package.depends_on.is_empty() {
-        let manifest = load_manifest(sprout_path)?;
-        let all_deps = manifest.get_all_dependencies(&module_id);
+    // (last used, size, name) of every cache dir
+    let mut caches = Vec::new();
+    for entry in fs::read_dir(&cache_root)? {
+        let entry = entry?;
+        let path = entry.path();
+        if !path.is_dir() {
+            continue;
+        }
+        // The newest file inside says when the cache was last used; an empty dir falls back to its own mtime
+        let mut last_used = None;
+        for file in fs::read_dir(&path)? {
+            last_used = last_used.max(Some(file?.metadata()?.modified()?));
+        }
+        let last_used = match last_used {
+            Some(time) => time,
+            None => entry.metadata()?.modified()?,
+        };
+        caches.push((last_used, crate::core::dir_size(&path)?, entry.file_name().to_string_lossy().to_string()));
+    }
 
-        // Skip the last one (it's the package itself)
-        for dep in all_deps.iter().take(all_deps.len().saturating_sub(1)) {
-            let dep_pkg = manifest.modules.iter().find(|p| p.id() == *dep);
+    let mut total: u64 = caches.iter().map(|(_, size, _)| size).sum();
+    caches.sort();
 
-            if let Some(dep_pkg) = dep_pkg {
-                let dep_dist = get_dist_path(sprout_path, dep_pkg);
+    let mut evicted = Vec::new();
+    for (_, size, name) in caches {
+        if total <= max_bytes {
+            break;
+        }
+        if referenced.contains(&name) {
+            continue;
+        }
+        fs::remove_dir_all(cache_root.join(&name))?;
+        total -= size;
+        evicted.push(name);
+    }
+    Ok(evicted)
+}
 
-                if !dep_dist.exists() {
-                    return Err(anyhow!(
-                        "Dependency '{}' is not built yet. Build it first.",
-                        dep
-                    ));
-                }
+/// Whether a module downloads or copies a single archive whose sha256 can be pinned
+pub fn has_archive_source(sprout_path: &str, package: &ModuleBlock) -> bool {
+    match package.fetch.as_ref().map(|f| &f.spec) {
+        Some(crate::ast::FetchSpec::Http(_) | crate::ast::FetchSpec::Github(_)) => true,
+        Some(crate::ast::FetchSpec::Local(local)) => Path::new(sprout_path).join(&local.path).is_file(),
+        _ => false,
+    }
+}
 
-                // Check if dependency is up to date
-                if let Some(dep_state) = lock.get_module_state(dep) {
-                    let current_hash = compute_build_hash(dep_pkg);
-                    if current_hash != dep_state.build_hash {
-                        return Err(anyhow!(
-                            "Dependency '{}' has changed and needs rebuilding. Rebuild it first.",
-                            dep
-                        ));
-                    }
-                }
+/// `fetch --print-hash`: download (or reuse the cached) archive of an http or
+/// github module, or read a local archive, and return its sha256. Unlike a
+/// normal fetch this never writes the hash into the manifest, unpacks
+/// anything or touches the lockfile
+pub fn print_fetch_hash(sprout_path: &str, package: &ModuleBlock, options: &FetchOptions) -> Result<String> {
+    let archive = match package.fetch.as_ref().map(|f| &f.spec) {
+        Some(crate::ast::FetchSpec::Http(http)) => http.clone(),
+        Some(crate::ast::FetchSpec::Github(github)) => github_archive(sprout_path, GITHUB_API, github, options.force).categorize(ErrorKind::Fetch)?,
+        Some(crate::ast::FetchSpec::Local(local)) => {
+            let path = Path::new(sprout_path).join(&local.path);
+            if !path.is_file() {
+                return Err(anyhow!("{} is not an archive file: {}", package.id(), path.display()));
             }
+            return compute_file_sha256(&path);
+        }
+        _ => return Err(anyhow!("--print-hash only applies to http, github and local archive modules; {} is neither", package.id())),
+    };
+
+    let cache_path = http_cache_path(sprout_path, package, &archive);
+    let filename = archive.url.split('/').next_back().unwrap_or("archive");
+    if options.force && cache_path.exists() {
+        fs::remove_file(&cache_path)?;
+    }
+    if !cache_path.exists() {
+        if let Some(cache_dir) = cache_path.parent() {
+            fs::create_dir_all(cache_dir)?;
         }
+        let timeout = package.fetch.as_ref().and_then(|fetch| fetch.timeout).map(Duration::from_secs);
+        download_file(&archive.url, &cache_path, filename, timeout, options.progress).categorize(ErrorKind::Fetch)?;
     }
 
-    // Check if package is already up-to-date
-    if !rebuild && dist_path.exists()
-        && let Some(state) = lock.get_module_state(&module_id) {
-            let current_hash = compute_build_hash(package);
-            if current_hash == state.build_hash {
-                info!("Package {} is already up-to-date, skipping build", module_id);
-                return Ok(());
+    let hash = compute_file_sha256(&cache_path)?;
+    if let Some(pinned) = archive.sha256.as_deref().filter(|pinned| !pinned.eq_ignore_ascii_case(&hash)) {
+        warn!("{}: manifest pins sha256 {} but {} hashes to {}", package.id(), pinned, filename, hash);
+    }
+    Ok(hash)
+}
+
+/// Run a module's `update` script (e.g. to look for a newer upstream version)
+/// in its source dir. Git sources get their full history first, so the script
+/// can rely on tags (`git describe --tags`) that a shallow clone lacks
+pub fn update_package(sprout_path: &str, package: &ModuleBlock, dry_run: bool, progress: Progress) -> Result<()> {
+    let update = package.update.as_ref()
+        .ok_or_else(|| anyhow!("Module {} has no update block", package.id()))?;
+    let script = render_build_script(sprout_path, package, update, build_jobs(None), &ResourceLimits::default());
+
+    if dry_run {
+        println!("# Would update: {}", package.id());
+        print!("{}", script);
+        return Ok(());
+    }
+
+    if let Some(crate::ast::FetchSpec::Git(git)) = package.fetch.as_ref().map(|f| &f.spec) {
+        ensure_full_history(sprout_path, package, git, progress)?;
+    }
+
+    let source_path = get_source_path(sprout_path, package);
+    let work_dir = if source_path.exists() { source_path } else { PathBuf::from(sprout_path) };
+    info!("Running update script of {}", package.id());
+    let status = Command::new("bash")
+        .arg("-c")
+        .arg(&script)
+        .current_dir(&work_dir)
+        .status()?;
+    if !status.success() {
+        return Err(anyhow!("Update script of {} failed with exit code: {:?}", package.id(), status.code()));
+    }
+    Ok(())
+}
+
+/// Turn the git checkout of `package` into a full clone: a shallow clone is
+/// deepened in place, a missing one (or one without .git) is cloned anew
+fn ensure_full_history(sprout_path: &str, package: &ModuleBlock, git: &crate::ast::GitSpec, progress: Progress) -> Result<()> {
+    let source_path = get_source_path(sprout_path, package);
+    if !source_path.join(".git").exists() {
+        info!("Cloning {} with full history", package.id());
+        return fetch_git(sprout_path, package, git, None, true, progress).categorize(ErrorKind::Fetch);
+    }
+
+    let output = Command::new("git")
+        .current_dir(&source_path)
+        .args(["rev-parse", "--is-shallow-repository"])
+        .output()?;
+    if String::from_utf8_lossy(&output.stdout).trim() == "true" {
+        info!("Fetching full history of {}", package.id());
+        let output = Command::new("git")
+            .current_dir(&source_path)
+            .args(["fetch", "--unshallow", "--tags", "origin"])
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "git fetch --unshallow failed for {}: {}",
+                package.id(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )).categorize(ErrorKind::Fetch);
+        }
+    }
+    Ok(())
+}
+
+/// Rough cost of fetching a module, used to order `fetch --all` so quick
+/// local copies and http downloads land before slow git clones
+fn fetch_cost(package: &ModuleBlock) -> u8 {
+    match package.fetch.as_ref().map(|f| &f.spec) {
+        Some(crate::ast::FetchSpec::Local(_)) => 0,
+        Some(crate::ast::FetchSpec::Http(_) | crate::ast::FetchSpec::Github(_)) => 1,
+        Some(crate::ast::FetchSpec::Git(_) | crate::ast::FetchSpec::Rsync(_)) => 2,
+        None => 3,
+    }
+}
+
+/// Outcome of `fetch --all`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FetchSummary {
+    pub succeeded: usize,
+    pub failed: Vec<String>,
+    /// Modules without a fetch block
+    pub skipped: usize,
+}
+
+/// Fetch every module, cheapest first. Failures are collected into the
+/// summary unless `stop_on_error` is set, in which case the first one aborts
+pub fn fetch_all(
+    sprout_path: &str,
+    manifest: &SproutManifest,
+    options: &FetchOptions,
+    stop_on_error: bool,
+) -> Result<FetchSummary> {
+    let mut packages: Vec<&ModuleBlock> = manifest.modules.iter().collect();
+    packages.sort_by_key(|p| fetch_cost(p));
+
+    let mut summary = FetchSummary::default();
+    let mut batch = BatchProgress::start(packages.iter().filter(|p| p.fetch.is_some()).count(), options.progress);
+    for package in packages {
+        if package.fetch.is_none() {
+            summary.skipped += 1;
+            continue;
+        }
+        batch.next(&package.id());
+        match fetch_package(sprout_path, package, options) {
+            Ok(()) => summary.succeeded += 1,
+            Err(e) if stop_on_error => return Err(e),
+            Err(e) => {
+                warn!("Failed to fetch {}: {}", package.id(), e);
+                summary.failed.push(package.id());
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// How often one module may be put back in the queue while a dependency catches up
+const MAX_REQUEUES: usize = 3;
+
+/// Build `packages` in the given order. A module blocked by a dependency that is
+/// still queued goes back to the end of the queue instead of failing, so a
+/// slightly wrong order sorts itself out. Returns the modules that failed
+pub fn build_queue(
+    sprout_path: &str,
+    packages: Vec<&ModuleBlock>,
+    options: &BuildOptions,
+) -> Vec<(String, anyhow::Error)> {
+    use std::collections::VecDeque;
+
+    let mut queue: VecDeque<(&ModuleBlock, usize)> = packages.into_iter().map(|p| (p, 0)).collect();
+    let mut failed = Vec::new();
+    let mut batch = BatchProgress::start(queue.len(), options.progress);
+
+    while let Some((package, requeues)) = queue.pop_front() {
+        if requeues == 0 {
+            batch.next(&package.id());
+        }
+        let err = match build_package(sprout_path, package, options) {
+            Ok(()) => continue,
+            Err(err) => err,
+        };
+
+        let dependency_queued = err.downcast_ref::<BlockedDependency>()
+            .is_some_and(|blocked| queue.iter().any(|(p, _)| p.id() == blocked.dependency()));
+        if dependency_queued && requeues < MAX_REQUEUES {
+            info!("{} waits for its dependencies, requeueing: {}", package.id(), err);
+            queue.push_back((package, requeues + 1));
+            continue;
+        }
+
+        warn!("Failed to build {}: {}", package.id(), err);
+        failed.push((package.id(), err));
+    }
+
+    failed
+}
+
+/// Number of parallel jobs build scripts should use, exported as `SPROUT_JOBS`
+pub fn build_jobs(requested: Option<usize>) -> usize {
+    requested.unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1))
+}
+
+/// Per-build resource limits from `build --memory-limit`/`--cpu-limit`,
+/// applied by the build script itself so every process it starts inherits them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ResourceLimits {
+    /// Virtual memory cap in KiB (`ulimit -v`)
+    pub memory_kb: Option<u64>,
+    /// Niceness the build runs at (0-19; higher yields more CPU to others)
+    pub niceness: Option<u8>,
+}
+
+impl ResourceLimits {
+    /// Shell lines that apply the limits to the running script
+    pub fn prelude(&self) -> String {
+        let mut prelude = String::new();
+        if let Some(kb) = self.memory_kb {
+            prelude.push_str(&format!("ulimit -v {}\n", kb));
+        }
+        if let Some(niceness) = self.niceness {
+            // The script cannot re-exec itself under nice, so renice its own shell
+            prelude.push_str(&format!("renice -n {} -p $$ > /dev/null\n", niceness));
+        }
+        prelude
+    }
+}
+
+/// Parse a memory size like `512M`, `4G` or `1048576K` (plain numbers are KiB) into KiB
+pub fn parse_memory_limit(text: &str) -> Result<u64> {
+    let text = text.trim();
+    let (digits, factor) = match text.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => match c.to_ascii_uppercase() {
+            'K' => (&text[..i], 1),
+            'M' => (&text[..i], 1024),
+            'G' => (&text[..i], 1024 * 1024),
+            _ => return Err(anyhow!("Invalid memory unit in {} (use K, M or G)", text)),
+        },
+        _ => (text, 1),
+    };
+    let value: u64 = digits.parse().map_err(|_| anyhow!("Invalid memory limit: {}", text))?;
+    match value.checked_mul(factor) {
+        Some(0) | None => Err(anyhow!("Invalid memory limit: {}", text)),
+        Some(kb) => Ok(kb),
+    }
+}
+
+/// Render the bash script that runs a module's build block
+///
+/// `SPROUT_JOBS`/`SPROUT_NPROC` and resource limits are applied here rather
+/// than in the manifest, so they never feed into the build hash.
+pub fn render_build_script(sprout_path: &str, package: &ModuleBlock, build: &ScriptBlock, jobs: usize, limits: &ResourceLimits) -> String {
+    let mut script = String::from("set -e\n");
+    script.push_str(&limits.prelude());
+
+    // Export base env variables
+    let sprout_dist = Path::new(sprout_path).join("dist");
+    script.push_str(&format!("export SPROUT_DIST='{}'\n", sprout_dist.display()));
+    script.push_str(&format!("export DIST_PATH='{}'\n", get_dist_path(sprout_path, package).display()));
+    script.push_str(&format!("export SOURCE_PATH='{}'\n", get_source_path(sprout_path, package).display()));
+    script.push_str(&format!("export SPROUT_JOBS='{}'\n", jobs));
+    script.push_str(&format!("export SPROUT_NPROC='{}'\n", build_jobs(None)));
+
+    // Export env block variables in order (bash will expand them with double quotes)
+    for (key, value) in &build.env {
+        script.push_str(&format!("export {}=\"{}\"\n", key, value));
+    }
+
+    for cmd in &build.commands {
+        script.push_str(cmd);
+        script.push('\n');
+    }
+
+    script
+}
+
+/// The build script as `--dry-run` shows it. An isolated build is preceded by
+/// the bash invocation, since the cleared environment is not in the script
+fn render_dry_run_build(sprout_path: &str, package: &ModuleBlock, build: &ScriptBlock, options: &BuildOptions) -> String {
+    let script = render_build_script(sprout_path, package, build, build_jobs(options.jobs), &options.limits);
+    if options.isolates(package) {
+        format!("# Isolated: env -i PATH={} bash --norc --noprofile -c <script>\n{}", ISOLATED_PATH, script)
+    } else {
+        script
+    }
+}
+
+/// Directory shared between sprout dirs at the same path that keeps a copy of
+/// every successful build's `dist/<id>`, keyed by the module's build inputs.
+/// Dirs at different paths never share entries, see `build_cache_key`
+pub const BUILD_CACHE_ENV: &str = "SPROUT_BUILD_CACHE";
+
+/// Name of the file next to a cached tree holding its content hash
+const BUILD_CACHE_CHECKSUM: &str = "tree.sha256";
+
+/// The build cache dir named by `SPROUT_BUILD_CACHE`, if set
+pub fn build_cache_from_env() -> Option<PathBuf> {
+    std::env::var_os(BUILD_CACHE_ENV).filter(|dir| !dir.is_empty()).map(PathBuf::from)
+}
+
+/// Key of a module's dist in the build cache: the fetch and build hashes and
+/// fetched commit of the module and of every dependency it may link against.
+/// Local sources are hashed by content since their spec says nothing about it.
+/// The absolute dist path is part of the key: builds bake it into rpaths,
+/// pkg-config files and shebangs, so a tree only fits the place it was built for
+fn build_cache_key(sprout_path: &str, lock: &SproutLock, package: &ModuleBlock, dependencies: &[&ModuleBlock], options: &BuildOptions) -> Result<Option<String>> {
+    let Some(build_hash) = options.build_hash(package) else {
+        return Ok(None);
+    };
+
+    let mut hasher = Sha256::new();
+    hasher.update(build_hash.as_bytes());
+    hasher.update([0]);
+    hasher.update(std::path::absolute(get_dist_path(sprout_path, package))?.to_string_lossy().as_bytes());
+    for module in std::iter::once(&package).chain(dependencies) {
+        hasher.update([0]);
+        hasher.update(module.id().as_bytes());
+        for part in [
+            compute_fetch_hash(module),
+            options.build_hash(module),
+            lock.get_module_state(&module.id()).and_then(|state| state.resolved_commit.clone()),
+        ] {
+            hasher.update([0]);
+            hasher.update(part.unwrap_or_default().as_bytes());
+        }
+        if let Some(crate::ast::FetchSpec::Local(_)) = module.fetch.as_ref().map(|f| &f.spec) {
+            let sources = compute_source_hash(&get_source_path(sprout_path, module), SourceCheck::Content)?;
+            hasher.update(sources.unwrap_or_default().as_bytes());
+        }
+    }
+    Ok(Some(format!("{:x}", hasher.finalize())))
+}
+
+/// Copy a dist tree, keeping symlinks (`libfoo.so -> libfoo.so.1`) as links
+fn copy_tree(src: &Path, dst: &Path) -> Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = dst.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_tree(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, &target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Restore `dist_path` from the build cache entry, if there is one whose
+/// tree still matches the checksum recorded when it was stored. A corrupt
+/// entry is dropped so the next successful build replaces it
+fn restore_from_build_cache(entry: &Path, dist_path: &Path) -> Result<bool> {
+    let tree = entry.join("tree");
+    let Ok(recorded) = fs::read_to_string(entry.join(BUILD_CACHE_CHECKSUM)) else {
+        return Ok(false);
+    };
+    if compute_source_hash(&tree, SourceCheck::Content)?.as_deref() != Some(recorded.trim()) {
+        warn!("Discarding corrupt build cache entry {}", entry.display());
+        fs::remove_dir_all(entry)?;
+        return Ok(false);
+    }
+
+    info!("Restoring {} from build cache", entry.display());
+    if dist_path.exists() {
+        fs::remove_dir_all(dist_path)?;
+    }
+    copy_tree(&tree, dist_path)?;
+    Ok(true)
+}
+
+/// Store a freshly built dist in the build cache. The entry is assembled next
+/// to its final place and renamed in, so readers never see half a tree. With
+/// `replace` (a `--rebuild`) an existing entry gives way to the new build
+fn store_in_build_cache(entry: &Path, dist_path: &Path, replace: bool) -> Result<()> {
+    let Some(cache_dir) = entry.parent() else {
+        return Ok(());
+    };
+    if entry.exists() && !replace {
+        return Ok(());
+    }
+    fs::create_dir_all(cache_dir)?;
+    let staging = tempfile::Builder::new().prefix(".staging-").tempdir_in(cache_dir)?;
+    copy_tree(dist_path, &staging.path().join("tree"))?;
+    let checksum = compute_source_hash(&staging.path().join("tree"), SourceCheck::Content)?.unwrap_or_default();
+    fs::write(staging.path().join(BUILD_CACHE_CHECKSUM), checksum)?;
+    if entry.exists() {
+        let retired = tempfile::Builder::new().prefix(".retired-").tempdir_in(cache_dir)?;
+        fs::rename(entry, retired.path().join("entry"))?;
+    }
+    if let Err(e) = fs::rename(staging.path(), entry) {
+        // Another sprout dir stored the same build first
+        if !entry.exists() {
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+/// Build a package
+pub fn build_package(sprout_path: &str, package: &ModuleBlock, options: &BuildOptions) -> Result<()> {
+    use indicatif::{ProgressBar, ProgressStyle};
+
+    let module_id = package.id();
+    let (rebuild, verbose) = (options.rebuild, options.verbose);
+
+    if options.dry_run {
+        // Show the exact script a real build would execute
+        match &package.build {
+            Some(build) => {
+                println!("# Would build: {}", module_id);
+                print!("{}", render_dry_run_build(sprout_path, package, build, options));
             }
+            None => println!("Would build: {} (no build block)", module_id),
+        }
+        return Ok(());
+    }
+
+    let pb = if !verbose && options.progress.bars() {
+        let pb = attach_progress(ProgressBar::new_spinner());
+        pb.set_style(ProgressStyle::default_spinner()
+            .template("  {spinner} {msg}")?);
+        pb.set_message(format!("Building {}", module_id));
+        pb.enable_steady_tick(Duration::from_millis(100));
+        Some(pb)
+    } else {
+        info!("Building package: {}", module_id);
+        None
+    };
+
+    debug!("Package build block: {:?}", package.build);
+
+    let source_path = get_source_path(sprout_path, package);
+    let dist_path = get_dist_path(sprout_path, package);
+    let lock = SproutLock::load(sprout_path)?;
+
+    // Check all dependencies are built
+    let manifest = if package.depends_on.is_empty() {
+        None
+    } else {
+        Some(load_manifest_from(&options.manifest_path(sprout_path), sprout_path)?)
+    };
+    if let Some(manifest) = &manifest
+        && let Some(blocked) = blocking_dependency(sprout_path, manifest, &lock, package, &HashSet::new(), options) {
+        return Err(blocked.into());
+    }
+
+    // Check if package is already up-to-date
+    if !rebuild && let Some(missing) = missing_outputs_if_current(sprout_path, &lock, package, options) {
+        if missing.is_empty() {
+            info!("Package {} is already up-to-date, skipping build", module_id);
+            record_source_baseline(sprout_path, lock, package, options.source_check)?;
+            return Ok(());
         }
+        info!("Package {} is missing declared outputs ({}), rebuilding", module_id, missing.join(", "));
+    }
+
+    // Fail before the script runs rather than deep inside it
+    let search_path = tool_search_path(options.isolates(package));
+    if let Some(tool) = package.requires.iter().find(|tool| find_on_path(tool, search_path.as_deref()).is_none()) {
+        return Err(anyhow!("missing tool: {} (required by {})", tool, module_id));
+    }
 
     // Only check source path if package has fetch configuration
     if package.fetch.is_some() && !source_path.exists() {
@@ -249,38 +1426,89 @@ pub fn build_package(
         fs::remove_dir_all(&dist_path)?;
     }
 
+    let cache_entry = match &options.build_cache {
+        Some(cache_dir) => {
+            let dependencies: Vec<&ModuleBlock> = manifest.as_ref()
+                .map(|manifest| manifest.get_all_dependencies(&module_id).iter().filter_map(|id| manifest.find_module(id)).collect())
+                .unwrap_or_default();
+            build_cache_key(sprout_path, &lock, package, &dependencies, options)?
+                .map(|key| cache_dir.join(format!("{}-{}", module_id, key)))
+        }
+        None => None,
+    };
+    // --rebuild always runs the script; its result still refreshes the cache
+    let restored = match &cache_entry {
+        Some(entry) if !rebuild => restore_from_build_cache(entry, &dist_path)?,
+        _ => false,
+    };
+    if restored && let Err(e) = crate::core::history::append_activity(sprout_path, &format!("restore {} from build cache", module_id)) {
+        warn!("Failed to write activity log: {}", e);
+    }
+
+    // Set once the build script runs; every run ends up in the build history
+    let mut run: Option<(std::time::Instant, PathBuf)> = None;
+    let record_run = |run: &Option<(std::time::Instant, PathBuf)>, success: bool| {
+        let Some((started, log_path)) = run else { return };
+        let event = if success {
+            format!("finish {} in {:.1}s", module_id, started.elapsed().as_secs_f64())
+        } else {
+            format!("fail {} after {:.1}s, see {}", module_id, started.elapsed().as_secs_f64(), log_path.display())
+        };
+        if let Err(e) = crate::core::history::append_activity(sprout_path, &event) {
+            warn!("Failed to write activity log: {}", e);
+        }
+        let record = crate::core::history::BuildRecord {
+            module: module_id.clone(),
+            timestamp: chrono::Utc::now(),
+            success,
+            duration_secs: started.elapsed().as_secs_f64(),
+            log: Some(log_path.clone()),
+            build_hash: options.build_hash(package),
+        };
+        if let Err(e) = crate::core::history::append_build_record(sprout_path, &record) {
+            warn!("Failed to record build history: {}", e);
+        }
+    };
+
+    // Cleared on drop, once the lockfile is written or the build bails out
+    let mut active_build = None;
+
     // Execute build commands if any
-    if let Some(build) = &package.build {
+    if let Some(build) = &package.build && !restored {
         debug!("Build env block: {:?}", build.env);
 
         // Create dist directory before build
         fs::create_dir_all(&dist_path)?;
 
         // Build single shell script with all commands
-        let mut script = String::from("set -e\n");
-
-        // Export base env variables
-        let sprout_dist = Path::new(sprout_path).join("dist");
-        script.push_str(&format!("export SPROUT_DIST='{}'\n", sprout_dist.display()));
-        script.push_str(&format!("export DIST_PATH='{}'\n", dist_path.display()));
-        script.push_str(&format!("export SOURCE_PATH='{}'\n", source_path.display()));
-
-        // Export env block variables in order (bash will expand them with double quotes)
-        for (key, value) in &build.env {
-            script.push_str(&format!("export {}=\"{}\"\n", key, value));
-        }
-
-        for cmd in &build.commands {
-            script.push_str(cmd);
-            script.push('\n');
-        }
+        let script = render_build_script(sprout_path, package, build, build_jobs(options.jobs), &options.limits);
 
         info!("Executing build script");
         debug!("Generated script:\n{}", script);
-        let work_dir = if source_path.exists() {
-            &source_path
+
+        // Out-of-tree builds run in a fresh directory so the source stays
+        // pristine; the script reaches the source through SOURCE_PATH
+        let temp_build_dir = if package.out_of_tree {
+            Some(tempfile::Builder::new().prefix(&format!("sprout-build-{}-", module_id)).tempdir()?)
         } else {
-            Path::new(sprout_path)
+            None
+        };
+        let work_dir = match (&temp_build_dir, &package.build_dir) {
+            (Some(temp_build_dir), _) => temp_build_dir.path().to_path_buf(),
+            (None, Some(build_dir)) => {
+                let dir = source_path.join(build_dir);
+                if !dir.is_dir() {
+                    return Err(anyhow!(
+                        "Build directory '{}' does not exist for {}: {}",
+                        build_dir,
+                        module_id,
+                        dir.display()
+                    ));
+                }
+                dir
+            }
+            (None, None) if source_path.exists() => source_path.clone(),
+            (None, None) => PathBuf::from(sprout_path),
         };
 
         // Create logs directory
@@ -295,17 +1523,34 @@ pub fn build_package(
         info!("Build log: {}", log_path.display());
 
         // Execute with output captured to both console and log file
-        let mut child = Command::new("bash")
+        run = Some((std::time::Instant::now(), log_path.clone()));
+        if let Err(e) = crate::core::history::append_activity(sprout_path, &format!("start {}", module_id)) {
+            warn!("Failed to write activity log: {}", e);
+        }
+        install_interrupt_handler();
+        let mut command = Command::new("bash");
+        if options.isolates(package) {
+            // Only the exports at the top of the script reach the build. Without
+            // SHLVL bash may take itself for an sshd session and source ~/.bashrc
+            command.args(["--norc", "--noprofile"]).env_clear().env("PATH", ISOLATED_PATH);
+        }
+        let mut child = spawn_in_group(command
             .arg("-c")
             .arg(&script)
-            .current_dir(work_dir)
+            .current_dir(&work_dir)
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()?;
+            .stderr(Stdio::piped()))?;
 
         let stdout = child.stdout.take().unwrap();
         let stderr = child.stderr.take().unwrap();
 
+        // Until the lockfile is written the dist is incomplete; let Ctrl-C clean it up
+        let child = Arc::new(Mutex::new(child));
+        active_build = Some(ActiveGuard::register(&ACTIVE_BUILD, ActiveBuild {
+            child: Arc::clone(&child),
+            dist_path: dist_path.clone(),
+        })?);
+
         // Create log file
         let mut log_file = fs::File::create(&log_path)?;
 
@@ -369,8 +1614,30 @@ pub fn build_package(
             }
         });
 
-        // Wait for process and threads to complete
-        let status = child.wait()?;
+        // Wait for process and threads to complete. Poll instead of blocking in
+        // wait() so the interrupt handler can take the lock and kill the child.
+        let started = std::time::Instant::now();
+        let mut heartbeat = (!verbose && options.progress.lines())
+            .then(|| Heartbeat::new(&module_id, HEARTBEAT_INTERVAL));
+        let limit = build.timeout.map(Duration::from_secs);
+        let mut timed_out = false;
+        let status = loop {
+            let mut guard = child.lock().map_err(|_| anyhow!("build process lock poisoned"))?;
+            if let Some(status) = guard.try_wait()? {
+                break status;
+            }
+            if limit.is_some_and(|limit| started.elapsed() >= limit) {
+                timed_out = true;
+                kill_process_group(&mut guard);
+                break guard.wait()?;
+            }
+            drop(guard);
+            if let Some(heartbeat) = &mut heartbeat
+                && let Some(line) = heartbeat.poll(started.elapsed(), || last_log_line(&log_path)) {
+                println!("{}", line);
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        };
         stdout_handle.join().map_err(|_| anyhow!("stdout thread panicked"))??;
         stderr_handle.join().map_err(|_| anyhow!("stderr thread panicked"))??;
 
@@ -378,52 +1645,167 @@ pub fn build_package(
             pb.finish_and_clear();
         }
 
+        if timed_out {
+            record_run(&run, false);
+            discard_broken_dist(&module_id, &dist_path, options.keep_broken);
+            return Err(anyhow!(
+                "Build of {} timed out after {}\nLog saved to: {}",
+                module_id,
+                crate::ast::format_duration(build.timeout.unwrap_or_default()),
+                log_path.display()
+            )).categorize(ErrorKind::Build);
+        }
+
         if !status.success() {
+            record_run(&run, false);
+            discard_broken_dist(&module_id, &dist_path, options.keep_broken);
+            if let Some(temp_build_dir) = temp_build_dir {
+                warn!("Kept build directory for debugging: {}", temp_build_dir.keep().display());
+            }
             return Err(anyhow!(
                 "Build failed for {} with exit code: {:?}\nLog saved to: {}",
                 module_id,
                 status.code(),
                 log_path.display()
-            ));
+            )).categorize(ErrorKind::Build);
         }
 
         info!("Build completed successfully. Log saved to: {}", log_path.display());
     }
 
+    // Verify the build left behind every declared output
+    let missing = find_missing_outputs(&dist_path, package);
+    if !missing.is_empty() {
+        record_run(&run, false);
+        discard_broken_dist(&module_id, &dist_path, options.keep_broken);
+        if let Some(pb) = &pb {
+            pb.finish_and_clear();
+        }
+        return Err(anyhow!(
+            "Build of {} did not produce declared outputs: {}",
+            module_id,
+            missing.join(", ")
+        )).categorize(ErrorKind::Build);
+    }
+
     if let Some(pb) = pb {
         pb.finish_and_clear();
-        println!("  ✓ Built {}", module_id);
+        if restored {
+            progress_println(format!("  ✓ Restored {} from build cache", module_id));
+        } else {
+            progress_println(format!("  ✓ Built {}", module_id));
+        }
+    }
+
+    if !restored && let Some(entry) = &cache_entry
+        && let Err(e) = store_in_build_cache(entry, &dist_path, rebuild) {
+        warn!("Failed to store {} in the build cache: {}", module_id, e);
     }
 
     // Update lockfile
     let mut lock = lock;
-    let build_hash = compute_build_hash(package);
+    let build_hash = options.build_hash(package);
     let mut state = lock.get_module_state(&module_id)
         .cloned()
         .unwrap_or(crate::lockfile::PackageState {
             fetch_hash: None,
             build_hash: None,
+            resolved_commit: None,
+            source_hash: None,
         });
     state.build_hash = build_hash;
+    // Hashed after the build so in-tree build artifacts are part of the baseline
+    state.source_hash = match package.fetch {
+        Some(_) => compute_source_hash(&source_path, options.source_check)?,
+        None => None,
+    };
     lock.set_module_state(module_id.clone(), state);
     lock.save(sprout_path)?;
+    drop(active_build);
+    record_run(&run, true);
 
     info!("Successfully built: {}", module_id);
     Ok(())
 }
 
+/// Record the current source hash for an up-to-date module built before the
+/// source check was turned on, so later edits have something to compare to
+fn record_source_baseline(sprout_path: &str, mut lock: SproutLock, package: &ModuleBlock, check: SourceCheck) -> Result<()> {
+    if check == SourceCheck::Off || package.fetch.is_none() {
+        return Ok(());
+    }
+    let Some(mut state) = lock.get_module_state(&package.id()).cloned() else {
+        return Ok(());
+    };
+    if state.source_hash.as_deref().is_some_and(|h| h.starts_with(check.prefix())) {
+        return Ok(());
+    }
+    state.source_hash = compute_source_hash(&get_source_path(sprout_path, package), check)?;
+    lock.set_module_state(package.id(), state);
+    lock.save(sprout_path)
+}
+
+/// PATH the build script will run with: the fixed one of an isolated build,
+/// otherwise the caller's
+fn tool_search_path(isolated: bool) -> Option<std::ffi::OsString> {
+    if isolated {
+        Some(ISOLATED_PATH.into())
+    } else {
+        std::env::var_os("PATH")
+    }
+}
+
+/// Locate an executable named `tool` on `search_path`, like `which`
+fn find_on_path(tool: &str, search_path: Option<&std::ffi::OsStr>) -> Option<PathBuf> {
+    if tool.contains('/') {
+        let path = PathBuf::from(tool);
+        return is_executable(&path).then_some(path);
+    }
+
+    search_path.and_then(|paths| {
+        std::env::split_paths(paths)
+            .map(|dir| dir.join(tool))
+            .find(|candidate| is_executable(candidate))
+    })
+}
+
+/// A regular file with an execute bit; elsewhere any regular file qualifies
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.is_file() && path.metadata().is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    path.is_file()
+}
+
+/// Return the declared `produces` entries that do not exist below `dist_path`
+pub fn find_missing_outputs(dist_path: &Path, package: &ModuleBlock) -> Vec<String> {
+    package.produces.iter()
+        .filter(|file| !dist_path.join(file.trim_start_matches('/')).exists())
+        .cloned()
+        .collect()
+}
+
 pub fn get_source_path(sprout_path: &str, package: &ModuleBlock) -> PathBuf {
+    source_path_with_hash(sprout_path, package, compute_fetch_hash(package).as_deref())
+}
+
+/// `get_source_path` for a fetch hash the caller already computed
+pub fn source_path_with_hash(sprout_path: &str, package: &ModuleBlock, fetch_hash: Option<&str>) -> PathBuf {
     let subdir = if let Some(fetch) = &package.fetch {
         match &fetch.spec {
             crate::ast::FetchSpec::Git(_) => "git",
-            crate::ast::FetchSpec::Http(_) => "http",
+            crate::ast::FetchSpec::Http(_) | crate::ast::FetchSpec::Github(_) => "http",
+            crate::ast::FetchSpec::Rsync(_) => "rsync",
             _ => "archive",
         }
     } else {
         "archive"
     };
     
-    let fetch_hash = compute_fetch_hash(package)
+    let fetch_hash = fetch_hash
         .map(|h| h[..8].to_string())
         .unwrap_or_else(|| "no-fetch".to_string());
     
@@ -435,30 +1817,118 @@ pub fn get_dist_path(sprout_path: &str, package: &ModuleBlock) -> PathBuf {
     Path::new(sprout_path).join("dist").join(package.id())
 }
 
-fn fetch_git(sprout_path: &str, package: &ModuleBlock, git: &crate::ast::GitSpec) -> Result<()> {
-    use std::process::Command;
-    use indicatif::{ProgressBar, ProgressStyle};
-    use std::time::Duration;
+/// The commit checked out in a git working tree
+fn git_head(repo: &Path) -> Result<String> {
+    let output = Command::new("git")
+        .current_dir(repo)
+        .args(["rev-parse", "HEAD"])
+        .output()?;
+    if !output.status.success() {
+        return Err(anyhow!("git rev-parse failed in {}", repo.display()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
 
-    let source_path = get_source_path(sprout_path, package);
+/// Whether `repo` is a checkout of `commit` without changes to tracked files.
+/// Untracked files such as in-tree build output do not count as changes
+fn git_checkout_is_current(repo: &Path, commit: &str) -> bool {
+    if !repo.join(".git").exists() || git_head(repo).ok().as_deref() != Some(commit) {
+        return false;
+    }
+    Command::new("git")
+        .current_dir(repo)
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .output()
+        .is_ok_and(|output| output.status.success() && output.stdout.is_empty())
+}
 
-    // Clean existing source directory
+/// Clean checkout of another module with the same fetch spec (url, ref and
+/// options) at its recorded commit, and that commit. With `locked` only a
+/// checkout of exactly that commit qualifies. Submodules are not shared
+fn shared_git_checkout(
+    sprout_path: &str,
+    package: &ModuleBlock,
+    git: &crate::ast::GitSpec,
+    locked: Option<&str>,
+    options: &FetchOptions,
+) -> Option<(PathBuf, String)> {
+    if git.recursive {
+        return None;
+    }
+    let fetch_hash = compute_fetch_hash(package)?;
+    let manifest = load_manifest_from(&options.manifest_path(sprout_path), sprout_path).ok()?;
+    let lock = SproutLock::load(sprout_path).ok()?;
+    manifest.modules.iter()
+        .filter(|other| other.id() != package.id() && compute_fetch_hash(other).as_deref() == Some(fetch_hash.as_str()))
+        .find_map(|other| {
+            let commit = lock.get_module_state(&other.id())?.resolved_commit.clone()?;
+            let source = get_source_path(sprout_path, other);
+            (locked.map_or_else(|| git_ref_is_at(git, &commit), |locked| locked == commit) && git_checkout_is_current(&source, &commit))
+                .then_some((source, commit))
+        })
+}
+
+/// Clone the local checkout `sibling` (objects are hardlinked, nothing is
+/// downloaded) and point its origin at the module's real url
+fn clone_shared_checkout(sibling: &Path, source_path: &Path, git: &crate::ast::GitSpec, commit: &str) -> Result<()> {
     if source_path.exists() {
-        info!("Cleaning existing source directory: {}", source_path.display());
-        fs::remove_dir_all(&source_path)?;
+        fs::remove_dir_all(source_path)?;
     }
-    fs::create_dir_all(&source_path)?;
+    info!("Cloning {} from identical checkout {}", git.url, sibling.display());
+    let git_in = |dir: &Path, args: &[&str]| -> Result<()> {
+        let output = Command::new("git").current_dir(dir).args(args).output()?;
+        if !output.status.success() {
+            return Err(anyhow!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(())
+    };
+    let parent = source_path.parent().context("Source path has no parent directory")?;
+    fs::create_dir_all(parent)?;
+    git_in(parent, &["clone", "--quiet", &sibling.to_string_lossy(), &source_path.to_string_lossy()])?;
+    git_in(source_path, &["checkout", "--quiet", "--detach", commit])?;
+    git_in(source_path, &["remote", "set-url", "origin", &git.url])
+}
 
-    // Create logs directory and log file for git operations
-    let logs_dir = Path::new(sprout_path).join("logs");
+/// Clone a git module; with `commit`, clone the full history and check that commit out.
+/// `full_history` skips the shallow clone for callers that need tags and history
+fn fetch_git(
+    sprout_path: &str,
+    package: &ModuleBlock,
+    git: &crate::ast::GitSpec,
+    commit: Option<&str>,
+    full_history: bool,
+    progress: Progress,
+) -> Result<()> {
+    use std::process::Command;
+    use indicatif::{ProgressBar, ProgressStyle};
+
+    let source_path = get_source_path(sprout_path, package);
+    let deadline = fetch_deadline(package);
+
+    if let Some(ref_) = git.ref_.as_deref().filter(|r| commit.is_none() && is_abbreviated_sha(r)) {
+        return Err(anyhow!(
+            "ref {} of {} looks like an abbreviated commit; use the full commit SHA (git rev-parse {} prints it)",
+            ref_, package.id(), ref_
+        ));
+    }
+
+    // Clean existing source directory
+    if source_path.exists() {
+        info!("Cleaning existing source directory: {}", source_path.display());
+        fs::remove_dir_all(&source_path)?;
+    }
+    fs::create_dir_all(&source_path)?;
+
+    // Create logs directory and log file for git operations
+    let logs_dir = Path::new(sprout_path).join("logs");
     fs::create_dir_all(&logs_dir)?;
 
     let timestamp = chrono::Utc::now().format("%Y%m%d-%H%M%S");
     let log_filename = format!("{}-fetch-{}.log", package.id(), timestamp);
     let log_path = logs_dir.join(&log_filename);
 
-    let pb = if atty::is(atty::Stream::Stderr) {
-        let pb = ProgressBar::new_spinner();
+    let pb = if progress.bars() {
+        let pb = attach_progress(ProgressBar::new_spinner());
         pb.set_style(ProgressStyle::default_spinner()
             .template("  {spinner} {msg}")?);
         pb.set_message(format!("Cloning {}", package.id()));
@@ -482,34 +1952,41 @@ fn fetch_git(sprout_path: &str, package: &ModuleBlock, git: &crate::ast::GitSpec
     writeln!(log_file, "=== Git Clone Output ===")?;
     drop(log_file);
 
-    // Execute git clone with depth 1 and optional recursive
+    // A ref that looks like a commit SHA cannot be passed to --branch; the
+    // default branch is cloned shallowly and the commit fetched by hash
+    let sha_ref = git.ref_.as_deref().filter(|r| commit.is_none() && is_commit_sha(r));
+
+    // Execute git clone with depth 1 and optional recursive. A locked commit
+    // may be anywhere in history, so that case clones everything instead
     let mut cmd = Command::new("git");
-    cmd.arg("clone")
-       .arg("--depth")
-       .arg("1");
+    cmd.arg("clone");
+
+    if commit.is_none() && !full_history {
+        cmd.arg("--depth").arg("1");
+    }
 
     if git.recursive {
         cmd.arg("--recursive");
     }
 
-    if let Some(ref_) = &git.ref_ {
+    if let (Some(ref_), None, None) = (&git.ref_, commit, sha_ref) {
         cmd.arg("--branch").arg(ref_);
     }
 
     cmd.arg(&git.url).arg(&source_path);
 
-    let status = cmd
-        .stdout(fs::OpenOptions::new().append(true).open(&log_path)?)
-        .stderr(fs::OpenOptions::new().append(true).open(&log_path)?)
-        .status()?;
+    cmd.stdout(fs::OpenOptions::new().append(true).open(&log_path)?)
+        .stderr(fs::OpenOptions::new().append(true).open(&log_path)?);
+    let status = status_with_deadline(&mut cmd, deadline, &log_path);
 
     if let Some(pb) = pb {
         pb.finish_and_clear();
-        if status.success() {
-            println!("  ✓ Cloned {}", package.id());
+        if status.as_ref().is_ok_and(|status| status.success()) {
+            progress_println(format!("  ✓ Cloned {}", package.id()));
         }
     }
 
+    let status = status?;
     if !status.success() {
         return Err(anyhow!(
             "git clone failed with exit code: {:?}\nLog saved to: {}",
@@ -518,26 +1995,329 @@ fn fetch_git(sprout_path: &str, package: &ModuleBlock, git: &crate::ast::GitSpec
         ));
     }
 
+    if let Some(sha) = sha_ref {
+        let fetch_args: &[&str] = if full_history {
+            &["fetch", "origin", sha]
+        } else {
+            &["fetch", "--depth", "1", "origin", sha]
+        };
+        let status = status_with_deadline(
+            Command::new("git")
+                .current_dir(&source_path)
+                .args(fetch_args)
+                .stdout(fs::OpenOptions::new().append(true).open(&log_path)?)
+                .stderr(fs::OpenOptions::new().append(true).open(&log_path)?),
+            deadline,
+            &log_path,
+        )?;
+        if !status.success() {
+            return Err(anyhow!(
+                "git fetch of commit {} failed\nLog saved to: {}",
+                sha,
+                log_path.display()
+            ));
+        }
+    }
+
+    if let Some(commit) = commit.or(sha_ref) {
+        let status = status_with_deadline(
+            Command::new("git")
+                .current_dir(&source_path)
+                .args(["checkout", "--detach", commit])
+                .stdout(fs::OpenOptions::new().append(true).open(&log_path)?)
+                .stderr(fs::OpenOptions::new().append(true).open(&log_path)?),
+            deadline,
+            &log_path,
+        )?;
+        if !status.success() {
+            return Err(anyhow!(
+                "git checkout of commit {} failed\nLog saved to: {}",
+                commit,
+                log_path.display()
+            ));
+        }
+        if git.recursive {
+            status_with_deadline(
+                Command::new("git")
+                    .current_dir(&source_path)
+                    .args(["submodule", "update", "--init", "--recursive"])
+                    .stdout(fs::OpenOptions::new().append(true).open(&log_path)?)
+                    .stderr(fs::OpenOptions::new().append(true).open(&log_path)?),
+                deadline,
+                &log_path,
+            )?;
+        }
+    }
+
     info!("Git fetch completed successfully. Log saved to: {}", log_path.display());
     Ok(())
 }
 
-fn fetch_archive(sprout_path: &str, package: &ModuleBlock, archive: &crate::ast::HttpSpec) -> Result<()> {
+/// Point in time by which a module's fetch must finish, from its `fetch.timeout`
+fn fetch_deadline(package: &ModuleBlock) -> Option<(std::time::Instant, u64)> {
+    package.fetch.as_ref()
+        .and_then(|fetch| fetch.timeout)
+        .map(|secs| (std::time::Instant::now() + Duration::from_secs(secs), secs))
+}
+
+/// Run `cmd` to completion, killing it once `deadline` has passed
+fn status_with_deadline(
+    cmd: &mut Command,
+    deadline: Option<(std::time::Instant, u64)>,
+    log_path: &Path,
+) -> Result<std::process::ExitStatus> {
+    let Some((deadline, secs)) = deadline else {
+        return Ok(cmd.status()?);
+    };
+
+    // The group no longer sees the terminal's Ctrl-C, so the handler kills it
+    install_interrupt_handler();
+    let child = Arc::new(Mutex::new(spawn_in_group(cmd)?));
+    let _active = ActiveGuard::register(&ACTIVE_FETCH, Arc::clone(&child))?;
+    loop {
+        let mut guard = child.lock().map_err(|_| anyhow!("fetch process lock poisoned"))?;
+        if let Some(status) = guard.try_wait()? {
+            return Ok(status);
+        }
+        if std::time::Instant::now() >= deadline {
+            kill_process_group(&mut guard);
+            guard.wait()?;
+            return Err(anyhow!(
+                "Fetch timed out after {}\nLog saved to: {}",
+                crate::ast::format_duration(secs),
+                log_path.display()
+            ));
+        }
+        drop(guard);
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Refs naming a commit: a full SHA-1 (40 hex digits) or SHA-256 (64). Only a
+/// full hash can be fetched by itself from a shallow clone
+pub fn is_commit_sha(ref_: &str) -> bool {
+    matches!(ref_.len(), 40 | 64) && ref_.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Refs that look like a shortened commit hash (7 or more hex digits)
+pub fn is_abbreviated_sha(ref_: &str) -> bool {
+    ref_.len() >= 7 && !is_commit_sha(ref_) && ref_.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Parse a tag like `v1.2.3` or `release-14.0` into numeric version components
+fn parse_version(tag: &str) -> Option<Vec<u64>> {
+    let start = tag.find(|c: char| c.is_ascii_digit())?;
+    let version = &tag[start..];
+    let mut parts = Vec::new();
+    for part in version.split('.') {
+        let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+        if digits.is_empty() {
+            break;
+        }
+        parts.push(digits.parse().ok()?);
+    }
+    Some(parts)
+}
+
+/// Whether a ref is a version tag that newer tags can be compared against;
+/// branches and commit SHAs are not
+pub fn is_version_ref(ref_: &str) -> bool {
+    !is_commit_sha(ref_) && !is_abbreviated_sha(ref_) && parse_version(ref_).is_some_and(|v| !v.is_empty())
+}
+
+/// Whether `candidate` is a strictly newer version than `current`
+pub fn is_newer_version(candidate: &str, current: &str) -> bool {
+    match (parse_version(candidate), parse_version(current)) {
+        (Some(candidate), Some(current)) => candidate > current,
+        _ => false,
+    }
+}
+
+/// Query a git remote for its tags and return the highest version-sorted one
+pub fn latest_remote_tag(url: &str) -> Result<Option<String>> {
+    let output = Command::new("git")
+        .args(["ls-remote", "--tags", url])
+        .stdin(Stdio::null())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "git ls-remote failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let latest = stdout
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .filter_map(|r| r.strip_prefix("refs/tags/"))
+        .map(|tag| tag.trim_end_matches("^{}"))
+        .filter_map(|tag| parse_version(tag).map(|v| (v, tag.to_string())))
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, tag)| tag);
+
+    Ok(latest)
+}
+
+/// Whether the spec's ref still names `commit` upstream. A SHA ref is compared
+/// as is; tags and branches are looked up with `git ls-remote`
+fn git_ref_is_at(git: &crate::ast::GitSpec, commit: &str) -> bool {
+    match git.ref_.as_deref() {
+        Some(ref_) if is_commit_sha(ref_) => commit.eq_ignore_ascii_case(ref_),
+        ref_ => remote_ref_commit(&git.url, ref_).is_some_and(|remote| remote == commit),
+    }
+}
+
+/// Commit a ref (or the default branch) currently names on a remote; annotated
+/// tags are peeled to their commit. `None` when the remote cannot be reached
+fn remote_ref_commit(url: &str, ref_: Option<&str>) -> Option<String> {
+    let ref_ = ref_.unwrap_or("HEAD");
+    let output = Command::new("git")
+        .args(["ls-remote", url, ref_, &format!("{}^{{}}", ref_)])
+        .stdin(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let refs: Vec<(&str, &str)> = stdout.lines()
+        .filter_map(|line| line.split_once('\t'))
+        .collect();
+    refs.iter()
+        .find(|(_, name)| name.ends_with("^{}"))
+        .or_else(|| refs.first())
+        .map(|(commit, _)| commit.to_string())
+}
+
+/// Where an http module's download is cached: `cache/http/<id>-<hash8>/<file>`
+fn http_cache_path(sprout_path: &str, package: &ModuleBlock, archive: &crate::ast::HttpSpec) -> PathBuf {
     let fetch_hash = compute_fetch_hash(package)
         .map(|h| h[..8].to_string())
         .unwrap_or_else(|| "no-fetch".to_string());
-    
-    let cache_dir_name = format!("{}-{}", package.id(), fetch_hash);
-    let cache_dir = Path::new(sprout_path).join("cache/http").join(&cache_dir_name);
-    std::fs::create_dir_all(&cache_dir)?;
+    let original_filename = archive.url.split('/').next_back().unwrap_or("archive");
+
+    Path::new(sprout_path)
+        .join("cache/http")
+        .join(format!("{}-{}", package.id(), fetch_hash))
+        .join(original_filename)
+}
+
+/// Cached download of another module fetching the same url. Matched by url
+/// rather than fetch hash, and looked up in all of that module's cache dirs:
+/// the first fetch writes the computed sha256 into its spec, which changes
+/// its fetch hash but not what was downloaded. A file is only shared when it
+/// matches a pin: this module's own, or else the other module's, and the two
+/// must agree when both are set
+fn shared_download(sprout_path: &str, package: &ModuleBlock, archive: &crate::ast::HttpSpec, options: &FetchOptions) -> Option<PathBuf> {
+    let manifest = load_manifest_from(&options.manifest_path(sprout_path), sprout_path).ok()?;
+    let filename = archive.url.split('/').next_back().unwrap_or("archive");
+    let cache_dirs: Vec<PathBuf> = fs::read_dir(Path::new(sprout_path).join("cache/http")).ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+
+    manifest.modules.iter()
+        .filter(|other| other.id() != package.id())
+        .filter_map(|other| {
+            let other_pin = match other.fetch.as_ref().map(|f| &f.spec) {
+                Some(crate::ast::FetchSpec::Http(other_archive)) if other_archive.url == archive.url => &other_archive.sha256,
+                Some(crate::ast::FetchSpec::Github(other_release)) => match package.fetch.as_ref().map(|f| &f.spec) {
+                    Some(crate::ast::FetchSpec::Github(release))
+                        if (&release.repo, &release.tag, &release.asset) == (&other_release.repo, &other_release.tag, &other_release.asset) => &other_release.sha256,
+                    _ => return None,
+                },
+                _ => return None,
+            };
+            match (&archive.sha256, other_pin) {
+                (Some(own), Some(theirs)) if !own.eq_ignore_ascii_case(theirs) => None,
+                (Some(pin), _) | (None, Some(pin)) => Some((other, pin.clone())),
+                (None, None) => None,
+            }
+        })
+        .flat_map(|(other, pin)| {
+            let prefix = format!("{}-", other.id());
+            cache_dirs.iter().filter(move |dir| {
+                dir.file_name().and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_prefix(&prefix))
+                    .is_some_and(|hash| hash.len() == 8 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+            }).map(move |dir| (dir.join(filename), pin.clone()))
+        })
+        .find(|(path, pin)| {
+            path.is_file() && compute_file_sha256(path).is_ok_and(|actual| actual.eq_ignore_ascii_case(pin))
+        })
+        .map(|(path, _)| path)
+}
+
+/// Re-check the sha256 of every cached http download that has one pinned,
+/// without downloading anything. Returns the ids of modules whose cache is
+/// corrupt; with `fix` those cache files are removed
+pub fn verify_caches(sprout_path: &str, manifest: &SproutManifest, fix: bool) -> Result<Vec<String>> {
+    let mut corrupt = Vec::new();
+
+    for package in &manifest.modules {
+        let archive = match package.fetch.as_ref().map(|f| &f.spec) {
+            Some(crate::ast::FetchSpec::Http(archive)) => archive.clone(),
+            // Without a remembered asset URL nothing of this release was downloaded yet
+            Some(crate::ast::FetchSpec::Github(github)) => match cached_github_url(sprout_path, github) {
+                Some(url) => crate::ast::HttpSpec { url, sha256: github.sha256.clone() },
+                None => continue,
+            },
+            _ => continue,
+        };
+        let Some(expected) = &archive.sha256 else {
+            continue;
+        };
+        let cache_path = http_cache_path(sprout_path, package, &archive);
+        if !cache_path.exists() {
+            debug!("No cached download for {}", package.id());
+            continue;
+        }
+
+        let filename = cache_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        match verify_sha256(&cache_path, expected, &filename) {
+            Ok(()) => info!("{}: cache ok", package.id()),
+            Err(e) => {
+                warn!("{}: {}", package.id(), e);
+                if fix {
+                    fs::remove_file(&cache_path)?;
+                    info!("Removed corrupt cache {}", cache_path.display());
+                }
+                corrupt.push(package.id());
+            }
+        }
+    }
+
+    Ok(corrupt)
+}
+
+fn fetch_archive(sprout_path: &str, package: &ModuleBlock, archive: &crate::ast::HttpSpec, options: &FetchOptions) -> Result<()> {
+    let force = options.force;
+    let cache_path = http_cache_path(sprout_path, package, archive);
+    if let Some(cache_dir) = cache_path.parent() {
+        std::fs::create_dir_all(cache_dir)?;
+    }
 
     let original_filename = archive.url.split('/').next_back().unwrap_or("archive");
-    let cache_path = cache_dir.join(original_filename);
 
-    if !cache_path.exists() {
-        download_file(&archive.url, &cache_path, original_filename)?;
+    if force && cache_path.exists() {
+        info!("Removing cached {} (--force)", original_filename);
+        fs::remove_file(&cache_path)?;
+    }
+
+    let shared = if cache_path.exists() || force { None } else { shared_download(sprout_path, package, archive, options) };
+    if let Some(shared) = shared {
+        // Another module already downloaded the same url and it matches the pin
+        info!("Reusing {} downloaded for another module: {}", original_filename, shared.display());
+        if fs::hard_link(&shared, &cache_path).is_err() {
+            fs::copy(&shared, &cache_path)?;
+        }
+    } else if !cache_path.exists() {
+        let timeout = package.fetch.as_ref().and_then(|fetch| fetch.timeout).map(Duration::from_secs);
+        download_file(&archive.url, &cache_path, original_filename, timeout, options.progress)?;
     } else {
         info!("Using cached {}", original_filename);
+        // The mtime orders caches for eviction; atime is unreliable under noatime
+        fs::File::options().write(true).open(&cache_path)?.set_modified(std::time::SystemTime::now())?;
     }
 
     // Compute SHA256 if not present in manifest
@@ -551,19 +2331,26 @@ fn fetch_archive(sprout_path: &str, package: &ModuleBlock, archive: &crate::ast:
     };
 
     if let Some(expected_hash) = &archive.sha256 {
-        verify_sha256(&cache_path, expected_hash, original_filename)?;
+        check_sha256(&cache_path, expected_hash, original_filename, options.no_verify)?;
     }
 
     // Update manifest with computed SHA256
     if let Some(hash) = computed_hash {
         let package_id = package.id();
-        let mut manifest = load_manifest(sprout_path)?;
+        let manifest_path = options.manifest_path(sprout_path);
+        let mut manifest = crate::manifest::load_manifest_raw_from(&manifest_path)?;
         if let Some(module) = manifest.modules.iter_mut().find(|m| m.id() == package_id) {
             if let Some(fetch) = &mut module.fetch {
-                if let crate::ast::FetchSpec::Http(http_spec) = &mut fetch.spec {
-                    http_spec.sha256 = Some(hash);
+                let sha256 = match &mut fetch.spec {
+                    crate::ast::FetchSpec::Http(http_spec) => Some(&mut http_spec.sha256),
+                    crate::ast::FetchSpec::Github(github_spec) => Some(&mut github_spec.sha256),
+                    _ => None,
+                };
+                if let Some(sha256) = sha256 {
+                    progress_println(format!("  ✓ Pinned sha256 = {} for {}", hash, package_id));
+                    *sha256 = Some(hash);
                     info!("Updated manifest with SHA256 for {}", package_id);
-                    crate::manifest::save_manifest(sprout_path, &manifest)?;
+                    crate::manifest::save_manifest_to(&manifest_path, &manifest)?;
                 }
             }
         }
@@ -576,36 +2363,212 @@ fn fetch_archive(sprout_path: &str, package: &ModuleBlock, archive: &crate::ast:
     }
     fs::create_dir_all(&source_path)?;
 
-    // Use custom output filename if specified
-    let output_filename = package.fetch.as_ref()
-        .and_then(|f| f.output.as_ref())
-        .map(|s| s.as_str())
-        .unwrap_or(original_filename);
+    unpack_fetched_file(package, &cache_path, &source_path, original_filename, options.progress)
+}
+
+/// GitHub REST API root used to resolve `github = { ... }` release assets
+const GITHUB_API: &str = "https://api.github.com";
+
+/// Where the asset URL a github spec resolved to is remembered. Keyed by repo,
+/// tag and asset pattern rather than fetch hash, so pinning the sha256 keeps it
+fn github_url_cache_path(sprout_path: &str, github: &crate::ast::GithubSpec) -> PathBuf {
+    let key = Sha256::digest(format!("{}\0{}\0{}", github.repo, github.tag, github.asset));
+    Path::new(sprout_path).join("cache/github").join(format!("{:x}.url", key))
+}
+
+/// The asset URL an earlier fetch resolved a github spec to, if any
+pub(crate) fn cached_github_url(sprout_path: &str, github: &crate::ast::GithubSpec) -> Option<String> {
+    let url = fs::read_to_string(github_url_cache_path(sprout_path, github)).ok()?;
+    Some(url.trim().to_string()).filter(|url| !url.is_empty())
+}
+
+/// The http download a github spec stands for. The release API is only asked
+/// once per repo, tag and asset pattern; `refresh` (a `--force` fetch) asks again
+fn github_archive(sprout_path: &str, api: &str, github: &crate::ast::GithubSpec, refresh: bool) -> Result<crate::ast::HttpSpec> {
+    let url = match cached_github_url(sprout_path, github).filter(|_| !refresh) {
+        Some(url) => {
+            debug!("Using remembered asset URL of {}@{}: {}", github.repo, github.tag, url);
+            url
+        }
+        None => {
+            let url = resolve_github_asset(api, github)?;
+            let cache_path = github_url_cache_path(sprout_path, github);
+            if let Some(cache_dir) = cache_path.parent() {
+                fs::create_dir_all(cache_dir)?;
+            }
+            fs::write(&cache_path, format!("{}\n", url))?;
+            url
+        }
+    };
+    Ok(crate::ast::HttpSpec { url, sha256: github.sha256.clone() })
+}
+
+/// Download URL of the one asset of `github.tag` whose name matches `github.asset`.
+/// Sends `GITHUB_TOKEN` when set, which lifts the anonymous rate limit
+fn resolve_github_asset(api: &str, github: &crate::ast::GithubSpec) -> Result<String> {
+    let url = format!("{}/repos/{}/releases/tags/{}", api.trim_end_matches('/'), github.repo, github.tag);
+    info!("Resolving GitHub release asset via {}", url);
+
+    let mut request = reqwest::blocking::Client::new()
+        .get(&url)
+        .header("User-Agent", "sprout")
+        .header("Accept", "application/vnd.github+json");
+    if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send()
+        .with_context(|| format!("Failed to query GitHub release {}@{}", github.repo, github.tag))?;
+    if !response.status().is_success() {
+        return Err(anyhow!("GitHub release {}@{} not found ({})", github.repo, github.tag, response.status()));
+    }
+    let release: serde_json::Value = serde_json::from_str(&response.text()?)?;
+
+    let pattern = regex::Regex::new(&format!("^(?:{})$", github.asset))
+        .with_context(|| format!("Invalid asset regex: {}", github.asset))?;
+    let assets = release["assets"].as_array().map(Vec::as_slice).unwrap_or_default();
+    let names = || assets.iter().filter_map(|a| a["name"].as_str()).collect::<Vec<_>>().join(", ");
+    let matches: Vec<&serde_json::Value> = assets.iter()
+        .filter(|a| a["name"].as_str().is_some_and(|name| pattern.is_match(name)))
+        .collect();
+    match matches.as_slice() {
+        [asset] => asset["browser_download_url"].as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Asset {} has no download URL", asset["name"])),
+        [] => Err(anyhow!("No asset of {}@{} matches '{}' (assets: {})", github.repo, github.tag, github.asset, names())),
+        _ => Err(anyhow!(
+            "{} assets of {}@{} match '{}'; make the pattern more specific (assets: {})",
+            matches.len(), github.repo, github.tag, github.asset, names()
+        )),
+    }
+}
 
-    // If output is specified, skip extraction (just copy the file)
-    let skip_extract = package.fetch.as_ref()
-        .and_then(|f| f.output.as_ref())
-        .is_some();
+/// Put a downloaded or local file into the source dir. Archives are unpacked
+/// unless the fetch block says `extract = false`, in which case the file is
+/// copied verbatim. `output` names the result of a single-file download (a
+/// raw file, or the decompressed content of a `.gz`/`.xz`); multi-file
+/// archives keep their own layout
+fn unpack_fetched_file(package: &ModuleBlock, path: &Path, source_path: &Path, original_filename: &str, progress: Progress) -> Result<()> {
+    let fetch = package.fetch.as_ref();
+    let output = fetch.and_then(|f| f.output.as_deref());
 
-    if skip_extract {
+    if fetch.is_some_and(|f| !f.extract) {
         info!("Copying {} -> {}", original_filename, source_path.display());
-        copy_file_with_progress(&cache_path, &source_path, original_filename, output_filename)?;
-    } else {
-        info!("Extracting {} -> {}", original_filename, source_path.display());
-        extract_archive_with_output(&cache_path, &source_path, original_filename, output_filename)?;
+        return copy_file_with_progress(path, source_path, original_filename, output.unwrap_or(original_filename), progress);
+    }
+
+    if let Some(output) = output
+        && is_multi_file_archive(original_filename) {
+        warn!(
+            "{}: output = {} only names single-file downloads; {} is extracted with its own layout (use extract = false to keep the archive)",
+            package.id(), output, original_filename
+        );
+    }
+    info!("Extracting {} -> {}", original_filename, source_path.display());
+    extract_archive_with_output(path, source_path, original_filename, output.unwrap_or(original_filename), progress)
+}
+
+/// Archives that unpack into a directory tree rather than a single file
+fn is_multi_file_archive(filename: &str) -> bool {
+    [".tar.gz", ".tgz", ".tar.xz", ".tar.lz", ".zip", ".deb", ".rpm"]
+        .iter()
+        .any(|ext| filename.ends_with(ext))
+}
+
+/// Fetch from the local filesystem. Archive files are verified and unpacked
+/// exactly like a cached download; directories are copied as-is
+fn fetch_local(sprout_path: &str, package: &ModuleBlock, local: &crate::ast::LocalSpec, options: &FetchOptions) -> Result<()> {
+    // Relative paths are taken relative to the sprout directory
+    let path = Path::new(sprout_path).join(&local.path);
+    if !path.exists() {
+        return Err(anyhow!("Local path does not exist for {}: {}", package.id(), path.display()));
+    }
+
+    let source_path = get_source_path(sprout_path, package);
+    if source_path.exists() {
+        info!("Cleaning existing source directory: {}", source_path.display());
+        fs::remove_dir_all(&source_path)?;
+    }
+
+    if path.is_dir() {
+        info!("Copying {} -> {}", path.display(), source_path.display());
+        return crate::core::symlinks::copy_dir_all(&path, &source_path);
+    }
+    fs::create_dir_all(&source_path)?;
+
+    let original_filename = path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "archive".to_string());
+    if let Some(expected_hash) = &local.sha256 {
+        check_sha256(&path, expected_hash, &original_filename, options.no_verify)?;
+    }
+
+    unpack_fetched_file(package, &path, &source_path, &original_filename, options.progress)
+}
+
+/// Mirror an rsync source into the module's source dir. An existing copy is
+/// updated in place, `--delete` drops files that vanished upstream
+fn fetch_rsync(sprout_path: &str, package: &ModuleBlock, rsync: &crate::ast::RsyncSpec, progress: Progress) -> Result<()> {
+    fetch_rsync_with(Path::new("rsync"), sprout_path, package, rsync, progress)
+}
+
+/// `fetch_rsync` running `program` in place of `rsync`
+fn fetch_rsync_with(program: &Path, sprout_path: &str, package: &ModuleBlock, rsync: &crate::ast::RsyncSpec, progress: Progress) -> Result<()> {
+    let source_path = get_source_path(sprout_path, package);
+    fs::create_dir_all(&source_path)?;
+
+    let logs_dir = Path::new(sprout_path).join("logs");
+    fs::create_dir_all(&logs_dir)?;
+    let timestamp = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+    let log_path = logs_dir.join(format!("{}-fetch-{}.log", package.id(), timestamp));
+
+    let mut log_file = fs::File::create(&log_path)?;
+    writeln!(log_file, "=== Rsync Fetch Log ===")?;
+    writeln!(log_file, "Source: {}", rsync.url)?;
+    writeln!(log_file, "Target: {}", source_path.display())?;
+    writeln!(log_file, "=== Rsync Output ===")?;
+    drop(log_file);
+
+    info!("Syncing {} -> {}", rsync.url, source_path.display());
+    info!("Fetch log: {}", log_path.display());
+
+    let status = status_with_deadline(
+        Command::new(program)
+            .args(["-a", "--delete"])
+            .arg(&rsync.url)
+            .arg(&source_path)
+            .stdout(fs::OpenOptions::new().append(true).open(&log_path)?)
+            .stderr(fs::OpenOptions::new().append(true).open(&log_path)?),
+        fetch_deadline(package),
+        &log_path,
+    ).context("Failed to run rsync")?;
+    if !status.success() {
+        return Err(anyhow!(
+            "rsync failed with exit code: {:?}\nLog saved to: {}",
+            status.code(),
+            log_path.display()
+        ));
     }
+
+    if progress.bars() {
+        progress_println(format!("  ✓ Synced {}", package.id()));
+    }
+    info!("Rsync fetch completed successfully. Log saved to: {}", log_path.display());
     Ok(())
 }
 
-fn download_file(url: &str, dest: &Path, filename: &str) -> Result<()> {
+/// Download `url` to `dest`; `timeout` bounds the whole transfer, body included
+fn download_file(url: &str, dest: &Path, filename: &str, timeout: Option<Duration>, progress: Progress) -> Result<()> {
     use std::io::Write;
     use indicatif::{ProgressBar, ProgressStyle};
 
-    let mut response = reqwest::blocking::get(url)?;
+    let mut response = match timeout {
+        Some(timeout) => reqwest::blocking::Client::builder().timeout(timeout).build()?.get(url).send()?,
+        None => reqwest::blocking::get(url)?,
+    };
     let total_size = response.content_length().unwrap_or(0);
 
-    let pb = if atty::is(atty::Stream::Stderr) {
-        let pb = ProgressBar::new(total_size);
+    let pb = if progress.bars() {
+        let pb = attach_progress(ProgressBar::new(total_size));
         pb.set_style(ProgressStyle::default_bar()
             .template("  {msg} [{bar:40}] {bytes}/{total_bytes} ({eta})")?
             .progress_chars("=>-"));
@@ -638,6 +2601,15 @@ fn download_file(url: &str, dest: &Path, filename: &str) -> Result<()> {
     Ok(())
 }
 
+/// Verify a pinned sha256 unless `--no-verify` turned integrity checking off
+fn check_sha256(path: &Path, expected: &str, filename: &str, no_verify: bool) -> Result<()> {
+    if no_verify {
+        warn!("INTEGRITY CHECK DISABLED (--no-verify): sha256 of {} is not verified", filename);
+        return Ok(());
+    }
+    verify_sha256(path, expected, filename)
+}
+
 fn verify_sha256(path: &Path, expected: &str, filename: &str) -> Result<()> {
     let computed = compute_file_sha256(path)?;
 
@@ -659,12 +2631,11 @@ fn compute_file_sha256(path: &Path) -> Result<String> {
     Ok(format!("{:x}", hasher.finalize()))
 }
 
-fn copy_file_with_progress(cache_path: &Path, dest_dir: &Path, filename: &str, output_name: &str) -> Result<()> {
+fn copy_file_with_progress(cache_path: &Path, dest_dir: &Path, filename: &str, output_name: &str, progress: Progress) -> Result<()> {
     use indicatif::{ProgressBar, ProgressStyle};
-    use std::time::Duration;
 
-    let pb = if atty::is(atty::Stream::Stderr) {
-        let pb = ProgressBar::new_spinner();
+    let pb = if progress.bars() {
+        let pb = attach_progress(ProgressBar::new_spinner());
         pb.set_style(ProgressStyle::default_spinner()
             .template("  {spinner} {msg}")?);
         pb.set_message(format!("Copying {}", filename));
@@ -680,26 +2651,26 @@ fn copy_file_with_progress(cache_path: &Path, dest_dir: &Path, filename: &str, o
 
     if let Some(pb) = pb {
         pb.finish_and_clear();
-        println!("  ✓ Copied {}", filename);
+        progress_println(format!("  ✓ Copied {}", filename));
     } else {
         info!("Copied {}", filename);
     }
     Ok(())
 }
 
-fn extract_archive_with_output(cache_path: &Path, dest: &Path, filename: &str, output_name: &str) -> Result<()> {
+fn extract_archive_with_output(cache_path: &Path, dest: &Path, filename: &str, output_name: &str, progress: Progress) -> Result<()> {
     use indicatif::{ProgressBar, ProgressStyle};
-    use std::time::Duration;
 
     let is_archive = filename.ends_with(".tar.gz") || filename.ends_with(".tgz") 
         || filename.ends_with(".tar.xz") || filename.ends_with(".tar.lz") || filename.ends_with(".zip")
-        || filename.ends_with(".gz") || filename.ends_with(".xz");
+        || filename.ends_with(".gz") || filename.ends_with(".xz")
+        || filename.ends_with(".deb") || filename.ends_with(".rpm");
 
     let action = if is_archive { "Extracting" } else { "Copying" };
     let action_past = if is_archive { "Extracted" } else { "Copied" };
 
-    let pb = if atty::is(atty::Stream::Stderr) {
-        let pb = ProgressBar::new_spinner();
+    let pb = if progress.bars() {
+        let pb = attach_progress(ProgressBar::new_spinner());
         pb.set_style(ProgressStyle::default_spinner()
             .template("  {spinner} {msg}")?);
         pb.set_message(format!("{} {}", action, filename));
@@ -730,6 +2701,10 @@ fn extract_archive_with_output(cache_path: &Path, dest: &Path, filename: &str, o
         let file = std::fs::File::open(cache_path)?;
         let mut archive = zip::ZipArchive::new(file)?;
         archive.extract(dest)?;
+    } else if filename.ends_with(".deb") {
+        extract_deb(cache_path, dest)?;
+    } else if filename.ends_with(".rpm") {
+        extract_rpm(cache_path, dest)?;
     } else if filename.ends_with(".gz") {
         let gz_file = std::fs::File::open(cache_path)?;
         let mut decoder = flate2::read::GzDecoder::new(gz_file);
@@ -750,18 +2725,151 @@ fn extract_archive_with_output(cache_path: &Path, dest: &Path, filename: &str, o
 
     if let Some(pb) = pb {
         pb.finish_and_clear();
-        println!("  ✓ {} {}", action_past, filename);
+        progress_println(format!("  ✓ {} {}", action_past, filename));
     } else {
         info!("{} {}", action_past, filename);
     }
     Ok(())
 }
 
+/// Unpack the `data.tar*` member of a Debian package; control files are skipped
+fn extract_deb(path: &Path, dest: &Path) -> Result<()> {
+    let mut archive = ar::Archive::new(fs::File::open(path)?);
+    while let Some(entry) = archive.next_entry() {
+        let entry = entry?;
+        let name = String::from_utf8_lossy(entry.header().identifier()).to_string();
+        let Some(compression) = name.strip_prefix("data.tar") else {
+            continue;
+        };
+
+        let reader: Box<dyn Read> = match compression {
+            "" => Box::new(entry),
+            ".gz" => Box::new(flate2::read::GzDecoder::new(entry)),
+            ".xz" => Box::new(xz::read::XzDecoder::new(entry)),
+            ".zst" => Box::new(zstd::stream::read::Decoder::new(entry)?),
+            other => return Err(anyhow!("Unsupported deb payload compression: data.tar{}", other)),
+        };
+        tar::Archive::new(reader).unpack(dest)?;
+        return Ok(());
+    }
+    Err(anyhow!("No data.tar member found in {}", path.display()))
+}
+
+/// Unpack the cpio payload of an RPM package (lead, signature and header are skipped)
+fn extract_rpm(path: &Path, dest: &Path) -> Result<()> {
+    const LEAD_SIZE: usize = 96;
+    const HEADER_MAGIC: [u8; 3] = [0x8e, 0xad, 0xe8];
+
+    let data = fs::read(path)?;
+    if data.len() < LEAD_SIZE || data[..4] != [0xed, 0xab, 0xee, 0xdb] {
+        return Err(anyhow!("{} is not an RPM package", path.display()));
+    }
+
+    // Signature header (padded to 8 bytes) followed by the main header
+    let header_end = |offset: usize| -> Result<usize> {
+        let header = data.get(offset..offset + 16)
+            .filter(|h| h[..3] == HEADER_MAGIC)
+            .ok_or_else(|| anyhow!("Corrupt RPM header in {}", path.display()))?;
+        let entries = u32::from_be_bytes(header[8..12].try_into()?) as usize;
+        let size = u32::from_be_bytes(header[12..16].try_into()?) as usize;
+        Ok(offset + 16 + entries * 16 + size)
+    };
+    let signature_end = header_end(LEAD_SIZE)?;
+    let payload_start = header_end(signature_end.next_multiple_of(8))?;
+    let payload = data.get(payload_start..)
+        .ok_or_else(|| anyhow!("RPM payload missing in {}", path.display()))?;
+
+    let mut cpio = Vec::new();
+    match payload {
+        [0x1f, 0x8b, ..] => { flate2::read::GzDecoder::new(payload).read_to_end(&mut cpio)?; }
+        [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => { xz::read::XzDecoder::new(payload).read_to_end(&mut cpio)?; }
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => { zstd::stream::read::Decoder::new(payload)?.read_to_end(&mut cpio)?; }
+        _ => cpio.extend_from_slice(payload),
+    }
+    unpack_cpio(&cpio, dest)
+}
+
+/// Unpack a "newc" cpio archive, the format RPM payloads use
+fn unpack_cpio(data: &[u8], dest: &Path) -> Result<()> {
+    use std::path::Component;
+
+    const HEADER_SIZE: usize = 110;
+    let field = |header: &[u8], index: usize| -> Result<usize> {
+        let start = 6 + index * 8;
+        let hex = std::str::from_utf8(&header[start..start + 8])?;
+        Ok(usize::from_str_radix(hex, 16)?)
+    };
+
+    let mut offset = 0;
+    loop {
+        let header = data.get(offset..offset + HEADER_SIZE)
+            .filter(|h| h.starts_with(b"07070"))
+            .ok_or_else(|| anyhow!("Corrupt cpio archive at offset {}", offset))?;
+        let mode = field(header, 1)?;
+        let file_size = field(header, 6)?;
+        let name_size = field(header, 11)?;
+
+        let name_start = offset + HEADER_SIZE;
+        let name_bytes = data.get(name_start..name_start + name_size.saturating_sub(1))
+            .ok_or_else(|| anyhow!("Truncated cpio archive"))?;
+        let name = std::str::from_utf8(name_bytes)?;
+        let data_start = (name_start + name_size).next_multiple_of(4);
+        let contents = data.get(data_start..data_start + file_size)
+            .ok_or_else(|| anyhow!("Truncated cpio archive"))?;
+        offset = (data_start + file_size).next_multiple_of(4);
+
+        if name == "TRAILER!!!" {
+            return Ok(());
+        }
+
+        // Entries are rooted at "./" or "/"; never write outside dest
+        let relative = Path::new(name.trim_start_matches("./").trim_start_matches('/'));
+        if relative.as_os_str().is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            continue;
+        }
+        // Symlinks are unpacked verbatim (absolute ones are common in packages),
+        // so an earlier entry may have planted a link that points anywhere.
+        // Nothing is ever written through one
+        let mut ancestor = dest.to_path_buf();
+        for component in relative.components() {
+            ancestor.push(component);
+            if ancestor.symlink_metadata().is_ok_and(|m| m.file_type().is_symlink()) {
+                if ancestor.as_path() != dest.join(relative) || mode & 0o170000 == 0o040000 {
+                    return Err(anyhow!("cpio entry {} would be written through the symlink {}", name, ancestor.display()));
+                }
+                // A file or link entry replaces the link itself
+                fs::remove_file(&ancestor)?;
+            }
+        }
+        let target = dest.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        match mode & 0o170000 {
+            0o040000 => fs::create_dir_all(&target)?,
+            0o120000 => {
+                #[cfg(unix)]
+                std::os::unix::fs::symlink(std::str::from_utf8(contents)?, &target)?;
+            }
+            0o100000 => {
+                fs::write(&target, contents)?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    fs::set_permissions(&target, fs::Permissions::from_mode((mode & 0o7777) as u32))?;
+                }
+            }
+            _ => debug!("Skipping special cpio entry {}", name),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::ast::*;
-    use std::collections::HashMap;
+    use crate::manifest::load_manifest;
 
     #[test]
     fn test_fetch_hash_consistency() {
@@ -773,6 +2881,9 @@ mod tests {
                 recursive: false,
             }),
             output: None,
+            extract: true,
+            discard_git: false,
+            timeout: None,
         };
 
         let fetch2 = FetchBlock {
@@ -782,12 +2893,23 @@ mod tests {
                 recursive: false,
             }),
             output: None,
+            extract: true,
+            discard_git: false,
+            timeout: None,
         };
 
         let module1 = ModuleBlock {
             name: "test".to_string(),
+            version: None,
+            description: None,
+            tags: Vec::new(),
             depends_on: vec![],
             provides: vec![],
+            produces: vec![],
+            requires: vec![],
+            build_dir: None,
+            out_of_tree: false,
+            isolated: false,
             fetch: Some(fetch1),
             build: None,
             update: None,
@@ -795,8 +2917,16 @@ mod tests {
 
         let module2 = ModuleBlock {
             name: "test".to_string(),
+            version: None,
+            description: None,
+            tags: Vec::new(),
             depends_on: vec![],
             provides: vec![],
+            produces: vec![],
+            requires: vec![],
+            build_dir: None,
+            out_of_tree: false,
+            isolated: false,
             fetch: Some(fetch2),
             build: None,
             update: None,
@@ -817,6 +2947,7 @@ mod tests {
                 ("CFLAGS".to_string(), "-O2".to_string()),
             ],
             commands: vec!["make".to_string()],
+            timeout: None,
         };
 
         let build2 = ScriptBlock {
@@ -825,12 +2956,21 @@ mod tests {
                 ("CFLAGS".to_string(), "-O2".to_string()),
             ],
             commands: vec!["make".to_string()],
+            timeout: None,
         };
 
         let module1 = ModuleBlock {
             name: "test".to_string(),
+            version: None,
+            description: None,
+            tags: Vec::new(),
             depends_on: vec![],
             provides: vec![],
+            produces: vec![],
+            requires: vec![],
+            build_dir: None,
+            out_of_tree: false,
+            isolated: false,
             fetch: None,
             build: Some(build1),
             update: None,
@@ -838,8 +2978,16 @@ mod tests {
 
         let module2 = ModuleBlock {
             name: "test".to_string(),
+            version: None,
+            description: None,
+            tags: Vec::new(),
             depends_on: vec![],
             provides: vec![],
+            produces: vec![],
+            requires: vec![],
+            build_dir: None,
+            out_of_tree: false,
+            isolated: false,
             fetch: None,
             build: Some(build2),
             update: None,
@@ -856,17 +3004,27 @@ mod tests {
         let build1 = ScriptBlock {
             env: vec![("CC".to_string(), "gcc".to_string())],
             commands: vec!["make".to_string()],
+            timeout: None,
         };
 
         let build2 = ScriptBlock {
             env: vec![("CC".to_string(), "clang".to_string())],
             commands: vec!["make".to_string()],
+            timeout: None,
         };
 
         let module1 = ModuleBlock {
             name: "test".to_string(),
+            version: None,
+            description: None,
+            tags: Vec::new(),
             depends_on: vec![],
             provides: vec![],
+            produces: vec![],
+            requires: vec![],
+            build_dir: None,
+            out_of_tree: false,
+            isolated: false,
             fetch: None,
             build: Some(build1),
             update: None,
@@ -874,8 +3032,16 @@ mod tests {
 
         let module2 = ModuleBlock {
             name: "test".to_string(),
+            version: None,
+            description: None,
+            tags: Vec::new(),
             depends_on: vec![],
             provides: vec![],
+            produces: vec![],
+            requires: vec![],
+            build_dir: None,
+            out_of_tree: false,
+            isolated: false,
             fetch: None,
             build: Some(build2),
             update: None,
@@ -896,10 +3062,1697 @@ mod tests {
                 ("M_VAR".to_string(), "middle".to_string()),
             ],
             commands: vec!["cmd1".to_string(), "cmd2".to_string()],
+            timeout: None,
         };
 
         let serialized = script.to_string();
         assert_eq!(serialized, "ScriptBlock{env:[Z_VAR=last,A_VAR=first,M_VAR=middle],commands:[cmd1,cmd2]}");
     }
-}
 
+    fn create_module_producing(commands: &[&str]) -> ModuleBlock {
+        ModuleBlock {
+            name: "foo".to_string(),
+            version: None,
+            description: None,
+            tags: Vec::new(),
+            depends_on: vec![],
+            provides: vec![],
+            produces: vec!["bin/foo".to_string()],
+            requires: vec![],
+            build_dir: None,
+            out_of_tree: false,
+            isolated: false,
+            fetch: None,
+            build: Some(ScriptBlock {
+                env: vec![],
+                commands: commands.iter().map(|c| c.to_string()).collect(),
+                timeout: None,
+            }),
+            update: None,
+        }
+    }
+
+    #[test]
+    fn test_build_cache_restores_identical_build_without_running_script() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let runs = temp_dir.path().join("runs");
+        let module = create_module_producing(&[
+            "mkdir -p ${DIST_PATH}/bin",
+            "echo ${DIST_PATH} > ${DIST_PATH}/bin/foo",
+            "ln -s foo ${DIST_PATH}/bin/foo-link",
+            &format!("echo run >> {}", runs.display()),
+        ]);
+        let sprout = |name: &str| temp_dir.path().join(name).to_string_lossy().to_string();
+        let run_count = || fs::read_to_string(&runs).unwrap_or_default().lines().count();
+        let wipe = |name: &str| {
+            fs::remove_dir_all(get_dist_path(&sprout(name), &module)).unwrap();
+            fs::remove_file(temp_dir.path().join(name).join("sprout.lock")).unwrap();
+        };
+        let cached_entry = |name: &str| {
+            let dist = get_dist_path(&sprout(name), &module);
+            fs::read_dir(temp_dir.path().join("cache")).unwrap()
+                .map(|entry| entry.unwrap().path())
+                .find(|path| fs::read_to_string(path.join("tree/bin/foo")).is_ok_and(|out| out.trim() == dist.to_string_lossy()))
+                .unwrap()
+        };
+
+        let options = BuildOptions { build_cache: Some(temp_dir.path().join("cache")), ..Default::default() };
+        build_package(&sprout("first"), &module, &options).unwrap();
+        wipe("first");
+        build_package(&sprout("first"), &module, &options).unwrap();
+        let runs_after_restore = run_count();
+        build_package(&sprout("other"), &module, &options).unwrap();
+        let runs_after_other = run_count();
+        let entry = cached_entry("first");
+        fs::write(entry.join("tree/bin/foo"), "tampered").unwrap();
+        wipe("first");
+        build_package(&sprout("first"), &module, &options).unwrap();
+        let runs_after_tampered = run_count();
+        build_package(&sprout("first"), &module, &BuildOptions { rebuild: true, ..options.clone() }).unwrap();
+
+        // The same sprout dir got its dist back, links intact, without a run
+        let dist = get_dist_path(&sprout("first"), &module);
+        assert_eq!(runs_after_restore, 1);
+        assert_eq!(fs::read_link(dist.join("bin/foo-link")).unwrap(), Path::new("foo"));
+        let lock = SproutLock::load(&sprout("first")).unwrap();
+        assert_eq!(lock.get_module_state("foo").unwrap().build_hash, compute_build_hash(&module));
+
+        // Another sprout dir builds its own tree pointing at its own dist
+        assert_eq!(runs_after_other, 2);
+        let other_dist = get_dist_path(&sprout("other"), &module);
+        assert_eq!(fs::read_to_string(other_dist.join("bin/foo")).unwrap().trim(), other_dist.to_string_lossy());
+        cached_entry("other");
+
+        // A tampered entry is rejected and rebuilt
+        assert_eq!(runs_after_tampered, 3);
+        assert_eq!(fs::read_to_string(dist.join("bin/foo")).unwrap().trim(), dist.to_string_lossy());
+
+        // --rebuild runs the script again and replaces the cached entry
+        assert_eq!(run_count(), 4);
+        assert_eq!(cached_entry("first"), entry);
+    }
+
+    #[test]
+    fn test_build_fails_when_declared_output_missing() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+
+        let module = create_module_producing(&["mkdir -p ${DIST_PATH}/bin"]);
+
+        let err = build_package(sprout_path, &module, &BuildOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("bin/foo"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn test_failed_build_removes_dist_unless_keep_broken() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let module = create_module_producing(&["touch ${DIST_PATH}/partial", "exit 1"]);
+        let dist_path = get_dist_path(sprout_path, &module);
+
+        assert!(build_package(sprout_path, &module, &BuildOptions::default()).is_err());
+        assert!(!dist_path.exists(), "incomplete dist must be removed");
+
+        let result = build_package(sprout_path, &module, &BuildOptions { keep_broken: true, ..Default::default() });
+        assert!(result.is_err());
+        assert!(dist_path.join("partial").exists(), "--keep-broken must retain the dist");
+    }
+
+    #[test]
+    fn test_heartbeat_fires_once_per_interval() {
+        let mut heartbeat = Heartbeat::new("llvm", Duration::from_secs(30));
+        let mut lines = Vec::new();
+        for secs in [0, 10, 29, 30, 31, 59, 95, 100, 120] {
+            if let Some(line) = heartbeat.poll(Duration::from_secs(secs), || Some("[ 42%] Linking foo\n".to_string())) {
+                lines.push(line);
+            }
+        }
+
+        // Due at 30, 60 (late: 95 also covers 90) and 120
+        assert_eq!(lines, [
+            "still building llvm... 30s | [ 42%] Linking foo",
+            "still building llvm... 95s | [ 42%] Linking foo",
+            "still building llvm... 120s | [ 42%] Linking foo",
+        ]);
+        assert_eq!(heartbeat.poll(Duration::from_secs(150), || None).as_deref(), Some("still building llvm... 150s"));
+    }
+
+    #[test]
+    fn test_build_fails_early_on_missing_required_tool() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let marker = temp_dir.path().join("script-ran");
+
+        let mut module = create_module_producing(&[&format!("touch {}", marker.display())]);
+        module.requires = vec!["sh".to_string(), "sprout-no-such-tool".to_string()];
+
+        let err = build_package(sprout_path, &module, &BuildOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("missing tool: sprout-no-such-tool"), "unexpected error: {}", err);
+        assert!(!marker.exists(), "build script must not run");
+    }
+
+    #[test]
+    fn test_isolated_build_looks_up_required_tools_on_isolated_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let tool = temp_dir.path().join("sprout-host-only-tool");
+        fs::write(&tool, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+        let host_path = std::env::join_paths([temp_dir.path()]).unwrap();
+
+        // Found on the caller's PATH, but not on the one an isolated build gets
+        assert_eq!(find_on_path("sprout-host-only-tool", Some(&host_path)), Some(tool));
+        assert!(find_on_path("sprout-host-only-tool", Some(ISOLATED_PATH.as_ref())).is_none());
+        assert!(find_on_path("sh", Some(ISOLATED_PATH.as_ref())).is_some());
+
+        let mut module = create_module_producing(&[]);
+        let isolated_env = BuildOptions { isolated_env: true, ..Default::default() };
+        assert_eq!(tool_search_path(BuildOptions::default().isolates(&module)), std::env::var_os("PATH"));
+        assert_eq!(tool_search_path(isolated_env.isolates(&module)).as_deref(), Some(ISOLATED_PATH.as_ref()));
+        module.isolated = true;
+        assert_eq!(tool_search_path(BuildOptions::default().isolates(&module)).as_deref(), Some(ISOLATED_PATH.as_ref()));
+    }
+
+    #[test]
+    fn test_thorough_source_check_rebuilds_after_source_edit() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let runs = temp_dir.path().join("runs");
+
+        let mut module = create_module_producing(&[
+            "mkdir -p ${DIST_PATH}/bin",
+            "touch ${DIST_PATH}/bin/foo",
+            &format!("echo run >> {}", runs.display()),
+        ]);
+        module.fetch = Some(crate::ast::FetchBlock {
+            spec: crate::ast::FetchSpec::Local(crate::ast::LocalSpec { path: "/unused".to_string(), sha256: None }),
+            output: None,
+            extract: true,
+            discard_git: false,
+            timeout: None,
+        });
+        let source_path = get_source_path(sprout_path, &module);
+        fs::create_dir_all(source_path.join("src")).unwrap();
+        fs::write(source_path.join("src/main.c"), "int main() { return 0; }").unwrap();
+        let run_count = || fs::read_to_string(&runs).unwrap().lines().count();
+
+        let options = BuildOptions { source_check: SourceCheck::Content, ..Default::default() };
+        build_package(sprout_path, &module, &options).unwrap();
+        let lock = SproutLock::load(sprout_path).unwrap();
+        assert!(lock.get_module_state("foo").unwrap().source_hash.as_deref().is_some_and(|h| h.starts_with("content:")));
+        build_package(sprout_path, &module, &options).unwrap();
+        assert_eq!(run_count(), 1, "unchanged sources must not rebuild");
+
+        // Same size, so only the content hash can tell
+        fs::write(source_path.join("src/main.c"), "int main() { return 1; }").unwrap();
+        let lock = SproutLock::load(sprout_path).unwrap();
+        assert!(sources_changed(sprout_path, &lock, &module, SourceCheck::Content));
+        assert!(!sources_changed(sprout_path, &lock, &module, SourceCheck::Off));
+        build_package(sprout_path, &module, &options).unwrap();
+        assert_eq!(run_count(), 2, "edited sources must rebuild");
+    }
+
+    #[test]
+    fn test_build_killed_after_module_timeout() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+
+        let mut module = create_module_producing(&["sleep 5"]);
+        module.build.as_mut().unwrap().timeout = Some(1);
+
+        let started = std::time::Instant::now();
+        let err = build_package(sprout_path, &module, &BuildOptions::default()).unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(4), "build was not killed");
+        assert!(err.to_string().contains("timed out after 1s"), "unexpected error: {}", err);
+
+        // Not the last command, so bash forks it instead of exec'ing it; the
+        // whole process group must die for the output pipes to close
+        let mut module = create_module_producing(&["sleep 5", "true"]);
+        module.build.as_mut().unwrap().timeout = Some(1);
+        let started = std::time::Instant::now();
+        assert!(build_package(sprout_path, &module, &BuildOptions::default()).is_err());
+        assert!(started.elapsed() < Duration::from_secs(4), "child of the build script outlived the timeout");
+
+        // The timeout is part of the build hash, so raising it counts as a change
+        let before = compute_build_hash(&module);
+        module.build.as_mut().unwrap().timeout = Some(60);
+        assert_ne!(before, compute_build_hash(&module));
+        module.build.as_mut().unwrap().timeout = None;
+        assert_ne!(before, compute_build_hash(&module));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_interrupt_kills_timed_fetch() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // The interrupted side: a child copy of this test binary runs the fetch
+        if let Ok(sprout_path) = std::env::var("SPROUT_TEST_INTERRUPTED_FETCH") {
+            let mut module = create_http_module("unused");
+            module.fetch = Some(FetchBlock {
+                spec: FetchSpec::Git(GitSpec {
+                    url: "https://example.invalid/tool.git".to_string(),
+                    ref_: None,
+                    recursive: false,
+                }),
+                output: None,
+                extract: true,
+                discard_git: false,
+                timeout: Some(60),
+            });
+            let _ = fetch_package(&sprout_path, &module, &FetchOptions::default());
+            return;
+        }
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let bin = temp_dir.path().join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        let pid_file = temp_dir.path().join("git.pid");
+        let git = bin.join("git");
+        fs::write(&git, format!("#!/bin/sh\necho $$ > {}\nexec sleep 30\n", pid_file.display())).unwrap();
+        fs::set_permissions(&git, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut child = Command::new(std::env::current_exe().unwrap())
+            .args(["core::deps::tests::test_interrupt_kills_timed_fetch", "--exact", "--test-threads=1"])
+            .env("SPROUT_TEST_INTERRUPTED_FETCH", temp_dir.path().join("sprout"))
+            .env("PATH", format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default()))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let started = std::time::Instant::now();
+        let git_pid = loop {
+            if let Some(pid) = fs::read_to_string(&pid_file).ok().and_then(|s| s.trim().parse::<libc::pid_t>().ok()) {
+                break pid;
+            }
+            assert!(started.elapsed() < Duration::from_secs(10), "fake git never started");
+            std::thread::sleep(Duration::from_millis(20));
+        };
+
+        // SAFETY: only sends signals to processes this test started
+        unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) };
+        assert_eq!(child.wait().unwrap().code(), Some(130));
+        let alive = unsafe { libc::kill(git_pid, 0) } == 0;
+        if alive {
+            unsafe { libc::kill(git_pid, libc::SIGKILL) };
+        }
+        assert!(!alive, "git outlived the interrupted fetch");
+    }
+
+    #[test]
+    fn test_build_reruns_when_declared_output_deleted() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+
+        let module = create_module_producing(&["mkdir -p ${DIST_PATH}/bin", "touch ${DIST_PATH}/bin/foo"]);
+        let output = get_dist_path(sprout_path, &module).join("bin/foo");
+
+        build_package(sprout_path, &module, &BuildOptions::default()).unwrap();
+        assert!(output.exists());
+
+        // Tamper with dist: the up-to-date check must notice and rebuild
+        fs::remove_file(&output).unwrap();
+        build_package(sprout_path, &module, &BuildOptions::default()).unwrap();
+        assert!(output.exists());
+    }
+
+    #[test]
+    fn test_build_runs_in_declared_build_dir() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+
+        let mut module = create_module_producing(&["mkdir -p ${DIST_PATH}/bin", "cp marker ${DIST_PATH}/bin/foo"]);
+        module.fetch = Some(FetchBlock {
+            spec: FetchSpec::Git(GitSpec {
+                url: "https://example.com/mono.git".to_string(),
+                ref_: None,
+                recursive: false,
+            }),
+            output: None,
+            extract: true,
+            discard_git: false,
+            timeout: None,
+        });
+        module.build_dir = Some("subproject".to_string());
+
+        // The subdir is required to exist
+        let source_path = get_source_path(sprout_path, &module);
+        fs::create_dir_all(&source_path).unwrap();
+        let err = build_package(sprout_path, &module, &BuildOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("subproject"), "unexpected error: {}", err);
+
+        fs::create_dir_all(source_path.join("subproject")).unwrap();
+        fs::write(source_path.join("subproject/marker"), "nested").unwrap();
+        build_package(sprout_path, &module, &BuildOptions::default()).unwrap();
+
+        let output = get_dist_path(sprout_path, &module).join("bin/foo");
+        assert_eq!(fs::read_to_string(output).unwrap(), "nested");
+
+        // The build dir is part of the build hash
+        let mut root_module = module.clone();
+        root_module.build_dir = None;
+        assert_ne!(compute_build_hash(&module), compute_build_hash(&root_module));
+    }
+
+    #[test]
+    fn test_isolated_build_does_not_inherit_environment() {
+        // Any variable of the test process that bash would not set itself
+        let (key, value) = std::env::vars()
+            .find(|(key, value)| !matches!(key.as_str(), "PATH" | "PWD" | "OLDPWD" | "SHLVL" | "_") && !value.contains('\n'))
+            .expect("test process has no environment");
+        let inherited_line = format!("{}={}", key, value);
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let mut module = create_module_producing(&[
+            "mkdir -p ${DIST_PATH}/bin",
+            "env > ${DIST_PATH}/bin/foo",
+        ]);
+        module.build.as_mut().unwrap().env = vec![("FROM_MODULE".to_string(), "yes".to_string())];
+        let build_env = |module: &ModuleBlock| {
+            build_package(sprout_path, module, &BuildOptions { rebuild: true, ..Default::default() }).unwrap();
+            fs::read_to_string(get_dist_path(sprout_path, module).join("bin/foo")).unwrap()
+        };
+
+        let inherited = build_env(&module);
+        assert!(inherited.lines().any(|line| line == inherited_line));
+
+        let mut isolated = module.clone();
+        isolated.isolated = true;
+        let env = build_env(&isolated);
+        assert!(!env.lines().any(|line| line.starts_with(&format!("{}=", key))), "{} leaked:\n{}", key, env);
+        assert!(env.lines().any(|line| line == format!("PATH={}", ISOLATED_PATH)), "{}", env);
+        assert!(env.contains("FROM_MODULE=yes") && env.contains("DIST_PATH="));
+
+        assert_ne!(compute_build_hash(&module), compute_build_hash(&isolated));
+    }
+
+    #[test]
+    fn test_out_of_tree_build_leaves_source_untouched() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+
+        let mut module = create_module_producing(&[
+            "touch in-source-artifact",
+            "mkdir -p ${DIST_PATH}/bin",
+            "cp ${SOURCE_PATH}/input ${DIST_PATH}/bin/foo",
+        ]);
+        module.out_of_tree = true;
+
+        let source_path = get_source_path(sprout_path, &module);
+        fs::create_dir_all(&source_path).unwrap();
+        fs::write(source_path.join("input"), "pristine").unwrap();
+
+        build_package(sprout_path, &module, &BuildOptions::default()).unwrap();
+
+        let entries: Vec<_> = fs::read_dir(&source_path).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(entries, vec!["input".to_string()]);
+        let output = get_dist_path(sprout_path, &module).join("bin/foo");
+        assert_eq!(fs::read_to_string(output).unwrap(), "pristine");
+
+        let mut in_tree = module.clone();
+        in_tree.out_of_tree = false;
+        assert_ne!(compute_build_hash(&module), compute_build_hash(&in_tree));
+    }
+
+    #[test]
+    fn test_continue_from_resumes_failed_build() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module base {
+    depends_on = []
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+module middle {
+    depends_on = [base]
+    build {
+        test -f ${SPROUT_DIST}/../ready
+        mkdir -p ${DIST_PATH}
+    }
+}
+module top {
+    depends_on = [middle]
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+"#).unwrap();
+        let manifest = load_manifest(sprout_path).unwrap();
+        let ordered = resolve_dependency_order(&manifest).unwrap();
+
+        build_package(sprout_path, ordered[0], &BuildOptions::default()).unwrap();
+        assert!(build_package(sprout_path, ordered[1], &BuildOptions::default()).is_err());
+
+        // Resuming past the failed module is refused
+        let err = continue_from(sprout_path, &manifest, ordered.clone(), "top", &BuildOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("'middle' is not built"), "unexpected error: {}", err);
+
+        fs::write(temp_dir.path().join("ready"), "").unwrap();
+        let resumed = continue_from(sprout_path, &manifest, ordered, "middle", &BuildOptions::default()).unwrap();
+        let ids: Vec<_> = resumed.iter().map(|p| p.id()).collect();
+        assert_eq!(ids, vec!["middle", "top"]);
+
+        for package in resumed {
+            build_package(sprout_path, package, &BuildOptions::default()).unwrap();
+        }
+        assert!(temp_dir.path().join("dist/top").exists());
+    }
+
+    #[test]
+    fn test_build_queue_requeues_module_blocked_by_queued_dependency() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module lib {
+    depends_on = []
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+module app {
+    depends_on = [lib]
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+"#).unwrap();
+        let manifest = load_manifest(sprout_path).unwrap();
+        let lib = manifest.find_module("lib").unwrap();
+        let app = manifest.find_module("app").unwrap();
+
+        // Nothing will build lib, so app fails with the typed error
+        let failed = build_queue(sprout_path, vec![app], &BuildOptions::default());
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].1.downcast_ref::<BlockedDependency>(), Some(&BlockedDependency::NotBuilt("lib".to_string())));
+
+        // Queued the wrong way round, app waits for lib and then builds
+        let failed = build_queue(sprout_path, vec![app, lib], &BuildOptions::default());
+        assert!(failed.is_empty(), "unexpected failures: {:?}", failed);
+        assert!(get_dist_path(sprout_path, app).exists());
+        assert!(get_dist_path(sprout_path, lib).exists());
+    }
+
+    #[test]
+    fn test_batch_progress_counts_against_dependency_order() {
+        let manifest = crate::parser::parse_manifest(r#"
+module zlib {
+    depends_on = []
+}
+module openssl {
+    depends_on = [zlib]
+}
+module curl {
+    depends_on = [zlib, openssl]
+}
+"#).unwrap();
+
+        let ordered = resolve_dependency_order(&manifest).unwrap();
+        let mut batch = BatchProgress::start(ordered.len(), Progress::default());
+        assert_eq!(batch.total, 3);
+        for package in &ordered {
+            batch.next(&package.id());
+        }
+        assert_eq!(batch.label, "[3/3] curl");
+
+        // Extra steps never run the counter past the total
+        batch.next("curl");
+        assert_eq!(batch.label, "[3/3] curl");
+    }
+
+    #[test]
+    fn test_plan_builds_labels_each_module() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest_path = temp_dir.path().join("manifest.sprout");
+        fs::write(&manifest_path, r#"
+module base {
+    depends_on = []
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+module tool {
+    depends_on = [base]
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+"#).unwrap();
+        let manifest = load_manifest(sprout_path).unwrap();
+        for package in resolve_dependency_order(&manifest).unwrap() {
+            build_package(sprout_path, package, &BuildOptions::default()).unwrap();
+        }
+
+        // Changing the build script makes tool stale while base stays current
+        let content = fs::read_to_string(&manifest_path).unwrap();
+        let content = content.replace("depends_on = [base]\n    build {\n", "depends_on = [base]\n    build {\n        echo v2\n");
+        fs::write(&manifest_path, content).unwrap();
+        let manifest = load_manifest(sprout_path).unwrap();
+        let ordered = resolve_dependency_order(&manifest).unwrap();
+
+        let plan = plan_builds(sprout_path, &manifest, &ordered, &BuildOptions::default()).unwrap();
+        let labels: Vec<_> = plan.iter().map(|(p, d)| format!("{} {}", d, p.id())).collect();
+        assert_eq!(labels, vec!["[skip up-to-date] base", "[build] tool"]);
+
+        // A dependency that is neither built nor planned blocks its dependents
+        fs::remove_dir_all(temp_dir.path().join("dist/base")).unwrap();
+        let plan = plan_builds(sprout_path, &manifest, &ordered[1..], &BuildOptions::default()).unwrap();
+        assert_eq!(plan[0].1, BuildDecision::Blocked("dep base not built".to_string()));
+    }
+
+    /// Serve `body` for a single HTTP request and return the URL to fetch it from
+    fn serve_once(filename: &str, body: &'static [u8]) -> String {
+        serve_times(filename, body, 1)
+    }
+
+    fn serve_times(filename: &str, body: &'static [u8], times: usize) -> String {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/{}", listener.local_addr().unwrap(), filename);
+        std::thread::spawn(move || {
+            for _ in 0..times {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request);
+                let header = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len());
+                stream.write_all(header.as_bytes()).unwrap();
+                stream.write_all(body).unwrap();
+            }
+        });
+        url
+    }
+
+    fn create_http_module(url: &str) -> ModuleBlock {
+        ModuleBlock {
+            name: "payload".to_string(),
+            version: None,
+            description: None,
+            tags: Vec::new(),
+            depends_on: vec![],
+            provides: vec![],
+            produces: vec![],
+            requires: vec![],
+            build_dir: None,
+            out_of_tree: false,
+            isolated: false,
+            fetch: Some(FetchBlock {
+                spec: FetchSpec::Http(HttpSpec { url: url.to_string(), sha256: None }),
+                output: None,
+                extract: true,
+                discard_git: false,
+                timeout: None,
+            }),
+            build: None,
+            update: None,
+        }
+    }
+
+    #[test]
+    fn test_fetch_force_redownloads_cached_archive() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+
+        let url = serve_once("payload.txt", b"fresh");
+        let module = create_http_module(&url);
+        crate::manifest::save_manifest(sprout_path, &SproutManifest {
+            modules: vec![module.clone()],
+            environments: None,
+        }).unwrap();
+
+        // Seed the cache with a corrupted download
+        let fetch_hash = &compute_fetch_hash(&module).unwrap()[..8];
+        let cache_dir = Path::new(sprout_path).join("cache/http").join(format!("payload-{}", fetch_hash));
+        fs::create_dir_all(&cache_dir).unwrap();
+        fs::write(cache_dir.join("payload.txt"), "corrupt").unwrap();
+
+        fetch_package(sprout_path, &module, &FetchOptions { force: true, ..Default::default() }).unwrap();
+
+        assert_eq!(fs::read_to_string(cache_dir.join("payload.txt")).unwrap(), "fresh");
+        let source = get_source_path(sprout_path, &module).join("payload.txt");
+        assert_eq!(fs::read_to_string(source).unwrap(), "fresh");
+    }
+
+    #[test]
+    fn test_identical_http_specs_download_once() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        // Serves a single request; a second download would fail to connect
+        let url = serve_once("payload.txt", b"shared");
+        let first = create_http_module(&url);
+        let second = ModuleBlock { name: "mirror".to_string(), ..first.clone() };
+        crate::manifest::save_manifest(sprout_path, &SproutManifest {
+            modules: vec![first.clone(), second.clone()],
+            environments: None,
+        }).unwrap();
+
+        fetch_package(sprout_path, &first, &FetchOptions::default()).unwrap();
+        // The first fetch pinned its sha256, the second module's spec is unchanged
+        fetch_package(sprout_path, &second, &FetchOptions::default()).unwrap();
+
+        for module in [&first, &second] {
+            let source = get_source_path(sprout_path, module).join("payload.txt");
+            assert_eq!(fs::read_to_string(source).unwrap(), "shared");
+        }
+        let manifest = load_manifest(sprout_path).unwrap();
+        for id in ["payload", "mirror"] {
+            let Some(FetchSpec::Http(http)) = manifest.find_module(id).and_then(|m| m.fetch.as_ref()).map(|f| &f.spec) else {
+                panic!("{} lost its http spec", id);
+            };
+            assert_eq!(http.sha256.as_deref(), Some(format!("{:x}", Sha256::digest(b"shared")).as_str()));
+        }
+    }
+
+    #[test]
+    fn test_shared_download_must_match_pin() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let url = serve_times("payload.txt", b"shared", 2);
+        let first = create_http_module(&url);
+        let second = ModuleBlock { name: "mirror".to_string(), ..first.clone() };
+        crate::manifest::save_manifest(sprout_path, &SproutManifest {
+            modules: vec![first.clone(), second.clone()],
+            environments: None,
+        }).unwrap();
+
+        fetch_package(sprout_path, &first, &FetchOptions::default()).unwrap();
+        let cached = shared_download(sprout_path, &second, &http_spec(&second), &FetchOptions::default()).unwrap();
+        fs::write(&cached, "tampered").unwrap();
+
+        // A pin that disagrees with the other module's never shares its file
+        let mut pinned = http_spec(&second);
+        pinned.sha256 = Some(format!("{:x}", Sha256::digest(b"other")));
+        assert!(shared_download(sprout_path, &second, &pinned, &FetchOptions::default()).is_none());
+
+        // The tampered file no longer matches the first module's pin, so it is downloaded again
+        assert!(shared_download(sprout_path, &second, &http_spec(&second), &FetchOptions::default()).is_none());
+        fetch_package(sprout_path, &second, &FetchOptions::default()).unwrap();
+        let source = get_source_path(sprout_path, &second).join("payload.txt");
+        assert_eq!(fs::read_to_string(source).unwrap(), "shared");
+    }
+
+    fn http_spec(module: &ModuleBlock) -> crate::ast::HttpSpec {
+        match module.fetch.as_ref().map(|f| &f.spec) {
+            Some(FetchSpec::Http(http)) => http.clone(),
+            _ => panic!("{} has no http spec", module.id()),
+        }
+    }
+
+    #[test]
+    fn test_identical_git_specs_clone_from_local_checkout() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout");
+        let sprout_path = sprout_path.to_str().unwrap();
+        let repo = temp_dir.path().join("upstream");
+        fs::create_dir_all(&repo).unwrap();
+        fs::create_dir_all(sprout_path).unwrap();
+        fs::write(repo.join("VERSION"), "1").unwrap();
+        for args in [
+            &["init", "-q"][..],
+            &["add", "VERSION"][..],
+            &["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "-q", "-m", "1"][..],
+        ] {
+            assert!(Command::new("git").current_dir(&repo).args(args).status().unwrap().success());
+        }
+        let head = Command::new("git").current_dir(&repo).args(["rev-parse", "HEAD"]).output().unwrap();
+        let head = String::from_utf8_lossy(&head.stdout).trim().to_string();
+
+        // Pinned to a commit, so sharing needs no remote lookup of where a branch is
+        let first = ModuleBlock {
+            fetch: Some(FetchBlock {
+                spec: FetchSpec::Git(GitSpec { url: repo.display().to_string(), ref_: Some(head), recursive: false }),
+                output: None,
+                extract: true,
+                discard_git: false,
+                timeout: None,
+            }),
+            ..create_http_module("unused")
+        };
+        let second = ModuleBlock { name: "mirror".to_string(), ..first.clone() };
+        crate::manifest::save_manifest(sprout_path, &SproutManifest {
+            modules: vec![first.clone(), second.clone()],
+            environments: None,
+        }).unwrap();
+
+        fetch_package(sprout_path, &first, &FetchOptions::default()).unwrap();
+        // Upstream is gone; only the first module's checkout can provide the source
+        fs::rename(&repo, temp_dir.path().join("moved")).unwrap();
+        fetch_package(sprout_path, &second, &FetchOptions::default()).unwrap();
+
+        let source = get_source_path(sprout_path, &second);
+        assert_eq!(fs::read_to_string(source.join("VERSION")).unwrap(), "1");
+        let origin = Command::new("git").current_dir(&source).args(["remote", "get-url", "origin"]).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&origin.stdout).trim(), repo.display().to_string());
+        let lock = SproutLock::load(sprout_path).unwrap();
+        assert_eq!(lock.get_module_state("mirror").unwrap().resolved_commit, lock.get_module_state("payload").unwrap().resolved_commit);
+    }
+
+    #[test]
+    fn test_verify_caches_detects_and_removes_corrupt_archive() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+
+        let mut module = create_http_module("http://127.0.0.1:9/payload.txt");
+        if let Some(FetchBlock { spec: FetchSpec::Http(http), .. }) = &mut module.fetch {
+            http.sha256 = Some(format!("{:x}", Sha256::digest(b"fresh")));
+        }
+        let manifest = SproutManifest { modules: vec![module.clone()], environments: None };
+
+        // Nothing cached yet, nothing to report
+        assert!(verify_caches(sprout_path, &manifest, false).unwrap().is_empty());
+
+        let fetch_hash = &compute_fetch_hash(&module).unwrap()[..8];
+        let cache_file = Path::new(sprout_path).join("cache/http").join(format!("payload-{}", fetch_hash)).join("payload.txt");
+        fs::create_dir_all(cache_file.parent().unwrap()).unwrap();
+        fs::write(&cache_file, "fresh").unwrap();
+        assert!(verify_caches(sprout_path, &manifest, false).unwrap().is_empty());
+
+        // A truncated download is reported but only removed with fix
+        fs::write(&cache_file, "fre").unwrap();
+        assert_eq!(verify_caches(sprout_path, &manifest, false).unwrap(), vec!["payload"]);
+        assert!(cache_file.exists());
+        assert_eq!(verify_caches(sprout_path, &manifest, true).unwrap(), vec!["payload"]);
+        assert!(!cache_file.exists());
+    }
+
+    #[test]
+    fn test_fetch_no_verify_skips_sha256_check() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+
+        let pinned = "0".repeat(64);
+        let url = serve_once("payload.txt", b"changed upstream");
+        let mut module = create_http_module(&url);
+        if let Some(FetchBlock { spec: FetchSpec::Http(http), .. }) = &mut module.fetch {
+            http.sha256 = Some(pinned.clone());
+        }
+        crate::manifest::save_manifest(sprout_path, &SproutManifest {
+            modules: vec![module.clone()],
+            environments: None,
+        }).unwrap();
+
+        let err = fetch_package(sprout_path, &module, &FetchOptions::default()).unwrap_err();
+        assert!(format!("{:#}", err).contains("SHA256 mismatch"), "unexpected error: {:#}", err);
+
+        // The download is cached, so this reuses it without verifying
+        fetch_package(sprout_path, &module, &FetchOptions { no_verify: true, ..Default::default() }).unwrap();
+        let source = get_source_path(sprout_path, &module).join("payload.txt");
+        assert_eq!(fs::read_to_string(source).unwrap(), "changed upstream");
+
+        // The pinned hash stays in the manifest
+        let manifest = load_manifest(sprout_path).unwrap();
+        match &manifest.modules[0].fetch.as_ref().unwrap().spec {
+            FetchSpec::Http(http) => assert_eq!(http.sha256.as_deref(), Some(pinned.as_str())),
+            _ => panic!("expected http fetch"),
+        }
+    }
+
+    /// A newc cpio archive of `(name, mode, contents)` entries plus the trailer
+    fn newc_cpio(entries: &[(&str, usize, &[u8])]) -> Vec<u8> {
+        let mut cpio = Vec::new();
+        for &(name, mode, body) in entries.iter().chain([&("TRAILER!!!", 0, &b""[..])]) {
+            let fields = [0, mode, 0, 0, 1, 0, body.len(), 0, 0, 0, 0, name.len() + 1, 0];
+            cpio.extend_from_slice(b"070701");
+            for value in fields {
+                cpio.extend_from_slice(format!("{:08x}", value).as_bytes());
+            }
+            cpio.extend_from_slice(name.as_bytes());
+            cpio.push(0);
+            cpio.resize(cpio.len().next_multiple_of(4), 0);
+            cpio.extend_from_slice(body);
+            cpio.resize(cpio.len().next_multiple_of(4), 0);
+        }
+        cpio
+    }
+
+    #[test]
+    fn test_cpio_never_writes_through_planted_symlink() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let outside = temp_dir.path().join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        let dest = temp_dir.path().join("dest");
+        fs::create_dir_all(&dest).unwrap();
+        let outside_str = outside.to_str().unwrap();
+
+        // A link to a directory outside dest, then a file below that link
+        let evil = newc_cpio(&[("./lib", 0o120777, outside_str.as_bytes()), ("./lib/pwned", 0o100644, b"gotcha")]);
+        let err = unpack_cpio(&evil, &dest).unwrap_err();
+        assert!(err.to_string().contains("through the symlink"), "{}", err);
+        assert!(!outside.join("pwned").exists());
+
+        // A file entry replacing an earlier link to a file outside must not write into that file
+        fs::write(outside.join("passwd"), "original").unwrap();
+        let passwd = outside.join("passwd");
+        let replaced = newc_cpio(&[("./passwd", 0o120777, passwd.to_str().unwrap().as_bytes()), ("./passwd", 0o100644, b"gotcha")]);
+        unpack_cpio(&replaced, &temp_dir.path().join("dest2")).unwrap();
+        assert_eq!(fs::read_to_string(&passwd).unwrap(), "original");
+        assert_eq!(fs::read_to_string(temp_dir.path().join("dest2/passwd")).unwrap(), "gotcha");
+
+        // Links themselves, absolute ones included, are still unpacked
+        let links = newc_cpio(&[("./usr/bin/python", 0o120777, b"/usr/bin/python3")]);
+        unpack_cpio(&links, &temp_dir.path().join("dest3")).unwrap();
+        assert_eq!(fs::read_link(temp_dir.path().join("dest3/usr/bin/python")).unwrap(), Path::new("/usr/bin/python3"));
+    }
+
+    #[test]
+    fn test_extract_deb_and_rpm_payloads() {
+        use std::io::Write;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let gzip = |bytes: &[u8]| {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(bytes).unwrap();
+            encoder.finish().unwrap()
+        };
+
+        // data.tar.gz holding ./usr/bin/tool, wrapped in an ar archive like dpkg-deb builds
+        let mut data_tar = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(8);
+        header.set_mode(0o755);
+        header.set_cksum();
+        data_tar.append_data(&mut header, "./usr/bin/tool", &b"echo ok\n"[..]).unwrap();
+        let data_tar_gz = gzip(&data_tar.into_inner().unwrap());
+
+        let deb = temp_dir.path().join("tool_1.0_amd64.deb");
+        let mut builder = ar::Builder::new(fs::File::create(&deb).unwrap());
+        for (name, body) in [("debian-binary", b"2.0\n".to_vec()), ("control.tar.gz", gzip(b"")), ("data.tar.gz", data_tar_gz)] {
+            let header = ar::Header::new(name.as_bytes().to_vec(), body.len() as u64);
+            builder.append(&header, body.as_slice()).unwrap();
+        }
+        drop(builder);
+
+        let deb_dest = temp_dir.path().join("deb");
+        fs::create_dir_all(&deb_dest).unwrap();
+        extract_archive_with_output(&deb, &deb_dest, "tool_1.0_amd64.deb", "tool_1.0_amd64.deb", Progress::default()).unwrap();
+        assert_eq!(fs::read_to_string(deb_dest.join("usr/bin/tool")).unwrap(), "echo ok\n");
+        assert!(!deb_dest.join("debian-binary").exists());
+
+        // Lead, empty signature and header, then a gzipped newc cpio payload
+        let cpio = newc_cpio(&[("./usr/bin", 0o040755, b""), ("./usr/bin/tool", 0o100755, b"binary")]);
+        let mut rpm = vec![0xed, 0xab, 0xee, 0xdb];
+        rpm.resize(96, 0);
+        for _ in 0..2 {
+            rpm.extend_from_slice(&[0x8e, 0xad, 0xe8, 0x01, 0, 0, 0, 0]);
+            rpm.extend_from_slice(&[0; 8]);
+        }
+        rpm.extend_from_slice(&gzip(&cpio));
+        let rpm_path = temp_dir.path().join("tool-1.0.x86_64.rpm");
+        fs::write(&rpm_path, rpm).unwrap();
+
+        let rpm_dest = temp_dir.path().join("rpm");
+        fs::create_dir_all(&rpm_dest).unwrap();
+        extract_archive_with_output(&rpm_path, &rpm_dest, "tool-1.0.x86_64.rpm", "tool-1.0.x86_64.rpm", Progress::default()).unwrap();
+        assert_eq!(fs::read_to_string(rpm_dest.join("usr/bin/tool")).unwrap(), "binary");
+    }
+
+    #[test]
+    fn test_cache_limit_evicts_least_recently_used_unreferenced_archive() {
+        use std::time::{Duration, SystemTime};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let module = create_http_module("https://example.com/payload.tar.gz");
+        let referenced = format!("payload-{}", &compute_fetch_hash(&module).unwrap()[..8]);
+        let manifest = SproutManifest { modules: vec![module], environments: None };
+
+        // The referenced cache is the oldest of all and must still survive
+        let now = SystemTime::now();
+        for (name, age) in [(referenced.as_str(), 400), ("old-11111111", 300), ("newer-22222222", 100)] {
+            let dir = temp_dir.path().join("cache/http").join(name);
+            fs::create_dir_all(&dir).unwrap();
+            let file = dir.join("archive.tar.gz");
+            fs::write(&file, vec![0u8; 100]).unwrap();
+            fs::File::options().write(true).open(&file).unwrap()
+                .set_modified(now - Duration::from_secs(age)).unwrap();
+        }
+
+        assert!(enforce_cache_limit(sprout_path, &manifest, 300).unwrap().is_empty());
+
+        let evicted = enforce_cache_limit(sprout_path, &manifest, 250).unwrap();
+        assert_eq!(evicted, ["old-11111111"]);
+        assert!(temp_dir.path().join("cache/http").join(&referenced).exists());
+        assert!(temp_dir.path().join("cache/http/newer-22222222").exists());
+
+        // A cap below the referenced cache alone evicts everything else, but not it
+        let evicted = enforce_cache_limit(sprout_path, &manifest, 0).unwrap();
+        assert_eq!(evicted, ["newer-22222222"]);
+        assert!(temp_dir.path().join("cache/http").join(&referenced).exists());
+    }
+
+    #[test]
+    fn test_github_spec_resolves_matching_release_asset() {
+        const RELEASE: &[u8] = br#"{
+            "tag_name": "v8.7.0",
+            "assets": [
+                {"name": "fd-v8.7.0-aarch64-unknown-linux-gnu.tar.gz", "browser_download_url": "https://github.com/sharkdp/fd/releases/download/v8.7.0/fd-v8.7.0-aarch64-unknown-linux-gnu.tar.gz"},
+                {"name": "fd-v8.7.0-x86_64-unknown-linux-gnu.tar.gz", "browser_download_url": "https://github.com/sharkdp/fd/releases/download/v8.7.0/fd-v8.7.0-x86_64-unknown-linux-gnu.tar.gz"},
+                {"name": "fd-v8.7.0-x86_64-unknown-linux-musl.tar.gz", "browser_download_url": "https://github.com/sharkdp/fd/releases/download/v8.7.0/fd-v8.7.0-x86_64-unknown-linux-musl.tar.gz"}
+            ]
+        }"#;
+        let spec = |asset: &str| crate::ast::GithubSpec {
+            repo: "sharkdp/fd".to_string(),
+            tag: "v8.7.0".to_string(),
+            asset: asset.to_string(),
+            sha256: None,
+        };
+        let api = || {
+            let url = serve_once("repos/sharkdp/fd/releases/tags/v8.7.0", RELEASE);
+            url.trim_end_matches("/repos/sharkdp/fd/releases/tags/v8.7.0").to_string()
+        };
+
+        assert_eq!(
+            resolve_github_asset(&api(), &spec(r"fd-.*-x86_64-unknown-linux-gnu\.tar\.gz")).unwrap(),
+            "https://github.com/sharkdp/fd/releases/download/v8.7.0/fd-v8.7.0-x86_64-unknown-linux-gnu.tar.gz"
+        );
+
+        let err = resolve_github_asset(&api(), &spec(r"fd-.*-x86_64-.*")).unwrap_err().to_string();
+        assert!(err.contains("2 assets"), "{}", err);
+        let err = resolve_github_asset(&api(), &spec(r"fd-.*-windows.*")).unwrap_err().to_string();
+        assert!(err.contains("No asset"), "{}", err);
+    }
+
+    #[test]
+    fn test_github_asset_url_is_remembered_for_cache_checks() {
+        const RELEASE: &[u8] = br#"{"assets": [{"name": "fd.tar.gz", "browser_download_url": "https://github.com/sharkdp/fd/releases/download/v8.7.0/fd.tar.gz"}]}"#;
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let spec = crate::ast::GithubSpec {
+            repo: "sharkdp/fd".to_string(),
+            tag: "v8.7.0".to_string(),
+            asset: r"fd\.tar\.gz".to_string(),
+            sha256: None,
+        };
+        // Answers a single request, so a second lookup must not reach the API
+        let api = serve_once("repos/sharkdp/fd/releases/tags/v8.7.0", RELEASE)
+            .trim_end_matches("/repos/sharkdp/fd/releases/tags/v8.7.0").to_string();
+        let first = github_archive(sprout_path, &api, &spec, false).unwrap();
+        let second = github_archive(sprout_path, &api, &spec, false).unwrap();
+        assert_eq!(first.url, "https://github.com/sharkdp/fd/releases/download/v8.7.0/fd.tar.gz");
+        assert_eq!(second, first);
+
+        let module = |sha256: Option<String>| ModuleBlock {
+            name: "fd".to_string(),
+            fetch: Some(FetchBlock {
+                spec: FetchSpec::Github(crate::ast::GithubSpec { sha256, ..spec.clone() }),
+                output: None,
+                extract: true,
+                discard_git: false,
+                timeout: None,
+            }),
+            ..create_http_module("unused")
+        };
+
+        // The missing pin is computed from the cached release like for http specs
+        let unpinned = module(None);
+        crate::manifest::save_manifest(sprout_path, &SproutManifest { modules: vec![unpinned.clone()], environments: None }).unwrap();
+        let cache_file = http_cache_path(sprout_path, &unpinned, &first);
+        fs::create_dir_all(cache_file.parent().unwrap()).unwrap();
+        fs::write(&cache_file, "fresh").unwrap();
+        let (_, pending) = crate::cli::compute_missing_hashes(sprout_path, &crate::manifest::manifest_path(sprout_path)).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].sha256, format!("{:x}", Sha256::digest(b"fresh")));
+
+        // And a corrupt cached release is found by verify
+        let pinned = module(Some(pending[0].sha256.clone()));
+        let cache_file = http_cache_path(sprout_path, &pinned, &first);
+        fs::create_dir_all(cache_file.parent().unwrap()).unwrap();
+        fs::write(&cache_file, "fresh").unwrap();
+        let manifest = SproutManifest { modules: vec![pinned], environments: None };
+        assert!(verify_caches(sprout_path, &manifest, false).unwrap().is_empty());
+        fs::write(&cache_file, "fre").unwrap();
+        assert_eq!(verify_caches(sprout_path, &manifest, false).unwrap(), vec!["fd"]);
+    }
+
+    #[test]
+    fn test_print_hash_reports_digest_without_touching_manifest() {
+        const BODY: &[u8] = b"release payload";
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let module = create_http_module(&serve_once("payload.tar.gz", BODY));
+        let manifest = SproutManifest { modules: vec![module.clone()], environments: None };
+        crate::manifest::save_manifest(sprout_path, &manifest).unwrap();
+        let before = fs::read_to_string(crate::manifest::manifest_path(sprout_path)).unwrap();
+
+        let hash = print_fetch_hash(sprout_path, &module, &FetchOptions::default()).unwrap();
+
+        assert_eq!(hash, format!("{:x}", Sha256::digest(BODY)));
+        assert_eq!(fs::read_to_string(crate::manifest::manifest_path(sprout_path)).unwrap(), before);
+        assert!(!get_source_path(sprout_path, &module).exists());
+        assert!(!temp_dir.path().join("sprout.lock").exists());
+
+        // The cached archive is reused without another download
+        assert_eq!(print_fetch_hash(sprout_path, &module, &FetchOptions::default()).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_fetch_rsync_mirrors_source_and_logs() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout");
+        let sprout_path = sprout_path.to_str().unwrap();
+        let mirror = temp_dir.path().join("mirror/tool");
+        fs::create_dir_all(&mirror).unwrap();
+        fs::write(mirror.join("VERSION"), "1").unwrap();
+
+        // Stand-in for rsync that records its arguments and copies SRC into DST
+        let args_file = temp_dir.path().join("rsync-args");
+        let shim = temp_dir.path().join("rsync");
+        fs::write(&shim, format!(
+            "#!/bin/sh\necho \"$@\" > {}\nshift 2\nrm -rf \"$2/$(basename \"$1\")\"\ncp -a \"$1\" \"$2\"\necho synced\n",
+            args_file.display()
+        )).unwrap();
+        fs::set_permissions(&shim, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let spec = RsyncSpec { url: mirror.display().to_string() };
+        let mut module = create_http_module("tool");
+        module.fetch = Some(FetchBlock {
+            spec: FetchSpec::Rsync(spec.clone()),
+            output: None,
+            extract: true,
+            discard_git: false,
+            timeout: None,
+        });
+
+        fetch_rsync_with(&shim, sprout_path, &module, &spec, Progress::default()).unwrap();
+        let source = get_source_path(sprout_path, &module);
+        assert!(source.starts_with(Path::new(sprout_path).join("sources/rsync")));
+        assert_eq!(fs::read_to_string(source.join("tool/VERSION")).unwrap(), "1");
+        assert_eq!(
+            fs::read_to_string(&args_file).unwrap().trim(),
+            format!("-a --delete {} {}", mirror.display(), source.display())
+        );
+        let logs: Vec<_> = fs::read_dir(Path::new(sprout_path).join("logs")).unwrap()
+            .map(|e| fs::read_to_string(e.unwrap().path()).unwrap())
+            .collect();
+        assert!(logs.iter().any(|log| log.contains("=== Rsync Fetch Log ===") && log.contains("synced")));
+
+        // The url is part of the fetch hash
+        let mut moved = module.clone();
+        moved.fetch.as_mut().unwrap().spec = FetchSpec::Rsync(RsyncSpec { url: "rsync://mirror/tool".to_string() });
+        assert_ne!(compute_fetch_hash(&module), compute_fetch_hash(&moved));
+    }
+
+    #[test]
+    fn test_fetch_local_tarball_extracts_into_source() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+
+        // Pack tool-1.0/README into a tarball next to the manifest
+        let tarball = temp_dir.path().join("tool-1.0.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(fs::File::create(&tarball).unwrap(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "tool-1.0/README", &b"hello"[..]).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let mut module = create_http_module("unused");
+        module.fetch = Some(FetchBlock {
+            spec: FetchSpec::Local(LocalSpec {
+                path: "tool-1.0.tar.gz".to_string(),
+                sha256: Some("0".repeat(64)),
+            }),
+            output: None,
+            extract: true,
+            discard_git: false,
+            timeout: None,
+        });
+        crate::manifest::save_manifest(sprout_path, &SproutManifest {
+            modules: vec![module.clone()],
+            environments: None,
+        }).unwrap();
+
+        // A wrong checksum is rejected before anything is extracted
+        assert!(fetch_package(sprout_path, &module, &FetchOptions::default()).is_err());
+
+        if let Some(FetchBlock { spec: FetchSpec::Local(local), .. }) = &mut module.fetch {
+            local.sha256 = Some(compute_file_sha256(&tarball).unwrap());
+        }
+        fetch_package(sprout_path, &module, &FetchOptions::default()).unwrap();
+
+        let readme = get_source_path(sprout_path, &module).join("tool-1.0/README");
+        assert_eq!(fs::read_to_string(readme).unwrap(), "hello");
+    }
+
+    /// Fetch a local file with the given `output`/`extract` settings and return the source dir
+    fn fetch_local_file(sprout_path: &str, file: &str, output: Option<&str>, extract: bool) -> PathBuf {
+        let mut module = create_http_module("unused");
+        module.fetch = Some(FetchBlock {
+            spec: FetchSpec::Local(LocalSpec { path: file.to_string(), sha256: None }),
+            output: output.map(str::to_string),
+            extract,
+            discard_git: false,
+            timeout: None,
+        });
+        crate::manifest::save_manifest(sprout_path, &SproutManifest {
+            modules: vec![module.clone()],
+            environments: None,
+        }).unwrap();
+        fetch_package(sprout_path, &module, &FetchOptions::default()).unwrap();
+        get_source_path(sprout_path, &module)
+    }
+
+    #[test]
+    fn test_fetch_output_names_decompressed_single_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+
+        let mut encoder = flate2::write::GzEncoder::new(
+            fs::File::create(temp_dir.path().join("tool-linux64.gz")).unwrap(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(b"#!/bin/sh\necho tool\n").unwrap();
+        encoder.finish().unwrap();
+
+        let source = fetch_local_file(sprout_path, "tool-linux64.gz", Some("tool"), true);
+        assert_eq!(fs::read_to_string(source.join("tool")).unwrap(), "#!/bin/sh\necho tool\n");
+        assert!(!source.join("tool-linux64.gz").exists());
+    }
+
+    #[test]
+    fn test_fetch_extract_false_keeps_archive_and_output_does_not_skip_extraction() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+
+        let tarball = temp_dir.path().join("tool-1.0.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(fs::File::create(&tarball).unwrap(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "tool-1.0/README", &b"hello"[..]).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let source = fetch_local_file(sprout_path, "tool-1.0.tar.gz", None, false);
+        assert_eq!(fs::read(source.join("tool-1.0.tar.gz")).unwrap(), fs::read(&tarball).unwrap());
+        assert!(!source.join("tool-1.0").exists());
+
+        // `output` on a multi-file archive no longer turns extraction off
+        let source = fetch_local_file(sprout_path, "tool-1.0.tar.gz", Some("tool.tar.gz"), true);
+        assert_eq!(fs::read_to_string(source.join("tool-1.0/README")).unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_fetch_all_summarizes_outcomes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        fs::create_dir_all(temp_dir.path().join("vendor/present")).unwrap();
+        fs::write(temp_dir.path().join("vendor/present/file"), "x").unwrap();
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module good {
+    depends_on = []
+    fetch {
+        local = {
+            path = "vendor/present"
+        }
+    }
+}
+module bad {
+    depends_on = []
+    fetch {
+        local = {
+            path = "vendor/missing"
+        }
+    }
+}
+module meta { depends_on = [good] }
+"#).unwrap();
+        let manifest = load_manifest(sprout_path).unwrap();
+
+        let summary = fetch_all(sprout_path, &manifest, &FetchOptions::default(), false).unwrap();
+        assert_eq!(summary, FetchSummary {
+            succeeded: 1,
+            failed: vec!["bad".to_string()],
+            skipped: 1,
+        });
+
+        assert!(fetch_all(sprout_path, &manifest, &FetchOptions::default(), true).is_err());
+    }
+
+    #[test]
+    fn test_fetch_from_lock_checks_out_recorded_commit() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout");
+        let sprout_path = sprout_path.to_str().unwrap();
+        let repo = temp_dir.path().join("upstream");
+        fs::create_dir_all(&repo).unwrap();
+        fs::create_dir_all(sprout_path).unwrap();
+
+        let commit = |content: &str| {
+            fs::write(repo.join("VERSION"), content).unwrap();
+            for args in [
+                &["add", "VERSION"][..],
+                &["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "-q", "-m", content][..],
+            ] {
+                assert!(Command::new("git").current_dir(&repo).args(args).status().unwrap().success());
+            }
+        };
+        assert!(Command::new("git").current_dir(&repo).args(["init", "-q"]).status().unwrap().success());
+        commit("1");
+
+        let module = ModuleBlock {
+            fetch: Some(FetchBlock {
+                spec: FetchSpec::Git(GitSpec { url: repo.display().to_string(), ref_: None, recursive: false }),
+                output: None,
+                extract: true,
+                discard_git: false,
+                timeout: None,
+            }),
+            ..create_http_module("unused")
+        };
+        crate::manifest::save_manifest(sprout_path, &SproutManifest {
+            modules: vec![module.clone()],
+            environments: None,
+        }).unwrap();
+
+        // Without a recorded commit --from-lock refuses to guess
+        assert!(fetch_package(sprout_path, &module, &FetchOptions { from_lock: true, ..Default::default() }).is_err());
+
+        fetch_package(sprout_path, &module, &FetchOptions::default()).unwrap();
+        let locked = SproutLock::load(sprout_path).unwrap()
+            .get_module_state("payload").unwrap()
+            .resolved_commit.clone().unwrap();
+
+        // Upstream moves on, but --from-lock reproduces the recorded commit
+        commit("2");
+        fetch_package(sprout_path, &module, &FetchOptions { force: true, from_lock: true, ..Default::default() }).unwrap();
+        let source = get_source_path(sprout_path, &module);
+        assert_eq!(fs::read_to_string(source.join("VERSION")).unwrap(), "1");
+        assert_eq!(git_head(&source).unwrap(), locked);
+
+        fetch_package(sprout_path, &module, &FetchOptions { force: true, ..Default::default() }).unwrap();
+        assert_eq!(fs::read_to_string(source.join("VERSION")).unwrap(), "2");
+    }
+
+    #[test]
+    fn test_fetch_git_reuses_unchanged_checkout() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout");
+        let sprout_path = sprout_path.to_str().unwrap();
+        let repo = temp_dir.path().join("upstream");
+        fs::create_dir_all(&repo).unwrap();
+
+        let git = |args: &[&str]| {
+            let output = Command::new("git").current_dir(&repo).args(args).output().unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+        };
+        fs::create_dir_all(sprout_path).unwrap();
+        git(&["init", "-q", "-b", "main"]);
+        fs::write(repo.join("VERSION"), "1").unwrap();
+        git(&["add", "VERSION"]);
+        git(&["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "-q", "-m", "1"]);
+
+        let module = ModuleBlock {
+            fetch: Some(FetchBlock {
+                spec: FetchSpec::Git(GitSpec { url: repo.display().to_string(), ref_: Some("main".to_string()), recursive: false }),
+                output: None,
+                extract: true,
+                discard_git: false,
+                timeout: None,
+            }),
+            ..create_http_module("unused")
+        };
+        crate::manifest::save_manifest(sprout_path, &SproutManifest {
+            modules: vec![module.clone()],
+            environments: None,
+        }).unwrap();
+
+        fetch_package(sprout_path, &module, &FetchOptions::default()).unwrap();
+        let source = get_source_path(sprout_path, &module);
+        let marker = source.join("build-marker");
+        fs::write(&marker, "kept").unwrap();
+
+        // Same ref, same commit, clean tree: the checkout is reused
+        fetch_package(sprout_path, &module, &FetchOptions::default()).unwrap();
+        assert!(marker.exists(), "unchanged ref must not reclone");
+
+        // The branch moved upstream: the old checkout is no longer current
+        fs::write(repo.join("VERSION"), "2").unwrap();
+        git(&["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "-q", "-am", "2"]);
+        fetch_package(sprout_path, &module, &FetchOptions::default()).unwrap();
+        assert!(!marker.exists(), "moved branch must be fetched again");
+        assert_eq!(fs::read_to_string(source.join("VERSION")).unwrap(), "2");
+        fs::write(&marker, "kept").unwrap();
+
+        // A modified tracked file forces a fresh clone
+        fs::write(source.join("VERSION"), "edited").unwrap();
+        fetch_package(sprout_path, &module, &FetchOptions::default()).unwrap();
+        assert!(!marker.exists());
+        assert_eq!(fs::read_to_string(source.join("VERSION")).unwrap(), "2");
+    }
+
+    #[test]
+    fn test_fetch_git_discard_git_records_commit_and_drops_metadata() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout");
+        let sprout_path = sprout_path.to_str().unwrap();
+        let repo = temp_dir.path().join("upstream");
+        fs::create_dir_all(&repo).unwrap();
+
+        let git = |args: &[&str]| {
+            let output = Command::new("git").current_dir(&repo).args(args).output().unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        fs::create_dir_all(sprout_path).unwrap();
+        git(&["init", "-q", "-b", "main"]);
+        fs::write(repo.join("VERSION"), "1").unwrap();
+        git(&["add", "VERSION"]);
+        git(&["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "-q", "-m", "1"]);
+        let head = git(&["rev-parse", "HEAD"]);
+
+        let module = ModuleBlock {
+            fetch: Some(FetchBlock {
+                spec: FetchSpec::Git(GitSpec { url: repo.display().to_string(), ref_: Some("main".to_string()), recursive: false }),
+                output: None,
+                extract: true,
+                discard_git: true,
+                timeout: None,
+            }),
+            ..create_http_module("unused")
+        };
+        crate::manifest::save_manifest(sprout_path, &SproutManifest {
+            modules: vec![module.clone()],
+            environments: None,
+        }).unwrap();
+
+        fetch_package(sprout_path, &module, &FetchOptions::default()).unwrap();
+        let source = get_source_path(sprout_path, &module);
+        assert_eq!(fs::read_to_string(source.join("VERSION")).unwrap(), "1");
+        assert!(!source.join(".git").exists());
+        assert_eq!(fs::read_to_string(source.join(".sprout-commit")).unwrap().trim(), head);
+        let recorded = || SproutLock::load(sprout_path).unwrap()
+            .get_module_state(&module.id()).unwrap()
+            .resolved_commit.clone();
+        assert_eq!(recorded().as_deref(), Some(head.as_str()));
+
+        // A second fetch keeps the plain checkout and the recorded commit
+        let marker = source.join("build-marker");
+        fs::write(&marker, "kept").unwrap();
+        fetch_package(sprout_path, &module, &FetchOptions::default()).unwrap();
+        assert!(marker.exists(), "discarded checkout must not reclone");
+        assert_eq!(recorded().as_deref(), Some(head.as_str()));
+
+        // A checkout whose marker names another commit is not the expected one
+        fs::write(source.join(".sprout-commit"), "0".repeat(40)).unwrap();
+        fetch_package(sprout_path, &module, &FetchOptions::default()).unwrap();
+        assert!(!marker.exists(), "stale checkout must be recloned");
+        assert_eq!(fs::read_to_string(source.join(".sprout-commit")).unwrap().trim(), head);
+    }
+
+    #[test]
+    fn test_update_fetches_full_history_for_update_script() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout");
+        let sprout_path = sprout_path.to_str().unwrap();
+        let repo = temp_dir.path().join("upstream");
+        fs::create_dir_all(&repo).unwrap();
+        fs::create_dir_all(sprout_path).unwrap();
+
+        let git = |dir: &Path, args: &[&str]| {
+            let output = Command::new("git").current_dir(dir).args(args).output().unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        git(&repo, &["init", "-q", "-b", "main"]);
+        for version in ["1", "2"] {
+            fs::write(repo.join("VERSION"), version).unwrap();
+            git(&repo, &["add", "VERSION"]);
+            git(&repo, &["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "-q", "-m", version]);
+            if version == "1" {
+                git(&repo, &["tag", "v1.0"]);
+            }
+        }
+
+        let described = temp_dir.path().join("described");
+        let module = ModuleBlock {
+            fetch: Some(FetchBlock {
+                // Local paths ignore --depth; file:// gives a real shallow clone
+                spec: FetchSpec::Git(GitSpec { url: format!("file://{}", repo.display()), ref_: Some("main".to_string()), recursive: false }),
+                output: None,
+                extract: true,
+                discard_git: false,
+                timeout: None,
+            }),
+            update: Some(ScriptBlock {
+                env: vec![],
+                commands: vec![format!("git describe --tags > {}", described.display())],
+                timeout: None,
+            }),
+            ..create_http_module("unused")
+        };
+        crate::manifest::save_manifest(sprout_path, &SproutManifest {
+            modules: vec![module.clone()],
+            environments: None,
+        }).unwrap();
+
+        fetch_package(sprout_path, &module, &FetchOptions::default()).unwrap();
+        let source = get_source_path(sprout_path, &module);
+        assert_eq!(git(&source, &["rev-parse", "--is-shallow-repository"]), "true");
+
+        update_package(sprout_path, &module, false, Progress::default()).unwrap();
+        assert_eq!(git(&source, &["rev-parse", "--is-shallow-repository"]), "false");
+        assert!(fs::read_to_string(&described).unwrap().starts_with("v1.0-1-g"));
+
+        // Without a source the update clones the full history directly
+        fs::remove_dir_all(&source).unwrap();
+        update_package(sprout_path, &module, false, Progress::default()).unwrap();
+        assert_eq!(git(&source, &["rev-parse", "--is-shallow-repository"]), "false");
+
+        let err = update_package(sprout_path, &create_http_module("plain"), false, Progress::default()).unwrap_err();
+        assert!(err.to_string().contains("has no update block"), "{}", err);
+    }
+
+    #[test]
+    fn test_fetch_git_at_tag_and_commit() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout");
+        let sprout_path = sprout_path.to_str().unwrap();
+        let repo = temp_dir.path().join("upstream");
+        fs::create_dir_all(&repo).unwrap();
+
+        let git = |args: &[&str]| {
+            let output = Command::new("git").current_dir(&repo).args(args).output().unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        let commit = |content: &str| {
+            fs::write(repo.join("VERSION"), content).unwrap();
+            git(&["add", "VERSION"]);
+            git(&["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "-q", "-m", content]);
+        };
+        git(&["init", "-q"]);
+        commit("1");
+        git(&["tag", "v1"]);
+        let first = git(&["rev-parse", "HEAD"]);
+        commit("2");
+
+        for ref_ in ["v1", first.as_str()] {
+            let spec = GitSpec {
+                url: repo.display().to_string(),
+                ref_: Some(ref_.to_string()),
+                recursive: false,
+            };
+            let module = ModuleBlock {
+                fetch: Some(FetchBlock { spec: FetchSpec::Git(spec.clone()), output: None, extract: true, discard_git: false, timeout: None }),
+                ..create_http_module("unused")
+            };
+            fetch_git(sprout_path, &module, &spec, None, false, Progress::default()).unwrap();
+            let source = get_source_path(sprout_path, &module);
+            assert_eq!(fs::read_to_string(source.join("VERSION")).unwrap(), "1", "ref {}", ref_);
+            assert_eq!(git_head(&source).unwrap(), first);
+        }
+
+        assert!(is_commit_sha(&first));
+        assert!(!is_commit_sha(&first[..7]));
+        assert!(!is_commit_sha("v1"));
+        assert!(!is_commit_sha("deadbee-branch"));
+
+        // An abbreviated commit cannot be fetched shallowly; it fails before cloning anything
+        let spec = GitSpec { url: repo.display().to_string(), ref_: Some(first[..7].to_string()), recursive: false };
+        let module = ModuleBlock {
+            fetch: Some(FetchBlock { spec: FetchSpec::Git(spec.clone()), output: None, extract: true, discard_git: false, timeout: None }),
+            ..create_http_module("unused")
+        };
+        let err = fetch_git(sprout_path, &module, &spec, None, false, Progress::default()).unwrap_err().to_string();
+        assert!(err.contains("abbreviated commit"), "{}", err);
+        assert!(!get_source_path(sprout_path, &module).exists());
+    }
+
+    #[test]
+    fn test_fetch_with_ref_overrides_manifest_ref() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout");
+        let sprout_path = sprout_path.to_str().unwrap();
+        let repo = temp_dir.path().join("upstream");
+        fs::create_dir_all(&repo).unwrap();
+
+        let git = |args: &[&str]| {
+            let output = Command::new("git").current_dir(&repo).args(args).output().unwrap();
+            assert!(output.status.success(), "git {:?} failed", args);
+        };
+        git(&["init", "-q", "-b", "main"]);
+        for (version, tag) in [("1", "v1"), ("2", "v2")] {
+            fs::write(repo.join("VERSION"), version).unwrap();
+            git(&["add", "VERSION"]);
+            git(&["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "-q", "-m", version]);
+            git(&["tag", tag]);
+        }
+
+        let spec = GitSpec { url: repo.display().to_string(), ref_: Some("v1".to_string()), recursive: false };
+        let module = ModuleBlock {
+            fetch: Some(FetchBlock { spec: FetchSpec::Git(spec), output: None, extract: true, discard_git: false, timeout: None }),
+            ..create_http_module("unused")
+        };
+
+        let source = fetch_with_ref(sprout_path, &module, "v2", &FetchOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string(source.join("VERSION")).unwrap(), "2");
+
+        // The pinned ref keeps its own source dir and no state is recorded
+        assert_ne!(source, get_source_path(sprout_path, &module));
+        assert!(SproutLock::load(sprout_path).unwrap().get_module_state(&module.id()).is_none());
+
+        let err = fetch_with_ref(sprout_path, &create_http_module("http://127.0.0.1:9/x"), "v2", &FetchOptions::default()).unwrap_err();
+        assert!(err.to_string().contains("only applies to git modules"));
+    }
+
+    #[test]
+    fn test_remove_incomplete_dist() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dist_path = temp_dir.path().join("dist/half-built");
+        fs::create_dir_all(dist_path.join("bin")).unwrap();
+        fs::write(dist_path.join("bin/partial"), "truncated").unwrap();
+
+        remove_incomplete_dist(&dist_path).unwrap();
+        assert!(!dist_path.exists());
+
+        // Cleaning up an already-missing dist is a no-op
+        remove_incomplete_dist(&dist_path).unwrap();
+    }
+
+    #[test]
+    fn test_latest_remote_tag_detects_newer_version() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = temp_dir.path();
+        let git = |args: &[&str]| {
+            let status = Command::new("git").current_dir(repo).args(args).status().unwrap();
+            assert!(status.success(), "git {:?} failed", args);
+        };
+
+        git(&["init", "-q"]);
+        git(&["-c", "user.name=test", "-c", "user.email=test@example.com", "commit", "-q", "--allow-empty", "-m", "init"]);
+        for tag in ["v1.2.0", "v1.10.0", "v1.9.3", "nightly"] {
+            git(&["tag", tag]);
+        }
+
+        let latest = latest_remote_tag(repo.to_str().unwrap()).unwrap();
+        assert_eq!(latest.as_deref(), Some("v1.10.0"));
+        assert!(is_newer_version("v1.10.0", "v1.2.0"));
+        assert!(!is_newer_version("v1.10.0", "v1.10.0"));
+        assert!(!is_newer_version("nightly", "v1.2.0"));
+        assert!(is_version_ref("v1.2.0"));
+        assert!(is_version_ref("release-14.0"));
+        assert!(!is_version_ref("main"));
+        assert!(!is_version_ref("0123456789abcdef0123456789abcdef01234567"));
+
+        // An unreachable remote is an error, not a panic
+        assert!(latest_remote_tag(temp_dir.path().join("missing").to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_build_script_exports_jobs() {
+        let module = create_module_producing(&["make -j${SPROUT_JOBS}"]);
+        let build = module.build.as_ref().unwrap();
+
+        let script = render_build_script("/sprout", &module, build, 3, &ResourceLimits::default());
+        assert!(script.contains("export SPROUT_JOBS='3'\n"), "unexpected script:\n{}", script);
+        assert!(script.contains(&format!("export SPROUT_NPROC='{}'\n", build_jobs(None))));
+        assert!(build_jobs(None) >= 1);
+        assert_eq!(build_jobs(Some(5)), 5);
+
+        // The jobs value is ephemeral and never part of the build hash
+        let hash = compute_build_hash(&module);
+        render_build_script("/sprout", &module, build, 64, &ResourceLimits::default());
+        assert_eq!(compute_build_hash(&module), hash);
+    }
+
+    #[test]
+    fn test_build_script_applies_resource_limits() {
+        let module = create_module_producing(&["make"]);
+        let build = module.build.as_ref().unwrap();
+        let limits = ResourceLimits { memory_kb: Some(parse_memory_limit("4G").unwrap()), niceness: Some(10) };
+
+        let script = render_build_script("/sprout", &module, build, 2, &limits);
+        assert!(script.starts_with("set -e\nulimit -v 4194304\nrenice -n 10 -p $$ > /dev/null\n"), "unexpected script:\n{}", script);
+        assert!(!render_build_script("/sprout", &module, build, 2, &ResourceLimits::default()).contains("ulimit"));
+
+        assert_eq!(parse_memory_limit("512M").unwrap(), 512 * 1024);
+        assert_eq!(parse_memory_limit("2048").unwrap(), 2048);
+        assert!(parse_memory_limit("4T").is_err());
+        assert!(parse_memory_limit("0").is_err());
+    }
+
+    #[test]
+    fn test_quiet_disables_progress() {
+        let quiet = Progress { quiet: true, plain: false };
+        assert!(!quiet.bars());
+        assert!(!Progress { quiet: true, plain: true }.lines());
+        assert!(Progress { quiet: false, plain: true }.lines());
+    }
+
+    #[test]
+    fn test_dry_run_shows_isolated_invocation() {
+        let mut module = create_module_producing(&["make"]);
+        let build = module.build.clone().unwrap();
+        let options = BuildOptions { dry_run: true, ..Default::default() };
+        assert!(!render_dry_run_build("/sprout", &module, &build, &options).contains("env -i"));
+
+        module.isolated = true;
+        let shown = render_dry_run_build("/sprout", &module, &build, &options);
+        let invocation = shown.lines().next().unwrap();
+        assert!(invocation.contains(&format!("env -i PATH={} bash --norc --noprofile", ISOLATED_PATH)), "{}", shown);
+        assert!(shown.ends_with("make\n"), "{}", shown);
+
+        // --isolated-env isolates every module the same way
+        module.isolated = false;
+        let options = BuildOptions { isolated_env: true, ..options };
+        assert!(render_dry_run_build("/sprout", &module, &build, &options).contains("env -i"));
+    }
+
+    #[test]
+    fn test_render_build_script_with_env() {
+        let mut module = create_module_producing(&["./configure --prefix=${DIST_PATH}", "make -j${SPROUT_JOBS} install"]);
+        module.build.as_mut().unwrap().env = vec![
+            ("CC".to_string(), "${SPROUT_DIST}/gcc/bin/gcc".to_string()),
+            ("CFLAGS".to_string(), "-O2".to_string()),
+        ];
+
+        let script = render_build_script("/sprout", &module, module.build.as_ref().unwrap(), 4, &ResourceLimits::default());
+        let expected = format!(
+            "set -e\n\
+             export SPROUT_DIST='/sprout/dist'\n\
+             export DIST_PATH='/sprout/dist/foo'\n\
+             export SOURCE_PATH='/sprout/sources/archive/foo-no-fetch'\n\
+             export SPROUT_JOBS='4'\n\
+             export SPROUT_NPROC='{}'\n\
+             export CC=\"${{SPROUT_DIST}}/gcc/bin/gcc\"\n\
+             export CFLAGS=\"-O2\"\n\
+             ./configure --prefix=${{DIST_PATH}}\n\
+             make -j${{SPROUT_JOBS}} install\n",
+            build_jobs(None)
+        );
+        assert_eq!(script, expected);
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Append-only record of every build script run, one JSON object per line
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+/// Combined one-line-per-event log of all builds, for `tail -f` during `--all`
+pub const ACTIVITY_LOG: &str = "logs/sprout.log";
+
+/// Size at which the activity log is rotated to `sprout.log.1`
+const ACTIVITY_LOG_MAX_BYTES: u64 = 1024 * 1024;
+
+/// One finished (or failed) run of a module's build script
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildRecord {
+    pub module: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub success: bool,
+    pub duration_secs: f64,
+    pub log: Option<PathBuf>,
+    pub build_hash: Option<String>,
+}
+
+/// Append `record` to the sprout dir's history file
+pub fn append_build_record(sprout_path: &str, record: &BuildRecord) -> Result<()> {
+    let path = Path::new(sprout_path).join(HISTORY_FILE);
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open build history: {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// Append a timestamped line such as `start llvm` to the activity log,
+/// rotating it once it outgrows `ACTIVITY_LOG_MAX_BYTES`
+pub fn append_activity(sprout_path: &str, event: &str) -> Result<()> {
+    let path = Path::new(sprout_path).join(ACTIVITY_LOG);
+    if let Some(logs_dir) = path.parent() {
+        fs::create_dir_all(logs_dir)?;
+    }
+    if fs::metadata(&path).is_ok_and(|m| m.len() >= ACTIVITY_LOG_MAX_BYTES) {
+        fs::rename(&path, path.with_extension("log.1"))?;
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open activity log: {}", path.display()))?;
+    writeln!(file, "{} {}", chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ"), event)?;
+    Ok(())
+}
+
+/// Read the build history, oldest first, optionally only for one module id
+pub fn read_build_history(sprout_path: &str, module: Option<&str>) -> Result<Vec<BuildRecord>> {
+    let path = Path::new(sprout_path).join(HISTORY_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read build history: {}", path.display()))?;
+
+    let mut records = Vec::new();
+    for (i, line) in content.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let record: BuildRecord = serde_json::from_str(line)
+            .with_context(|| format!("Malformed entry on line {} of {}", i + 1, path.display()))?;
+        if module.is_none_or(|m| record.module == m) {
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::deps::{build_package, BuildOptions};
+    use crate::parser::parse_manifest;
+
+    #[test]
+    fn test_build_appends_history_record() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest = parse_manifest(r#"
+module tool {
+    depends_on = []
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+module broken {
+    depends_on = []
+    build {
+        exit 1
+    }
+}
+"#).unwrap();
+
+        let tool = manifest.find_module("tool").unwrap();
+        build_package(sprout_path, tool, &BuildOptions::default()).unwrap();
+        assert!(build_package(sprout_path, manifest.find_module("broken").unwrap(), &BuildOptions::default()).is_err());
+
+        let content = fs::read_to_string(temp_dir.path().join(HISTORY_FILE)).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let json: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        for key in ["module", "timestamp", "success", "duration_secs", "log", "build_hash"] {
+            assert!(json.get(key).is_some(), "missing {} in {}", key, lines[0]);
+        }
+
+        let history = read_build_history(sprout_path, Some("tool")).unwrap();
+        assert_eq!(history.len(), 1);
+        assert!(history[0].success);
+        assert_eq!(history[0].build_hash, crate::core::deps::compute_build_hash(tool));
+        assert!(history[0].log.as_ref().is_some_and(|log| log.exists()));
+
+        let all = read_build_history(sprout_path, None).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].module, "broken");
+        assert!(!all[1].success);
+    }
+
+    #[test]
+    fn test_builds_append_events_to_activity_log() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+        let manifest = parse_manifest(r#"
+module zlib {
+    depends_on = []
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+module cmake {
+    depends_on = []
+    build {
+        mkdir -p ${DIST_PATH}
+    }
+}
+"#).unwrap();
+
+        for id in ["zlib", "cmake"] {
+            build_package(sprout_path, manifest.find_module(id).unwrap(), &BuildOptions::default()).unwrap();
+        }
+
+        let content = fs::read_to_string(temp_dir.path().join(ACTIVITY_LOG)).unwrap();
+        let events: Vec<Vec<&str>> = content.lines()
+            .map(|line| line.split_whitespace().skip(1).take(2).collect())
+            .collect();
+        assert_eq!(events, vec![
+            vec!["start", "zlib"],
+            vec!["finish", "zlib"],
+            vec!["start", "cmake"],
+            vec!["finish", "cmake"],
+        ], "{}", content);
+
+        // Already up to date: nothing runs, nothing is logged
+        build_package(sprout_path, manifest.find_module("zlib").unwrap(), &BuildOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string(temp_dir.path().join(ACTIVITY_LOG)).unwrap(), content);
+    }
+}
@@ -1,9 +1,13 @@
 pub mod deps;
+pub mod history;
 pub mod symlinks;
+pub mod util;
 
 // Re-export commonly used functions
 pub use deps::*;
+pub use history::*;
 pub use symlinks::*;
+pub use util::*;
 
 use anyhow::{Context, Result};
 use std::fs;
@@ -20,8 +24,33 @@ const AI_MODEL_ID: &str = "global.anthropic.claude-haiku-4-5-20251001-v1:0";
 const AI_AWS_PROFILE: &str = "my-aws-bedrock";
 const AI_AWS_REGION: &str = "us-east-1";
 
+/// File in the sprout directory that overrides the AI commit prompt
+pub const COMMIT_TEMPLATE_FILE: &str = "commit_template.txt";
+
+const DEFAULT_COMMIT_TEMPLATE: &str = "Generate a concise git commit message for the following changes. \
+    Return ONLY the commit message, no explanations or quotes.\n\n{diff}";
+
+/// Build the AI commit prompt from `template` (or `commit_template.txt` in the
+/// sprout dir, or the built-in text) by substituting `{diff}`
+pub fn build_commit_prompt<P: AsRef<Path>>(sprout_path: P, template: Option<&Path>, diff: &str) -> Result<String> {
+    let default_path = sprout_path.as_ref().join(COMMIT_TEMPLATE_FILE);
+    let template_path = template.or_else(|| default_path.exists().then_some(default_path.as_path()));
+
+    let template = match template_path {
+        Some(path) => fs::read_to_string(path)
+            .with_context(|| format!("Failed to read commit template {}", path.display()))?,
+        None => DEFAULT_COMMIT_TEMPLATE.to_string(),
+    };
+
+    if !template.contains("{diff}") {
+        return Err(anyhow::anyhow!("Commit template must contain a {{diff}} placeholder"));
+    }
+
+    Ok(template.replace("{diff}", diff))
+}
+
 /// Generate commit message using AWS Bedrock
-async fn generate_commit_message<P: AsRef<Path>>(sprout_path: P) -> Result<String> {
+async fn generate_commit_message<P: AsRef<Path>>(sprout_path: P, template: Option<&Path>) -> Result<String> {
     let sprout_path = sprout_path.as_ref();
     
     // Get git diff
@@ -37,6 +66,8 @@ async fn generate_commit_message<P: AsRef<Path>>(sprout_path: P) -> Result<Strin
         return Err(anyhow::anyhow!("No staged changes to commit"));
     }
     
+    let prompt = build_commit_prompt(sprout_path, template, &diff)?;
+
     // Set up AWS Bedrock client
     let sdk_config = aws_config::defaults(BehaviorVersion::latest())
         .region(Region::new(AI_AWS_REGION.to_owned()))
@@ -45,13 +76,6 @@ async fn generate_commit_message<P: AsRef<Path>>(sprout_path: P) -> Result<Strin
         .await;
     let client = Client::new(&sdk_config);
     
-    // Create prompt
-    let prompt = format!(
-        "Generate a concise git commit message for the following changes. \
-        Return ONLY the commit message, no explanations or quotes.\n\n{}",
-        diff
-    );
-    
     let user_message = Message::builder()
         .role(ConversationRole::User)
         .content(ContentBlock::Text(prompt))
@@ -124,8 +148,62 @@ pub fn git_commit<P: AsRef<Path>>(sprout_path: P, message: &str) -> Result<()> {
     Ok(())
 }
 
+/// Whether the sprout repository already has a tag called `name`
+pub fn git_tag_exists<P: AsRef<Path>>(sprout_path: P, name: &str) -> Result<bool> {
+    let status = std::process::Command::new("git")
+        .current_dir(sprout_path.as_ref())
+        .args(["rev-parse", "--quiet", "--verify", &format!("refs/tags/{}", name)])
+        .stdout(std::process::Stdio::null())
+        .status()
+        .context("Failed to execute git rev-parse")?;
+    Ok(status.success())
+}
+
+/// Create an annotated tag at HEAD, using `message` or else the HEAD commit message
+pub fn git_tag<P: AsRef<Path>>(sprout_path: P, name: &str, message: Option<&str>, force: bool) -> Result<()> {
+    let sprout_path = sprout_path.as_ref();
+
+    if !sprout_path.join(".git").exists() {
+        return Err(anyhow::anyhow!("Not a git repository"));
+    }
+
+    let message = match message {
+        Some(message) => message.to_string(),
+        None => {
+            let output = std::process::Command::new("git")
+                .current_dir(sprout_path)
+                .args(["log", "-1", "--format=%B"])
+                .output()
+                .context("Failed to read HEAD commit message")?;
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        }
+    };
+
+    let mut args = vec!["tag", "-a", name, "-m", &message];
+    if force {
+        args.push("--force");
+    }
+
+    let output = std::process::Command::new("git")
+        .current_dir(sprout_path)
+        .args(&args)
+        .output()
+        .context("Failed to execute git tag")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("already exists") {
+            return Err(anyhow::anyhow!("Tag '{}' already exists (use --force-tag to move it)", name));
+        }
+        return Err(anyhow::anyhow!("git tag failed: {}", stderr));
+    }
+
+    info!("Created git tag: {}", name);
+    Ok(())
+}
+
 /// Create a git commit with AI-generated message
-pub async fn git_commit_ai<P: AsRef<Path>>(sprout_path: P) -> Result<()> {
+pub async fn git_commit_ai<P: AsRef<Path>>(sprout_path: P, template: Option<&Path>) -> Result<()> {
     let sprout_path = sprout_path.as_ref();
     
     // Check if git repo exists
@@ -149,7 +227,7 @@ pub async fn git_commit_ai<P: AsRef<Path>>(sprout_path: P) -> Result<()> {
     
     // Generate commit message
     info!("Generating commit message with AI...");
-    let message = generate_commit_message(sprout_path).await?;
+    let message = generate_commit_message(sprout_path, template).await?;
     info!("Generated message: {}", message);
     
     // Commit with generated message (without staging again)
@@ -201,8 +279,35 @@ pub fn init_sprout_from_git<P: AsRef<Path>>(path: P, git_url: &str) -> Result<()
     Ok(())
 }
 
+/// Manifests bundled for `sprout init --template <name>`
+pub const INIT_TEMPLATES: &[(&str, &str)] = &[
+    ("default", include_str!("../templates/default_manifest.sprout")),
+    ("minimal", include_str!("../templates/minimal.sprout")),
+    ("rust-tools", include_str!("../templates/rust_tools.sprout")),
+    ("c-toolchain", include_str!("../templates/c_toolchain.sprout")),
+];
+
+/// Content of the bundled init template `name`
+pub fn init_template(name: &str) -> Result<&'static str> {
+    INIT_TEMPLATES.iter()
+        .find(|(template, _)| *template == name)
+        .map(|(_, content)| *content)
+        .ok_or_else(|| anyhow::anyhow!(
+            "Unknown template '{}'. Available templates: {}",
+            name,
+            INIT_TEMPLATES.iter().map(|(template, _)| *template).collect::<Vec<_>>().join(", ")
+        ))
+}
+
 pub fn init_sprout<P: AsRef<Path>>(path: P, empty: bool) -> Result<()> {
+    init_sprout_with_template(path, (!empty).then_some("default"))
+}
+
+/// Initialize a sprout directory whose manifest starts as the bundled
+/// `template`, or empty for `None`
+pub fn init_sprout_with_template<P: AsRef<Path>>(path: P, template: Option<&str>) -> Result<()> {
     let sprout_path = path.as_ref();
+    let template_content = template.map(init_template).transpose()?.unwrap_or("");
 
     info!("Initializing sprout directory at: {}", sprout_path.display());
 
@@ -217,12 +322,7 @@ pub fn init_sprout<P: AsRef<Path>>(path: P, empty: bool) -> Result<()> {
     // Create manifest.sprout
     let manifest_path = sprout_path.join("manifest.sprout");
     if !manifest_path.exists() {
-        if empty {
-            fs::write(&manifest_path, "")?;
-        } else {
-            let default_manifest = include_str!("../templates/default_manifest.sprout");
-            fs::write(&manifest_path, default_manifest)?;
-        }
+        fs::write(&manifest_path, template_content)?;
     }
 
     // Create empty sprout.lock
@@ -239,6 +339,7 @@ dist/
 cache/
 sources/
 logs/
+history.jsonl
 
 # Keep symlinks and manifest
 !symlinks/
@@ -272,6 +373,52 @@ logs/
     Ok(())
 }
 
+/// Files and directories that make up a portable sprout setup; build
+/// artifacts (dist, cache, sources) are rebuilt from these on the new machine
+const BUNDLE_ENTRIES: &[&str] = &["manifest.sprout", "sprout.lock", "symlinks"];
+
+/// Bundle the manifest, lockfile and tracked symlinks into a .tar.gz
+pub fn export_bundle<P: AsRef<Path>>(sprout_path: P, bundle: &Path) -> Result<()> {
+    let sprout_path = sprout_path.as_ref();
+    let file = fs::File::create(bundle)
+        .with_context(|| format!("Failed to create bundle: {}", bundle.display()))?;
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(file, flate2::Compression::default()));
+
+    for entry in BUNDLE_ENTRIES {
+        let path = sprout_path.join(entry);
+        if path.is_dir() {
+            builder.append_dir_all(entry, &path)?;
+        } else if path.is_file() {
+            builder.append_path_with_name(&path, entry)?;
+        }
+    }
+
+    builder.into_inner()?.finish()?;
+    info!("Exported {} to {}", sprout_path.display(), bundle.display());
+    Ok(())
+}
+
+/// Unpack a bundle created by `export_bundle` into a new sprout directory and
+/// create the rest of the directory structure around it
+pub fn import_bundle<P: AsRef<Path>>(bundle: &Path, sprout_path: P) -> Result<()> {
+    let sprout_path = sprout_path.as_ref();
+    if sprout_path.join("manifest.sprout").exists() {
+        return Err(anyhow::anyhow!(
+            "Refusing to import: {} already contains a manifest.sprout",
+            sprout_path.display()
+        ));
+    }
+
+    let file = fs::File::open(bundle)
+        .with_context(|| format!("Failed to open bundle: {}", bundle.display()))?;
+    fs::create_dir_all(sprout_path)?;
+    tar::Archive::new(flate2::read::GzDecoder::new(file)).unpack(sprout_path)?;
+
+    init_sprout(sprout_path, true)?;
+    info!("Imported {} into {}", bundle.display(), sprout_path.display());
+    Ok(())
+}
+
 pub fn git_status<P: AsRef<Path>>(sprout_path: P) -> Result<()> {
     let sprout_path = sprout_path.as_ref();
     if !sprout_path.join(".git").exists() {
@@ -340,6 +487,18 @@ pub fn git_push<P: AsRef<Path>>(sprout_path: P, remote: Option<String>, branch:
         return Err(anyhow::anyhow!("Not a git repository"));
     }
     let target_remote = remote.unwrap_or_else(|| "origin".to_string());
+    let has_remote = std::process::Command::new("git")
+        .current_dir(sprout_path)
+        .args(["remote", "get-url", &target_remote])
+        .output()?
+        .status
+        .success();
+    if !has_remote {
+        return Err(anyhow::anyhow!(
+            "No git remote '{}' configured; add one with `git remote add {} <url>`",
+            target_remote, target_remote
+        ));
+    }
     let target_branch = if let Some(b) = branch {
         b
     } else {
@@ -349,9 +508,12 @@ pub fn git_push<P: AsRef<Path>>(sprout_path: P, remote: Option<String>, branch:
             .output()?;
         String::from_utf8_lossy(&output.stdout).trim().to_string()
     };
-    std::process::Command::new("git")
+    let status = std::process::Command::new("git")
         .current_dir(sprout_path)
         .args(["push", &target_remote, &target_branch])
         .status()?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("git push to '{}' failed", target_remote));
+    }
     Ok(())
 }
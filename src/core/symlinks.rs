@@ -7,9 +7,11 @@ use tracing::{debug, info, warn};
 use crate::lockfile::SproutLock;
 
 const SYMLINKS_DIR: &str = "symlinks";
-const LOCAL_PREFIX: &str = "/local/";
+const DEFAULT_LOCAL_PREFIX: &str = "/local";
+/// Overrides the prefix `normalize_path` strips (e.g. `/net` or `/mnt`)
+pub const LOCAL_PREFIX_ENV: &str = "SPROUT_LOCAL_PREFIX";
 
-enum SymlinkStatus {
+pub(crate) enum SymlinkStatus {
     UpToDate,
     Modified { reason: ModificationReason },
     Deleted,
@@ -17,7 +19,7 @@ enum SymlinkStatus {
     Untracked,
 }
 
-enum ModificationReason {
+pub(crate) enum ModificationReason {
     DifferentHash,
     RegularFile,
     ContentModified,
@@ -52,10 +54,36 @@ fn hash_symlink_target(path: &Path, tracking_path: &str) -> Result<String> {
     Ok(hex::encode(hasher.finalize()))
 }
 
+/// Hash the content of a file, or of every file below a directory, for copy-mode entries.
+fn hash_content(path: &Path) -> Result<String> {
+    use sha2::{Digest, Sha256};
+
+    fn visit(root: &Path, path: &Path, hasher: &mut Sha256) -> Result<()> {
+        if path.is_dir() {
+            let mut entries: Vec<_> = fs::read_dir(path)?.collect::<std::io::Result<_>>()?;
+            entries.sort_by_key(|e| e.file_name());
+            for entry in entries {
+                visit(root, &entry.path(), hasher)?;
+            }
+        } else {
+            let relative = path.strip_prefix(root).unwrap_or(path);
+            hasher.update(relative.to_string_lossy().as_bytes());
+            hasher.update([0]);
+            hasher.update(fs::read(path).context(format!("Failed to read {}", path.display()))?);
+        }
+        Ok(())
+    }
+
+    let mut hasher = Sha256::new();
+    visit(path, path, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
 // On some systems (e.g., NFS-mounted home directories), /home/<user> and /local/home/<user>
 // refer to the same physical path. The /local prefix is often used for local disk access
 // to avoid network latency. This normalization ensures consistent hashing and symlink checking
-// by treating both paths as equivalent.
+// by treating both paths as equivalent. Sites using another prefix (/net, /mnt, ...) set
+// SPROUT_LOCAL_PREFIX.
 //
 // Note: We use string manipulation instead of fs::canonicalize because:
 // - Works on non-existent paths (canonicalize requires the path to exist)
@@ -63,17 +91,84 @@ fn hash_symlink_target(path: &Path, tracking_path: &str) -> Result<String> {
 // - Faster (no filesystem I/O)
 // - More predictable (doesn't depend on current filesystem state)
 fn normalize_path(path: &str) -> &str {
-    if path.starts_with(LOCAL_PREFIX) {
-        &path[LOCAL_PREFIX.len() - 1..] // Remove "/local" prefix
+    let prefix = env::var(LOCAL_PREFIX_ENV)
+        .ok()
+        .filter(|prefix| !prefix.trim_matches('/').is_empty())
+        .unwrap_or_else(|| DEFAULT_LOCAL_PREFIX.to_string());
+    normalize_path_with(path, &prefix)
+}
+
+/// `normalize_path` for an explicit prefix such as `/local` or `/net/`
+pub(crate) fn normalize_path_with<'a>(path: &'a str, prefix: &str) -> &'a str {
+    let prefix = format!("/{}/", prefix.trim_matches('/'));
+    if path.starts_with(&prefix) {
+        &path[prefix.len() - 1..] // Keep the slash that starts the remaining path
     } else {
         path
     }
 }
 
-/// Adds a local file or directory to be managed by Sprout.
-pub fn add_file(sprout_path: &str, path: PathBuf, recursive: bool, dry_run: bool, tracking_path: &str) -> Result<()> {
+/// Expand a literal leading `~` (left alone by quoted or scripted invocations) to the home directory
+pub(crate) fn expand_tilde(path: PathBuf) -> Result<PathBuf> {
+    match path.strip_prefix("~") {
+        Ok(rest) => {
+            let home = dirs::home_dir().context("Could not find home directory")?;
+            Ok(if rest.as_os_str().is_empty() { home } else { home.join(rest) })
+        }
+        Err(_) => Ok(path),
+    }
+}
+
+/// Where `path` points if it is a symlink into the sprout `symlinks/` directory
+fn managed_link_target(sprout_path: &str, path: &Path) -> Option<PathBuf> {
+    if !path.is_symlink() {
+        return None;
+    }
+    let link = fs::read_link(path).ok()?;
+    let link = match path.parent() {
+        Some(parent) if link.is_relative() => parent.join(link),
+        _ => link,
+    };
+    let symlinks_root = fs::canonicalize(Path::new(sprout_path).join(SYMLINKS_DIR)).ok()?;
+    let resolved = fs::canonicalize(&link).unwrap_or(link);
+    resolved.starts_with(&symlinks_root).then_some(resolved)
+}
+
+/// Where `symlinks add` puts a path, worked out before anything is touched
+#[derive(Debug)]
+pub(crate) struct AddPlan {
+    /// Absolute path being added
+    pub target: PathBuf,
+    /// Path relative to the tracking directory, the key in the lockfile
+    pub relative: String,
+    /// Destination inside `symlinks/`
+    pub sprout_target: PathBuf,
+    /// Absolute path the new symlink will point at
+    pub link_target: PathBuf,
+    /// Whether an already tracked path is replaced (`--force`)
+    pub replacing: bool,
+}
+
+impl AddPlan {
+    /// The dry-run description of the plan
+    pub(crate) fn describe(&self, copy: bool) -> String {
+        let action = if self.replacing { "replace" } else if copy { "add (copy)" } else { "add" };
+        let mut out = format!("Would {}: {}\n", action, self.target.display());
+        out.push_str(&format!("  Would copy to: {}\n", self.sprout_target.display()));
+        if copy {
+            out.push_str("  Would keep the original in place and track the copy by content\n");
+        } else {
+            out.push_str(&format!("  Would symlink: {} -> {}\n", self.target.display(), self.link_target.display()));
+        }
+        out
+    }
+}
+
+/// Validate that `path` can be added and work out where it goes.
+pub(crate) fn plan_add(sprout_path: &str, path: PathBuf, force: bool, tracking_path: &str) -> Result<AddPlan> {
     debug!("Original path: {:?}", path);
-    
+    let path = expand_tilde(path)?;
+
     // Normalize the path to handle /local prefix
     let path_str = path.to_string_lossy();
     let normalized_path = normalize_path(&path_str);
@@ -110,9 +205,20 @@ pub fn add_file(sprout_path: &str, path: PathBuf, recursive: bool, dry_run: bool
     debug!("Relative tracking path: {}", relative_home_path);
 
     // Check if this path or any parent is already managed
-    let mut index = SproutLock::load(sprout_path)?;
+    let index = SproutLock::load(sprout_path)?;
     debug!("Checking if path '{}' or its parents are already managed by sprout", relative_home_path);
 
+    // Re-adding a symlink into symlinks/ would copy sprout's own file onto itself
+    if let Some(link_target) = managed_link_target(sprout_path, &target) {
+        if index.symlinks.contains_key(relative_home_path) {
+            return Err(anyhow!("'{}' is already managed by sprout", relative_home_path));
+        }
+        return Err(anyhow!(
+            "'{}' is already a symlink into sprout ({}) but is missing from the lockfile; run `sprout symlinks rehash --discover` to register it",
+            relative_home_path, link_target.display()
+        ));
+    }
+
     // Check if any parent directory is already managed
     let path_parts: Vec<&str> = relative_home_path.split('/').collect();
     for i in 1..path_parts.len() {
@@ -136,20 +242,57 @@ pub fn add_file(sprout_path: &str, path: PathBuf, recursive: bool, dry_run: bool
         }
     }
 
+    let replacing = index.symlinks.contains_key(relative_home_path);
+    if replacing && !force {
+        return Err(anyhow!("'{}' is already managed by sprout (use --force to replace it)", relative_home_path));
+    }
+
     debug!("Path '{}' is not already managed and can be added", relative_home_path);
 
+    let sprout_target = Path::new(sprout_path).join(SYMLINKS_DIR).join(relative_home_path);
+    let link_target = fs::canonicalize(sprout_path)?.join(SYMLINKS_DIR).join(relative_home_path);
+
+    Ok(AddPlan {
+        relative: relative_home_path.to_string(),
+        replacing,
+        sprout_target,
+        link_target,
+        target,
+    })
+}
+
+/// Adds a local file or directory to be managed by Sprout.
+///
+/// With `copy`, the original stays a regular file and only a copy is tracked.
+/// With `force`, a path that is already tracked is copied into sprout again.
+pub fn add_file(sprout_path: &str, path: PathBuf, recursive: bool, copy: bool, force: bool, dry_run: bool, tracking_path: &str) -> Result<()> {
+    let plan = plan_add(sprout_path, path, force, tracking_path)?;
+
     if dry_run {
-        println!("Would add: {}", target.display());
+        print!("{}", plan.describe(copy));
         return Ok(());
     }
 
+    let AddPlan { target, relative, sprout_target, link_target, replacing } = plan;
+    let relative_home_path = relative.as_str();
+    let mut index = SproutLock::load(sprout_path)?;
+
     // Create the symlinks directory structure
-    let sprout_target = Path::new(sprout_path).join(SYMLINKS_DIR).join(relative_home_path);
     if let Some(parent) = sprout_target.parent() {
         fs::create_dir_all(parent)
             .context(format!("Failed to create directory structure for {}", sprout_target.display()))?;
     }
 
+    // A forced re-add replaces the stored copy entirely
+    if replacing && sprout_target.exists() {
+        info!("Replacing stored copy {}", sprout_target.display());
+        if sprout_target.is_dir() {
+            fs::remove_dir_all(&sprout_target)?;
+        } else {
+            fs::remove_file(&sprout_target)?;
+        }
+    }
+
     // Copy the file/directory to sprout
     if target.is_dir() {
         if !recursive {
@@ -165,6 +308,18 @@ pub fn add_file(sprout_path: &str, path: PathBuf, recursive: bool, dry_run: bool
         return Err(anyhow!("Path {} is neither a file nor directory", target.display()));
     }
 
+    if copy {
+        // Keep the original in place and track the copy by content
+        let hash = hash_content(&target)?;
+        index.symlinks.insert(relative_home_path.to_string(), hash);
+        index.copies.insert(relative_home_path.to_string());
+        index.contents.remove(relative_home_path);
+        index.save(sprout_path)?;
+
+        info!("Successfully added {} as a tracked copy", target.display());
+        return Ok(());
+    }
+
     // Remove the original file/directory
     info!("Removing existing entry at {}", target.display());
     if target.is_dir() {
@@ -175,41 +330,191 @@ pub fn add_file(sprout_path: &str, path: PathBuf, recursive: bool, dry_run: bool
             .context(format!("Failed to remove file {}", target.display()))?;
     }
 
-    // Create absolute symlink path
-    let absolute_sprout_path = fs::canonicalize(sprout_path)?;
-    let absolute_sprout_target = absolute_sprout_path.join(SYMLINKS_DIR).join(relative_home_path);
-
     // Create symlink
-    info!("Creating symlink {} -> {}", target.display(), absolute_sprout_target.display());
+    info!("Creating symlink {} -> {}", target.display(), link_target.display());
     #[cfg(unix)]
-    std::os::unix::fs::symlink(&absolute_sprout_target, &target)
-        .context(format!("Failed to create symlink {} -> {}", target.display(), absolute_sprout_target.display()))?;
+    std::os::unix::fs::symlink(&link_target, &target)
+        .context(format!("Failed to create symlink {} -> {}", target.display(), link_target.display()))?;
 
     // Calculate hash and update index
     let hash = hash_symlink_target(&target, tracking_path)?;
     index.symlinks.insert(relative_home_path.to_string(), hash);
+    index.copies.remove(relative_home_path);
+    index.contents.insert(relative_home_path.to_string(), hash_content(&sprout_target)?);
     index.save(sprout_path)?;
 
-    info!("Successfully added and symlinked {}", normalized_target);
+    info!("Successfully added and symlinked {}", target.display());
+    Ok(())
+}
+
+/// Adds several paths, continuing past individual failures and reporting a summary.
+pub fn add_files(sprout_path: &str, paths: Vec<PathBuf>, recursive: bool, copy: bool, force: bool, dry_run: bool, tracking_path: &str) -> Result<()> {
+    use colored::Colorize;
+
+    let total = paths.len();
+    let mut failed = 0;
+
+    for path in paths {
+        let display = path.display().to_string();
+        match add_file(sprout_path, path, recursive, copy, force, dry_run, tracking_path) {
+            Ok(()) => {
+                if !dry_run {
+                    println!("{} {}", "✓".green(), display);
+                }
+            }
+            Err(e) => {
+                println!("{} {}: {}", "✗".red(), display, e);
+                failed += 1;
+            }
+        }
+    }
+
+    if total > 1 {
+        if dry_run {
+            println!("\nWould add {} of {} path(s).", total - failed, total);
+        } else {
+            println!("\nAdded {} of {} path(s).", total - failed, total);
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow!("Failed to add {} of {} path(s)", failed, total));
+    }
     Ok(())
 }
 
+/// Outcome of `restore_symlinks`, by tracked path
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RestoreSummary {
+    pub restored: Vec<String>,
+    pub unchanged: usize,
+    pub failed: Vec<String>,
+    /// Conflicting real files left in place by `ConflictChoice::Skip`
+    pub skipped: Vec<String>,
+}
+
+/// What to do with a real file or directory where a symlink should be restored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictChoice {
+    Overwrite,
+    Backup,
+    Skip,
+}
+
+impl ConflictChoice {
+    pub const ALL: [ConflictChoice; 3] = [ConflictChoice::Overwrite, ConflictChoice::Backup, ConflictChoice::Skip];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ConflictChoice::Overwrite => "Overwrite",
+            ConflictChoice::Backup => "Back up and replace",
+            ConflictChoice::Skip => "Skip",
+        }
+    }
+}
+
+/// Act on `choice` for the conflicting entry at `home_path`. Returns whether the
+/// symlink should still be restored; a backup moves the entry to `<path>.sprout-backup`
+/// (numbered when that is taken)
+pub(crate) fn apply_conflict_choice(home_path: &Path, choice: ConflictChoice) -> Result<bool> {
+    match choice {
+        ConflictChoice::Overwrite => Ok(true),
+        ConflictChoice::Skip => Ok(false),
+        ConflictChoice::Backup => {
+            let file_name = home_path.file_name()
+                .context(format!("Cannot back up {}", home_path.display()))?
+                .to_string_lossy();
+            let backup = (0..)
+                .map(|n| match n {
+                    0 => home_path.with_file_name(format!("{}.sprout-backup", file_name)),
+                    n => home_path.with_file_name(format!("{}.sprout-backup.{}", file_name, n)),
+                })
+                .find(|candidate| !candidate.exists() && !candidate.is_symlink())
+                .expect("unbounded candidates");
+            fs::rename(home_path, &backup)
+                .context(format!("Failed to back up {} to {}", home_path.display(), backup.display()))?;
+            println!("Backed up {} to {}", home_path.display(), backup.display());
+            Ok(true)
+        }
+    }
+}
+
 /// Restores symlinks from the index, repairing broken or missing ones.
-pub fn restore_symlinks(sprout_path: &str, dry_run: bool, _tracking_path: &str) -> Result<()> {
-    let index = SproutLock::load(sprout_path)?;
-    let home = dirs::home_dir().context("Could not find home directory")?;
+///
+/// A failed entry does not stop the others; a summary is printed at the end.
+/// Real files in the way of a symlink are overwritten.
+pub fn restore_symlinks(sprout_path: &str, dry_run: bool, tracking_path: &str) -> Result<RestoreSummary> {
+    restore_symlinks_with(sprout_path, dry_run, tracking_path, &mut |_| Ok(ConflictChoice::Overwrite))
+}
+
+/// `restore_symlinks`, asking `resolve` what to do with each real file or
+/// directory sitting where a symlink should go
+pub fn restore_symlinks_with(
+    sprout_path: &str,
+    dry_run: bool,
+    tracking_path: &str,
+    resolve: &mut dyn FnMut(&Path) -> Result<ConflictChoice>,
+) -> Result<RestoreSummary> {
+    use colored::Colorize;
+
+    let mut index = SproutLock::load(sprout_path)?;
+    let home = PathBuf::from(tracking_path);
+    let mut summary = RestoreSummary::default();
 
     if index.symlinks.is_empty() {
         info!("No symlinks found in index. Nothing to restore.");
-        return Ok(());
+        return Ok(summary);
     }
 
-    let mut restore_count = 0;
+    let symlinks_root = fs::canonicalize(Path::new(sprout_path))?.join(SYMLINKS_DIR);
+    let mut restored_copies = Vec::new();
+    let mut synced_contents = Vec::new();
 
     for home_path_str in index.symlinks.keys() {
         // All paths in index are now relative - convert to absolute
         let home_path = home.join(home_path_str);
-        let expected_target = fs::canonicalize(Path::new(sprout_path))?.join(SYMLINKS_DIR).join(home_path_str);
+        let expected_target = symlinks_root.join(home_path_str);
+
+        if index.copies.contains(home_path_str) {
+            let tracked_hash = match hash_content(&expected_target) {
+                Ok(hash) => hash,
+                Err(e) => {
+                    warn!("Failed to restore {}: {}", home_path.display(), e);
+                    summary.failed.push(home_path_str.clone());
+                    continue;
+                }
+            };
+            let up_to_date = home_path.exists() && !home_path.is_symlink()
+                && hash_content(&home_path).is_ok_and(|h| h == tracked_hash);
+            if up_to_date {
+                summary.unchanged += 1;
+                continue;
+            }
+
+            if dry_run {
+                println!("Would restore copy: {} <- {}", home_path.display(), expected_target.display());
+                summary.restored.push(home_path_str.clone());
+                continue;
+            }
+
+            match restore_copy(&home_path, &expected_target) {
+                Ok(()) => {
+                    info!("Restored copy: {} <- {}", home_path.display(), expected_target.display());
+                    restored_copies.push((home_path_str.clone(), tracked_hash));
+                    summary.restored.push(home_path_str.clone());
+                }
+                Err(e) => {
+                    warn!("Failed to restore {}: {:#}", home_path.display(), e);
+                    summary.failed.push(home_path_str.clone());
+                }
+            }
+            continue;
+        }
+
+        // Restoring acknowledges whatever content symlinks/ currently holds
+        if let Ok(content_hash) = hash_content(&expected_target) {
+            synced_contents.push((home_path_str.clone(), content_hash));
+        }
 
         let should_restore = if !home_path.exists() {
             true
@@ -219,62 +524,139 @@ pub fn restore_symlinks(sprout_path: &str, dry_run: bool, _tracking_path: &str)
             true
         };
 
-        if should_restore {
-            restore_count += 1;
-            if dry_run {
-                println!("Would restore symlink: {} -> {}", home_path.display(), expected_target.display());
-                if home_path.exists() {
-                    println!("  (Would remove existing: {})", home_path.display());
-                }
-                continue;
+        if !should_restore {
+            summary.unchanged += 1;
+            continue;
+        }
+
+        if dry_run {
+            println!("Would restore symlink: {} -> {}", home_path.display(), expected_target.display());
+            if home_path.exists() {
+                println!("  (Would remove existing: {})", home_path.display());
             }
+            summary.restored.push(home_path_str.clone());
+            continue;
+        }
 
-            // Force remove anything that exists at the target location
-            if home_path.exists() || home_path.is_symlink() {
-                debug!("Removing existing entry at {}", home_path.display());
-                if home_path.is_dir() && !home_path.is_symlink() {
-                    fs::remove_dir_all(&home_path)
-                        .context(format!("Failed to remove directory {}", home_path.display()))?;
-                } else {
-                    // This handles both regular files and symlinks (including broken symlinks)
-                    fs::remove_file(&home_path)
-                        .context(format!("Failed to remove file/symlink {}", home_path.display()))?;
+        if home_path.exists() && !home_path.is_symlink() {
+            let choice = resolve(&home_path)?;
+            match apply_conflict_choice(&home_path, choice) {
+                Ok(true) => {}
+                Ok(false) => {
+                    info!("Skipped conflicting entry: {}", home_path.display());
+                    summary.skipped.push(home_path_str.clone());
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Failed to restore {}: {:#}", home_path.display(), e);
+                    summary.failed.push(home_path_str.clone());
+                    continue;
                 }
             }
+        }
 
-            // Ensure parent directory exists
-            if let Some(parent) = home_path.parent() {
-                fs::create_dir_all(parent)
-                    .context(format!("Failed to create parent directory for {}", home_path.display()))?;
+        match restore_link(&home_path, &expected_target) {
+            Ok(()) => {
+                info!("Restored symlink: {} -> {}", home_path.display(), expected_target.display());
+                summary.restored.push(home_path_str.clone());
             }
+            Err(e) => {
+                warn!("Failed to restore {}: {:#}", home_path.display(), e);
+                summary.failed.push(home_path_str.clone());
+            }
+        }
+    }
 
-            debug!("Creating symlink {} -> {}", home_path.display(), expected_target.display());
-            #[cfg(unix)]
-            std::os::unix::fs::symlink(&expected_target, &home_path)
-                .context(format!("Failed to create symlink {} -> {}", home_path.display(), expected_target.display()))?;
+    if !dry_run && (!restored_copies.is_empty() || synced_contents.iter().any(|(p, h)| index.contents.get(p) != Some(h))) {
+        // Restored copies now match the tracked content
+        index.symlinks.extend(restored_copies);
+        index.contents.extend(synced_contents);
+        index.save(sprout_path)?;
+    }
 
-            info!(
-                "Restored symlink: {} -> {}",
-                home_path.display(),
-                expected_target.display()
-            );
+    if dry_run {
+        println!("Would restore {} symlink(s).", summary.restored.len());
+    } else {
+        for path in &summary.restored {
+            println!("  {} {}", "✓".green(), path);
         }
+        for path in &summary.failed {
+            println!("  {} {}", "✗".red(), path);
+        }
+        for path in &summary.skipped {
+            println!("  {} {} (skipped)", "-".bright_black(), path);
+        }
+        let skipped = if summary.skipped.is_empty() {
+            String::new()
+        } else {
+            format!(", skipped {}", summary.skipped.len())
+        };
+        println!(
+            "Restored {}, already correct {}, failed {}{}",
+            summary.restored.len(), summary.unchanged, summary.failed.len(), skipped
+        );
     }
+    Ok(summary)
+}
 
-    if dry_run {
-        println!("Would restore {} symlink(s).", restore_count);
+/// Remove whatever is at `path` (file, directory or dangling symlink) and make
+/// sure its parent directory exists
+fn clear_restore_target(path: &Path) -> Result<()> {
+    if path.is_dir() && !path.is_symlink() {
+        fs::remove_dir_all(path)
+            .context(format!("Failed to remove directory {}", path.display()))?;
+    } else if path.exists() || path.is_symlink() {
+        // This handles both regular files and symlinks (including broken symlinks)
+        fs::remove_file(path)
+            .context(format!("Failed to remove file/symlink {}", path.display()))?;
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create parent directory for {}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn restore_copy(home_path: &Path, expected_target: &Path) -> Result<()> {
+    clear_restore_target(home_path)?;
+    if expected_target.is_dir() {
+        copy_dir_all(expected_target, home_path)?;
     } else {
-        info!("Symlink restoration complete.");
+        fs::copy(expected_target, home_path)
+            .context(format!("Failed to copy {} to {}", expected_target.display(), home_path.display()))?;
     }
     Ok(())
 }
 
-/// Shows the status of tracked dotfiles.
-pub fn check_symlinks(sprout_path: &str, show_all: bool, tracking_path: &str) -> Result<()> {
-    use colored::Colorize;
+fn restore_link(home_path: &Path, expected_target: &Path) -> Result<()> {
+    debug!("Removing existing entry at {}", home_path.display());
+    clear_restore_target(home_path)?;
+
+    debug!("Creating symlink {} -> {}", home_path.display(), expected_target.display());
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(expected_target, home_path)
+        .context(format!("Failed to create symlink {} -> {}", home_path.display(), expected_target.display()))?;
+    Ok(())
+}
+
+/// Whether a symlinked entry's content in symlinks/ differs from the hash recorded at
+/// its last add/restore/rehash. Catches changes git no longer reports, e.g. pulled commits.
+fn content_drifted(sprout_path: &str, index: &SproutLock, tracked_path: &str) -> bool {
+    let Some(recorded) = index.contents.get(tracked_path) else {
+        return false;
+    };
+    let tracked = Path::new(sprout_path).join(SYMLINKS_DIR).join(tracked_path);
+    hash_content(&tracked).ok().as_ref() != Some(recorded)
+}
+
+/// A tracked entry's (path, recorded hash, status, current hash)
+pub(crate) type TrackedStatus = (String, String, SymlinkStatus, Option<String>);
+
+/// Computes the status of every tracked entry.
+pub(crate) fn collect_symlink_statuses(sprout_path: &str, tracking_path: &str) -> Result<Vec<TrackedStatus>> {
     use std::process::Command;
 
-    let home = dirs::home_dir().context("Could not find home directory")?;
+    let home = PathBuf::from(tracking_path);
     let index = SproutLock::load(sprout_path)?;
 
     debug!("Home directory: {}", home.display());
@@ -304,13 +686,29 @@ pub fn check_symlinks(sprout_path: &str, show_all: bool, tracking_path: &str) ->
         }
     }
 
-    let mut statuses: Vec<(String, String, SymlinkStatus, Option<String>)> = vec![];
+    let mut statuses: Vec<TrackedStatus> = vec![];
 
     debug!("Checking tracked symlinks for modifications...");
     for (tracked_path, hash) in &index.symlinks {
         let absolute_path = home.join(tracked_path);
 
-        let (status, current_hash) = if absolute_path.exists() {
+        let (status, current_hash) = if index.copies.contains(tracked_path) {
+            if absolute_path.exists() {
+                // Copies are compared by content: the live file against the recorded
+                // hash, and the tracked copy against it for upstream changes
+                let hash_now = hash_content(&absolute_path)?;
+                let tracked = Path::new(sprout_path).join(SYMLINKS_DIR).join(tracked_path);
+                if hash_now != *hash {
+                    (SymlinkStatus::Modified { reason: ModificationReason::DifferentHash }, Some(hash_now))
+                } else if git_modified.contains(tracked_path) || hash_content(&tracked).ok().as_ref() != Some(hash) {
+                    (SymlinkStatus::Modified { reason: ModificationReason::ContentModified }, Some(hash_now))
+                } else {
+                    (SymlinkStatus::UpToDate, Some(hash_now))
+                }
+            } else {
+                (SymlinkStatus::Deleted, None)
+            }
+        } else if absolute_path.exists() {
             if absolute_path.is_symlink() {
                 let hash_now = hash_symlink_target(&absolute_path, tracking_path)?;
                 debug!("Checking tracked file: {} (indexed hash: {}, current hash: {})",
@@ -318,8 +716,8 @@ pub fn check_symlinks(sprout_path: &str, show_all: bool, tracking_path: &str) ->
                 if hash_now != *hash {
                     debug!("Hash mismatch detected for: {}", tracked_path);
                     (SymlinkStatus::Modified { reason: ModificationReason::DifferentHash }, Some(hash_now))
-                } else if git_modified.contains(tracked_path) {
-                    debug!("Content modified detected by git for: {}", tracked_path);
+                } else if git_modified.contains(tracked_path) || content_drifted(sprout_path, &index, tracked_path) {
+                    debug!("Content modified detected for: {}", tracked_path);
                     (SymlinkStatus::Modified { reason: ModificationReason::ContentModified }, Some(hash_now))
                 } else {
                     (SymlinkStatus::UpToDate, Some(hash_now))
@@ -336,6 +734,44 @@ pub fn check_symlinks(sprout_path: &str, show_all: bool, tracking_path: &str) ->
         statuses.push((tracked_path.clone(), hash.clone(), status, current_hash));
     }
 
+    Ok(statuses)
+}
+
+/// Render every tracked entry straight from the lockfile, without touching the filesystem
+pub(crate) fn render_symlink_list(lock: &SproutLock, json: bool) -> Result<String> {
+    if json {
+        let entries: Vec<_> = lock.symlinks.iter()
+            .map(|(path, hash)| serde_json::json!({
+                "path": path,
+                "target": format!("{}/{}", SYMLINKS_DIR, path),
+                "hash": hash,
+                "copy": lock.copies.contains(path),
+            }))
+            .collect();
+        return Ok(format!("{}\n", serde_json::to_string_pretty(&entries)?));
+    }
+
+    let mut out = String::new();
+    for (path, hash) in &lock.symlinks {
+        let kind = if lock.copies.contains(path) { " (copy)" } else { "" };
+        out.push_str(&format!("{} -> {}/{} [{}]{}\n", path, SYMLINKS_DIR, path, hash, kind));
+    }
+    Ok(out)
+}
+
+/// Prints the raw inventory of managed entries (`symlinks list`)
+pub fn list_symlinks(sprout_path: &str, json: bool) -> Result<()> {
+    let lock = SproutLock::load(sprout_path)?;
+    print!("{}", render_symlink_list(&lock, json)?);
+    Ok(())
+}
+
+/// Shows the status of tracked dotfiles.
+pub fn check_symlinks(sprout_path: &str, show_all: bool, tracking_path: &str) -> Result<()> {
+    use colored::Colorize;
+
+    let statuses = collect_symlink_statuses(sprout_path, tracking_path)?;
+
     let modified: Vec<_> = statuses.iter().filter_map(|(p, h, s, ch)| match s {
         SymlinkStatus::Modified { reason } => Some((p, h, reason, ch)),
         _ => None,
@@ -413,7 +849,7 @@ pub fn check_symlinks(sprout_path: &str, show_all: bool, tracking_path: &str) ->
         println!("  {} = Up-to-date (symlink is correct).", "✓".green());
     }
     println!("  {} = Modified (hash mismatch or regular file).", "M".red());
-    println!("  {} = Modified (content changed in git).", "M".yellow());
+    println!("  {} = Modified (content changed in git or since last restore).", "M".yellow());
     println!("  {} = Deleted (symlink missing).", "D".red());
     Ok(())
 }
@@ -422,6 +858,7 @@ pub fn check_symlinks(sprout_path: &str, show_all: bool, tracking_path: &str) ->
 pub fn undo_symlink(sprout_path: &str, path: PathBuf, dry_run: bool, _tracking_path: &str) -> Result<()> {
     debug!("Starting undo_symlink for path: {}", path.display());
     debug!("Sprout path: {}", sprout_path);
+    let path = expand_tilde(path)?;
 
     let mut index = SproutLock::load(sprout_path)?;
     let home = dirs::home_dir().context("Could not find home directory")?;
@@ -487,6 +924,28 @@ pub fn undo_symlink(sprout_path: &str, path: PathBuf, dry_run: bool, _tracking_p
         return Err(anyhow!("Source file {} no longer exists in sprout directory", sprout_source.display()));
     }
 
+    if index.copies.contains(relative_home_path) {
+        // The original was never replaced; only the tracked copy goes away
+        if dry_run {
+            println!("Would stop tracking copy: {}", home_target.display());
+            println!("  Would remove tracked copy: {}", sprout_source.display());
+            return Ok(());
+        }
+        if sprout_source.is_dir() {
+            fs::remove_dir_all(&sprout_source)
+                .context(format!("Failed to remove directory {}", sprout_source.display()))?;
+        } else {
+            fs::remove_file(&sprout_source)
+                .context(format!("Failed to remove file {}", sprout_source.display()))?;
+        }
+        index.symlinks.remove(relative_home_path);
+        index.copies.remove(relative_home_path);
+        index.save(sprout_path)?;
+
+        info!("Successfully stopped tracking copy of {}", relative_home_path);
+        return Ok(());
+    }
+
     if dry_run {
         println!("Would undo symlink: {}", home_target.display());
         println!("  Source in sprout: {}", sprout_source.display());
@@ -556,6 +1015,7 @@ pub fn undo_symlink(sprout_path: &str, path: PathBuf, dry_run: bool, _tracking_p
     // Remove from index
     debug!("Removing entry from index: {}", relative_home_path);
     let removed_entry = index.symlinks.remove(relative_home_path);
+    index.contents.remove(relative_home_path);
     debug!("Index removal result: {:?}", removed_entry.is_some());
 
     debug!("Writing updated index to disk");
@@ -567,22 +1027,307 @@ pub fn undo_symlink(sprout_path: &str, path: PathBuf, dry_run: bool, _tracking_p
     Ok(())
 }
 
-pub fn rehash_symlinks(sprout_path: &str, tracking_path: &str, discover: bool, dry_run: bool) -> Result<()> {
+/// Make `path` absolute without following symlinks and split off its part
+/// below the tracking directory
+fn resolve_tracked_path(path: &Path, tracking_path: &str) -> Result<(PathBuf, String)> {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir().context("Could not get current directory")?.join(path)
+    };
+
+    let mut components = Vec::new();
+    for component in absolute.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    let absolute: PathBuf = components.iter().collect();
+
+    let absolute_str = absolute.to_string_lossy();
+    let relative = normalize_path(&absolute_str)
+        .strip_prefix(normalize_path(tracking_path))
+        .map(|s| s.trim_start_matches('/'))
+        .filter(|s| !s.is_empty())
+        .with_context(|| format!("{} is not within the tracking directory {}", absolute.display(), tracking_path))?
+        .to_string();
+    Ok((absolute, relative))
+}
+
+/// Relocate a tracked entry: its stored file in symlinks/ is renamed, the old
+/// link replaced by one at the new location, and the lockfile entry re-keyed
+/// with a hash of the new mapping. Copies move their original along with them
+pub fn move_symlink(sprout_path: &str, old: &Path, new: &Path, dry_run: bool, tracking_path: &str) -> Result<()> {
+    let (old_home, old_relative) = resolve_tracked_path(old, tracking_path)?;
+    let (new_home, new_relative) = resolve_tracked_path(new, tracking_path)?;
     let mut index = SproutLock::load(sprout_path)?;
-    let home = dirs::home_dir().context("Could not find home directory")?;
+
+    if !index.symlinks.contains_key(&old_relative) {
+        return Err(anyhow!("Path '{}' is not tracked by sprout", old_relative));
+    }
+    let overlaps = |a: &str, b: &str| a == b || b.starts_with(&format!("{}/", a)) || a.starts_with(&format!("{}/", b));
+    if let Some(existing) = index.symlinks.keys().find(|k| **k != old_relative && overlaps(k, &new_relative)) {
+        return Err(anyhow!("Cannot move to '{}' because '{}' is already managed by sprout", new_relative, existing));
+    }
+    if overlaps(&old_relative, &new_relative) {
+        return Err(anyhow!("Cannot move '{}' into itself ('{}')", old_relative, new_relative));
+    }
+    if new_home.exists() || new_home.is_symlink() {
+        return Err(anyhow!("{} already exists", new_home.display()));
+    }
+
+    let is_copy = index.copies.contains(&old_relative);
+    let old_stored = Path::new(sprout_path).join(SYMLINKS_DIR).join(&old_relative);
+    let new_stored = Path::new(sprout_path).join(SYMLINKS_DIR).join(&new_relative);
+
+    if dry_run {
+        println!("Would move: {} -> {}", old_relative, new_relative);
+        println!("  Would rename {} to {}", old_stored.display(), new_stored.display());
+        if is_copy {
+            println!("  Would move original {} to {}", old_home.display(), new_home.display());
+        } else {
+            println!("  Would replace symlink {} with {}", old_home.display(), new_home.display());
+        }
+        return Ok(());
+    }
+
+    if !old_stored.exists() {
+        return Err(anyhow!("Source file {} no longer exists in sprout directory", old_stored.display()));
+    }
+    if let Some(parent) = new_stored.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(&old_stored, &new_stored)
+        .with_context(|| format!("Failed to move {} to {}", old_stored.display(), new_stored.display()))?;
+
+    if let Some(parent) = new_home.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let hash = if is_copy {
+        if old_home.exists() {
+            fs::rename(&old_home, &new_home)
+                .with_context(|| format!("Failed to move {} to {}", old_home.display(), new_home.display()))?;
+        }
+        hash_content(&new_stored)?
+    } else {
+        if old_home.is_symlink() {
+            fs::remove_file(&old_home)
+                .with_context(|| format!("Failed to remove symlink {}", old_home.display()))?;
+        }
+        let link_target = fs::canonicalize(sprout_path)?.join(SYMLINKS_DIR).join(&new_relative);
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&link_target, &new_home)
+            .with_context(|| format!("Failed to create symlink {} -> {}", new_home.display(), link_target.display()))?;
+        hash_symlink_target(&new_home, tracking_path)?
+    };
+
+    index.symlinks.remove(&old_relative);
+    index.symlinks.insert(new_relative.clone(), hash);
+    if index.copies.remove(&old_relative) {
+        index.copies.insert(new_relative.clone());
+    }
+    if let Some(content) = index.contents.remove(&old_relative) {
+        index.contents.insert(new_relative.clone(), content);
+    }
+    index.save(sprout_path)?;
+
+    info!("Moved {} -> {}", old_relative, new_relative);
+    Ok(())
+}
+
+/// Unified diff of tracked entries (all of them, or those at or below `path`)
+/// against the last commit of the sprout repo. Stored files git does not know
+/// yet show up as added; copies are diffed against their file in the tracking dir
+pub fn diff_symlinks(sprout_path: &str, path: Option<&Path>, tracking_path: &str) -> Result<String> {
+    if !Path::new(sprout_path).join(".git").exists() {
+        return Err(anyhow!("Not a git repository: {}", sprout_path));
+    }
+    let index = SproutLock::load(sprout_path)?;
+
+    let selected: Vec<&String> = match path {
+        Some(path) => {
+            let (_, relative) = resolve_tracked_path(path, tracking_path)?;
+            let selected: Vec<_> = index.symlinks.keys()
+                .filter(|k| **k == relative || k.starts_with(&format!("{}/", relative)))
+                .collect();
+            if selected.is_empty() {
+                return Err(anyhow!("Path '{}' is not tracked by sprout", relative));
+            }
+            selected
+        }
+        None => index.symlinks.keys().collect(),
+    };
+
+    let color = if colored::control::SHOULD_COLORIZE.should_colorize() { "--color=always" } else { "--color=never" };
+    let git = |args: &[&str]| -> Result<String> {
+        let output = std::process::Command::new("git")
+            .current_dir(sprout_path)
+            .args(args)
+            .output()
+            .context("Failed to execute git diff")?;
+        // `diff --no-index` exits with 1 when the files differ
+        if !matches!(output.status.code(), Some(0 | 1)) {
+            return Err(anyhow!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    };
+
+    let mut diff = String::new();
+    for relative in selected {
+        let stored = format!("{}/{}", SYMLINKS_DIR, relative);
+        if index.copies.contains(relative) {
+            let original = Path::new(tracking_path).join(relative);
+            if original.exists() {
+                diff.push_str(&git(&["diff", "--no-index", color, "--", &stored, &original.to_string_lossy()])?);
+            }
+            continue;
+        }
+
+        diff.push_str(&git(&["diff", color, "HEAD", "--", &stored])?);
+        let untracked = git(&["ls-files", "--others", "--exclude-standard", "--", &stored])?;
+        for file in untracked.lines() {
+            diff.push_str(&git(&["diff", "--no-index", color, "--", "/dev/null", file])?);
+        }
+    }
+    Ok(diff)
+}
+
+/// Tracked entries whose path no longer exists, or is no longer a symlink
+/// for entries that are not tracked as copies
+pub fn find_dead_symlinks(sprout_path: &str, tracking_path: &str) -> Result<Vec<String>> {
+    let index = SproutLock::load(sprout_path)?;
+    let home = PathBuf::from(tracking_path);
+
+    Ok(index.symlinks.keys()
+        .filter(|relative_path| {
+            let absolute_path = home.join(relative_path);
+            let is_copy = index.copies.contains(*relative_path);
+            !(absolute_path.exists() && (is_copy || absolute_path.is_symlink()))
+        })
+        .cloned()
+        .collect())
+}
+
+/// Several lockfile entries that resolve to the same file
+#[derive(Debug, PartialEq)]
+pub(crate) struct SymlinkConflict {
+    /// "target" for a shared file in symlinks/, "home path" for a shared location
+    pub(crate) kind: &'static str,
+    pub(crate) path: PathBuf,
+    pub(crate) entries: Vec<String>,
+}
+
+impl std::fmt::Display for SymlinkConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} share the {} {}", self.entries.join(", "), self.kind, self.path.display())
+    }
+}
+
+/// Drop `.` and resolve `..` components without touching the filesystem
+fn lexical_normalize(path: &Path) -> PathBuf {
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    let normalized: PathBuf = components.iter().collect();
+    PathBuf::from(normalize_path(&normalized.to_string_lossy()))
+}
+
+/// Find lockfile entries that resolve to the same file in symlinks/ or the
+/// same path in the tracking directory. Keys written by hand or by discovery
+/// after a migration (`./.vimrc` next to `.vimrc`, a link repointed at another
+/// entry's file) can end up sharing one; restoring or undoing either of them
+/// then clobbers the other
+pub(crate) fn find_symlink_conflicts(sprout_path: &str, lock: &SproutLock, tracking_path: &str) -> Vec<SymlinkConflict> {
+    // Links point into the canonical root (see add_file); compare everything in that form
+    let resolve = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| lexical_normalize(path));
+    let symlinks_dir = resolve(&Path::new(sprout_path).join(SYMLINKS_DIR));
+    let mut targets: std::collections::BTreeMap<PathBuf, Vec<String>> = Default::default();
+    let mut homes: std::collections::BTreeMap<PathBuf, Vec<String>> = Default::default();
+
+    for relative_path in lock.symlinks.keys() {
+        let home_path = Path::new(tracking_path).join(relative_path);
+        homes.entry(lexical_normalize(&home_path)).or_default().push(relative_path.clone());
+        if lock.copies.contains(relative_path) {
+            continue;
+        }
+
+        // Where the link on disk actually points, or where it would be restored to
+        let target = match fs::read_link(&home_path) {
+            Ok(target) => home_path.parent().map_or(target.clone(), |parent| parent.join(target)),
+            Err(_) => symlinks_dir.join(relative_path),
+        };
+        targets.entry(resolve(&target)).or_default().push(relative_path.clone());
+    }
+
+    let shared = |kind: &'static str, groups: std::collections::BTreeMap<PathBuf, Vec<String>>| {
+        groups.into_iter()
+            .filter(|(_, entries)| entries.len() > 1)
+            .map(move |(path, entries)| SymlinkConflict { kind, path, entries })
+    };
+    shared("target", targets).chain(shared("home path", homes)).collect()
+}
+
+/// Check the lockfile for entries sharing a target (`symlinks verify`)
+pub fn verify_symlinks(sprout_path: &str, tracking_path: &str) -> Result<()> {
+    let lock = SproutLock::load(sprout_path)?;
+    let conflicts = find_symlink_conflicts(sprout_path, &lock, tracking_path);
+    if conflicts.is_empty() {
+        println!("{} tracked entries, no conflicts.", lock.symlinks.len());
+        return Ok(());
+    }
+
+    for conflict in &conflicts {
+        println!("conflict: {}", conflict);
+    }
+    Err(anyhow!(
+        "Found {} conflicting group(s) in the lockfile; `symlinks undo` or edit sprout.lock so each target is tracked once",
+        conflicts.len()
+    ))
+}
+
+/// Rehash tracked symlinks. With `prune`, entries whose path is gone are
+/// dropped from the lockfile instead of being reported as errors. With
+/// `discover`, untracked links into symlinks/ are added; `include_untracked`
+/// also accepts links into an old location of the sprout dir and repoints them
+pub fn rehash_symlinks(sprout_path: &str, tracking_path: &str, discover: bool, include_untracked: bool, prune: bool, dry_run: bool) -> Result<()> {
+    let mut index = SproutLock::load(sprout_path)?;
+    let home = PathBuf::from(tracking_path);
 
     if discover {
         info!("Discovering managed symlinks (dry_run: {})...", dry_run);
-        let symlinks_dir = Path::new(sprout_path).join("symlinks");
-        
+        let symlinks_dir = Path::new(sprout_path).join(SYMLINKS_DIR);
+
         if !symlinks_dir.exists() {
             info!("No symlinks directory found.");
             return Ok(());
         }
+        // Links are created against the canonical root (see add_file), so compare against it
+        let symlinks_dir = fs::canonicalize(&symlinks_dir)?;
 
         let mut discovered_count = 0;
-        discover_symlinks_recursive(&symlinks_dir, &symlinks_dir, &home, tracking_path, &mut index, &mut discovered_count, dry_run)?;
+        let discovery = Discovery {
+            symlinks_root: &symlinks_dir,
+            home: &home,
+            tracking_path,
+            include_relocated: include_untracked,
+            dry_run,
+        };
+        discover_symlinks_recursive(&discovery, &symlinks_dir, &mut index, &mut discovered_count)?;
         
+        for conflict in find_symlink_conflicts(sprout_path, &index, tracking_path) {
+            warn!("Conflicting lockfile entries: {}", conflict);
+        }
         if !dry_run {
             index.save(sprout_path)?;
             info!("Discovery complete: {} symlinks added to lockfile", discovered_count);
@@ -599,6 +1344,7 @@ pub fn rehash_symlinks(sprout_path: &str, tracking_path: &str, discover: bool, d
 
     let mut updated_count = 0;
     let mut error_count = 0;
+    let mut pruned_count = 0;
 
     info!("Rehashing {} tracked symlinks (dry_run: {})...", index.symlinks.len(), dry_run);
 
@@ -606,15 +1352,21 @@ pub fn rehash_symlinks(sprout_path: &str, tracking_path: &str, discover: bool, d
 
     for relative_path in symlink_paths {
         let absolute_path = home.join(&relative_path);
+        let is_copy = index.copies.contains(&relative_path);
 
-        if absolute_path.exists() && absolute_path.is_symlink() {
-            match hash_symlink_target(&absolute_path, tracking_path) {
+        if absolute_path.exists() && (is_copy || absolute_path.is_symlink()) {
+            let new_hash = if is_copy {
+                hash_content(&absolute_path)
+            } else {
+                hash_symlink_target(&absolute_path, tracking_path)
+            };
+            match new_hash {
                 Ok(new_hash) => {
                     let old_hash = index.symlinks.get(&relative_path).cloned();
                     if old_hash.as_ref() != Some(&new_hash) {
                         info!("Updated hash for {}: {:?} -> {}", relative_path, old_hash, new_hash);
                         if !dry_run {
-                            index.symlinks.insert(relative_path, new_hash);
+                            index.symlinks.insert(relative_path.clone(), new_hash);
                         }
                         updated_count += 1;
                     } else {
@@ -626,6 +1378,23 @@ pub fn rehash_symlinks(sprout_path: &str, tracking_path: &str, discover: bool, d
                     error_count += 1;
                 }
             }
+
+            if !is_copy && !dry_run {
+                let tracked = Path::new(sprout_path).join(SYMLINKS_DIR).join(&relative_path);
+                if let Ok(content_hash) = hash_content(&tracked) {
+                    index.contents.insert(relative_path, content_hash);
+                }
+            }
+        } else if prune {
+            if dry_run {
+                println!("Would prune {}", relative_path);
+            } else {
+                index.symlinks.remove(&relative_path);
+                index.copies.remove(&relative_path);
+                index.contents.remove(&relative_path);
+                info!("Pruned {}", relative_path);
+            }
+            pruned_count += 1;
         } else {
             warn!("Symlink {} no longer exists or is not a symlink", relative_path);
             error_count += 1;
@@ -634,92 +1403,96 @@ pub fn rehash_symlinks(sprout_path: &str, tracking_path: &str, discover: bool, d
 
     if !dry_run {
         index.save(sprout_path)?;
-        info!("Rehashing complete: {} updated, {} errors", updated_count, error_count);
+        info!("Rehashing complete: {} updated, {} pruned, {} errors", updated_count, pruned_count, error_count);
     } else {
-        info!("Would update {} symlinks, {} errors", updated_count, error_count);
+        info!("Would update {} symlinks, prune {}, {} errors", updated_count, pruned_count, error_count);
     }
     Ok(())
 }
 
+/// Settings shared by every level of `discover_symlinks_recursive`
+struct Discovery<'a> {
+    symlinks_root: &'a Path,
+    home: &'a Path,
+    tracking_path: &'a str,
+    /// Also accept (and repair) links into a `symlinks/` dir at an outdated location
+    include_relocated: bool,
+    dry_run: bool,
+}
+
+/// Whether `target` is `<anything>/symlinks/<relative>`, i.e. the entry as seen
+/// from a sprout directory that has since moved
+fn is_relocated_target(target: &Path, relative: &Path) -> bool {
+    let suffix = Path::new(SYMLINKS_DIR).join(relative);
+    target.is_absolute() && target.ends_with(&suffix)
+}
+
 fn discover_symlinks_recursive(
-    symlinks_root: &Path,
+    discovery: &Discovery,
     current_sprout_dir: &Path,
-    home: &Path,
-    tracking_path: &str,
     index: &mut SproutLock,
     discovered_count: &mut usize,
-    dry_run: bool,
 ) -> Result<()> {
     debug!("Scanning directory: {}", current_sprout_dir.display());
     
     for entry in fs::read_dir(current_sprout_dir)? {
         let entry = entry?;
         let sprout_path = entry.path();
-        let relative_path = sprout_path.strip_prefix(symlinks_root)
+        let relative_path = sprout_path.strip_prefix(discovery.symlinks_root)
             .context("Failed to get relative path")?;
-        let home_path = home.join(relative_path);
-
-        if sprout_path.is_dir() {
-            // Check if home path is a symlink to this directory
-            if home_path.is_symlink() {
-                let target = fs::read_link(&home_path)?;
-                debug!("Found symlink: {} -> {}", home_path.display(), target.display());
-                if target == sprout_path {
-                    // It's a directory symlink
-                    let relative_str = relative_path.to_string_lossy().to_string();
-                    if !index.symlinks.contains_key(&relative_str) {
-                        match hash_symlink_target(&home_path, tracking_path) {
-                            Ok(hash) => {
-                                if !dry_run {
-                                    index.symlinks.insert(relative_str.clone(), hash);
-                                }
-                                info!("Discovered directory symlink: {}", relative_str);
-                                *discovered_count += 1;
-                            }
-                            Err(e) => warn!("Failed to hash {}: {}", relative_str, e),
-                        }
-                    } else {
-                        debug!("Already tracked: {}", relative_str);
-                    }
+        let home_path = discovery.home.join(relative_path);
+
+        if home_path.is_symlink() {
+            // Check if home path is a symlink to this file or directory
+            let kind = if sprout_path.is_dir() { "directory" } else { "file" };
+            let target = fs::read_link(&home_path)?;
+            debug!("Found symlink: {} -> {}", home_path.display(), target.display());
+            let relocated = target != sprout_path
+                && discovery.include_relocated
+                && is_relocated_target(&target, relative_path);
+            if target != sprout_path && !relocated {
+                continue;
+            }
+
+            let relative_str = relative_path.to_string_lossy().to_string();
+            if relocated {
+                if discovery.dry_run {
+                    println!("Would repair {}: {} -> {}", home_path.display(), target.display(), sprout_path.display());
+                } else {
+                    fs::remove_file(&home_path)?;
+                    #[cfg(unix)]
+                    std::os::unix::fs::symlink(&sprout_path, &home_path)
+                        .context(format!("Failed to repair symlink {}", home_path.display()))?;
+                    println!("Repaired {}: {} -> {}", home_path.display(), target.display(), sprout_path.display());
                 }
-            } else if home_path.is_dir() {
-                // Real directory, descend into it
-                debug!("Descending into directory: {}", relative_path.display());
-                discover_symlinks_recursive(symlinks_root, &sprout_path, home, tracking_path, index, discovered_count, dry_run)?;
-            } else {
-                debug!("Home path doesn't exist or is not a directory: {}", home_path.display());
+            } else if index.symlinks.contains_key(&relative_str) {
+                debug!("Already tracked: {}", relative_str);
+                continue;
             }
-        } else if sprout_path.is_file() {
-            // Check if home path is a symlink to this file
-            if home_path.is_symlink() {
-                let target = fs::read_link(&home_path)?;
-                debug!("Found symlink: {} -> {}", home_path.display(), target.display());
-                if target == sprout_path {
-                    let relative_str = relative_path.to_string_lossy().to_string();
-                    if !index.symlinks.contains_key(&relative_str) {
-                        match hash_symlink_target(&home_path, tracking_path) {
-                            Ok(hash) => {
-                                if !dry_run {
-                                    index.symlinks.insert(relative_str.clone(), hash);
-                                }
-                                info!("Discovered file symlink: {}", relative_str);
-                                *discovered_count += 1;
-                            }
-                            Err(e) => warn!("Failed to hash {}: {}", relative_str, e),
-                        }
-                    } else {
-                        debug!("Already tracked: {}", relative_str);
+
+            // The hash only covers the part after symlinks/, so it is the same for the old target
+            match hash_symlink_target(&home_path, discovery.tracking_path) {
+                Ok(hash) => {
+                    if !discovery.dry_run {
+                        index.symlinks.insert(relative_str.clone(), hash);
                     }
+                    info!("Discovered {} symlink: {}", kind, relative_str);
+                    *discovered_count += 1;
                 }
-            } else {
-                debug!("Home path is not a symlink: {}", home_path.display());
+                Err(e) => warn!("Failed to hash {}: {}", relative_str, e),
             }
+        } else if sprout_path.is_dir() && home_path.is_dir() {
+            // Real directory, descend into it
+            debug!("Descending into directory: {}", relative_path.display());
+            discover_symlinks_recursive(discovery, &sprout_path, index, discovered_count)?;
+        } else {
+            debug!("Home path is not a symlink or directory: {}", home_path.display());
         }
     }
     Ok(())
 }
 
-fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<()> {
+pub(crate) fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> Result<()> {
     fs::create_dir_all(&dst)?;
     for entry in fs::read_dir(src)? {
         let entry = entry?;
@@ -0,0 +1,39 @@
+use anyhow::Result;
+use std::path::Path;
+
+/// Total size in bytes of a file, or of every file below a directory
+pub fn dir_size(path: &Path) -> Result<u64> {
+    let mut size = 0u64;
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                size += dir_size(&entry.path())?;
+            } else {
+                size += metadata.len();
+            }
+        }
+    } else {
+        size = path.metadata()?.len();
+    }
+    Ok(size)
+}
+
+/// Human readable byte count, e.g. `512 B` or `3.4 MB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if value < 1000.0 {
+            break;
+        }
+        value /= 1000.0;
+        unit = next;
+    }
+    format!("{:.1} {}", value, unit)
+}
@@ -0,0 +1,84 @@
+use std::fmt;
+
+/// Failure categories with a stable process exit code, attached where the
+/// error originates (`.categorize(ErrorKind::Build)`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// manifest.sprout could not be parsed
+    Parse,
+    /// A build script failed or did not produce its outputs
+    Build,
+    /// Downloading, cloning or unpacking sources failed
+    Fetch,
+    /// The lockfile does not contain what the command requires
+    LockMismatch,
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Parse => 2,
+            ErrorKind::Build => 3,
+            ErrorKind::Fetch => 4,
+            ErrorKind::LockMismatch => 5,
+        }
+    }
+}
+
+/// An error tagged with its category. Displays exactly like the wrapped error
+/// so tagging never changes what the user sees
+#[derive(Debug)]
+pub struct Categorized {
+    pub kind: ErrorKind,
+    source: anyhow::Error,
+}
+
+impl fmt::Display for Categorized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.source, f)
+    }
+}
+
+impl std::error::Error for Categorized {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.source()
+    }
+}
+
+/// Tag the error of a result with the category deciding the exit code
+pub trait Categorize<T> {
+    fn categorize(self, kind: ErrorKind) -> anyhow::Result<T>;
+}
+
+impl<T> Categorize<T> for anyhow::Result<T> {
+    fn categorize(self, kind: ErrorKind) -> anyhow::Result<T> {
+        self.map_err(|source| Categorized { kind, source }.into())
+    }
+}
+
+/// Exit code for an error: that of its category, or 1 if it has none
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    err.downcast_ref::<Categorized>().map_or(1, |c| c.kind.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::load_manifest;
+
+    #[test]
+    fn test_parse_error_exits_with_code_2() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("manifest.sprout"), "module broken {").unwrap();
+
+        let err = load_manifest(temp_dir.path().to_str().unwrap()).unwrap_err();
+        assert_eq!(exit_code(&err), 2);
+        assert_eq!(err.to_string(), "Failed to parse manifest.sprout");
+
+        // Context added further up keeps the category
+        let err = err.context("Failed to load sprout");
+        assert_eq!(exit_code(&err), 2);
+
+        assert_eq!(exit_code(&anyhow::anyhow!("something else")), 1);
+    }
+}
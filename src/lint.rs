@@ -0,0 +1,219 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::ast::{FetchSpec, ModuleBlock, SproutManifest};
+
+/// How serious a lint finding is; errors make `sprout lint` fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A single finding reported by `sprout lint`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintIssue {
+    pub severity: Severity,
+    pub module: String,
+    pub message: String,
+}
+
+impl LintIssue {
+    fn new(severity: Severity, module: &str, message: impl Into<String>) -> Self {
+        Self { severity, module: module.to_string(), message: message.into() }
+    }
+}
+
+/// Run every lint check against the manifest
+pub fn lint_manifest(manifest: &SproutManifest) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+
+    for module in &manifest.modules {
+        issues.extend(check_fetch_without_build(module));
+        issues.extend(check_empty_build(module));
+        issues.extend(check_absolute_exports(module));
+        issues.extend(check_branch_ref(module));
+    }
+    issues.extend(check_unknown_environment_modules(manifest));
+    issues.extend(check_unused_modules(manifest));
+
+    issues
+}
+
+/// Fetched sources are never installed without a build block
+pub fn check_fetch_without_build(module: &ModuleBlock) -> Option<LintIssue> {
+    (module.fetch.is_some() && module.build.is_none()).then(|| {
+        LintIssue::new(Severity::Warning, &module.id(), "has a fetch block but no build block")
+    })
+}
+
+/// A build block without commands leaves an empty dist directory
+pub fn check_empty_build(module: &ModuleBlock) -> Option<LintIssue> {
+    let build = module.build.as_ref()?;
+    build.commands.iter().all(|c| c.trim().is_empty()).then(|| {
+        LintIssue::new(Severity::Error, &module.id(), "has an empty build block")
+    })
+}
+
+/// System locations that an export (joined onto the module's dist dir) should never name
+const SYSTEM_PREFIXES: &[&str] = &["/usr/", "/etc/", "/opt/", "/var/", "/tmp/", "/home/", "/root/"];
+
+/// Exports are joined onto the module's dist directory, so values naming system
+/// locations or climbing out with `..` almost certainly point somewhere else
+pub fn check_absolute_exports(module: &ModuleBlock) -> Vec<LintIssue> {
+    module.provides.iter()
+        .filter(|export| {
+            let value = format!("{}/", export.value);
+            SYSTEM_PREFIXES.iter().any(|prefix| value.starts_with(prefix))
+                || export.value.split('/').any(|part| part == "..")
+        })
+        .map(|export| LintIssue::new(
+            Severity::Warning,
+            &module.id(),
+            format!("export {} points at suspicious path '{}'", export.name, export.value),
+        ))
+        .collect()
+}
+
+/// Git refs should be tags or commits; branches move and are not reproducible
+pub fn check_branch_ref(module: &ModuleBlock) -> Option<LintIssue> {
+    let Some(FetchSpec::Git(git)) = module.fetch.as_ref().map(|f| &f.spec) else {
+        return None;
+    };
+
+    match &git.ref_ {
+        None => Some(LintIssue::new(
+            Severity::Warning,
+            &module.id(),
+            "git fetch has no ref and follows the default branch",
+        )),
+        Some(ref_) if crate::core::deps::is_abbreviated_sha(ref_) => Some(LintIssue::new(
+            Severity::Error,
+            &module.id(),
+            format!("git ref '{}' is an abbreviated commit; fetching needs the full SHA", ref_),
+        )),
+        Some(ref_) => {
+            let is_commit = crate::core::deps::is_commit_sha(ref_);
+            let is_version = ref_.chars().any(|c| c.is_ascii_digit());
+            (!is_commit && !is_version).then(|| LintIssue::new(
+                Severity::Warning,
+                &module.id(),
+                format!("git ref '{}' looks like a branch name and is not reproducible", ref_),
+            ))
+        }
+    }
+}
+
+/// Environments may only list modules that exist
+pub fn check_unknown_environment_modules(manifest: &SproutManifest) -> Vec<LintIssue> {
+    let Some(environments) = &manifest.environments else {
+        return Vec::new();
+    };
+
+    let mut names: Vec<_> = environments.environments.keys().collect();
+    names.sort();
+
+    names.into_iter()
+        .flat_map(|env| environments.environments[env].iter().map(move |module| (env, module)))
+        .filter(|(_, module)| *module != crate::ast::ALL_BUILT_MODULES && manifest.find_module(module).is_none())
+        .map(|(env, module)| LintIssue::new(
+            Severity::Error,
+            module,
+            format!("is listed in environment '{}' but not defined", env),
+        ))
+        .collect()
+}
+
+/// Modules that no environment needs, directly or as a dependency
+pub fn check_unused_modules(manifest: &SproutManifest) -> Vec<LintIssue> {
+    let Some(environments) = &manifest.environments else {
+        return Vec::new();
+    };
+    // Whether a module is built is not known here, so a wildcard uses them all
+    if environments.environments.values().flatten().any(|m| m == crate::ast::ALL_BUILT_MODULES) {
+        return Vec::new();
+    }
+
+    let used: HashSet<String> = environments.environments.values()
+        .flatten()
+        .flat_map(|module| manifest.get_all_dependencies(module))
+        .collect();
+
+    manifest.modules.iter()
+        .filter(|module| !used.contains(&module.id()))
+        .map(|module| LintIssue::new(Severity::Warning, &module.id(), "is not used by any environment"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_manifest;
+
+    #[test]
+    fn test_fetch_without_build_warns() {
+        let manifest = parse_manifest(r#"
+module headers {
+    depends_on = []
+    fetch {
+        git = {
+            url = "https://example.com/headers.git"
+            ref = "v1.0"
+        }
+    }
+}
+"#).unwrap();
+
+        let issue = check_fetch_without_build(&manifest.modules[0]).unwrap();
+        assert_eq!(issue.severity, Severity::Warning);
+        assert_eq!(issue.module, "headers");
+        assert!(check_branch_ref(&manifest.modules[0]).is_none());
+    }
+
+    #[test]
+    fn test_absolute_exports_warn_only_for_system_paths() {
+        let manifest = parse_manifest(r#"
+module tool {
+    depends_on = []
+    provides = {
+        prepend PATH = "/bin"
+        prepend LD_LIBRARY_PATH = "/usr/lib"
+        set TOOL_HOME = "/../shared"
+    }
+}
+"#).unwrap();
+
+        let issues = check_absolute_exports(&manifest.modules[0]);
+        assert_eq!(issues.len(), 2);
+        assert!(issues.iter().all(|i| !i.message.contains("'/bin'")));
+    }
+
+    #[test]
+    fn test_unused_module_warns() {
+        let manifest = parse_manifest(r#"
+module cmake { depends_on = [] }
+module ninja { depends_on = [cmake] }
+module leftover { depends_on = [] }
+environments {
+    default = [ninja]
+}
+"#).unwrap();
+
+        let issues = check_unused_modules(&manifest);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].module, "leftover");
+
+        // Without an environments block nothing can be judged unused
+        let manifest = SproutManifest { environments: None, ..manifest };
+        assert!(check_unused_modules(&manifest).is_empty());
+    }
+}
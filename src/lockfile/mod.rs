@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::path::Path;
 use anyhow::{Context, Result};
@@ -12,6 +12,12 @@ mod test_lockfile;
 pub struct PackageState {
     pub fetch_hash: Option<String>,
     pub build_hash: Option<String>,
+    /// Commit the last git fetch checked out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_commit: Option<String>,
+    /// Source tree hash from the last build run with `--check-sources`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_hash: Option<String>,
 }
 
 /// The lockfile tracks build hashes and symlink hashes
@@ -19,6 +25,12 @@ pub struct PackageState {
 pub struct SproutLock {
     pub modules: BTreeMap<String, PackageState>,
     pub symlinks: BTreeMap<String, String>, // symlink_path -> content_hash
+    /// Entries tracked as plain copies rather than symlinks; their hash is a content hash
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub copies: BTreeSet<String>,
+    /// Content hash of each symlinked entry in symlinks/ as of its last add, restore or rehash
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub contents: BTreeMap<String, String>,
 }
 
 impl SproutLock {
@@ -13,6 +13,8 @@ fn test_lockfile_operations() {
     lock.set_module_state("test@1.0".to_string(), PackageState {
         fetch_hash: None,
         build_hash: Some("hash123".to_string()),
+        resolved_commit: None,
+        source_hash: None,
     });
     assert_eq!(lock.get_module_state("test@1.0").unwrap().build_hash, Some("hash123".to_string()));
     
@@ -29,6 +31,8 @@ fn test_lockfile_save_load() {
     lock.set_module_state("test@1.0".to_string(), PackageState {
         fetch_hash: None,
         build_hash: Some("hash123".to_string()),
+        resolved_commit: None,
+        source_hash: None,
     });
     lock.symlinks.insert(".zshrc".to_string(), "symlink_hash".to_string());
     
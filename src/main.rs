@@ -2,6 +2,8 @@ mod ast;
 mod cli;
 mod cli_tests;
 mod core;
+mod error;
+mod lint;
 mod lockfile;
 mod manifest;
 mod parser;
@@ -11,10 +13,10 @@ mod symlink_tests;
 mod tests;
 
 use anyhow::Result;
-use tracing_subscriber::{FmtSubscriber, filter::LevelFilter};
+use tracing_subscriber::filter::LevelFilter;
 use clap::Parser;
 
-use cli::{Cli, run_cli};
+use cli::{Cli, log_subscriber, run_cli};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -32,15 +34,14 @@ async fn main() -> Result<()> {
         }
     };
 
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(level)
-        .with_writer(std::io::stderr)
-        .without_time()
-        .with_target(false)
-        .finish();
+    let subscriber = log_subscriber(cli.log_format, level, std::io::stderr);
 
     tracing::subscriber::set_global_default(subscriber)
         .expect("setting default subscriber failed");
 
-    run_cli(cli).await
+    if let Err(err) = run_cli(cli).await {
+        eprintln!("Error: {:?}", err);
+        std::process::exit(error::exit_code(&err));
+    }
+    Ok(())
 }
@@ -1,15 +1,49 @@
 use anyhow::{Context, Result};
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tracing::{debug, info};
 
-use crate::ast::{SproutManifest, PrettyPrint};
+use crate::error::{Categorize, ErrorKind};
+use crate::ast::{FetchSpec, ModuleBlock, SproutManifest, PrettyPrint};
 use crate::parser::parse_manifest;
 
-/// Load and parse manifest.sprout
+/// The default manifest file of a sprout dir, `manifest.sprout` inside it
+pub fn manifest_path(sprout_path: &str) -> PathBuf {
+    Path::new(sprout_path).join("manifest.sprout")
+}
+
+/// The manifest file for a sprout dir: the `--manifest-path` override if
+/// given, otherwise `manifest.sprout` inside the sprout dir
+pub fn resolve_manifest_path(sprout_path: &str, override_path: Option<&Path>) -> PathBuf {
+    override_path.map(Path::to_path_buf).unwrap_or_else(|| manifest_path(sprout_path))
+}
+
+/// Load and parse manifest.sprout, expanding `${VAR}` references in values.
+/// The CLI goes through `load_manifest_from` so `--manifest-path` applies
+#[cfg(test)]
 pub fn load_manifest(sprout_path: &str) -> Result<SproutManifest> {
-    let manifest_path = Path::new(sprout_path).join("manifest.sprout");
+    load_manifest_from(&manifest_path(sprout_path), sprout_path)
+}
 
+/// `load_manifest` for a manifest file outside the sprout dir
+pub fn load_manifest_from(manifest_path: &Path, sprout_path: &str) -> Result<SproutManifest> {
+    let mut manifest = load_manifest_raw_from(manifest_path)?;
+    interpolate_manifest(&mut manifest, sprout_path)?;
+    Ok(manifest)
+}
+
+/// Load and parse manifest.sprout without variable expansion.
+///
+/// Use this when the manifest is modified and saved again, so that `${VAR}`
+/// references are written back verbatim instead of their expanded values.
+#[cfg(test)]
+pub fn load_manifest_raw(sprout_path: &str) -> Result<SproutManifest> {
+    load_manifest_raw_from(&manifest_path(sprout_path))
+}
+
+/// `load_manifest_raw` for a manifest file outside the sprout dir
+pub fn load_manifest_raw_from(manifest_path: &Path) -> Result<SproutManifest> {
     debug!("Loading manifest from: {}", manifest_path.display());
 
     if !manifest_path.exists() {
@@ -20,13 +54,15 @@ pub fn load_manifest(sprout_path: &str) -> Result<SproutManifest> {
         });
     }
 
-    let content = fs::read_to_string(&manifest_path)
+    let content = fs::read_to_string(manifest_path)
         .with_context(|| format!("Failed to read manifest: {}", manifest_path.display()))?;
 
     debug!("Manifest content length: {} bytes", content.len());
     debug!("Manifest content:\n{}", content);
 
-    let manifest = parse_manifest(&content).with_context(|| "Failed to parse manifest.sprout")?;
+    let manifest = parse_manifest(&content)
+        .with_context(|| "Failed to parse manifest.sprout")
+        .categorize(ErrorKind::Parse)?;
 
     info!(
         "Successfully loaded manifest with {} modules",
@@ -49,6 +85,113 @@ pub fn load_manifest(sprout_path: &str) -> Result<SproutManifest> {
     Ok(manifest)
 }
 
+/// Expand `${VAR}` references in manifest values (not in build commands, which
+/// bash expands at runtime). Sprout variables (`SPROUT_PATH`, `SPROUT_DIST`,
+/// `DIST_PATH`, `SOURCE_PATH`) take precedence over the process environment.
+fn interpolate_manifest(manifest: &mut SproutManifest, sprout_path: &str) -> Result<()> {
+    let sprout_dist = Path::new(sprout_path).join("dist");
+
+    for module in &mut manifest.modules {
+        let mut vars = HashMap::new();
+        vars.insert("SPROUT_PATH", sprout_path.to_string());
+        vars.insert("SPROUT_DIST", sprout_dist.display().to_string());
+
+        interpolate_module(module, &vars)
+            .with_context(|| format!("Failed to expand variables in module {}", module.id()))?;
+    }
+
+    Ok(())
+}
+
+fn interpolate_module(module: &mut ModuleBlock, vars: &HashMap<&str, String>) -> Result<()> {
+    if let Some(fetch) = &mut module.fetch {
+        match &mut fetch.spec {
+            FetchSpec::Git(git) => {
+                git.url = interpolate(&git.url, vars, true)?;
+                if let Some(ref_) = &git.ref_ {
+                    git.ref_ = Some(interpolate(ref_, vars, true)?);
+                }
+            }
+            FetchSpec::Http(http) => {
+                http.url = interpolate(&http.url, vars, true)?;
+            }
+            FetchSpec::Local(local) => {
+                local.path = interpolate(&local.path, vars, true)?;
+            }
+            FetchSpec::Rsync(rsync) => {
+                rsync.url = interpolate(&rsync.url, vars, true)?;
+            }
+            FetchSpec::Github(github) => {
+                github.repo = interpolate(&github.repo, vars, true)?;
+                github.tag = interpolate(&github.tag, vars, true)?;
+            }
+        }
+        if let Some(output) = &fetch.output {
+            fetch.output = Some(interpolate(output, vars, true)?);
+        }
+    }
+
+    // Module-scoped paths are only known once the fetch spec is expanded
+    let sprout_path = &vars["SPROUT_PATH"];
+    let mut vars = vars.clone();
+    vars.insert("DIST_PATH", crate::core::get_dist_path(sprout_path, module).display().to_string());
+    vars.insert("SOURCE_PATH", crate::core::get_source_path(sprout_path, module).display().to_string());
+
+    for export in &mut module.provides {
+        export.value = interpolate(&export.value, &vars, true)?;
+    }
+    for script in [&mut module.build, &mut module.update].into_iter().flatten() {
+        // Script env is exported into the build shell, so anything that is not
+        // a sprout variable (e.g. ${LD_LIBRARY_PATH}) is left for bash
+        for (_, value) in &mut script.env {
+            *value = interpolate(value, &vars, false)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Expand `${NAME}` references in a single value. Anything that is not a plain
+/// variable name (e.g. `${VAR:-default}`) is left for the shell. With `strict`,
+/// names are also looked up in the process environment and unknown ones are an
+/// error; otherwise they are kept verbatim.
+fn interpolate(value: &str, vars: &HashMap<&str, String>, strict: bool) -> Result<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            result.push_str(&rest[start..]);
+            return Ok(result);
+        };
+
+        let name = &after[..end];
+        if !is_variable_name(name) {
+            result.push_str(&rest[start..start + 2 + end + 1]);
+        } else if let Some(expanded) = vars.get(name) {
+            result.push_str(expanded);
+        } else if !strict {
+            result.push_str(&rest[start..start + 2 + end + 1]);
+        } else if let Ok(expanded) = std::env::var(name) {
+            result.push_str(&expanded);
+        } else {
+            return Err(anyhow::anyhow!("Unknown variable '${{{}}}' in \"{}\"", name, value));
+        }
+        rest = &after[end + 1..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 /// Validate manifest for correctness
 fn validate_manifest(manifest: &SproutManifest) -> Result<()> {
     use std::collections::HashSet;
@@ -62,12 +205,34 @@ fn validate_manifest(manifest: &SproutManifest) -> Result<()> {
         }
     }
 
+    // Exports become `export NAME=...` lines, so the name must be a plain shell identifier
+    for pkg in &manifest.modules {
+        if let Some(export) = pkg.provides.iter().find(|e| !is_env_var_name(&e.name)) {
+            return Err(anyhow::anyhow!(
+                "Invalid environment variable name '{}' in provides of package {}",
+                export.name,
+                pkg.id()
+            ));
+        }
+    }
+
     // Validate dependencies
     for pkg in &manifest.modules {
         for dep in &pkg.depends_on {
             // Check existence
-            let dep_exists = manifest.modules.iter().any(|p| p.id() == *dep);
-            if !dep_exists {
+            if manifest.find_module(dep).is_none() {
+                let versions: Vec<String> = manifest.modules.iter()
+                    .filter(|p| p.name == *dep)
+                    .map(|p| p.id())
+                    .collect();
+                if versions.len() > 1 {
+                    return Err(anyhow::anyhow!(
+                        "Ambiguous dependency '{}' for package {}: specify one of {}",
+                        dep,
+                        pkg.id(),
+                        versions.join(", ")
+                    ));
+                }
                 return Err(anyhow::anyhow!(
                     "Dependency '{}' not found for package {}",
                     dep,
@@ -77,15 +242,30 @@ fn validate_manifest(manifest: &SproutManifest) -> Result<()> {
         }
     }
 
+    // Reject dependency cycles up front rather than on the first build
+    crate::core::deps::resolve_dependency_order(manifest)?;
+
     Ok(())
 }
 
+/// POSIX environment variable name: `[A-Za-z_][A-Za-z0-9_]*`
+fn is_env_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 /// Save manifest to manifest.sprout (for programmatic modifications)
+#[cfg(test)]
 pub fn save_manifest(sprout_path: &str, manifest: &SproutManifest) -> Result<()> {
-    let manifest_path = Path::new(sprout_path).join("manifest.sprout");
+    save_manifest_to(&manifest_path(sprout_path), manifest)
+}
+
+/// `save_manifest` for a manifest file outside the sprout dir
+pub fn save_manifest_to(manifest_path: &Path, manifest: &SproutManifest) -> Result<()> {
     let content = manifest.pretty_print();
 
-    fs::write(&manifest_path, content)
+    fs::write(manifest_path, content)
         .with_context(|| format!("Failed to write manifest: {}", manifest_path.display()))?;
 
     Ok(())
@@ -101,8 +281,16 @@ mod tests {
     fn create_test_manifest() -> SproutManifest {
         let dep_module = ModuleBlock {
             name: "dep1".to_string(),
+            version: None,
+            description: None,
+            tags: Vec::new(),
             depends_on: vec![],
             provides: vec![],
+            produces: vec![],
+            requires: vec![],
+            build_dir: None,
+            out_of_tree: false,
+            isolated: false,
             fetch: None,
             build: None,
             update: None,
@@ -110,8 +298,16 @@ mod tests {
 
         let module = ModuleBlock {
             name: "test".to_string(),
+            version: None,
+            description: None,
+            tags: Vec::new(),
             depends_on: vec!["dep1".to_string()],
             provides: vec![Export { mode: ExportMode::Prepend, name: "PATH".to_string(), value: "/bin".to_string() }],
+            produces: vec![],
+            requires: vec![],
+            build_dir: None,
+            out_of_tree: false,
+            isolated: false,
             fetch: Some(FetchBlock {
                 spec: FetchSpec::Git(GitSpec {
                     url: "https://example.com/repo.git".to_string(),
@@ -119,10 +315,14 @@ mod tests {
                     recursive: false,
                 }),
                 output: None,
+                extract: true,
+                discard_git: false,
+                timeout: None,
             }),
             build: Some(ScriptBlock {
                 env: vec![("CC".to_string(), "gcc".to_string())],
                 commands: vec!["make".to_string()],
+                timeout: None,
             }),
             update: None,
         };
@@ -136,6 +336,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_validate_rejects_invalid_export_names() {
+        let mut manifest = create_test_manifest();
+        manifest.modules[1].provides[0].name = "LD_LIBRARY_PATH".to_string();
+        assert!(validate_manifest(&manifest).is_ok());
+
+        for name in ["PA TH", "2FOO", "FOO=BAR", "PA-TH", ""] {
+            manifest.modules[1].provides[0].name = name.to_string();
+            let err = validate_manifest(&manifest).unwrap_err();
+            assert!(err.to_string().contains("Invalid environment variable name"), "{}: {}", name, err);
+        }
+    }
+
     #[test]
     fn test_serialize_manifest() {
         let manifest = create_test_manifest();
@@ -177,4 +390,101 @@ mod tests {
         assert!(manifest.modules.is_empty());
         assert!(manifest.environments.is_none());
     }
+
+    #[test]
+    fn test_load_cyclic_manifest_errors_with_cycle() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module zlib { depends_on = [] }
+module a { depends_on = [zlib, b] }
+module b { depends_on = [c] }
+module c { depends_on = [a] }
+"#).unwrap();
+
+        let err = load_manifest(sprout_path).unwrap_err();
+        assert!(format!("{:#}", err).contains("Circular dependency detected: a -> b -> c -> a"), "{:#}", err);
+    }
+
+    #[test]
+    fn test_load_manifest_from_other_location() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout");
+        let sprout_path = sprout_path.to_str().unwrap();
+        let manifest_path = temp_dir.path().join("elsewhere/alt.sprout");
+        fs::create_dir_all(manifest_path.parent().unwrap()).unwrap();
+
+        fs::write(&manifest_path, r#"
+module tool {
+    depends_on = []
+    build {
+        env {
+            PREFIX = "${SPROUT_PATH}/opt"
+        }
+        make install
+    }
+}
+"#).unwrap();
+
+        // Variables still expand against the sprout dir, not the manifest's
+        let manifest = load_manifest_from(&manifest_path, sprout_path).unwrap();
+        assert_eq!(manifest.modules[0].build.as_ref().unwrap().env[0].1, format!("{}/opt", sprout_path));
+        assert!(load_manifest(sprout_path).unwrap().modules.is_empty());
+
+        save_manifest_to(&manifest_path, &manifest).unwrap();
+        assert_eq!(load_manifest_from(&manifest_path, sprout_path).unwrap().modules[0].name, "tool");
+    }
+
+    #[test]
+    fn test_interpolate_env_value() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module tool {
+    depends_on = []
+    build {
+        env {
+            PREFIX = "${SPROUT_PATH}/opt"
+            LIBDIR = "${DIST_PATH}/lib"
+            DEFAULTED = "${CFLAGS:-none}"
+            LD_LIBRARY_PATH = "${DIST_PATH}/lib:${SPROUT_SURELY_UNDEFINED_VARIABLE}"
+        }
+        make install PREFIX=${DIST_PATH}
+    }
+}
+"#).unwrap();
+
+        let manifest = load_manifest(sprout_path).unwrap();
+        let build = manifest.modules[0].build.as_ref().unwrap();
+        assert_eq!(build.env[0].1, format!("{}/opt", sprout_path));
+        assert_eq!(build.env[1].1, format!("{}/dist/tool/lib", sprout_path));
+        // Shell-only constructs and build commands are left for bash
+        assert_eq!(build.env[2].1, "${CFLAGS:-none}");
+        assert_eq!(build.env[3].1, format!("{}/dist/tool/lib:${{SPROUT_SURELY_UNDEFINED_VARIABLE}}", sprout_path));
+        assert_eq!(build.commands[0], "make install PREFIX=${DIST_PATH}");
+
+        // The raw manifest keeps the references so saving never bakes them in
+        let raw = load_manifest_raw(sprout_path).unwrap();
+        assert_eq!(raw.modules[0].build.as_ref().unwrap().env[0].1, "${SPROUT_PATH}/opt");
+    }
+
+    #[test]
+    fn test_interpolate_unknown_variable_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().to_str().unwrap();
+
+        fs::write(temp_dir.path().join("manifest.sprout"), r#"
+module tool {
+    depends_on = []
+    provides = {
+        set TOOL_HOME = "${SPROUT_SURELY_UNDEFINED_VARIABLE}/tool"
+    }
+}
+"#).unwrap();
+
+        let err = load_manifest(sprout_path).unwrap_err();
+        assert!(format!("{:#}", err).contains("SPROUT_SURELY_UNDEFINED_VARIABLE"), "unexpected error: {:#}", err);
+    }
 }
@@ -10,11 +10,31 @@ use crate::ast::*;
 #[grammar = "parser/sprout.pest"]
 pub struct SproutParser;
 
+/// A parse error as "line L, column C: message" plus the offending line with a caret
+fn describe_parse_error(error: &pest::error::Error<Rule>) -> anyhow::Error {
+    use pest::error::LineColLocation;
+
+    let (line, column) = match error.line_col {
+        LineColLocation::Pos(pos) | LineColLocation::Span(pos, _) => pos,
+    };
+    let gutter = " ".repeat(line.to_string().len());
+    anyhow!(
+        "Parse error at line {}, column {}: {}\n{} |\n{} | {}\n{} | {}^",
+        line,
+        column,
+        error.variant.message(),
+        gutter,
+        line,
+        error.line().trim_end(),
+        gutter,
+        " ".repeat(column.saturating_sub(1))
+    )
+}
+
 pub fn parse_manifest(input: &str) -> Result<SproutManifest> {
     debug!("Starting to parse manifest");
 
-    let pairs =
-        SproutParser::parse(Rule::manifest, input).map_err(|e| anyhow!("Parse error: {}", e))?;
+    let pairs = SproutParser::parse(Rule::manifest, input).map_err(|e| describe_parse_error(&e))?;
 
     let mut modules = Vec::new();
     let mut environments = None;
@@ -85,10 +105,20 @@ fn parse_module_block(pair: pest::iterators::Pair<Rule>) -> Result<ModuleBlock>
 
     // Parse package ID (name)
     let module_id = inner.next().ok_or_else(|| anyhow!("Missing package ID"))?;
-    let name = module_id.as_str().to_string();
+    let (name, version) = match module_id.as_str().split_once('@') {
+        Some((name, version)) => (name.to_string(), Some(version.to_string())),
+        None => (module_id.as_str().to_string(), None),
+    };
 
     let mut depends_on = Vec::new();
     let mut provides = Vec::new();
+    let mut produces = Vec::new();
+    let mut requires = Vec::new();
+    let mut description = None;
+    let mut tags = Vec::new();
+    let mut build_dir = None;
+    let mut out_of_tree = false;
+    let mut isolated = false;
     let mut fetch = None;
     let mut build = None;
 
@@ -111,6 +141,34 @@ fn parse_module_block(pair: pest::iterators::Pair<Rule>) -> Result<ModuleBlock>
                         debug!("Parsing provides field");
                         provides = parse_provides_map(inner_field.into_inner().next().unwrap())?;
                     }
+                    Rule::produces_field => {
+                        debug!("Parsing produces field");
+                        produces = parse_array(inner_field.into_inner().next().unwrap())?;
+                    }
+                    Rule::requires_field => {
+                        debug!("Parsing requires field");
+                        requires = parse_array(inner_field.into_inner().next().unwrap())?;
+                    }
+                    Rule::build_dir_field => {
+                        debug!("Parsing build_dir field");
+                        build_dir = Some(parse_value(inner_field.into_inner().next().unwrap())?);
+                    }
+                    Rule::description_field => {
+                        debug!("Parsing description field");
+                        description = Some(parse_string(inner_field.into_inner().next().unwrap())?);
+                    }
+                    Rule::tags_field => {
+                        debug!("Parsing tags field");
+                        tags = parse_array(inner_field.into_inner().next().unwrap())?;
+                    }
+                    Rule::out_of_tree_field => {
+                        debug!("Parsing out_of_tree field");
+                        out_of_tree = inner_field.into_inner().next().unwrap().as_str() == "true";
+                    }
+                    Rule::isolated_field => {
+                        debug!("Parsing isolated field");
+                        isolated = inner_field.into_inner().next().unwrap().as_str() == "true";
+                    }
                     Rule::fetch_block => {
                         debug!("Parsing fetch block");
                         fetch = Some(parse_fetch_block(inner_field)?);
@@ -140,6 +198,34 @@ fn parse_module_block(pair: pest::iterators::Pair<Rule>) -> Result<ModuleBlock>
                 debug!("Parsing provides field");
                 provides = parse_provides_map(field.into_inner().next().unwrap())?;
             }
+            Rule::produces_field => {
+                debug!("Parsing produces field");
+                produces = parse_array(field.into_inner().next().unwrap())?;
+            }
+            Rule::requires_field => {
+                debug!("Parsing requires field");
+                requires = parse_array(field.into_inner().next().unwrap())?;
+            }
+            Rule::build_dir_field => {
+                debug!("Parsing build_dir field");
+                build_dir = Some(parse_value(field.into_inner().next().unwrap())?);
+            }
+            Rule::description_field => {
+                debug!("Parsing description field");
+                description = Some(parse_string(field.into_inner().next().unwrap())?);
+            }
+            Rule::tags_field => {
+                debug!("Parsing tags field");
+                tags = parse_array(field.into_inner().next().unwrap())?;
+            }
+            Rule::out_of_tree_field => {
+                debug!("Parsing out_of_tree field");
+                out_of_tree = field.into_inner().next().unwrap().as_str() == "true";
+            }
+            Rule::isolated_field => {
+                debug!("Parsing isolated field");
+                isolated = field.into_inner().next().unwrap().as_str() == "true";
+            }
             Rule::fetch_block => {
                 debug!("Parsing fetch block");
                 fetch = Some(parse_fetch_block(field)?);
@@ -161,8 +247,16 @@ fn parse_module_block(pair: pest::iterators::Pair<Rule>) -> Result<ModuleBlock>
 
     Ok(ModuleBlock {
         name,
+        version,
+        description,
+        tags,
         depends_on,
         provides,
+        produces,
+        requires,
+        build_dir,
+        out_of_tree,
+        isolated,
         fetch,
         build,
         update,
@@ -172,6 +266,9 @@ fn parse_module_block(pair: pest::iterators::Pair<Rule>) -> Result<ModuleBlock>
 fn parse_fetch_block(pair: pest::iterators::Pair<Rule>) -> Result<FetchBlock> {
     let mut spec = None;
     let mut output = None;
+    let mut extract = true;
+    let mut discard_git = false;
+    let mut timeout = None;
 
     for field in pair.into_inner() {
         match field.as_rule() {
@@ -182,9 +279,20 @@ fn parse_fetch_block(pair: pest::iterators::Pair<Rule>) -> Result<FetchBlock> {
                         let value = inner.into_inner().next().ok_or_else(|| anyhow!("Missing output value"))?;
                         output = Some(parse_value(value)?);
                     }
+                    Rule::fetch_extract_field => {
+                        let value = inner.into_inner().next().ok_or_else(|| anyhow!("Missing extract value"))?;
+                        extract = value.as_str() == "true";
+                    }
+                    Rule::fetch_discard_git_field => {
+                        let value = inner.into_inner().next().ok_or_else(|| anyhow!("Missing discard_git value"))?;
+                        discard_git = value.as_str() == "true";
+                    }
                     Rule::fetch_spec => {
                         spec = Some(parse_fetch_spec(inner)?);
                     }
+                    Rule::timeout_field => {
+                        timeout = Some(parse_timeout_field(inner)?);
+                    }
                     _ => {}
                 }
             }
@@ -195,6 +303,9 @@ fn parse_fetch_block(pair: pest::iterators::Pair<Rule>) -> Result<FetchBlock> {
     Ok(FetchBlock {
         spec: spec.ok_or_else(|| anyhow!("Missing fetch spec"))?,
         output,
+        extract,
+        discard_git,
+        timeout,
     })
 }
 
@@ -272,16 +383,73 @@ fn parse_fetch_spec(fetch_spec: pest::iterators::Pair<Rule>) -> Result<FetchSpec
         }
         Rule::local_spec => {
             let mut path = None;
+            let mut sha256 = None;
 
             for field in inner_spec.into_inner() {
                 if field.as_rule() == Rule::local_field {
-                    let value = field.into_inner().next().unwrap();
-                    path = Some(parse_value(value)?);
+                    let inner_field = field.into_inner().next().unwrap();
+                    match inner_field.as_rule() {
+                        Rule::local_path_field => {
+                            let value = inner_field.into_inner().next().unwrap();
+                            path = Some(parse_value(value)?);
+                        }
+                        Rule::local_sha256_field => {
+                            let value = inner_field.into_inner().next().unwrap();
+                            sha256 = Some(parse_value(value)?);
+                        }
+                        _ => {}
+                    }
                 }
             }
 
             Ok(FetchSpec::Local(LocalSpec {
                 path: path.ok_or_else(|| anyhow!("Local spec missing path"))?,
+                sha256,
+            }))
+        }
+        Rule::rsync_spec => {
+            let mut url = None;
+
+            for field in inner_spec.into_inner() {
+                if field.as_rule() == Rule::rsync_field {
+                    let inner_field = field.into_inner().next().unwrap();
+                    if inner_field.as_rule() == Rule::rsync_url_field {
+                        let value = inner_field.into_inner().next().unwrap();
+                        url = Some(parse_value(value)?);
+                    }
+                }
+            }
+
+            Ok(FetchSpec::Rsync(RsyncSpec {
+                url: url.ok_or_else(|| anyhow!("Rsync spec missing url"))?,
+            }))
+        }
+        Rule::github_spec => {
+            let mut repo = None;
+            let mut tag = None;
+            let mut asset = None;
+            let mut sha256 = None;
+
+            for field in inner_spec.into_inner() {
+                if field.as_rule() == Rule::github_field {
+                    let inner_field = field.into_inner().next().unwrap();
+                    let rule = inner_field.as_rule();
+                    let value = parse_value(inner_field.into_inner().next().unwrap())?;
+                    match rule {
+                        Rule::github_repo_field => repo = Some(value),
+                        Rule::github_tag_field => tag = Some(value),
+                        Rule::github_asset_field => asset = Some(value),
+                        Rule::github_sha256_field => sha256 = Some(value),
+                        _ => {}
+                    }
+                }
+            }
+
+            Ok(FetchSpec::Github(GithubSpec {
+                repo: repo.ok_or_else(|| anyhow!("GitHub spec missing repo"))?,
+                tag: tag.ok_or_else(|| anyhow!("GitHub spec missing tag"))?,
+                asset: asset.ok_or_else(|| anyhow!("GitHub spec missing asset"))?,
+                sha256,
             }))
         }
         _ => {
@@ -296,6 +464,7 @@ fn parse_fetch_spec(fetch_spec: pest::iterators::Pair<Rule>) -> Result<FetchSpec
 fn parse_script_block(pair: pest::iterators::Pair<Rule>) -> Result<ScriptBlock> {
     let mut env = Vec::new();
     let mut commands = Vec::new();
+    let mut timeout = None;
 
     debug!("Parsing script block, rule: {:?}", pair.as_rule());
     for inner in pair.into_inner() {
@@ -323,6 +492,9 @@ fn parse_script_block(pair: pest::iterators::Pair<Rule>) -> Result<ScriptBlock>
                                 commands.push(cmd.to_string());
                             }
                         }
+                        Rule::timeout_field => {
+                            timeout = Some(parse_timeout_field(content_inner)?);
+                        }
                         Rule::env_block => {
                             debug!("Found env_block inside script_content");
                             for env_entry in content_inner.into_inner() {
@@ -341,7 +513,29 @@ fn parse_script_block(pair: pest::iterators::Pair<Rule>) -> Result<ScriptBlock>
         }
     }
 
-    Ok(ScriptBlock { env, commands })
+    Ok(ScriptBlock { env, commands, timeout })
+}
+
+/// Parse `timeout = <duration>` into seconds
+fn parse_timeout_field(pair: pest::iterators::Pair<Rule>) -> Result<u64> {
+    let duration = pair.into_inner().next().ok_or_else(|| anyhow!("Missing timeout value"))?;
+    parse_duration(duration.as_str())
+}
+
+/// Parse a duration like `90`, `45s`, `30m` or `2h` into seconds
+pub fn parse_duration(text: &str) -> Result<u64> {
+    let (digits, unit) = match text.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&text[..i], c),
+        _ => (text, 's'),
+    };
+    let value: u64 = digits.parse().map_err(|_| anyhow!("Invalid duration: {}", text))?;
+    let factor = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        _ => return Err(anyhow!("Invalid duration unit in {} (use s, m or h)", text)),
+    };
+    value.checked_mul(factor).ok_or_else(|| anyhow!("Duration too large: {}", text))
 }
 
 fn parse_environments_block(pair: pest::iterators::Pair<Rule>) -> Result<EnvironmentsBlock> {
@@ -1,5 +1,6 @@
 use super::*;
 use crate::ast::FetchSpec;
+use crate::ast::PrettyPrint;
 use pest::Parser;
 
 #[test]
@@ -159,3 +160,272 @@ module localproject {
         _ => panic!("Expected local fetch spec"),
     }
 }
+
+#[test]
+fn test_parse_manifest_with_produces() {
+    let manifest_content = r#"
+module foo {
+    depends_on = []
+    produces = ["bin/foo", "lib/libbar.so"]
+    build_dir = "cmd/foo"
+    out_of_tree = true
+    isolated = true
+    build {
+        make install PREFIX=${DIST_PATH}
+    }
+}
+"#;
+
+    let manifest = parse_manifest(manifest_content).unwrap();
+    let package = &manifest.modules[0];
+    assert_eq!(package.produces, vec!["bin/foo", "lib/libbar.so"]);
+    assert_eq!(package.build_dir.as_deref(), Some("cmd/foo"));
+    assert!(package.out_of_tree);
+    assert!(package.isolated);
+
+    // Round-trip through the pretty printer
+    let reparsed = parse_manifest(&manifest.pretty_print()).unwrap();
+    assert_eq!(reparsed.modules[0].produces, package.produces);
+    assert_eq!(reparsed.modules[0].build_dir, package.build_dir);
+    assert!(reparsed.modules[0].out_of_tree);
+    assert!(reparsed.modules[0].isolated);
+}
+
+#[test]
+fn test_parse_module_requires() {
+    let manifest_content = r#"
+module zstd {
+    depends_on = []
+    requires = ["cmake", "pkg-config"]
+}
+"#;
+
+    let manifest = parse_manifest(manifest_content).unwrap();
+    let package = &manifest.modules[0];
+    assert_eq!(package.requires, vec!["cmake", "pkg-config"]);
+    assert!(package.pretty_print().contains(r#"requires = ["cmake", "pkg-config"]"#));
+    assert_eq!(parse_manifest(&package.pretty_print()).unwrap().modules[0], *package);
+}
+
+#[test]
+fn test_parse_module_description() {
+    let manifest_content = r#"
+module ripgrep {
+    depends_on = [rust]
+    description = "Fast recursive grep, \"rg\" on the PATH"
+}
+"#;
+
+    let manifest = parse_manifest(manifest_content).unwrap();
+    let package = &manifest.modules[0];
+    assert_eq!(package.description.as_deref(), Some(r#"Fast recursive grep, "rg" on the PATH"#));
+
+    // The description is emitted first, right after the module header
+    let printed = package.pretty_print();
+    let lines: Vec<_> = printed.lines().collect();
+    assert_eq!(lines[1], r#"    description = "Fast recursive grep, \"rg\" on the PATH""#);
+    assert_eq!(lines[2], "    depends_on = [rust]");
+    assert_eq!(parse_manifest(&printed).unwrap().modules[0], *package);
+}
+
+#[test]
+fn test_parse_versioned_module_ids() {
+    let manifest_content = r#"
+module gcc@11.4.0 {
+    depends_on = []
+}
+
+module gcc@13 {
+    depends_on = []
+}
+
+module app {
+    depends_on = ["gcc@13"]
+}
+"#;
+
+    let manifest = parse_manifest(manifest_content).unwrap();
+    let gcc11 = manifest.find_module("gcc@11.4.0").unwrap();
+    assert_eq!(gcc11.name, "gcc");
+    assert_eq!(gcc11.version.as_deref(), Some("11.4.0"));
+    assert_eq!(manifest.find_module("gcc@13").unwrap().version.as_deref(), Some("13"));
+    assert_eq!(manifest.find_module("app").unwrap().version, None);
+
+    let reparsed = parse_manifest(&manifest.pretty_print()).unwrap();
+    assert!(reparsed.find_module("gcc@11.4.0").is_some());
+}
+
+#[test]
+fn test_parse_timeouts_with_duration_suffixes() {
+    let manifest_content = r#"
+module llvm {
+    depends_on = []
+    fetch {
+        git = { url = "https://github.com/llvm/llvm-project.git" }
+        timeout = 30m
+    }
+    build {
+        timeout = 2h
+        make
+    }
+}
+"#;
+
+    let manifest = parse_manifest(manifest_content).unwrap();
+    let package = &manifest.modules[0];
+    assert_eq!(package.fetch.as_ref().unwrap().timeout, Some(1800));
+    let build = package.build.as_ref().unwrap();
+    assert_eq!(build.timeout, Some(7200));
+    assert_eq!(build.commands, vec!["make"]);
+    assert_eq!(parse_manifest(&package.pretty_print()).unwrap().modules[0], *package);
+
+    assert_eq!(parse_duration("90").unwrap(), 90);
+    assert_eq!(parse_duration("45s").unwrap(), 45);
+    assert_eq!(parse_duration("30m").unwrap(), 1800);
+    assert_eq!(parse_duration("2h").unwrap(), 7200);
+    assert!(parse_duration("2d").is_err());
+    assert_eq!(crate::ast::format_duration(90), "90s");
+    assert_eq!(crate::ast::format_duration(7200), "2h");
+}
+
+#[test]
+fn test_parse_environment_wildcard() {
+    let manifest = parse_manifest(r#"
+environments {
+    default = [*]
+    dev = [rg, *]
+}
+"#).unwrap();
+
+    let environments = &manifest.environments.as_ref().unwrap().environments;
+    assert_eq!(environments["default"], vec![crate::ast::ALL_BUILT_MODULES]);
+    assert_eq!(environments["dev"], vec!["rg", "*"]);
+    assert_eq!(parse_manifest(&manifest.pretty_print()).unwrap(), manifest);
+}
+
+#[test]
+fn test_parse_fetch_extract_flag() {
+    let manifest = parse_manifest(r#"
+module release {
+    depends_on = []
+    fetch {
+        http = {
+            url = "https://example.com/release-1.0.tar.gz"
+        }
+        extract = false
+    }
+}
+"#).unwrap();
+
+    let fetch = manifest.modules[0].fetch.as_ref().unwrap();
+    assert!(!fetch.extract);
+    assert!(manifest.modules[0].pretty_print().contains("extract = false"));
+    assert_eq!(parse_manifest(&manifest.pretty_print()).unwrap(), manifest);
+
+    // Extraction is the default and is not printed
+    let reparsed = parse_manifest(r#"
+module release {
+    depends_on = []
+    fetch {
+        http = {
+            url = "https://example.com/release-1.0.tar.gz"
+        }
+    }
+}
+"#).unwrap();
+    assert!(reparsed.modules[0].fetch.as_ref().unwrap().extract);
+    assert!(!reparsed.modules[0].pretty_print().contains("extract"));
+    assert!(!reparsed.modules[0].fetch.as_ref().unwrap().discard_git);
+}
+
+#[test]
+fn test_parse_rsync_spec_round_trip() {
+    let manifest = parse_manifest(r#"
+module mirrored {
+    depends_on = []
+    fetch {
+        rsync = {
+            url = "rsync://mirror.internal/tools/mirrored-1.0"
+        }
+    }
+}
+"#).unwrap();
+
+    match &manifest.modules[0].fetch.as_ref().unwrap().spec {
+        FetchSpec::Rsync(rsync) => assert_eq!(rsync.url, "rsync://mirror.internal/tools/mirrored-1.0"),
+        other => panic!("Expected rsync fetch spec, got {:?}", other),
+    }
+    assert_eq!(parse_manifest(&manifest.pretty_print()).unwrap(), manifest);
+    assert!(parse_manifest("module m {\n    depends_on = []\n    fetch {\n        rsync = {\n        }\n    }\n}\n").is_err());
+}
+
+#[test]
+fn test_parse_fetch_discard_git() {
+    let manifest = parse_manifest(r#"
+module tool {
+    depends_on = []
+    fetch {
+        git = {
+            url = "https://github.com/user/tool.git"
+        }
+        discard_git = true
+    }
+}
+"#).unwrap();
+
+    assert!(manifest.modules[0].fetch.as_ref().unwrap().discard_git);
+    assert_eq!(parse_manifest(&manifest.pretty_print()).unwrap(), manifest);
+}
+
+#[test]
+fn test_parse_error_points_at_line_and_column() {
+    let err = parse_manifest("module a {\n    depends_on = []\n}\nmodule b {\n    depends_on = []\n    build {\n        make\n    }\n\nmodule c {\n}\n")
+        .unwrap_err()
+        .to_string();
+
+    assert!(err.contains("line 10, column 1"), "unexpected error: {}", err);
+    assert!(err.contains("10 | module c {\n   | ^"), "missing snippet: {}", err);
+}
+
+#[test]
+fn test_parse_tags_round_trip() {
+    let manifest = parse_manifest(r#"
+module ripgrep {
+    description = "Fast recursive grep"
+    tags = ["rust", "cli"]
+    depends_on = []
+}
+"#).unwrap();
+
+    assert_eq!(manifest.modules[0].tags, ["rust", "cli"]);
+    let printed = manifest.pretty_print();
+    assert!(printed.contains("    tags = [\"rust\", \"cli\"]\n"), "{}", printed);
+    assert_eq!(parse_manifest(&printed).unwrap(), manifest);
+}
+
+#[test]
+fn test_parse_github_spec_round_trip() {
+    let manifest = parse_manifest(r#"
+module fd {
+    depends_on = []
+    fetch {
+        github = {
+            repo = "sharkdp/fd"
+            tag = "v8.7.0"
+            asset = "fd-.*-x86_64-unknown-linux-gnu\\.tar\\.gz"
+        }
+    }
+}
+"#).unwrap();
+
+    match &manifest.modules[0].fetch.as_ref().unwrap().spec {
+        FetchSpec::Github(github) => {
+            assert_eq!(github.repo, "sharkdp/fd");
+            assert_eq!(github.tag, "v8.7.0");
+            assert_eq!(github.asset, r"fd-.*-x86_64-unknown-linux-gnu\.tar\.gz");
+            assert_eq!(github.sha256, None);
+        }
+        other => panic!("Expected github fetch spec, got {:?}", other),
+    }
+    assert_eq!(parse_manifest(&manifest.pretty_print()).unwrap(), manifest);
+}
@@ -1,6 +1,8 @@
 #[cfg(test)]
 mod tests {
-    use crate::core::{init_sprout, add_file};
+    use crate::core::{init_sprout, add_file, add_files, diff_symlinks, move_symlink, rehash_symlinks, restore_symlinks, restore_symlinks_with};
+    use crate::core::symlinks::{collect_symlink_statuses, apply_conflict_choice, find_dead_symlinks, find_symlink_conflicts, normalize_path_with, ConflictChoice, plan_add, render_symlink_list, ModificationReason, RestoreSummary, SymlinkStatus};
+    use crate::lockfile::SproutLock;
     use tempfile::TempDir;
     use std::fs;
     use std::path::Path;
@@ -89,12 +91,30 @@ mod tests {
         
         // Add single file using full path
         let bashrc_path = temp_dir.path().join("home/.bashrc");
-        add_file(&sprout_path, bashrc_path, false, false, &tracking_path).unwrap();
+        add_file(&sprout_path, bashrc_path, false, false, false, false, &tracking_path).unwrap();
         
         let state = capture_symlink_state(&sprout_path);
         insta::assert_snapshot!(state);
     }
 
+    #[test]
+    fn test_add_expands_leading_tilde() {
+        let home = dirs::home_dir().unwrap();
+        let home_dir = TempDir::new_in(&home).unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        init_sprout(&sprout_path, false).unwrap();
+
+        let name = home_dir.path().file_name().unwrap().to_string_lossy().to_string();
+        fs::write(home_dir.path().join(".bashrc"), "alias ll='ls -l'\n").unwrap();
+        let quoted = Path::new("~").join(&name).join(".bashrc");
+        add_file(&sprout_path, quoted, false, false, false, false, &home.to_string_lossy()).unwrap();
+
+        let stored = Path::new(&sprout_path).join("symlinks").join(&name).join(".bashrc");
+        assert_eq!(fs::read_to_string(&stored).unwrap(), "alias ll='ls -l'\n");
+        assert!(home_dir.path().join(".bashrc").is_symlink());
+    }
+
     #[test]
     fn test_add_recursive_directory() {
         let temp_dir = TempDir::new().unwrap();
@@ -106,7 +126,7 @@ mod tests {
         
         // Add .config directory recursively using full path
         let config_path = temp_dir.path().join("home/.config");
-        add_file(&sprout_path, config_path, true, false, &tracking_path).unwrap();
+        add_file(&sprout_path, config_path, true, false, false, false, &tracking_path).unwrap();
         
         let state = capture_symlink_state(&sprout_path);
         insta::assert_snapshot!(state);
@@ -123,9 +143,508 @@ mod tests {
         
         // Dry run using full path
         let bashrc_path = temp_dir.path().join("home/.bashrc");
-        add_file(&sprout_path, bashrc_path, false, true, &tracking_path).unwrap();
+        add_file(&sprout_path, bashrc_path, false, false, false, true, &tracking_path).unwrap();
         
         let state = capture_symlink_state(&sprout_path);
         insta::assert_snapshot!(state);
     }
+
+    #[test]
+    fn test_add_multiple_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&sprout_path, false).unwrap();
+
+        let paths = vec![
+            temp_dir.path().join("home/.bashrc"),
+            temp_dir.path().join("home/.zshrc"),
+            temp_dir.path().join("home/.gitconfig"),
+        ];
+        add_files(&sprout_path, paths, false, false, false, false, &tracking_path).unwrap();
+
+        let lock = SproutLock::load(&sprout_path).unwrap();
+        assert!(lock.symlinks.contains_key(".bashrc"));
+        assert!(lock.symlinks.contains_key(".zshrc"));
+        assert!(lock.symlinks.contains_key(".gitconfig"));
+    }
+
+    #[test]
+    fn test_copy_mode_add_status_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&sprout_path, false).unwrap();
+
+        // Add keeps the original as a regular file next to a tracked copy
+        let bashrc_path = temp_dir.path().join("home/.bashrc");
+        add_file(&sprout_path, bashrc_path.clone(), false, true, false, false, &tracking_path).unwrap();
+        assert!(!bashrc_path.is_symlink());
+        assert_eq!(
+            fs::read_to_string(&bashrc_path).unwrap(),
+            fs::read_to_string(Path::new(&sprout_path).join("symlinks/.bashrc")).unwrap()
+        );
+        let lock = SproutLock::load(&sprout_path).unwrap();
+        assert!(lock.copies.contains(".bashrc"));
+
+        let status_of = |path: &str| {
+            collect_symlink_statuses(&sprout_path, &tracking_path).unwrap()
+                .into_iter()
+                .find(|(p, _, _, _)| p == path)
+                .map(|(_, _, status, _)| status)
+                .unwrap()
+        };
+
+        // Status compares content rather than symlink targets
+        assert!(matches!(status_of(".bashrc"), SymlinkStatus::UpToDate));
+        fs::write(&bashrc_path, "# edited locally").unwrap();
+        assert!(matches!(status_of(".bashrc"), SymlinkStatus::Modified { .. }));
+
+        // Restore copies the tracked content back instead of symlinking
+        restore_symlinks(&sprout_path, false, &tracking_path).unwrap();
+        assert!(!bashrc_path.is_symlink());
+        assert_eq!(fs::read_to_string(&bashrc_path).unwrap(), "# Test content for .bashrc");
+        assert!(matches!(status_of(".bashrc"), SymlinkStatus::UpToDate));
+
+        fs::remove_file(&bashrc_path).unwrap();
+        assert!(matches!(status_of(".bashrc"), SymlinkStatus::Deleted));
+        restore_symlinks(&sprout_path, false, &tracking_path).unwrap();
+        assert!(bashrc_path.is_file() && !bashrc_path.is_symlink());
+    }
+
+    #[test]
+    fn test_status_reports_committed_content_change() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&sprout_path, false).unwrap();
+        add_file(&sprout_path, temp_dir.path().join("home/.bashrc"), false, false, false, false, &tracking_path).unwrap();
+        crate::core::git_commit(&sprout_path, "Track .bashrc").unwrap();
+
+        let status_of = |path: &str| {
+            collect_symlink_statuses(&sprout_path, &tracking_path).unwrap()
+                .into_iter()
+                .find(|(p, _, _, _)| p == path)
+                .map(|(_, _, status, _)| status)
+                .unwrap()
+        };
+        assert!(matches!(status_of(".bashrc"), SymlinkStatus::UpToDate));
+
+        // Change and commit the tracked file: git status is clean again, but
+        // the content no longer matches what the lockfile recorded
+        fs::write(Path::new(&sprout_path).join("symlinks/.bashrc"), "# changed upstream").unwrap();
+        crate::core::git_commit(&sprout_path, "Update .bashrc").unwrap();
+        assert!(matches!(
+            status_of(".bashrc"),
+            SymlinkStatus::Modified { reason: ModificationReason::ContentModified }
+        ));
+
+        // Restoring acknowledges the new content
+        restore_symlinks(&sprout_path, false, &tracking_path).unwrap();
+        assert!(matches!(status_of(".bashrc"), SymlinkStatus::UpToDate));
+    }
+
+    #[test]
+    fn test_rehash_prune_drops_deleted_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&sprout_path, false).unwrap();
+        add_file(&sprout_path, temp_dir.path().join("home/.bashrc"), false, false, false, false, &tracking_path).unwrap();
+        add_file(&sprout_path, temp_dir.path().join("home/.zshrc"), false, false, false, false, &tracking_path).unwrap();
+
+        // The user deleted the symlink and does not want it back
+        fs::remove_file(temp_dir.path().join("home/.zshrc")).unwrap();
+        assert_eq!(find_dead_symlinks(&sprout_path, &tracking_path).unwrap(), vec![".zshrc".to_string()]);
+
+        // A dry run only lists what would go
+        rehash_symlinks(&sprout_path, &tracking_path, false, false, true, true).unwrap();
+        assert!(SproutLock::load(&sprout_path).unwrap().symlinks.contains_key(".zshrc"));
+
+        rehash_symlinks(&sprout_path, &tracking_path, false, false, true, false).unwrap();
+        let lock = SproutLock::load(&sprout_path).unwrap();
+        assert!(!lock.symlinks.contains_key(".zshrc"));
+        assert!(!lock.contents.contains_key(".zshrc"));
+        assert!(lock.symlinks.contains_key(".bashrc"));
+    }
+
+    #[test]
+    fn test_add_existing_managed_symlink_is_refused() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+        let bashrc = temp_dir.path().join("home/.bashrc");
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&sprout_path, false).unwrap();
+        add_file(&sprout_path, bashrc.clone(), false, false, false, false, &tracking_path).unwrap();
+
+        let err = add_file(&sprout_path, bashrc.clone(), false, false, false, false, &tracking_path).unwrap_err();
+        assert!(err.to_string().contains("already managed"), "{}", err);
+
+        // The lockfile was lost but the symlink survived
+        SproutLock::default().save(&sprout_path).unwrap();
+        let err = add_file(&sprout_path, bashrc.clone(), false, false, false, false, &tracking_path).unwrap_err();
+        assert!(err.to_string().contains("rehash --discover"), "{}", err);
+
+        assert!(bashrc.is_symlink());
+        let tracked = Path::new(&sprout_path).join("symlinks/.bashrc");
+        assert_eq!(fs::read_to_string(tracked).unwrap(), "# Test content for .bashrc");
+
+        rehash_symlinks(&sprout_path, &tracking_path, true, false, false, false).unwrap();
+        assert!(SproutLock::load(&sprout_path).unwrap().symlinks.contains_key(".bashrc"));
+    }
+
+    #[test]
+    fn test_discover_through_non_canonical_sprout_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let alias = temp_dir.path().join("alias").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+        let bashrc = temp_dir.path().join("home/.bashrc");
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&sprout_path, false).unwrap();
+        std::os::unix::fs::symlink(&sprout_path, &alias).unwrap();
+        add_file(&alias, bashrc.clone(), false, false, false, false, &tracking_path).unwrap();
+        SproutLock::default().save(&alias).unwrap();
+
+        // The link points at the canonical root, the sprout dir is named through the alias
+        rehash_symlinks(&alias, &tracking_path, true, false, false, false).unwrap();
+        assert!(SproutLock::load(&sprout_path).unwrap().symlinks.contains_key(".bashrc"));
+        assert_eq!(fs::read_link(&bashrc).unwrap(), fs::canonicalize(&sprout_path).unwrap().join("symlinks/.bashrc"));
+    }
+
+    #[test]
+    fn test_force_readd_refreshes_tracked_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+        let bashrc = temp_dir.path().join("home/.bashrc");
+        let stored = Path::new(&sprout_path).join("symlinks/.bashrc");
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&sprout_path, false).unwrap();
+        add_file(&sprout_path, bashrc.clone(), false, false, false, false, &tracking_path).unwrap();
+        let old_hash = SproutLock::load(&sprout_path).unwrap().contents[".bashrc"].clone();
+
+        // The stored copy got corrupted; the user puts a good file back in place
+        fs::write(&stored, "garbage").unwrap();
+        fs::remove_file(&bashrc).unwrap();
+        fs::write(&bashrc, "export EDITOR=vim\n").unwrap();
+
+        let err = add_file(&sprout_path, bashrc.clone(), false, false, false, false, &tracking_path).unwrap_err();
+        assert!(err.to_string().contains("--force"), "{}", err);
+
+        add_file(&sprout_path, bashrc.clone(), false, false, true, false, &tracking_path).unwrap();
+        assert_eq!(fs::read_to_string(&stored).unwrap(), "export EDITOR=vim\n");
+        assert!(bashrc.is_symlink());
+        let lock = SproutLock::load(&sprout_path).unwrap();
+        assert_ne!(lock.contents[".bashrc"], old_hash);
+
+        // Overlapping paths are still refused
+        add_file(&sprout_path, temp_dir.path().join("home/.config/nvim"), true, false, false, false, &tracking_path).unwrap();
+        let err = add_file(&sprout_path, temp_dir.path().join("home/.config"), true, false, true, false, &tracking_path).unwrap_err();
+        assert!(err.to_string().contains("child path"), "{}", err);
+    }
+
+    #[test]
+    fn test_restore_summary_counts() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&sprout_path, false).unwrap();
+        add_file(&sprout_path, temp_dir.path().join("home/.bashrc"), false, false, false, false, &tracking_path).unwrap();
+        add_file(&sprout_path, temp_dir.path().join("home/.zshrc"), false, false, false, false, &tracking_path).unwrap();
+
+        // .zshrc now dangles somewhere else, .bashrc is still correct
+        let zshrc = temp_dir.path().join("home/.zshrc");
+        fs::remove_file(&zshrc).unwrap();
+        std::os::unix::fs::symlink(temp_dir.path().join("missing"), &zshrc).unwrap();
+
+        let summary = restore_symlinks(&sprout_path, false, &tracking_path).unwrap();
+        assert_eq!(summary, RestoreSummary { restored: vec![".zshrc".to_string()], unchanged: 1, failed: vec![], skipped: vec![] });
+        assert_eq!(fs::read_to_string(&zshrc).unwrap(), "# Test content for .zshrc");
+
+        let summary = restore_symlinks(&sprout_path, false, &tracking_path).unwrap();
+        assert_eq!(summary.unchanged, 2);
+        assert!(summary.restored.is_empty());
+    }
+
+    #[test]
+    fn test_add_dry_run_plan_shows_destination_and_link() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+        let init_vim = temp_dir.path().join("home/.config/nvim/init.vim");
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&sprout_path, false).unwrap();
+
+        let plan = plan_add(&sprout_path, init_vim.clone(), false, &tracking_path).unwrap();
+        assert_eq!(plan.relative, ".config/nvim/init.vim");
+
+        let stored = Path::new(&sprout_path).join("symlinks/.config/nvim/init.vim");
+        let linked = fs::canonicalize(&sprout_path).unwrap().join("symlinks/.config/nvim/init.vim");
+        let description = plan.describe(false);
+        assert!(description.contains(&format!("Would copy to: {}", stored.display())), "{}", description);
+        assert!(description.contains(&format!("Would symlink: {} -> {}", init_vim.display(), linked.display())), "{}", description);
+
+        // The dry run itself leaves everything in place
+        add_file(&sprout_path, init_vim.clone(), false, false, false, true, &tracking_path).unwrap();
+        assert!(!init_vim.is_symlink());
+        assert!(!stored.exists());
+    }
+
+    #[test]
+    fn test_list_shows_every_tracked_entry() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&sprout_path, false).unwrap();
+        add_file(&sprout_path, temp_dir.path().join("home/.bashrc"), false, false, false, false, &tracking_path).unwrap();
+        add_file(&sprout_path, temp_dir.path().join("home/.zshrc"), false, true, false, false, &tracking_path).unwrap();
+        add_file(&sprout_path, temp_dir.path().join("home/.config/nvim"), true, false, false, false, &tracking_path).unwrap();
+
+        let lock = SproutLock::load(&sprout_path).unwrap();
+        let listing = render_symlink_list(&lock, false).unwrap();
+        assert_eq!(listing.lines().count(), lock.symlinks.len());
+        for (path, hash) in &lock.symlinks {
+            assert!(listing.contains(&format!("{} -> symlinks/{} [{}]", path, path, hash)), "{}", listing);
+        }
+        assert!(listing.contains(".zshrc -> symlinks/.zshrc"));
+        assert!(listing.lines().any(|l| l.starts_with(".zshrc") && l.ends_with("(copy)")));
+
+        let json: serde_json::Value = serde_json::from_str(&render_symlink_list(&lock, true).unwrap()).unwrap();
+        let entries = json.as_array().unwrap();
+        assert_eq!(entries.len(), lock.symlinks.len());
+        assert!(entries.iter().any(|e| e["path"] == ".bashrc" && e["target"] == "symlinks/.bashrc" && e["copy"] == false));
+    }
+
+    #[test]
+    fn test_move_relocates_tracked_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+        let home = temp_dir.path().join("home");
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&sprout_path, false).unwrap();
+        add_file(&sprout_path, home.join(".bashrc"), false, false, false, false, &tracking_path).unwrap();
+
+        let err = move_symlink(&sprout_path, &home.join(".bashrc"), &temp_dir.path().join("elsewhere"), false, &tracking_path).unwrap_err();
+        assert!(err.to_string().contains("not within the tracking directory"), "{}", err);
+
+        move_symlink(&sprout_path, &home.join(".bashrc"), &home.join(".config/bash/bashrc"), false, &tracking_path).unwrap();
+
+        let old = home.join(".bashrc");
+        let new = home.join(".config/bash/bashrc");
+        assert!(!old.exists() && !old.is_symlink(), "old symlink should be gone");
+        assert!(new.is_symlink());
+        assert_eq!(fs::read_to_string(&new).unwrap(), "# Test content for .bashrc");
+        assert!(Path::new(&sprout_path).join("symlinks/.config/bash/bashrc").is_file());
+        assert!(!Path::new(&sprout_path).join("symlinks/.bashrc").exists());
+
+        let lock = SproutLock::load(&sprout_path).unwrap();
+        assert!(!lock.symlinks.contains_key(".bashrc"));
+        assert!(lock.contents.contains_key(".config/bash/bashrc"));
+        let status = collect_symlink_statuses(&sprout_path, &tracking_path).unwrap()
+            .into_iter()
+            .find(|(p, _, _, _)| p == ".config/bash/bashrc")
+            .map(|(_, _, status, _)| status)
+            .unwrap();
+        assert!(matches!(status, SymlinkStatus::UpToDate));
+    }
+
+    #[test]
+    fn test_diff_reports_changed_lines_of_tracked_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+        let home = temp_dir.path().join("home");
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&sprout_path, false).unwrap();
+        add_file(&sprout_path, home.join(".bashrc"), false, false, false, false, &tracking_path).unwrap();
+        add_file(&sprout_path, home.join(".zshrc"), false, false, false, false, &tracking_path).unwrap();
+
+        // Not committed yet: the whole file shows up as added
+        let diff = diff_symlinks(&sprout_path, Some(&home.join(".bashrc")), &tracking_path).unwrap();
+        assert!(diff.contains("+# Test content for .bashrc"), "{}", diff);
+        assert!(!diff.contains(".zshrc"), "{}", diff);
+
+        crate::core::git_commit(&sprout_path, "Track dotfiles").unwrap();
+        assert_eq!(diff_symlinks(&sprout_path, None, &tracking_path).unwrap(), "");
+
+        // Editing through the symlink changes the stored file
+        fs::write(home.join(".bashrc"), "# Test content for .bashrc\nexport EDITOR=vim\n").unwrap();
+        let diff = diff_symlinks(&sprout_path, None, &tracking_path).unwrap();
+        assert!(diff.contains("symlinks/.bashrc"), "{}", diff);
+        assert!(diff.contains("+export EDITOR=vim"), "{}", diff);
+        assert!(!diff.contains(".zshrc"), "{}", diff);
+
+        let err = diff_symlinks(&sprout_path, Some(&home.join(".gitconfig")), &tracking_path).unwrap_err();
+        assert!(err.to_string().contains("not tracked"), "{}", err);
+    }
+
+    #[test]
+    fn test_custom_local_prefix_equates_paths() {
+        let net = normalize_path_with("/net/home/u/.bashrc", "/net");
+        assert_eq!(net, "/home/u/.bashrc");
+        assert_eq!(net, normalize_path_with("/home/u/.bashrc", "/net"));
+        assert_eq!(normalize_path_with("/mnt/home/u/.bashrc", "/mnt/"), "/home/u/.bashrc");
+
+        // Only whole path components are stripped, and /local is no longer special
+        assert_eq!(normalize_path_with("/network/home/u/.bashrc", "/net"), "/network/home/u/.bashrc");
+        assert_eq!(normalize_path_with("/local/home/u/.bashrc", "/net"), "/local/home/u/.bashrc");
+        assert_eq!(normalize_path_with("/local/home/u/.bashrc", "/local"), "/home/u/.bashrc");
+    }
+
+    #[test]
+    fn test_restore_conflict_choices() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&sprout_path, false).unwrap();
+        let home = temp_dir.path().join("home");
+        for name in [".bashrc", ".zshrc", ".gitconfig"] {
+            add_file(&sprout_path, home.join(name), false, false, false, false, &tracking_path).unwrap();
+            // A fresh machine: real files where the symlinks should be
+            fs::remove_file(home.join(name)).unwrap();
+            fs::write(home.join(name), format!("local {}", name)).unwrap();
+        }
+
+        // The decision unit on its own
+        let scratch = temp_dir.path().join("scratch");
+        fs::write(&scratch, "old").unwrap();
+        assert!(!apply_conflict_choice(&scratch, ConflictChoice::Skip).unwrap());
+        assert!(apply_conflict_choice(&scratch, ConflictChoice::Overwrite).unwrap());
+        assert!(scratch.exists());
+        assert!(apply_conflict_choice(&scratch, ConflictChoice::Backup).unwrap());
+        assert!(!scratch.exists());
+        assert_eq!(fs::read_to_string(temp_dir.path().join("scratch.sprout-backup")).unwrap(), "old");
+
+        let summary = restore_symlinks_with(&sprout_path, false, &tracking_path, &mut |path: &Path| {
+            Ok(match path.file_name().unwrap().to_str().unwrap() {
+                ".bashrc" => ConflictChoice::Overwrite,
+                ".zshrc" => ConflictChoice::Backup,
+                _ => ConflictChoice::Skip,
+            })
+        }).unwrap();
+
+        assert_eq!(summary.skipped, vec![".gitconfig".to_string()]);
+        assert_eq!(summary.restored.len(), 2);
+        assert!(home.join(".bashrc").is_symlink());
+        assert!(home.join(".zshrc").is_symlink());
+        assert_eq!(fs::read_to_string(home.join(".zshrc.sprout-backup")).unwrap(), "local .zshrc");
+        assert!(!home.join(".gitconfig").is_symlink());
+        assert_eq!(fs::read_to_string(home.join(".gitconfig")).unwrap(), "local .gitconfig");
+    }
+
+    #[test]
+    fn test_discover_repairs_links_into_moved_sprout_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let old_sprout = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+        let home = temp_dir.path().join("home");
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&old_sprout, false).unwrap();
+        add_file(&old_sprout, home.join(".bashrc"), false, false, false, false, &tracking_path).unwrap();
+        add_file(&old_sprout, home.join(".config/nvim"), true, false, false, false, &tracking_path).unwrap();
+
+        // Relocate the sprout dir and lose the lockfile: every link now dangles
+        let new_sprout = temp_dir.path().join("relocated/sprout");
+        fs::create_dir_all(new_sprout.parent().unwrap()).unwrap();
+        fs::rename(&old_sprout, &new_sprout).unwrap();
+        let sprout_path = new_sprout.to_string_lossy().to_string();
+        SproutLock::default().save(&sprout_path).unwrap();
+        assert!(!home.join(".bashrc").exists());
+
+        rehash_symlinks(&sprout_path, &tracking_path, true, false, false, false).unwrap();
+        assert!(SproutLock::load(&sprout_path).unwrap().symlinks.is_empty());
+
+        rehash_symlinks(&sprout_path, &tracking_path, true, true, false, true).unwrap();
+        assert!(SproutLock::load(&sprout_path).unwrap().symlinks.is_empty(), "dry run must not record");
+        assert!(!home.join(".bashrc").exists(), "dry run must not repair");
+
+        rehash_symlinks(&sprout_path, &tracking_path, true, true, false, false).unwrap();
+        let lock = SproutLock::load(&sprout_path).unwrap();
+        assert!(lock.symlinks.contains_key(".bashrc"));
+        assert!(lock.symlinks.contains_key(".config/nvim"));
+        assert_eq!(fs::read_link(home.join(".bashrc")).unwrap(), new_sprout.join("symlinks/.bashrc"));
+        assert_eq!(fs::read_to_string(home.join(".bashrc")).unwrap(), "# Test content for .bashrc");
+        assert!(home.join(".config/nvim/init.vim").exists());
+    }
+
+    #[test]
+    fn test_verify_reports_entries_sharing_a_target() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&sprout_path, false).unwrap();
+        add_files(&sprout_path, vec![temp_dir.path().join("home/.bashrc"), temp_dir.path().join("home/.zshrc")], false, false, false, false, &tracking_path).unwrap();
+
+        let mut lock = SproutLock::load(&sprout_path).unwrap();
+        assert!(find_symlink_conflicts(&sprout_path, &lock, &tracking_path).is_empty());
+
+        // A second spelling of .bashrc, and .zshrc's link repointed at .bashrc's file
+        let hash = lock.symlinks[".bashrc"].clone();
+        lock.symlinks.insert("./.bashrc".to_string(), hash);
+        let zshrc = temp_dir.path().join("home/.zshrc");
+        fs::remove_file(&zshrc).unwrap();
+        std::os::unix::fs::symlink(Path::new(&sprout_path).join("symlinks/.bashrc"), &zshrc).unwrap();
+
+        let conflicts = find_symlink_conflicts(&sprout_path, &lock, &tracking_path);
+        assert_eq!(conflicts.len(), 2, "{:?}", conflicts);
+        assert_eq!(conflicts[0].kind, "target");
+        assert_eq!(conflicts[0].path, fs::canonicalize(&sprout_path).unwrap().join("symlinks/.bashrc"));
+        assert_eq!(conflicts[0].entries, vec!["./.bashrc", ".bashrc", ".zshrc"]);
+        assert_eq!(conflicts[1].kind, "home path");
+        assert_eq!(conflicts[1].entries, vec!["./.bashrc", ".bashrc"]);
+        assert!(conflicts[1].to_string().contains("./.bashrc, .bashrc share the home path"));
+    }
+
+    #[test]
+    fn test_verify_compares_targets_through_non_canonical_sprout_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let sprout_path = temp_dir.path().join("sprout").to_string_lossy().to_string();
+        let alias = temp_dir.path().join("alias").to_string_lossy().to_string();
+        let tracking_path = temp_dir.path().join("home").to_string_lossy().to_string();
+
+        let _files = create_test_files(temp_dir.path());
+        init_sprout(&sprout_path, false).unwrap();
+        std::os::unix::fs::symlink(&sprout_path, &alias).unwrap();
+        add_files(&alias, vec![temp_dir.path().join("home/.bashrc"), temp_dir.path().join("home/.zshrc")], false, false, false, false, &tracking_path).unwrap();
+
+        // .bashrc's link is gone, so it resolves through the alias; .zshrc points at its file
+        let bashrc = temp_dir.path().join("home/.bashrc");
+        let zshrc = temp_dir.path().join("home/.zshrc");
+        let target = fs::read_link(&bashrc).unwrap();
+        fs::remove_file(&bashrc).unwrap();
+        fs::remove_file(&zshrc).unwrap();
+        std::os::unix::fs::symlink(&target, &zshrc).unwrap();
+
+        let lock = SproutLock::load(&alias).unwrap();
+        let conflicts = find_symlink_conflicts(&alias, &lock, &tracking_path);
+        assert_eq!(conflicts.len(), 1, "{:?}", conflicts);
+        assert_eq!(conflicts[0].path, target);
+        assert_eq!(conflicts[0].entries, vec![".bashrc", ".zshrc"]);
+    }
 }
@@ -9,8 +9,16 @@ mod tests {
     fn create_test_git_package() -> ModuleBlock {
         ModuleBlock {
             name: "fd".to_string(),
+            version: None,
+            description: None,
+            tags: Vec::new(),
             depends_on: vec![],
             provides: vec![Export { mode: ExportMode::Prepend, name: "PATH".to_string(), value: "/bin".to_string() }],
+            produces: vec![],
+            requires: vec![],
+            build_dir: None,
+            out_of_tree: false,
+            isolated: false,
             fetch: Some(FetchBlock {
                 spec: FetchSpec::Git(GitSpec {
                     url: "https://github.com/sharkdp/fd.git".to_string(),
@@ -18,6 +26,9 @@ mod tests {
                     recursive: false,
                 }),
                 output: None,
+                extract: true,
+                discard_git: false,
+                timeout: None,
             }),
             build: Some(ScriptBlock {
                 env: vec![],
@@ -25,6 +36,7 @@ mod tests {
                     "make".to_string(),
                     "make install PREFIX=${DIST_PATH}".to_string(),
                 ],
+                timeout: None,
             }),
             update: None,
         }
@@ -33,14 +45,23 @@ mod tests {
     fn create_test_cargo_package() -> ModuleBlock {
         ModuleBlock {
             name: "bat".to_string(),
+            version: None,
+            description: None,
+            tags: Vec::new(),
             depends_on: vec![],
             provides: vec![Export { mode: ExportMode::Prepend, name: "PATH".to_string(), value: "/bin".to_string() }],
+            produces: vec![],
+            requires: vec![],
+            build_dir: None,
+            out_of_tree: false,
+            isolated: false,
             fetch: None, // Cargo modules don't need fetch
             build: Some(ScriptBlock {
                 env: vec![],
                 commands: vec![
                     "cargo install bat --version 0.24.0 --root ${DIST_PATH}".to_string(),
                 ],
+                timeout: None,
             }),
             update: None,
         }
@@ -49,14 +70,25 @@ mod tests {
     fn create_test_tar_package() -> ModuleBlock {
         ModuleBlock {
             name: "hello".to_string(),
+            version: None,
+            description: None,
+            tags: Vec::new(),
             depends_on: vec![],
             provides: vec![Export { mode: ExportMode::Prepend, name: "PATH".to_string(), value: "/bin".to_string() }],
+            produces: vec![],
+            requires: vec![],
+            build_dir: None,
+            out_of_tree: false,
+            isolated: false,
             fetch: Some(FetchBlock {
                 spec: FetchSpec::Http(HttpSpec {
                     url: "https://ftp.gnu.org/gnu/hello/hello-2.12.tar.gz".to_string(),
                     sha256: None,
                 }),
                 output: None,
+                extract: true,
+                discard_git: false,
+                timeout: None,
             }),
             build: Some(ScriptBlock {
                 env: vec![],
@@ -64,6 +96,7 @@ mod tests {
                     "make".to_string(),
                     "make install PREFIX=${DIST_PATH}".to_string(),
                 ],
+                timeout: None,
             }),
             update: None,
         }
@@ -405,4 +438,82 @@ environments {
         assert!(parsed_envs.environments.contains_key("dev"));
         assert!(parsed_envs.environments.contains_key("minimal"));
     }
+
+    fn create_module_with_deps(name: &str, depends_on: &[&str]) -> ModuleBlock {
+        ModuleBlock {
+            name: name.to_string(),
+            version: None,
+            description: None,
+            tags: Vec::new(),
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            provides: vec![],
+            produces: vec![],
+            requires: vec![],
+            build_dir: None,
+            out_of_tree: false,
+            isolated: false,
+            fetch: None,
+            build: None,
+            update: None,
+        }
+    }
+
+    #[test]
+    fn test_dependency_paths_reports_all_chains() {
+        // clang -> llvm -> cmake and clang -> ninja -> cmake both reach cmake
+        let manifest = SproutManifest {
+            modules: vec![
+                create_module_with_deps("clang", &["llvm", "ninja"]),
+                create_module_with_deps("llvm", &["cmake"]),
+                create_module_with_deps("ninja", &["cmake"]),
+                create_module_with_deps("cmake", &[]),
+                create_module_with_deps("unrelated", &[]),
+            ],
+            environments: None,
+        };
+
+        let paths = manifest.get_dependency_paths("clang", "cmake");
+        assert_eq!(paths.len(), 2);
+        assert!(paths.contains(&vec!["clang".to_string(), "llvm".to_string(), "cmake".to_string()]));
+        assert!(paths.contains(&vec!["clang".to_string(), "ninja".to_string(), "cmake".to_string()]));
+
+        assert!(manifest.get_dependency_paths("unrelated", "cmake").is_empty());
+        assert_eq!(manifest.get_root_modules(), vec!["clang".to_string(), "unrelated".to_string()]);
+    }
+
+    #[test]
+    fn test_versioned_modules_resolve_to_requested_version() {
+        let mut gcc11 = create_module_with_deps("gcc", &[]);
+        gcc11.version = Some("11".to_string());
+        let mut gcc13 = create_module_with_deps("gcc", &[]);
+        gcc13.version = Some("13".to_string());
+
+        let manifest = SproutManifest {
+            modules: vec![
+                gcc11,
+                gcc13,
+                create_module_with_deps("legacy", &["gcc@11"]),
+                create_module_with_deps("app", &["gcc@13"]),
+            ],
+            environments: None,
+        };
+
+        assert_eq!(manifest.find_module("gcc@11").unwrap().id(), "gcc@11");
+        assert_eq!(manifest.find_module("gcc@13").unwrap().id(), "gcc@13");
+        // A bare name is ambiguous when several versions are declared
+        assert!(manifest.find_module("gcc").is_none());
+
+        assert_eq!(manifest.get_all_dependencies("app"), vec!["gcc@13".to_string(), "app".to_string()]);
+        assert_eq!(manifest.get_all_dependencies("legacy"), vec!["gcc@11".to_string(), "legacy".to_string()]);
+
+        let order: Vec<String> = crate::core::deps::resolve_dependency_order(&manifest)
+            .unwrap()
+            .iter()
+            .map(|m| m.id())
+            .collect();
+        let pos = |id: &str| order.iter().position(|m| m == id).unwrap();
+        assert_eq!(order.len(), 4);
+        assert!(pos("gcc@13") < pos("app"));
+        assert!(pos("gcc@11") < pos("legacy"));
+    }
 }